@@ -1,5 +1,11 @@
 use clap::{App, Arg};
 use easy_fs::{BlockDevice, EasyFileSystem};
+#[cfg(test)]
+use easy_fs::{
+    block_cache_flush_expired, block_cache_sync_all, cache_mode, check_image,
+    set_block_cache_capacity, set_cache_mode, set_readahead_window, BlockCacheManager, CacheMode,
+    DiskInodeType, FsError, Inode, BLOCK_CACHE_SIZE,
+};
 use std::fs::{read_dir, File, OpenOptions};
 use std::io::{Read, Seek, SeekFrom, Write};
 use std::sync::Arc;
@@ -8,6 +14,8 @@ use std::sync::Mutex;
 /// Use a block size of 512 bytes
 const BLOCK_SZ: usize = 512;
 const BLOCK_NUM: usize = 16384;
+/// Blocks after the filesystem the kernel swaps pages out to
+const SWAP_BLOCK_NUM: usize = 8192;
 
 /// Wrapper for turning a File into a BlockDevice
 struct BlockFile(Mutex<File>);
@@ -27,6 +35,13 @@ impl BlockDevice for BlockFile {
             .expect("Error when seeking!");
         assert_eq!(file.write(buf).unwrap(), BLOCK_SZ, "Not a complete block!");
     }
+    /// Read consecutive blocks from file at once
+    fn read_blocks(&self, block_id: usize, buf: &mut [u8]) {
+        let mut file = self.0.lock().unwrap();
+        file.seek(SeekFrom::Start((block_id * BLOCK_SZ) as u64))
+            .expect("Error when seeking!");
+        file.read_exact(buf).expect("Not complete blocks!");
+    }
 }
 
 fn main() {
@@ -50,6 +65,11 @@ fn easy_fs_pack() -> std::io::Result<()> {
                 .takes_value(true)
                 .help("Executable target dir(with backslash)"),
         )
+        .arg(
+            Arg::with_name("checksum")
+                .long("checksum")
+                .help("Keep a checksum of every block of the image"),
+        )
         .get_matches();
     let src_path = matches.value_of("source").unwrap();
     let target_path = matches.value_of("target").unwrap();
@@ -60,11 +80,15 @@ fn easy_fs_pack() -> std::io::Result<()> {
             .write(true)
             .create(true)
             .open(format!("{}{}", target_path, "fs.img"))?;
-        f.set_len((BLOCK_NUM * BLOCK_SZ) as u64).unwrap();
+        f.set_len(((BLOCK_NUM + SWAP_BLOCK_NUM) * BLOCK_SZ) as u64).unwrap();
         f
     })));
-    let efs = EasyFileSystem::create(block_file.clone(), BLOCK_NUM as u32, 1);
-    let root_inode = Arc::new(EasyFileSystem::root_inode(&efs));
+    let efs = if matches.is_present("checksum") {
+        EasyFileSystem::create_with_checksums(block_file.clone(), BLOCK_NUM as u32, 1)
+    } else {
+        EasyFileSystem::create(block_file.clone(), BLOCK_NUM as u32, 1)
+    };
+    let root_inode = EasyFileSystem::root_inode(&efs);
     let apps: Vec<_> = read_dir(src_path)
         .unwrap()
         .into_iter()
@@ -91,17 +115,50 @@ fn easy_fs_pack() -> std::io::Result<()> {
     Ok(())
 }
 
-#[test]
-fn efs_test() -> std::io::Result<()> {
-    let block_file = Arc::new(BlockFile(Mutex::new({
+/// The block cache is global and keyed by block id only,
+/// so tests sharing the image must not run concurrently
+#[cfg(test)]
+static TEST_LOCK: std::sync::atomic::AtomicBool = std::sync::atomic::AtomicBool::new(false);
+
+#[cfg(test)]
+struct TestGuard;
+
+#[cfg(test)]
+impl Drop for TestGuard {
+    fn drop(&mut self) {
+        TEST_LOCK.store(false, std::sync::atomic::Ordering::Release);
+    }
+}
+
+#[cfg(test)]
+fn lock_test_image() -> TestGuard {
+    use std::sync::atomic::Ordering;
+    while TEST_LOCK
+        .compare_exchange(false, true, Ordering::Acquire, Ordering::Relaxed)
+        .is_err()
+    {
+        std::thread::yield_now();
+    }
+    TestGuard
+}
+
+#[cfg(test)]
+fn test_block_file() -> std::io::Result<Arc<BlockFile>> {
+    Ok(Arc::new(BlockFile(Mutex::new({
         let f = OpenOptions::new()
             .read(true)
             .write(true)
             .create(true)
             .open("target/fs.img")?;
-        f.set_len(BLOCK_NUM * BLOCK_SZ).unwrap();
+        f.set_len((BLOCK_NUM * BLOCK_SZ) as u64).unwrap();
         f
-    })));
+    }))))
+}
+
+#[test]
+fn efs_test() -> std::io::Result<()> {
+    let _guard = lock_test_image();
+    let block_file = test_block_file()?;
     EasyFileSystem::create(block_file.clone(), 4096, 1);
     let efs = EasyFileSystem::open(block_file.clone());
     let root_inode = EasyFileSystem::root_inode(&efs);
@@ -153,3 +210,1278 @@ fn efs_test() -> std::io::Result<()> {
 
     Ok(())
 }
+
+#[test]
+fn efs_dir_test() -> std::io::Result<()> {
+    let _guard = lock_test_image();
+    let block_file = test_block_file()?;
+    EasyFileSystem::create(block_file.clone(), 4096, 1);
+    let efs = EasyFileSystem::open(block_file.clone());
+    let root_inode = EasyFileSystem::root_inode(&efs);
+    let dira = root_inode.create_dir("dira").unwrap();
+    assert!(dira.is_dir());
+    let dirb = dira.create_dir("dirb").unwrap();
+    let filec = dirb.create("filec").unwrap();
+    assert!(!filec.is_dir());
+    let greet_str = "Hello, world!";
+    filec.write_at(0, greet_str.as_bytes());
+    // creating an existing entry fails, no matter its type
+    assert!(root_inode.create_dir("dira").is_none());
+    assert!(dira.create("dirb").is_none());
+    // walk /dira/dirb/filec component by component
+    let found = root_inode
+        .find("dira")
+        .and_then(|dir| dir.find("dirb"))
+        .and_then(|dir| dir.find("filec"))
+        .unwrap();
+    let mut buffer = [0u8; 233];
+    let len = found.read_at(0, &mut buffer);
+    assert_eq!(greet_str, core::str::from_utf8(&buffer[..len]).unwrap());
+    // missing components and lookups under a regular file are not found
+    assert!(root_inode.find("dirb").is_none());
+    assert!(filec.find("anything").is_none());
+    assert!(filec.create("anything").is_none());
+    // `.` and `..` point at the directory itself and its parent
+    assert_eq!(dirb.find(".").unwrap().inode_id(), dirb.inode_id());
+    assert_eq!(dirb.find("..").unwrap().inode_id(), dira.inode_id());
+    assert_eq!(root_inode.find("..").unwrap().inode_id(), root_inode.inode_id());
+    // entries come with their inode id and type
+    let entries: Vec<_> = dirb.read_dir().collect();
+    assert_eq!(
+        entries,
+        vec![
+            (String::from("."), dirb.inode_id(), DiskInodeType::Directory),
+            (String::from(".."), dira.inode_id(), DiskInodeType::Directory),
+            (String::from("filec"), filec.inode_id(), DiskInodeType::File),
+        ]
+    );
+    // iteration resumes from the offset of an entry
+    let mut iter = dirb.read_dir();
+    iter.next();
+    let names: Vec<_> = dirb.read_dir_at(iter.offset()).map(|(name, _, _)| name).collect();
+    assert_eq!(names, vec!["..", "filec"]);
+    // only empty directories can be removed
+    assert_eq!(dira.unlinkat("dirb"), -1);
+    assert_eq!(dirb.unlinkat("."), -1);
+    assert_eq!(dirb.unlinkat("filec"), 0);
+    assert_eq!(dira.unlinkat("dirb"), 0);
+    assert!(dira.find("dirb").is_none());
+    assert_eq!(dirb.size(), 0);
+    Ok(())
+}
+
+#[test]
+fn efs_link_test() -> std::io::Result<()> {
+    let _guard = lock_test_image();
+    let block_file = test_block_file()?;
+    EasyFileSystem::create(block_file.clone(), 4096, 1);
+    let efs = EasyFileSystem::open(block_file.clone());
+    let root_inode = EasyFileSystem::root_inode(&efs);
+    let nlink = |inode: &Inode| inode.stat().2;
+    assert_eq!(nlink(&root_inode), 2);
+    let filea = root_inode.create("filea").unwrap();
+    assert_eq!(nlink(&filea), 1);
+    let dira = root_inode.create_dir("dira").unwrap();
+    assert_eq!(nlink(&dira), 2);
+    assert_eq!(nlink(&root_inode), 3);
+    // links from different directories are all counted
+    assert!(root_inode.linkat("fileb", &filea));
+    assert!(dira.linkat("filec", &filea));
+    assert_eq!(nlink(&filea), 3);
+    // a taken name is left alone
+    assert!(!dira.linkat("filec", &filea));
+    assert!(!root_inode.linkat("dira", &filea));
+    assert_eq!(nlink(&filea), 3);
+    assert_eq!(dira.ls().iter().filter(|name| *name == "filec").count(), 1);
+    assert_eq!(nlink(&dira.find("filec").unwrap()), 3);
+    filea.write_at(0, "Hello, world!".as_bytes());
+    assert_eq!(root_inode.unlinkat("filea"), 0);
+    assert_eq!(root_inode.unlinkat("filea"), -1);
+    assert_eq!(nlink(&filea), 2);
+    assert_eq!(dira.unlinkat("filec"), 0);
+    assert_eq!(nlink(&filea), 1);
+    assert_eq!(filea.size(), 13);
+    // the data is given back with the last link
+    assert_eq!(root_inode.unlinkat("fileb"), 0);
+    assert_eq!(nlink(&filea), 0);
+    assert_eq!(filea.size(), 0);
+    assert_eq!(root_inode.unlinkat("dira"), 0);
+    assert_eq!(nlink(&dira), 0);
+    assert_eq!(nlink(&root_inode), 2);
+    Ok(())
+}
+
+#[test]
+fn efs_rename_test() -> std::io::Result<()> {
+    let _guard = lock_test_image();
+    let block_file = test_block_file()?;
+    EasyFileSystem::create(block_file.clone(), 4096, 1);
+    let efs = EasyFileSystem::open(block_file.clone());
+    let root_inode = EasyFileSystem::root_inode(&efs);
+    let nlink = |inode: &Inode| inode.stat().2;
+    let free_blocks = efs.lock().free_data_blocks();
+    let filea = root_inode.create("filea").unwrap();
+    filea.write_at(0, "Hello, world!".as_bytes());
+    let fileb = root_inode.create("fileb").unwrap();
+    fileb.write_at(0, &[b'x'; 10 * BLOCK_SZ]);
+    // the replaced file is given back
+    assert!(root_inode.rename("filea", &root_inode, "fileb"));
+    assert!(root_inode.find("filea").is_none());
+    let renamed = root_inode.find("fileb").unwrap();
+    assert_eq!(renamed.inode_id(), filea.inode_id());
+    assert_eq!(nlink(&renamed), 1);
+    assert_eq!(nlink(&fileb), 0);
+    assert_eq!(efs.lock().free_data_blocks(), free_blocks - 1);
+    // onto itself, or a link of itself, nothing changes
+    assert!(root_inode.linkat("filec", &filea));
+    assert!(root_inode.rename("fileb", &root_inode, "filec"));
+    assert!(root_inode.find("fileb").is_some());
+    assert_eq!(nlink(&filea), 2);
+    assert_eq!(root_inode.unlinkat("filec"), 0);
+    assert!(!root_inode.rename("filea", &root_inode, "filed"));
+    // a directory moves with its `..`
+    let dira = root_inode.create_dir("dira").unwrap();
+    let dirb = root_inode.create_dir("dirb").unwrap();
+    assert!(root_inode.rename("fileb", &dira, "file"));
+    assert!(root_inode.rename("dira", &dirb, "sub"));
+    assert_eq!(dirb.find("sub").unwrap().find("..").unwrap().inode_id(), dirb.inode_id());
+    assert_eq!(nlink(&root_inode), 3);
+    assert_eq!(nlink(&dirb), 3);
+    assert!(!root_inode.rename("dirb", &dira, "dirb"));
+    assert!(!dira.rename("file", &root_inode, "dirb"));
+    assert!(!root_inode.rename("dirb", &root_inode, "."));
+    assert_eq!(check_image(block_file.clone()), vec![]);
+    assert_eq!(dira.read_dir().filter(|(name, _, _)| name == "file").count(), 1);
+    Ok(())
+}
+
+#[test]
+fn efs_symlink_test() -> std::io::Result<()> {
+    let _guard = lock_test_image();
+    let block_file = test_block_file()?;
+    EasyFileSystem::create(block_file.clone(), 4096, 1);
+    let efs = EasyFileSystem::open(block_file.clone());
+    let root_inode = EasyFileSystem::root_inode(&efs);
+    let filea = root_inode.create("filea").unwrap();
+    assert!(!filea.is_symlink());
+    assert!(filea.read_link().is_none());
+    // the target is only stored, it does not need to exist
+    let link = root_inode.create_symlink("linka", "dira/filea").unwrap();
+    assert!(link.is_symlink());
+    assert!(!link.is_dir());
+    assert_eq!(link.read_link().unwrap(), "dira/filea");
+    assert_eq!(link.size(), "dira/filea".len());
+    assert_eq!(link.stat().1, 2);
+    assert!(root_inode.create_symlink("linka", "filea").is_none());
+    assert!(root_inode.create_symlink("linkb", "").is_none());
+    let found = root_inode.find("linka").unwrap();
+    assert_eq!(found.inode_id(), link.inode_id());
+    assert!(root_inode
+        .read_dir()
+        .any(|(name, _, type_)| name == "linka" && type_ == DiskInodeType::Symlink));
+    // removing the link leaves the file alone
+    assert_eq!(root_inode.unlinkat("linka"), 0);
+    assert_eq!(link.size(), 0);
+    assert!(root_inode.find("filea").is_some());
+    Ok(())
+}
+
+#[test]
+fn efs_reclaim_test() -> std::io::Result<()> {
+    let _guard = lock_test_image();
+    let block_file = test_block_file()?;
+    EasyFileSystem::create(block_file.clone(), 4096, 1);
+    let efs = EasyFileSystem::open(block_file.clone());
+    let root_inode = EasyFileSystem::root_inode(&efs);
+    let free_blocks = efs.lock().free_data_blocks();
+    let free_inodes = efs.lock().free_inodes();
+    // nearly all of the data area, which would run out without reclamation
+    let data = vec![b'x'; 3000 * BLOCK_SZ];
+    for _ in 0..5 {
+        let file = root_inode.create("big").unwrap();
+        assert_eq!(file.write_at(0, &data), data.len());
+        assert_eq!(efs.lock().free_inodes(), free_inodes - 1);
+        assert!(efs.lock().free_data_blocks() < free_blocks - 3000);
+        assert_eq!(root_inode.unlinkat("big"), 0);
+        assert_eq!(efs.lock().free_data_blocks(), free_blocks);
+        assert_eq!(efs.lock().free_inodes(), free_inodes);
+    }
+    // the inode is handed out again
+    let filea = root_inode.create("filea").unwrap();
+    let fileb = root_inode.create("fileb").unwrap();
+    let filea_id = filea.inode_id();
+    assert_eq!(root_inode.unlinkat("filea"), 0);
+    assert_eq!(root_inode.create("filec").unwrap().inode_id(), filea_id);
+    assert_ne!(fileb.inode_id(), filea_id);
+    Ok(())
+}
+
+#[test]
+fn efs_usage_test() -> std::io::Result<()> {
+    let _guard = lock_test_image();
+    let block_file = test_block_file()?;
+    EasyFileSystem::create(block_file.clone(), 4096, 1);
+    let efs = EasyFileSystem::open(block_file.clone());
+    let root_inode = EasyFileSystem::root_inode(&efs);
+    let (total_blocks, free_blocks, total_inodes, free_inodes) = root_inode.fs_usage();
+    // only the root directory and the block of its `.` and `..`
+    assert!(total_blocks < 4096);
+    assert_eq!(free_blocks, total_blocks - 1);
+    assert_eq!(total_inodes, BLOCK_SZ * 8);
+    assert_eq!(free_inodes, total_inodes - 1);
+    let file = root_inode.create("filea").unwrap();
+    assert_eq!(file.write_at(0, &[b'x'; 10 * BLOCK_SZ]), 10 * BLOCK_SZ);
+    let (_, free_blocks_now, _, free_inodes_now) = file.fs_usage();
+    assert_eq!(free_blocks_now, free_blocks - 10);
+    assert_eq!(free_inodes_now, free_inodes - 1);
+    assert_eq!(root_inode.unlinkat("filea"), 0);
+    assert_eq!(root_inode.fs_usage(), (total_blocks, free_blocks, total_inodes, free_inodes));
+    Ok(())
+}
+
+#[test]
+fn efs_unlink_opened_test() -> std::io::Result<()> {
+    let _guard = lock_test_image();
+    let block_file = test_block_file()?;
+    EasyFileSystem::create(block_file.clone(), 4096, 1);
+    let efs = EasyFileSystem::open(block_file.clone());
+    let root_inode = EasyFileSystem::root_inode(&efs);
+    let free_blocks = efs.lock().free_data_blocks();
+    let free_inodes = efs.lock().free_inodes();
+    let filea = root_inode.create("filea").unwrap();
+    filea.open();
+    // a second user of the same inode
+    let opened = root_inode.find("filea").unwrap();
+    opened.open();
+    assert_eq!(root_inode.unlinkat("filea"), 0);
+    assert!(root_inode.find("filea").is_none());
+    // still usable through the opened inodes
+    let data = vec![b'x'; 40 * BLOCK_SZ];
+    assert_eq!(filea.write_at(0, &data), data.len());
+    let mut buffer = vec![0u8; data.len()];
+    assert_eq!(opened.read_at(0, &mut buffer), data.len());
+    assert_eq!(buffer, data);
+    assert_eq!(efs.lock().free_inodes(), free_inodes - 1);
+    filea.close();
+    assert!(efs.lock().free_data_blocks() < free_blocks);
+    // the last user gives everything back
+    opened.close();
+    assert_eq!(efs.lock().free_data_blocks(), free_blocks);
+    assert_eq!(efs.lock().free_inodes(), free_inodes);
+    Ok(())
+}
+
+#[test]
+fn efs_truncate_test() -> std::io::Result<()> {
+    let _guard = lock_test_image();
+    let block_file = test_block_file()?;
+    EasyFileSystem::create(block_file.clone(), 4096, 1);
+    let efs = EasyFileSystem::open(block_file.clone());
+    let root_inode = EasyFileSystem::root_inode(&efs);
+    let filea = root_inode.create("filea").unwrap();
+    let free_blocks = efs.lock().free_data_blocks();
+    let free_inodes = efs.lock().free_inodes();
+    // spans the direct, indirect1 and indirect2 blocks
+    let data = vec![b'x'; 200 * BLOCK_SZ];
+    filea.write_at(0, &data);
+    assert!(efs.lock().free_data_blocks() < free_blocks - 200);
+    filea.clear();
+    assert_eq!(filea.size(), 0);
+    assert_eq!(efs.lock().free_data_blocks(), free_blocks);
+    // the inode stays allocated and reachable
+    assert_eq!(efs.lock().free_inodes(), free_inodes);
+    assert_eq!(root_inode.find("filea").unwrap().inode_id(), filea.inode_id());
+    let mut buffer = [0u8; 16];
+    assert_eq!(filea.read_at(0, &mut buffer), 0);
+    // shrinking gives back exactly the blocks extending allocated
+    for (small, large) in [
+        (0, 30),
+        (1, 300),
+        (100, 2000),
+        (30 * BLOCK_SZ, 190 * BLOCK_SZ + 3),
+        (160 * BLOCK_SZ + 7, 190 * BLOCK_SZ),
+    ] {
+        filea.truncate(small);
+        let free_small = efs.lock().free_data_blocks();
+        filea.write_at(0, &data[..large]);
+        filea.truncate(small);
+        assert_eq!(filea.size(), small);
+        assert_eq!(efs.lock().free_data_blocks(), free_small);
+        // no stale data shows up after extending again
+        filea.truncate(large);
+        let mut buffer = vec![1u8; large];
+        assert_eq!(filea.read_at(0, &mut buffer), large);
+        assert!(buffer[..small].iter().all(|&b| b == b'x'));
+        assert!(buffer[small..].iter().all(|&b| b == 0));
+    }
+    filea.truncate(0);
+    assert_eq!(efs.lock().free_data_blocks(), free_blocks);
+    Ok(())
+}
+
+#[test]
+fn efs_timestamps_test() -> std::io::Result<()> {
+    use std::sync::atomic::{AtomicU64, Ordering};
+    static NOW: AtomicU64 = AtomicU64::new(1);
+    // every reading of the clock moves it forward
+    fn tick() -> u64 {
+        NOW.fetch_add(1, Ordering::Relaxed)
+    }
+    let _guard = lock_test_image();
+    let block_file = test_block_file()?;
+    EasyFileSystem::create(block_file.clone(), 4096, 1);
+    let efs = EasyFileSystem::open(block_file.clone());
+    efs.lock().set_clock(tick);
+    let root_inode = EasyFileSystem::root_inode(&efs);
+    let filea = root_inode.create("filea").unwrap();
+    let (atime, mtime, ctime) = filea.timestamps();
+    assert!(atime > 0 && atime == mtime && mtime == ctime);
+    // the directory changes together with its entries
+    assert_eq!(root_inode.timestamps().1, mtime);
+    filea.write_at(0, "Hello, world!".as_bytes());
+    let (_, written, _) = filea.timestamps();
+    assert!(written > mtime);
+    let mut buffer = [0u8; 16];
+    filea.read_at(0, &mut buffer);
+    let (read, mtime, ctime) = filea.timestamps();
+    assert!(read > written);
+    assert_eq!((mtime, ctime), (written, written));
+    assert!(root_inode.linkat("fileb", &filea));
+    let (atime, mtime, ctime) = filea.timestamps();
+    assert_eq!((atime, mtime), (read, written));
+    assert!(ctime > read);
+    // kept on disk, seen by another inode handle
+    let (_, _, reopened) = root_inode.find("fileb").unwrap().timestamps();
+    assert_eq!(reopened, ctime);
+    Ok(())
+}
+
+#[test]
+fn efs_large_file_test() -> std::io::Result<()> {
+    // 20 direct blocks and 128 blocks under the indirect1 block
+    const INDIRECT1_LIMIT: usize = (20 + BLOCK_SZ / 4) * BLOCK_SZ;
+    let _guard = lock_test_image();
+    let block_file = test_block_file()?;
+    EasyFileSystem::create(block_file.clone(), 4096, 1);
+    let efs = EasyFileSystem::open(block_file.clone());
+    let root_inode = EasyFileSystem::root_inode(&efs);
+    let filea = root_inode.create("filea").unwrap();
+    let free_blocks = efs.lock().free_data_blocks();
+    // up to the limit, no block goes to the indirect2 index
+    let data: Vec<u8> = (0..INDIRECT1_LIMIT + 300 * BLOCK_SZ)
+        .map(|i| (i % 251) as u8)
+        .collect();
+    filea.write_at(0, &data[..INDIRECT1_LIMIT]);
+    assert_eq!(
+        efs.lock().free_data_blocks(),
+        free_blocks - INDIRECT1_LIMIT / BLOCK_SZ - 1
+    );
+    // a single byte more takes the indirect2 block and one block under it
+    filea.write_at(INDIRECT1_LIMIT, &data[INDIRECT1_LIMIT..INDIRECT1_LIMIT + 1]);
+    assert_eq!(
+        efs.lock().free_data_blocks(),
+        free_blocks - INDIRECT1_LIMIT / BLOCK_SZ - 4
+    );
+    filea.write_at(INDIRECT1_LIMIT + 1, &data[INDIRECT1_LIMIT + 1..]);
+    assert_eq!(filea.size(), data.len());
+    let mut buffer = vec![0u8; data.len()];
+    assert_eq!(filea.read_at(0, &mut buffer), data.len());
+    assert!(buffer == data);
+    // nothing goes beyond the max size
+    assert!(!filea.truncate(EasyFileSystem::max_file_size() + 1));
+    assert_eq!(filea.size(), data.len());
+    filea.clear();
+    assert_eq!(efs.lock().free_data_blocks(), free_blocks);
+    Ok(())
+}
+
+#[test]
+fn efs_sparse_file_test() -> std::io::Result<()> {
+    const SPARSE_SIZE: usize = 1 << 20;
+    let _guard = lock_test_image();
+    let block_file = test_block_file()?;
+    let device: Arc<dyn BlockDevice> = block_file.clone();
+    EasyFileSystem::create(block_file.clone(), 4096, 1);
+    let efs = EasyFileSystem::open(block_file.clone());
+    let root_inode = EasyFileSystem::root_inode(&efs);
+    let filea = root_inode.create("filea").unwrap();
+    let free_blocks = efs.lock().free_data_blocks();
+    // a block at each end, the last one under the indirect2 block
+    assert_eq!(filea.write_at(0, b"head"), 4);
+    assert_eq!(filea.write_at(SPARSE_SIZE - 4, b"tail"), 4);
+    assert_eq!(filea.size(), SPARSE_SIZE);
+    assert_eq!(filea.allocated_blocks(), 4);
+    assert_eq!(efs.lock().free_data_blocks(), free_blocks - 4);
+    // the hole in between reads as zeros
+    let mut buffer = vec![1u8; SPARSE_SIZE];
+    assert_eq!(filea.read_at(0, &mut buffer), SPARSE_SIZE);
+    assert_eq!(&buffer[..4], b"head");
+    assert_eq!(&buffer[SPARSE_SIZE - 4..], b"tail");
+    assert!(buffer[4..SPARSE_SIZE - 4].iter().all(|&b| b == 0));
+    // extending takes no block, writing into the hole only the block written
+    assert!(filea.truncate(2 * SPARSE_SIZE));
+    assert_eq!(efs.lock().free_data_blocks(), free_blocks - 4);
+    assert_eq!(filea.write_at(100 * BLOCK_SZ + 10, b"middle"), 6);
+    assert_eq!(filea.allocated_blocks(), 6);
+    assert_eq!(efs.lock().free_data_blocks(), free_blocks - 6);
+    let mut buffer = [1u8; BLOCK_SZ];
+    assert_eq!(filea.read_at(100 * BLOCK_SZ, &mut buffer), BLOCK_SZ);
+    assert_eq!(&buffer[10..16], b"middle");
+    assert!(buffer[..10].iter().chain(buffer[16..].iter()).all(|&b| b == 0));
+    block_cache_sync_all();
+    assert_eq!(check_image(device.clone()), vec![]);
+    // shrinking gives back the blocks past the new size, holes and all
+    assert!(filea.truncate(BLOCK_SZ));
+    assert_eq!(filea.allocated_blocks(), 1);
+    assert_eq!(efs.lock().free_data_blocks(), free_blocks - 1);
+    filea.clear();
+    assert_eq!(efs.lock().free_data_blocks(), free_blocks);
+    block_cache_sync_all();
+    assert_eq!(check_image(device), vec![]);
+    Ok(())
+}
+
+#[test]
+fn efs_fallocate_test() -> std::io::Result<()> {
+    let _guard = lock_test_image();
+    let block_file = test_block_file()?;
+    let device: Arc<dyn BlockDevice> = block_file.clone();
+    EasyFileSystem::create(block_file.clone(), 4096, 1);
+    let efs = EasyFileSystem::open(block_file.clone());
+    let root_inode = EasyFileSystem::root_inode(&efs);
+    let filea = root_inode.create("filea").unwrap();
+    let free_blocks = efs.lock().free_data_blocks();
+    // the blocks are taken up front, the size stays
+    assert!(filea.fallocate(0, 10 * BLOCK_SZ, true));
+    assert_eq!(filea.size(), 0);
+    assert_eq!(efs.lock().free_data_blocks(), free_blocks - 10);
+    let data = vec![b'x'; 10 * BLOCK_SZ];
+    assert_eq!(filea.write_at(0, &data), data.len());
+    assert_eq!(efs.lock().free_data_blocks(), free_blocks - 10);
+    // growing the size as well, with the indirect1 block
+    assert!(filea.fallocate(5 * BLOCK_SZ, 25 * BLOCK_SZ, false));
+    assert_eq!(filea.size(), 30 * BLOCK_SZ);
+    assert_eq!(filea.allocated_blocks(), 31);
+    assert_eq!(efs.lock().free_data_blocks(), free_blocks - 31);
+    let mut buffer = vec![1u8; 30 * BLOCK_SZ];
+    assert_eq!(filea.read_at(0, &mut buffer), buffer.len());
+    assert!(buffer[..data.len()] == data[..]);
+    assert!(buffer[data.len()..].iter().all(|&b| b == 0));
+    // blocks past the end belong to the file as well
+    assert!(filea.fallocate(40 * BLOCK_SZ, BLOCK_SZ, true));
+    assert_eq!(filea.size(), 30 * BLOCK_SZ);
+    assert_eq!(efs.lock().free_data_blocks(), free_blocks - 32);
+    block_cache_sync_all();
+    assert_eq!(check_image(device.clone()), vec![]);
+    // all or nothing, too many blocks or beyond the max size
+    let free_now = efs.lock().free_data_blocks();
+    assert!(!filea.fallocate(0, (free_now + 40) * BLOCK_SZ, false));
+    assert!(!filea.fallocate(EasyFileSystem::max_file_size(), 1, true));
+    assert_eq!(filea.size(), 30 * BLOCK_SZ);
+    assert_eq!(filea.allocated_blocks(), 32);
+    assert_eq!(efs.lock().free_data_blocks(), free_now);
+    // shrinking gives back the blocks past the end too
+    assert!(filea.truncate(BLOCK_SZ));
+    assert_eq!(efs.lock().free_data_blocks(), free_blocks - 1);
+    filea.clear();
+    assert_eq!(efs.lock().free_data_blocks(), free_blocks);
+    block_cache_sync_all();
+    assert_eq!(check_image(device), vec![]);
+    Ok(())
+}
+
+/// A block device counting the reads of every block
+/// and the read requests it serves
+#[cfg(test)]
+struct CountingBlockFile {
+    inner: Arc<BlockFile>,
+    reads: Mutex<Vec<usize>>,
+    requests: Mutex<usize>,
+}
+
+#[cfg(test)]
+impl CountingBlockFile {
+    fn new(inner: Arc<BlockFile>) -> Self {
+        Self {
+            inner,
+            reads: Mutex::new(vec![0; BLOCK_NUM]),
+            requests: Mutex::new(0),
+        }
+    }
+}
+
+#[cfg(test)]
+impl BlockDevice for CountingBlockFile {
+    fn read_block(&self, block_id: usize, buf: &mut [u8]) {
+        self.reads.lock().unwrap()[block_id] += 1;
+        *self.requests.lock().unwrap() += 1;
+        self.inner.read_block(block_id, buf)
+    }
+    fn read_blocks(&self, block_id: usize, buf: &mut [u8]) {
+        let mut reads = self.reads.lock().unwrap();
+        for i in 0..buf.len() / BLOCK_SZ {
+            reads[block_id + i] += 1;
+        }
+        *self.requests.lock().unwrap() += 1;
+        self.inner.read_blocks(block_id, buf)
+    }
+    fn write_block(&self, block_id: usize, buf: &[u8]) {
+        self.inner.write_block(block_id, buf)
+    }
+}
+
+#[test]
+fn efs_block_cache_lru_test() -> std::io::Result<()> {
+    let _guard = lock_test_image();
+    let block_file = Arc::new(CountingBlockFile::new(test_block_file()?));
+    EasyFileSystem::create(block_file.clone(), 4096, 1);
+    let efs = EasyFileSystem::open(block_file.clone());
+    let root_inode = EasyFileSystem::root_inode(&efs);
+    // the first inodes share a block with the root inode, so the streamed
+    // file is created after them to have its inode in a block of its own
+    for name in ["filea", "fileb", "filec"] {
+        root_inode.create(name).unwrap();
+    }
+    let cold = root_inode.create("cold").unwrap();
+    let (root_id, cold_id) = (root_inode.inode_id(), cold.inode_id());
+    let (hot_block, _) = efs.lock().get_disk_inode_pos(root_id);
+    let (cold_block, _) = efs.lock().get_disk_inode_pos(cold_id);
+    assert_ne!(hot_block, cold_block);
+    let cold_blocks = 100;
+    cold.write_at(0, &vec![b'x'; cold_blocks * BLOCK_SZ]);
+    assert!(root_inode.find("filea").is_some());
+    block_file.reads.lock().unwrap().iter_mut().for_each(|count| *count = 0);
+    // stream the file by blocks, looking up the root directory in between
+    let mut buffer = [0u8; BLOCK_SZ];
+    for i in 0..cold_blocks {
+        assert_eq!(cold.read_at(i * BLOCK_SZ, &mut buffer), BLOCK_SZ);
+        assert!(root_inode.find("filea").is_some());
+    }
+    let reads = block_file.reads.lock().unwrap();
+    // the block of the root inode stays in the cache
+    assert_eq!(reads[hot_block as usize], 0);
+    // while the cold blocks do come from the disk
+    assert!(reads.iter().sum::<usize>() >= cold_blocks - 16);
+    Ok(())
+}
+
+#[test]
+fn efs_readahead_test() -> std::io::Result<()> {
+    let _guard = lock_test_image();
+    let block_file = Arc::new(CountingBlockFile::new(test_block_file()?));
+    EasyFileSystem::create(block_file.clone(), 4096, 1);
+    let efs = EasyFileSystem::open(block_file.clone());
+    let root_inode = EasyFileSystem::root_inode(&efs);
+    // the streamed file gets an inode block of its own, as in the LRU test
+    for name in ["filea", "fileb", "filec"] {
+        root_inode.create(name).unwrap();
+    }
+    let stream = root_inode.create("stream").unwrap();
+    let (hot_block, _) = efs.lock().get_disk_inode_pos(root_inode.inode_id());
+    let blocks = 64;
+    let data: Vec<u8> = (0..blocks * BLOCK_SZ).map(|i| (i / BLOCK_SZ) as u8).collect();
+    assert_eq!(stream.write_at(0, &data), data.len());
+    // start over with an empty cache, opening the file anew
+    let cold_start = |window: usize| {
+        set_readahead_window(window);
+        set_block_cache_capacity(1);
+        set_block_cache_capacity(BLOCK_CACHE_SIZE);
+        *block_file.requests.lock().unwrap() = 0;
+        block_file.reads.lock().unwrap().iter_mut().for_each(|count| *count = 0);
+        root_inode.find("stream").unwrap()
+    };
+    // stream the file by blocks, looking up the root directory in between,
+    // and count the read requests it takes
+    let stream_requests = |window: usize| {
+        let inode = cold_start(window);
+        let mut buffer = [0u8; BLOCK_SZ];
+        for i in 0..blocks {
+            assert_eq!(inode.read_at(i * BLOCK_SZ, &mut buffer), BLOCK_SZ);
+            assert!(buffer.iter().all(|&b| b == i as u8));
+            assert!(root_inode.find("filea").is_some());
+        }
+        let requests = *block_file.requests.lock().unwrap();
+        requests
+    };
+    let naive = stream_requests(0);
+    assert!(naive >= blocks);
+    let with_readahead = stream_requests(8);
+    assert!(with_readahead * 3 < naive);
+    // no block comes from the disk twice, so none of them, the hot one
+    // included, is evicted to make room for the blocks read ahead
+    let reads = block_file.reads.lock().unwrap().clone();
+    assert!(reads.iter().all(|&count| count <= 1));
+    assert!(reads[hot_block as usize] <= 1);
+
+    // reads jumping around are not followed by any read-ahead
+    let inode = cold_start(8);
+    let mut buffer = [0u8; BLOCK_SZ];
+    for i in [40, 10, 50, 20] {
+        assert_eq!(inode.read_at(i * BLOCK_SZ, &mut buffer), BLOCK_SZ);
+    }
+    // the blocks read, the two inode blocks, the root directory
+    // and the indirect block
+    assert!(block_file.reads.lock().unwrap().iter().sum::<usize>() <= 4 + 4);
+    set_readahead_window(0);
+    Ok(())
+}
+
+#[test]
+fn efs_block_cache_capacity_test() -> std::io::Result<()> {
+    let _guard = lock_test_image();
+    let block_file = Arc::new(CountingBlockFile::new(test_block_file()?));
+    let device: Arc<dyn BlockDevice> = block_file.clone();
+    // visit blocks 0, 1, 2, 3 and then block 0 again
+    let reads_of_first_block = |capacity: usize| {
+        let mut manager = BlockCacheManager::with_capacity(capacity);
+        block_file.reads.lock().unwrap().iter_mut().for_each(|count| *count = 0);
+        for block_id in [0, 1, 2, 3, 0] {
+            manager.get_block_cache(block_id, device.clone());
+        }
+        let reads = block_file.reads.lock().unwrap()[0];
+        reads
+    };
+    // too small to keep block 0 around
+    assert_eq!(reads_of_first_block(2), 2);
+    // large enough to hit block 0 at last
+    assert_eq!(reads_of_first_block(4), 1);
+    // shrinking drops the least recently used blocks
+    let mut manager = BlockCacheManager::with_capacity(4);
+    for block_id in [0, 1, 2, 3] {
+        manager.get_block_cache(block_id, device.clone());
+    }
+    manager.set_capacity(2);
+    assert_eq!(manager.capacity(), 2);
+    block_file.reads.lock().unwrap().iter_mut().for_each(|count| *count = 0);
+    manager.get_block_cache(3, device.clone());
+    manager.get_block_cache(0, device.clone());
+    let reads = block_file.reads.lock().unwrap();
+    assert_eq!((reads[3], reads[0]), (0, 1));
+    Ok(())
+}
+
+#[test]
+fn efs_sync_test() -> std::io::Result<()> {
+    let _guard = lock_test_image();
+    let block_file = test_block_file()?;
+    let device: Arc<dyn BlockDevice> = block_file.clone();
+    let block_id = 7;
+    let mut raw = [0u8; BLOCK_SZ];
+    device.write_block(block_id, &raw);
+    // a dirty block stays in the cache until a sync
+    let mut manager = BlockCacheManager::new();
+    manager
+        .get_block_cache(block_id, device.clone())
+        .lock()
+        .modify(0, |data: &mut [u8; BLOCK_SZ]| data.fill(0x5a));
+    device.read_block(block_id, &mut raw);
+    assert!(raw.iter().all(|&b| b == 0));
+    manager.sync_all();
+    device.read_block(block_id, &mut raw);
+    assert!(raw.iter().all(|&b| b == 0x5a));
+    // syncing twice, or with nothing dirty, is fine
+    manager.sync_all();
+    drop(manager);
+
+    // the content of a file reaches the disk after a global sync
+    EasyFileSystem::create(block_file.clone(), 4096, 1);
+    let efs = EasyFileSystem::open(block_file.clone());
+    let root_inode = EasyFileSystem::root_inode(&efs);
+    block_cache_sync_all();
+    let filea = root_inode.create("filea").unwrap();
+    filea.write_at(0, &[0xa5u8; BLOCK_SZ]);
+    block_cache_sync_all();
+    let persisted = (0..4096).any(|block_id| {
+        device.read_block(block_id, &mut raw);
+        raw.iter().all(|&b| b == 0xa5)
+    });
+    assert!(persisted);
+    Ok(())
+}
+
+#[test]
+fn efs_cache_mode_test() -> std::io::Result<()> {
+    let _guard = lock_test_image();
+    let block_file = Arc::new(CountingBlockFile::new(test_block_file()?));
+    let device: Arc<dyn BlockDevice> = block_file.clone();
+    let block_id = 7;
+    let mut raw = [0u8; BLOCK_SZ];
+    device.write_block(block_id, &raw);
+    // a modification reaches the disk at once in write-through mode
+    let mut manager = BlockCacheManager::new();
+    assert_eq!(manager.mode(), CacheMode::WriteBack);
+    manager.set_mode(CacheMode::WriteThrough);
+    manager
+        .get_block_cache(block_id, device.clone())
+        .lock()
+        .modify(0, |data: &mut [u8; BLOCK_SZ]| data.fill(0x5a));
+    device.read_block(block_id, &mut raw);
+    assert!(raw.iter().all(|&b| b == 0x5a));
+    drop(manager);
+
+    // a file written in write-back mode stays in the cache until a sync
+    EasyFileSystem::create(block_file.clone(), 4096, 1);
+    let efs = EasyFileSystem::open(block_file.clone());
+    let root_inode = EasyFileSystem::root_inode(&efs);
+    let filea = root_inode.create("filea").unwrap();
+    assert_eq!(cache_mode(), CacheMode::WriteBack);
+    let on_disk = |pattern: u8| {
+        let mut raw = [0u8; BLOCK_SZ];
+        (0..4096).any(|block_id| {
+            block_file.inner.read_block(block_id, &mut raw);
+            raw.iter().all(|&b| b == pattern)
+        })
+    };
+    filea.write_at(0, &[0x6bu8; BLOCK_SZ]);
+    assert!(!on_disk(0x6b));
+    // turning to write-through writes back what is dirty
+    set_cache_mode(CacheMode::WriteThrough);
+    assert_eq!(cache_mode(), CacheMode::WriteThrough);
+    assert!(on_disk(0x6b));
+    filea.write_at(0, &[0x3cu8; BLOCK_SZ]);
+    assert!(on_disk(0x3c));
+    // while reads are still served from the cache
+    block_file.reads.lock().unwrap().iter_mut().for_each(|count| *count = 0);
+    let mut buffer = [0u8; BLOCK_SZ];
+    assert_eq!(filea.read_at(0, &mut buffer), BLOCK_SZ);
+    assert!(buffer.iter().all(|&b| b == 0x3c));
+    assert_eq!(block_file.reads.lock().unwrap().iter().sum::<usize>(), 0);
+    set_cache_mode(CacheMode::WriteBack);
+    filea.write_at(0, &[0x96u8; BLOCK_SZ]);
+    assert!(!on_disk(0x96));
+    block_cache_sync_all();
+    assert!(on_disk(0x96));
+    Ok(())
+}
+
+#[test]
+fn efs_inode_sync_test() -> std::io::Result<()> {
+    use std::sync::atomic::{AtomicU64, Ordering};
+    static NOW: AtomicU64 = AtomicU64::new(1);
+    fn tick() -> u64 {
+        NOW.fetch_add(1, Ordering::Relaxed)
+    }
+    // `atime` follows the size, the block ids and `nlink` in a disk inode
+    const ATIME_OFFSET: usize = 96;
+    let _guard = lock_test_image();
+    let block_file = test_block_file()?;
+    let device: Arc<dyn BlockDevice> = block_file.clone();
+    EasyFileSystem::create(block_file.clone(), 4096, 1);
+    let efs = EasyFileSystem::open(block_file.clone());
+    efs.lock().set_clock(tick);
+    let root_inode = EasyFileSystem::root_inode(&efs);
+    let filea = root_inode.create("filea").unwrap();
+    let (block_id, offset) = efs.lock().get_disk_inode_pos(filea.inode_id());
+    let on_disk = |at: usize, len: usize| {
+        let mut raw = [0u8; BLOCK_SZ];
+        device.read_block(block_id as usize, &mut raw);
+        let mut value = [0u8; 8];
+        value[..len].copy_from_slice(&raw[offset + at..offset + at + len]);
+        u64::from_le_bytes(value)
+    };
+    filea.open();
+    assert_eq!(filea.write_at(0, &[b'x'; 100]), 100);
+    let mut buffer = [0u8; 10];
+    assert_eq!(filea.read_at(0, &mut buffer), 10);
+    // reading leaves the disk inode dirty in the cache
+    let atime = filea.timestamps().0;
+    assert_ne!(on_disk(ATIME_OFFSET, 8), atime);
+    filea.sync();
+    assert_eq!(on_disk(0, 4), 100);
+    assert_eq!(on_disk(ATIME_OFFSET, 8), atime);
+    filea.close();
+    Ok(())
+}
+
+#[test]
+fn efs_check_test() -> std::io::Result<()> {
+    let _guard = lock_test_image();
+    let block_file = test_block_file()?;
+    let device: Arc<dyn BlockDevice> = block_file.clone();
+    EasyFileSystem::create(block_file.clone(), 4096, 1);
+    let efs = EasyFileSystem::open(block_file.clone());
+    let root_inode = EasyFileSystem::root_inode(&efs);
+    // large enough to need both indirect blocks
+    let filea = root_inode.create("filea").unwrap();
+    filea.write_at(0, &[b'a'; 200 * BLOCK_SZ]);
+    let dir = root_inode.create_dir("dir").unwrap();
+    assert!(dir.linkat("fileb", &filea));
+    dir.create_symlink("link", "/filea").unwrap();
+    block_cache_sync_all();
+    assert_eq!(check_image(device.clone()), vec![]);
+
+    // locate the data bitmap through the super block
+    let mut raw = [0u8; BLOCK_SZ];
+    device.read_block(0, &mut raw);
+    let field = |i: usize| {
+        let mut bytes = [0u8; 4];
+        bytes.copy_from_slice(&raw[4 * i..4 * i + 4]);
+        u32::from_ne_bytes(bytes)
+    };
+    let data_bitmap_start = 1 + field(2) + field(3);
+    let data_area_start = data_bitmap_start + field(4);
+    let last_bit = field(5) - 1;
+    let flip = |bit: u32| {
+        let mut bitmap = [0u8; BLOCK_SZ];
+        let block_id = (data_bitmap_start + bit / (BLOCK_SZ as u32 * 8)) as usize;
+        let bit = (bit % (BLOCK_SZ as u32 * 8)) as usize;
+        device.read_block(block_id, &mut bitmap);
+        bitmap[bit / 8] ^= 1 << (bit % 8);
+        device.write_block(block_id, &bitmap);
+    };
+    // the last block is free, the first one holds the entries of the root
+    flip(last_bit);
+    assert_eq!(
+        check_image(device.clone()),
+        vec![FsError::BlockLeaked { block: data_area_start + last_bit }],
+    );
+    flip(last_bit);
+    flip(0);
+    assert_eq!(
+        check_image(device.clone()),
+        vec![FsError::BlockNotMarked { block: data_area_start }],
+    );
+    flip(0);
+    assert_eq!(check_image(device.clone()), vec![]);
+    Ok(())
+}
+
+#[test]
+fn efs_checksum_test() -> std::io::Result<()> {
+    let _guard = lock_test_image();
+    let block_file = test_block_file()?;
+    let device: Arc<dyn BlockDevice> = block_file.clone();
+    let efs = EasyFileSystem::create_with_checksums(block_file.clone(), 4096, 1);
+    let root_inode = EasyFileSystem::root_inode(&efs);
+    let filea = root_inode.create("filea").unwrap();
+    filea.write_at(0, &[0x77u8; 2 * BLOCK_SZ]);
+    let fileb = root_inode.create("fileb").unwrap();
+    fileb.write_at(0, b"untouched");
+    drop((filea, fileb, root_inode, efs));
+    assert_eq!(check_image(device.clone()), vec![]);
+
+    // flip a byte of the first block of filea right on the device
+    let mut raw = [0u8; BLOCK_SZ];
+    let block_id = (0..4096)
+        .find(|&block_id| {
+            device.read_block(block_id, &mut raw);
+            raw.iter().all(|&b| b == 0x77)
+        })
+        .unwrap();
+    raw[100] ^= 0x01;
+    device.write_block(block_id, &raw);
+    assert_eq!(
+        check_image(device.clone()),
+        vec![FsError::ChecksumMismatch { block: block_id as u32 }],
+    );
+
+    // the damage is found on the next read of the block
+    let efs = EasyFileSystem::open(block_file.clone());
+    let root_inode = EasyFileSystem::root_inode(&efs);
+    let filea = root_inode.find("filea").unwrap();
+    let mut buffer = [0u8; BLOCK_SZ];
+    assert_eq!(
+        filea.try_read_at(0, &mut buffer),
+        Err(FsError::ChecksumMismatch { block: block_id as u32 }),
+    );
+    assert_eq!(filea.try_read_at(BLOCK_SZ, &mut buffer), Ok(BLOCK_SZ));
+    assert!(buffer.iter().all(|&b| b == 0x77));
+    let fileb = root_inode.find("fileb").unwrap();
+    assert_eq!(fileb.try_read_at(0, &mut buffer), Ok(9));
+    assert_eq!(&buffer[..9], b"untouched");
+    // rewriting the block gives it a checksum again
+    filea.write_at(0, &[0x77u8; BLOCK_SZ]);
+    drop((filea, fileb, root_inode, efs));
+    assert_eq!(check_image(device.clone()), vec![]);
+
+    // an image without checksums is opened as before
+    EasyFileSystem::create(block_file.clone(), 4096, 1);
+    let efs = EasyFileSystem::open(block_file.clone());
+    let root_inode = EasyFileSystem::root_inode(&efs);
+    let filec = root_inode.create("filec").unwrap();
+    filec.write_at(0, b"plain");
+    assert_eq!(filec.try_read_at(0, &mut buffer), Ok(5));
+    assert_eq!(&buffer[..5], b"plain");
+    Ok(())
+}
+
+#[test]
+fn efs_concurrent_append_test() -> std::io::Result<()> {
+    let _guard = lock_test_image();
+    let block_file = test_block_file()?;
+    EasyFileSystem::create(block_file.clone(), 4096, 1);
+    let efs = EasyFileSystem::open(block_file.clone());
+    let root_inode = EasyFileSystem::root_inode(&efs);
+    root_inode.create("shared").unwrap();
+    let (chunk, rounds) = (100, 50);
+    let writers: Vec<_> = [b'a', b'b']
+        .iter()
+        .map(|&tag| {
+            // a vfs inode of its own, as every opened file has
+            let inode = root_inode.find("shared").unwrap();
+            std::thread::spawn(move || {
+                for _ in 0..rounds {
+                    assert_eq!(inode.append(&vec![tag; chunk]).1, chunk);
+                    std::thread::yield_now();
+                }
+            })
+        })
+        .collect();
+    for writer in writers {
+        writer.join().unwrap();
+    }
+    let inode = root_inode.find("shared").unwrap();
+    assert_eq!(inode.size(), 2 * chunk * rounds);
+    let mut data = vec![0u8; 2 * chunk * rounds];
+    assert_eq!(inode.read_at(0, &mut data), data.len());
+    // no append is torn apart or lost
+    for piece in data.chunks(chunk) {
+        assert!(piece.iter().all(|&b| b == piece[0]));
+    }
+    let count_a = data.iter().filter(|&&b| b == b'a').count();
+    assert_eq!(count_a, chunk * rounds);
+    Ok(())
+}
+
+/// A block device that can be powered off, after which
+/// it counts the writes it drops instead of doing them
+#[cfg(test)]
+struct PowerOffBlockFile {
+    inner: Arc<BlockFile>,
+    powered_off: Mutex<bool>,
+    lost_writes: Mutex<usize>,
+}
+
+#[cfg(test)]
+impl BlockDevice for PowerOffBlockFile {
+    fn read_block(&self, block_id: usize, buf: &mut [u8]) {
+        self.inner.read_block(block_id, buf)
+    }
+    fn write_block(&self, block_id: usize, buf: &[u8]) {
+        if *self.powered_off.lock().unwrap() {
+            *self.lost_writes.lock().unwrap() += 1;
+        } else {
+            self.inner.write_block(block_id, buf)
+        }
+    }
+}
+
+#[test]
+fn efs_shutdown_test() -> std::io::Result<()> {
+    let _guard = lock_test_image();
+    let block_file = test_block_file()?;
+    let device = Arc::new(PowerOffBlockFile {
+        inner: block_file.clone(),
+        powered_off: Mutex::new(false),
+        lost_writes: Mutex::new(0),
+    });
+    EasyFileSystem::create(device.clone(), 4096, 1);
+    let efs = EasyFileSystem::open(device.clone());
+    let root_inode = EasyFileSystem::root_inode(&efs);
+    let filea = root_inode.create("filea").unwrap();
+    let blocks = 3;
+    for i in 0..blocks {
+        filea.write_at(i * BLOCK_SZ, &[0xc0 + i as u8; BLOCK_SZ]);
+    }
+    // what the kernel does on shutdown: every dirty block is
+    // written back before the power goes
+    block_cache_sync_all();
+    *device.powered_off.lock().unwrap() = true;
+    let mut raw = [0u8; BLOCK_SZ];
+    for i in 0..blocks {
+        let persisted = (0..4096).any(|block_id| {
+            block_file.read_block(block_id, &mut raw);
+            raw.iter().all(|&b| b == 0xc0 + i as u8)
+        });
+        assert!(persisted);
+    }
+    // nothing was left to write after the power went
+    block_cache_sync_all();
+    drop(filea);
+    drop(root_inode);
+    drop(efs);
+    block_cache_sync_all();
+    assert_eq!(*device.lost_writes.lock().unwrap(), 0);
+    Ok(())
+}
+
+#[test]
+fn efs_inode_cache_test() -> std::io::Result<()> {
+    let _guard = lock_test_image();
+    let block_file = Arc::new(CountingBlockFile::new(test_block_file()?));
+    EasyFileSystem::create(block_file.clone(), 4096, 1);
+    let efs = EasyFileSystem::open(block_file.clone());
+    let root_inode = EasyFileSystem::root_inode(&efs);
+    let created = root_inode.create("filea").unwrap();
+    // every lookup of the same file shares the vfs inode
+    let first = root_inode.find("filea").unwrap();
+    let second = root_inode.find("filea").unwrap();
+    assert!(Arc::ptr_eq(&created, &first));
+    assert!(Arc::ptr_eq(&first, &second));
+    let fileb = root_inode.create("fileb").unwrap();
+    assert!(!Arc::ptr_eq(&first, &fileb));
+    // a write through one is seen by the other, with nothing read from the disk
+    *block_file.requests.lock().unwrap() = 0;
+    assert_eq!(first.write_at(0, b"hello"), 5);
+    assert_eq!(second.size(), 5);
+    let mut buffer = [0u8; 8];
+    assert_eq!(second.read_at(0, &mut buffer), 5);
+    assert_eq!(&buffer[..5], b"hello");
+    assert!(root_inode.linkat("link", &first));
+    assert_eq!(second.stat().2, 2);
+    assert_eq!(*block_file.requests.lock().unwrap(), 0);
+    // and through a link of it
+    let link = root_inode.find("link").unwrap();
+    assert!(Arc::ptr_eq(&link, &second));
+    // once nobody holds it, it is looked up anew
+    let inode_id = first.inode_id();
+    drop((created, first, second, link));
+    assert!(efs.lock().cached_inode(inode_id).is_none());
+    let again = root_inode.find("filea").unwrap();
+    assert_eq!(again.inode_id(), inode_id);
+    assert_eq!(again.size(), 5);
+    Ok(())
+}
+
+#[test]
+fn efs_dir_index_test() -> std::io::Result<()> {
+    let _guard = lock_test_image();
+    let block_file = Arc::new(CountingBlockFile::new(test_block_file()?));
+    EasyFileSystem::create(block_file.clone(), 4096, 1);
+    let efs = EasyFileSystem::open(block_file.clone());
+    let root_inode = EasyFileSystem::root_inode(&efs);
+    let dir = root_inode.create_dir("many").unwrap();
+    let files = 400;
+    for i in 0..files {
+        dir.create(format!("file{}", i).as_str()).unwrap();
+    }
+    // the entries take far more blocks than the cache holds
+    set_block_cache_capacity(4);
+    let requests_of = |name: &str| {
+        *block_file.requests.lock().unwrap() = 0;
+        assert!(dir.find(name).is_some());
+        let requests = *block_file.requests.lock().unwrap();
+        requests
+    };
+    // every lookup reads about the same, however far the entry lies
+    let first = requests_of("file0");
+    let last = requests_of(format!("file{}", files - 1).as_str());
+    assert!(first <= 2 && last <= 2);
+    *block_file.requests.lock().unwrap() = 0;
+    for i in (0..files).step_by(37) {
+        assert!(dir.find(format!("file{}", i).as_str()).is_some());
+    }
+    assert!(dir.find("nothing").is_none());
+    assert!(*block_file.requests.lock().unwrap() <= 2 * (files / 37 + 1));
+    // the index follows the entries as they change
+    assert_eq!(dir.unlinkat("file7"), 0);
+    assert!(dir.find("file7").is_none());
+    assert!(dir.rename("file8", &dir, "file7"));
+    assert!(dir.find("file8").is_none());
+    let renamed = dir.find("file7").unwrap();
+    assert!(root_inode.linkat("linked", &renamed));
+    assert_eq!(root_inode.find("linked").unwrap().inode_id(), renamed.inode_id());
+    let sub = dir.create_dir("sub").unwrap();
+    assert!(dir.rename("sub", &root_inode, "moved"));
+    assert_eq!(sub.find("..").unwrap().inode_id(), root_inode.inode_id());
+    assert!(Arc::ptr_eq(&sub.find("..").unwrap(), &root_inode));
+    set_block_cache_capacity(BLOCK_CACHE_SIZE);
+    // built anew from the disk, the index agrees
+    drop((dir, renamed, sub));
+    let dir = root_inode.find("many").unwrap();
+    assert!(dir.find("file7").is_some());
+    assert!(dir.find("file8").is_none());
+    assert!(dir.find(format!("file{}", files - 1).as_str()).is_some());
+    Ok(())
+}
+
+#[test]
+fn efs_dirty_flush_test() -> std::io::Result<()> {
+    let _guard = lock_test_image();
+    let block_file = test_block_file()?;
+    let device: Arc<dyn BlockDevice> = block_file.clone();
+    let block_id = 9;
+    let mut raw = [0u8; BLOCK_SZ];
+    device.write_block(block_id, &raw);
+    let mut manager = BlockCacheManager::new();
+    let cache = manager.get_block_cache(block_id, device.clone());
+    cache
+        .lock()
+        .modify(0, |data: &mut [u8; BLOCK_SZ]| data.fill(0x4d));
+    // found dirty at 1000, not old enough until 3000
+    assert_eq!(manager.flush_expired(1000, 2000), 0);
+    assert_eq!(manager.flush_expired(2500, 2000), 0);
+    device.read_block(block_id, &mut raw);
+    assert!(raw.iter().all(|&b| b == 0));
+    // a block in use is left for the next round
+    let locked = cache.lock();
+    assert_eq!(manager.flush_expired(3000, 2000), 0);
+    drop(locked);
+    assert_eq!(manager.flush_expired(3500, 2000), 1);
+    device.read_block(block_id, &mut raw);
+    assert!(raw.iter().all(|&b| b == 0x4d));
+    // written back, so it starts over when dirtied again
+    cache
+        .lock()
+        .modify(0, |data: &mut [u8; BLOCK_SZ]| data.fill(0x4e));
+    assert_eq!(manager.flush_expired(4000, 2000), 0);
+    assert_eq!(manager.flush_expired(5000, 2000), 0);
+    assert_eq!(manager.flush_expired(6000, 2000), 1);
+    device.read_block(block_id, &mut raw);
+    assert!(raw.iter().all(|&b| b == 0x4e));
+    drop(cache);
+    drop(manager);
+
+    // a file written to the global cache reaches the disk with no sync
+    EasyFileSystem::create(block_file.clone(), 4096, 1);
+    let efs = EasyFileSystem::open(block_file.clone());
+    let root_inode = EasyFileSystem::root_inode(&efs);
+    block_cache_sync_all();
+    let filea = root_inode.create("filea").unwrap();
+    filea.write_at(0, &[0xd2u8; BLOCK_SZ]);
+    let on_disk = |pattern: u8| {
+        let mut raw = [0u8; BLOCK_SZ];
+        (0..4096).any(|block_id| {
+            device.read_block(block_id, &mut raw);
+            raw.iter().all(|&b| b == pattern)
+        })
+    };
+    assert!(!on_disk(0xd2));
+    block_cache_flush_expired(10_000, 2000);
+    assert!(!on_disk(0xd2));
+    assert!(block_cache_flush_expired(12_000, 2000) > 0);
+    assert!(on_disk(0xd2));
+    Ok(())
+}
+
+#[test]
+fn efs_dir_tombstone_test() -> std::io::Result<()> {
+    let _guard = lock_test_image();
+    let block_file = test_block_file()?;
+    EasyFileSystem::create(block_file.clone(), 4096, 1);
+    let efs = EasyFileSystem::open(block_file.clone());
+    let root_inode = EasyFileSystem::root_inode(&efs);
+    let dira = root_inode.create_dir("dira").unwrap();
+    // more entries than a block holds
+    let files = 20;
+    for i in 0..files {
+        dira.create(format!("f{}", i).as_str()).unwrap();
+    }
+    let full_size = dira.size();
+
+    // remove the entry visited last while going through the directory
+    dira.open();
+    let mut seen = Vec::new();
+    let mut offset = 0;
+    loop {
+        let mut iter = dira.read_dir_at(offset);
+        let (name, _, _) = match iter.next() {
+            Some(entry) => entry,
+            None => break,
+        };
+        offset = iter.offset();
+        if name.starts_with('f') {
+            assert_eq!(dira.unlinkat(name.as_str()), 0);
+        }
+        seen.push(name);
+    }
+    let mut expected = vec![String::from("."), String::from("..")];
+    expected.extend((0..files).map(|i| format!("f{}", i)));
+    assert_eq!(seen, expected);
+    // the removed entries keep their place until the directory is closed
+    assert_eq!(dira.size(), full_size);
+    assert!(dira.create("late").is_some());
+    dira.close();
+    let names: Vec<_> = dira.read_dir().map(|(name, _, _)| name).collect();
+    assert_eq!(names, vec![".", "..", "late"]);
+    let entry_size = full_size / (files + 2);
+    assert_eq!(dira.size(), 3 * entry_size);
+    assert!(dira.find("late").is_some());
+    assert!(dira.find("f0").is_none());
+
+    // with nobody using the directory the entry goes at once
+    let size = dira.size();
+    dira.create("other").unwrap();
+    assert_eq!(dira.unlinkat("late"), 0);
+    assert_eq!(dira.size(), size);
+    let names: Vec<_> = dira.read_dir().map(|(name, _, _)| name).collect();
+    assert_eq!(names, vec![".", "..", "other"]);
+    assert!(dira.find("other").is_some());
+    assert_eq!(check_image(block_file.clone()), vec![]);
+    Ok(())
+}
+
+#[test]
+fn efs_zeroed_alloc_test() -> std::io::Result<()> {
+    let _guard = lock_test_image();
+    let block_file = test_block_file()?;
+    EasyFileSystem::create(block_file.clone(), 4096, 1);
+    let efs = EasyFileSystem::open(block_file.clone());
+    let root_inode = EasyFileSystem::root_inode(&efs);
+    let blocks_of = |pattern: u8| {
+        block_cache_sync_all();
+        let mut raw = [0u8; BLOCK_SZ];
+        (0..4096)
+            .filter(|&block_id| {
+                block_file.read_block(block_id, &mut raw);
+                raw.iter().all(|&b| b == pattern)
+            })
+            .count()
+    };
+    let filea = root_inode.create("filea").unwrap();
+    assert_eq!(filea.write_at(0, &[0x77u8; 4 * BLOCK_SZ]), 4 * BLOCK_SZ);
+    drop(filea);
+    assert_eq!(root_inode.unlinkat("filea"), 0);
+    // freeing the blocks leaves their content on the disk
+    assert_eq!(blocks_of(0x77), 4);
+
+    // a new file gets the same blocks, with a few bytes written in each
+    let fileb = root_inode.create("fileb").unwrap();
+    for i in 0..4 {
+        assert_eq!(fileb.write_at(i * BLOCK_SZ + 100, b"new"), 3);
+    }
+    let mut buffer = [0xffu8; 4 * BLOCK_SZ];
+    assert_eq!(fileb.read_at(0, &mut buffer), 3 * BLOCK_SZ + 103);
+    for (offset, &b) in buffer[..3 * BLOCK_SZ + 103].iter().enumerate() {
+        let expected = match offset % BLOCK_SZ {
+            100 => b'n',
+            101 => b'e',
+            102 => b'w',
+            _ => 0,
+        };
+        assert_eq!(b, expected);
+    }
+    assert_eq!(blocks_of(0x77), 0);
+    assert_eq!(check_image(block_file.clone()), vec![]);
+    Ok(())
+}
+
+#[test]
+fn efs_write_bounds_test() -> std::io::Result<()> {
+    let _guard = lock_test_image();
+    let block_file = test_block_file()?;
+    let device: Arc<dyn BlockDevice> = block_file.clone();
+    EasyFileSystem::create(block_file.clone(), 4096, 1);
+    let efs = EasyFileSystem::open(block_file.clone());
+    let root_inode = EasyFileSystem::root_inode(&efs);
+    let filea = root_inode.create("filea").unwrap();
+    let free_blocks = efs.lock().free_data_blocks();
+    // nothing is written at or past the max size, the size stays
+    assert_eq!(filea.write_at(EasyFileSystem::max_file_size(), b"x"), 0);
+    assert_eq!(filea.write_at(1 << 32, b"x"), 0);
+    assert_eq!(filea.size(), 0);
+    // nor when the data takes more blocks than there are free
+    let data = vec![b'x'; (free_blocks + 40) * BLOCK_SZ];
+    assert_eq!(filea.write_at(0, &data), 0);
+    assert_eq!(filea.append(&data), (0, 0));
+    assert_eq!(filea.size(), 0);
+    assert_eq!(efs.lock().free_data_blocks(), free_blocks);
+    // a write fitting in is not affected
+    assert_eq!(filea.write_at(0, b"fits"), 4);
+    assert_eq!(filea.size(), 4);
+    filea.clear();
+    assert_eq!(efs.lock().free_data_blocks(), free_blocks);
+    block_cache_sync_all();
+    assert_eq!(check_image(device), vec![]);
+    Ok(())
+}
@@ -70,6 +70,22 @@ impl Bitmap {
             bitmap_block[bits64_pos] -= 1u64 << inner_pos;
         });
     }
+    /// Count the bits that are not allocated yet
+    pub fn count_free(&self, block_device: &Arc<dyn BlockDevice>) -> usize {
+        (0..self.blocks)
+            .map(|block_id| {
+                get_block_cache(
+                    block_id + self.start_block_id,
+                    Arc::clone(block_device),
+                ).lock().read(0, |bitmap_block: &BitmapBlock| {
+                    bitmap_block
+                        .iter()
+                        .map(|bits64| bits64.count_zeros() as usize)
+                        .sum::<usize>()
+                })
+            })
+            .sum()
+    }
     /// Get the max number of allocatable blocks
     pub fn maximum(&self) -> usize {
         self.blocks * BLOCK_BITS
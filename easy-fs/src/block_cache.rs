@@ -4,9 +4,21 @@ use super::{
 };
 use alloc::collections::VecDeque;
 use alloc::sync::Arc;
+use alloc::vec;
+use alloc::vec::Vec;
 use lazy_static::*;
 use spin::Mutex;
 
+/// How modified blocks reach the block device
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CacheMode {
+    /// Every modification is written to the device at once,
+    /// reads are still served from the cache
+    WriteThrough,
+    /// Modified blocks are written back on sync or eviction
+    WriteBack,
+}
+
 /// Cached block inside memory
 pub struct BlockCache {
     /// cached block data
@@ -17,6 +29,12 @@ pub struct BlockCache {
     block_device: Arc<dyn BlockDevice>,
     /// whether the block is dirty
     modified: bool,
+    /// whether every modification is written to the device at once
+    write_through: bool,
+    /// whether the device found the block damaged when reading it
+    damaged: bool,
+    /// when the flusher first found the block dirty
+    dirty_since: Option<u64>,
 }
 
 impl BlockCache {
@@ -27,13 +45,37 @@ impl BlockCache {
     ) -> Self {
         let mut cache = [0u8; BLOCK_SZ];
         block_device.read_block(block_id, &mut cache);
+        Self::with_data(block_id, block_device, cache)
+    }
+    /// A BlockCache of data already read from disk
+    fn with_data(
+        block_id: usize,
+        block_device: Arc<dyn BlockDevice>,
+        cache: [u8; BLOCK_SZ],
+    ) -> Self {
+        let damaged = block_device.damaged(block_id);
         Self {
             cache,
             block_id,
             block_device,
             modified: false,
+            write_through: false,
+            damaged,
+            dirty_since: None,
         }
     }
+    /// Write every modification to the device at once or not,
+    /// writing back the block if it is dirty when turned on
+    fn set_write_through(&mut self, write_through: bool) {
+        self.write_through = write_through;
+        if write_through {
+            self.sync();
+        }
+    }
+    /// Whether the block was found damaged when read from the device
+    pub fn damaged(&self) -> bool {
+        self.damaged
+    }
     /// Get the address of an offset inside the cached block data
     fn addr_of_offset(&self, offset: usize) -> usize {
         &self.cache[offset] as *const _ as usize
@@ -59,10 +101,15 @@ impl BlockCache {
     }
 
     pub fn modify<T, V>(&mut self, offset:usize, f: impl FnOnce(&mut T) -> V) -> V {
-        f(self.get_mut(offset))
+        let ret = f(self.get_mut(offset));
+        if self.write_through {
+            self.sync();
+        }
+        ret
     }
 
     pub fn sync(&mut self) {
+        self.dirty_since = None;
         if self.modified {
             self.modified = false;
             self.block_device.write_block(self.block_id, &self.cache);
@@ -76,16 +123,130 @@ impl Drop for BlockCache {
     }
 }
 
-/// Use a block cache of 16 blocks
-const BLOCK_CACHE_SIZE: usize = 16;
+/// Use a block cache of 16 blocks by default
+pub const BLOCK_CACHE_SIZE: usize = 16;
 
+/// Keeps the cached blocks from the least recently used to the most recently used
 pub struct BlockCacheManager {
-    queue: VecDeque<(usize, Arc<Mutex<BlockCache>>)>,
+    /// block id, cached block and whether it is read ahead and not used yet
+    queue: VecDeque<(usize, Arc<Mutex<BlockCache>>, bool)>,
+    /// max number of cached blocks
+    capacity: usize,
+    /// max number of blocks read ahead of a sequential reader, 0 for none
+    readahead: usize,
+    /// how modified blocks reach the block device
+    mode: CacheMode,
 }
 
 impl BlockCacheManager {
     pub fn new() -> Self {
-        Self { queue: VecDeque::new() }
+        Self::with_capacity(BLOCK_CACHE_SIZE)
+    }
+
+    /// A manager caching at most `capacity` blocks
+    pub fn with_capacity(capacity: usize) -> Self {
+        assert!(capacity > 0);
+        Self {
+            queue: VecDeque::new(),
+            capacity,
+            readahead: 0,
+            mode: CacheMode::WriteBack,
+        }
+    }
+
+    /// Get how modified blocks reach the block device
+    pub fn mode(&self) -> CacheMode {
+        self.mode
+    }
+
+    /// Change how modified blocks reach the block device,
+    /// the dirty blocks are written back when turning to write-through
+    pub fn set_mode(&mut self, mode: CacheMode) {
+        self.mode = mode;
+        for (_, cache, _) in self.queue.iter() {
+            cache.lock().set_write_through(mode == CacheMode::WriteThrough);
+        }
+    }
+
+    /// Get the max number of cached blocks
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Change the max number of cached blocks,
+    /// blocks beyond a smaller capacity are written back and dropped
+    pub fn set_capacity(&mut self, capacity: usize) {
+        assert!(capacity > 0);
+        self.capacity = capacity;
+        while self.queue.len() > self.capacity && self.evict() {}
+    }
+
+    /// Get the number of blocks read ahead of a sequential reader,
+    /// which is never more than half of the cache
+    pub fn readahead_window(&self) -> usize {
+        self.readahead.min(self.capacity / 2)
+    }
+
+    /// Change the max number of blocks read ahead of a sequential reader,
+    /// 0 turns read-ahead off
+    pub fn set_readahead_window(&mut self, window: usize) {
+        self.readahead = window;
+    }
+
+    /// Drop the least recently used block not in use,
+    /// which is written back on drop if it is dirty
+    fn evict(&mut self) -> bool {
+        self.evict_where(|_| true)
+    }
+
+    /// Drop the least recently used block not in use among those
+    /// satisfying `f`, which receives whether the block is read ahead
+    fn evict_where(&mut self, f: impl Fn(bool) -> bool) -> bool {
+        if let Some(idx) = self.queue
+            .iter()
+            .position(|entry| Arc::strong_count(&entry.1) == 1 && f(entry.2)) {
+            self.queue.remove(idx);
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Bring the blocks a reader is about to need into the cache, reading
+    /// each run of consecutive ones in a single request. A block read ahead
+    /// only takes a free slot or the place of a block neither in use
+    /// nor read ahead itself, the blocks there is no room for are left out
+    pub fn prefetch(&mut self, block_ids: &[usize], block_device: Arc<dyn BlockDevice>) {
+        let mut wanted: Vec<usize> = block_ids
+            .iter()
+            .copied()
+            .filter(|&block_id| self.queue.iter().all(|entry| entry.0 != block_id))
+            .take(self.capacity / 2)
+            .collect();
+        let mut room = self.capacity.saturating_sub(self.queue.len());
+        while room < wanted.len() && self.evict_where(|readahead| !readahead) {
+            room += 1;
+        }
+        wanted.truncate(room);
+        let mut start = 0;
+        while start < wanted.len() {
+            let mut end = start + 1;
+            while end < wanted.len() && wanted[end] == wanted[end - 1] + 1 {
+                end += 1;
+            }
+            let mut data = vec![0u8; (end - start) * BLOCK_SZ];
+            block_device.read_blocks(wanted[start], &mut data);
+            for (i, chunk) in data.chunks(BLOCK_SZ).enumerate() {
+                let mut cache = [0u8; BLOCK_SZ];
+                cache.copy_from_slice(chunk);
+                let block_id = wanted[start] + i;
+                let mut block_cache = BlockCache::with_data(block_id, Arc::clone(&block_device), cache);
+                block_cache.set_write_through(self.mode == CacheMode::WriteThrough);
+                let block_cache = Arc::new(Mutex::new(block_cache));
+                self.queue.push_back((block_id, block_cache, true));
+            }
+            start = end;
+        }
     }
 
     pub fn get_block_cache(
@@ -93,31 +254,112 @@ impl BlockCacheManager {
         block_id: usize,
         block_device: Arc<dyn BlockDevice>,
     ) -> Arc<Mutex<BlockCache>> {
-        if let Some(pair) = self.queue
+        if let Some(idx) = self.queue
             .iter()
-            .find(|pair| pair.0 == block_id) {
-                Arc::clone(&pair.1)
+            .position(|entry| entry.0 == block_id) {
+                // move to tail as the most recently used
+                let mut entry = self.queue.remove(idx).unwrap();
+                let block_cache = Arc::clone(&entry.1);
+                entry.2 = false;
+                self.queue.push_back(entry);
+                block_cache
         } else {
             // substitute
-            if self.queue.len() == BLOCK_CACHE_SIZE {
-                // from front to tail
-                if let Some((idx, _)) = self.queue
-                    .iter()
-                    .enumerate()
-                    .find(|(_, pair)| Arc::strong_count(&pair.1) == 1) {
-                    self.queue.drain(idx..=idx);
-                } else {
-                    panic!("Run out of BlockCache!");
-                }
-            }
+            self.make_room();
             // load block into mem and push back
-            let block_cache = Arc::new(Mutex::new(
-                BlockCache::new(block_id, Arc::clone(&block_device))
-            ));
-            self.queue.push_back((block_id, Arc::clone(&block_cache)));
+            self.push(BlockCache::new(block_id, Arc::clone(&block_device)))
+        }
+    }
+
+    /// Get the cache of a block whose content is of no use any more,
+    /// filled with zeros. A block out of the cache is not read from the
+    /// block device, the zeros reach it as the block is written back
+    pub fn get_zeroed_block_cache(
+        &mut self,
+        block_id: usize,
+        block_device: Arc<dyn BlockDevice>,
+    ) -> Arc<Mutex<BlockCache>> {
+        if self.queue.iter().any(|entry| entry.0 == block_id) {
+            let block_cache = self.get_block_cache(block_id, block_device);
             block_cache
+                .lock()
+                .modify(0, |data: &mut [u8; BLOCK_SZ]| data.fill(0));
+            return block_cache;
+        }
+        self.make_room();
+        let mut block_cache = BlockCache::with_data(block_id, block_device, [0u8; BLOCK_SZ]);
+        block_cache.modified = true;
+        self.push(block_cache)
+    }
+
+    /// Drop a block if the cache is full
+    fn make_room(&mut self) {
+        if self.queue.len() >= self.capacity && !self.evict() {
+            panic!("Run out of BlockCache!");
+        }
+    }
+
+    /// Cache a block as the most recently used one
+    fn push(&mut self, mut block_cache: BlockCache) -> Arc<Mutex<BlockCache>> {
+        block_cache.set_write_through(self.mode == CacheMode::WriteThrough);
+        let block_id = block_cache.block_id;
+        let block_cache = Arc::new(Mutex::new(block_cache));
+        self.queue.push_back((block_id, Arc::clone(&block_cache), false));
+        block_cache
+    }
+
+    /// Write back all dirty blocks
+    pub fn sync_all(&self) {
+        for (_, cache, _) in self.queue.iter() {
+            cache.lock().sync();
+        }
+    }
+
+    /// Write back the blocks found dirty at least `age` ago by earlier calls,
+    /// marking those newly found dirty with `now`. Calling it every `interval`
+    /// keeps any block from staying dirty much longer than `age + interval`.
+    /// Blocks in use are left for the next call. Returns the number written back
+    pub fn flush_expired(&self, now: u64, age: u64) -> usize {
+        let mut flushed = 0;
+        for (_, cache, _) in self.queue.iter() {
+            let mut cache = match cache.try_lock() {
+                Some(cache) => cache,
+                None => continue,
+            };
+            if !cache.modified {
+                continue;
+            }
+            match cache.dirty_since {
+                Some(since) if now.saturating_sub(since) >= age => {
+                    cache.sync();
+                    flushed += 1;
+                }
+                Some(_) => {}
+                None => cache.dirty_since = Some(now),
+            }
+        }
+        flushed
+    }
+
+    /// Write back a block if it is cached and dirty,
+    /// a block out of the cache has been written back already
+    pub fn sync_block(&self, block_id: usize) {
+        if let Some((_, cache, _)) = self.queue.iter().find(|entry| entry.0 == block_id) {
+            cache.lock().sync();
         }
     }
+
+    /// Write back and drop all blocks not in use,
+    /// they are read from the block device again when needed
+    pub fn drop_all(&mut self) {
+        while self.evict() {}
+    }
+}
+
+impl Default for BlockCacheManager {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 lazy_static! {
@@ -135,10 +377,67 @@ pub fn get_block_cache(
     BLOCK_CACHE_MANAGER.lock().get_block_cache(block_id, block_device)
 }
 
+/// Get the block cache of the given block filled with zeros,
+/// without reading the block device
+pub fn get_zeroed_block_cache(
+    block_id: usize,
+    block_device: Arc<dyn BlockDevice>
+) -> Arc<Mutex<BlockCache>> {
+    BLOCK_CACHE_MANAGER.lock().get_zeroed_block_cache(block_id, block_device)
+}
+
 /// Sync all block cache to block device
 pub fn block_cache_sync_all() {
-    let manager = BLOCK_CACHE_MANAGER.lock();
-    for (_, cache) in manager.queue.iter() {
-        cache.lock().sync();
+    BLOCK_CACHE_MANAGER.lock().sync_all();
+}
+
+/// Write back the blocks of the global block cache dirty for at least `age`,
+/// as `BlockCacheManager::flush_expired` does. Nothing is done if the cache
+/// is in use, so a periodic flusher never waits on a foreground reader or writer
+pub fn block_cache_flush_expired(now: u64, age: u64) -> usize {
+    match BLOCK_CACHE_MANAGER.try_lock() {
+        Some(manager) => manager.flush_expired(now, age),
+        None => 0,
     }
 }
+
+/// Write back and drop all blocks of the global block cache not in use
+pub fn block_cache_drop_all() {
+    BLOCK_CACHE_MANAGER.lock().drop_all();
+}
+
+/// Sync a single block to block device if it is cached
+pub fn block_cache_sync(block_id: usize) {
+    BLOCK_CACHE_MANAGER.lock().sync_block(block_id);
+}
+
+/// Change the max number of blocks in the global block cache
+pub fn set_block_cache_capacity(capacity: usize) {
+    BLOCK_CACHE_MANAGER.lock().set_capacity(capacity);
+}
+
+/// Read the given blocks into the global block cache ahead of time
+pub fn prefetch_blocks(block_ids: &[usize], block_device: Arc<dyn BlockDevice>) {
+    BLOCK_CACHE_MANAGER.lock().prefetch(block_ids, block_device);
+}
+
+/// Get the number of blocks the global block cache reads ahead
+pub fn readahead_window() -> usize {
+    BLOCK_CACHE_MANAGER.lock().readahead_window()
+}
+
+/// Change the max number of blocks the global block cache reads
+/// ahead of a sequential reader, 0 turns read-ahead off
+pub fn set_readahead_window(window: usize) {
+    BLOCK_CACHE_MANAGER.lock().set_readahead_window(window);
+}
+
+/// Get how modified blocks in the global block cache reach the block device
+pub fn cache_mode() -> CacheMode {
+    BLOCK_CACHE_MANAGER.lock().mode()
+}
+
+/// Change how modified blocks in the global block cache reach the block device
+pub fn set_cache_mode(mode: CacheMode) {
+    BLOCK_CACHE_MANAGER.lock().set_mode(mode);
+}
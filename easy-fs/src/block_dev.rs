@@ -1,8 +1,22 @@
 use core::any::Any;
+use super::BLOCK_SZ;
 
 /// Trait for block devices
 /// which reads and writes data in the unit of blocks
 pub trait BlockDevice : Send + Sync + Any {
     fn read_block(&self, block_id: usize, buf: &mut [u8]);
     fn write_block(&self, block_id: usize, buf: &[u8]);
+    /// Read consecutive blocks from `block_id` on into `buf`, whose length
+    /// is a multiple of the block size. A device able to serve them
+    /// in a single request should do so
+    fn read_blocks(&self, block_id: usize, buf: &mut [u8]) {
+        for (i, block) in buf.chunks_mut(BLOCK_SZ).enumerate() {
+            self.read_block(block_id + i, block);
+        }
+    }
+    /// Whether the last read of a block found it damaged,
+    /// always false for a device unable to tell
+    fn damaged(&self, _block_id: usize) -> bool {
+        false
+    }
 }
@@ -0,0 +1,115 @@
+//! CRC32 checksums of blocks, kept in a table on the device itself
+//!
+//! An image created with checksums has a table of one CRC32 for every
+//! block of the image, right after the inode area. The blocks of the
+//! table itself are not covered.
+
+use super::{BlockDevice, BLOCK_SZ};
+use alloc::collections::BTreeSet;
+use alloc::sync::Arc;
+use spin::Mutex;
+
+/// Number of checksums in a block of the table
+const CHECKSUMS_PER_BLOCK: usize = BLOCK_SZ / 4;
+
+/// CRC32 (IEEE 802.3) of `data`
+pub fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xedb8_8320 & mask);
+        }
+    }
+    !crc
+}
+
+/// Get the number of table blocks holding the checksums of `total_blocks` blocks
+pub fn checksum_table_blocks(total_blocks: u32) -> u32 {
+    ((total_blocks as usize + CHECKSUMS_PER_BLOCK - 1) / CHECKSUMS_PER_BLOCK) as u32
+}
+
+/// A block device checking every block read against its checksum
+/// and updating the checksum of every block written
+pub struct ChecksumDevice {
+    inner: Arc<dyn BlockDevice>,
+    /// First block of the table
+    table_start: usize,
+    /// Number of blocks of the table
+    table_blocks: usize,
+    /// Blocks whose last read did not match their checksum
+    damaged: Mutex<BTreeSet<usize>>,
+    /// Taken while reading or writing a block along with its checksum
+    table_lock: Mutex<()>,
+}
+
+impl ChecksumDevice {
+    /// Check the blocks of `inner` against the table
+    /// of `table_blocks` blocks from `table_start` on
+    pub fn new(inner: Arc<dyn BlockDevice>, table_start: u32, table_blocks: u32) -> Self {
+        Self {
+            inner,
+            table_start: table_start as usize,
+            table_blocks: table_blocks as usize,
+            damaged: Mutex::new(BTreeSet::new()),
+            table_lock: Mutex::new(()),
+        }
+    }
+    /// Whether a block is a block of the table, which has no checksum
+    fn in_table(&self, block_id: usize) -> bool {
+        block_id >= self.table_start && block_id < self.table_start + self.table_blocks
+    }
+    /// Get the table block holding the checksum of a block
+    /// and the byte offset of the checksum in it
+    fn checksum_pos(&self, block_id: usize) -> (usize, usize) {
+        (
+            self.table_start + block_id / CHECKSUMS_PER_BLOCK,
+            block_id % CHECKSUMS_PER_BLOCK * 4,
+        )
+    }
+    /// Get the checksum recorded for a block
+    fn checksum(&self, block_id: usize) -> u32 {
+        let (table_block, offset) = self.checksum_pos(block_id);
+        let mut table = [0u8; BLOCK_SZ];
+        self.inner.read_block(table_block, &mut table);
+        let mut bytes = [0u8; 4];
+        bytes.copy_from_slice(&table[offset..offset + 4]);
+        u32::from_le_bytes(bytes)
+    }
+}
+
+impl BlockDevice for ChecksumDevice {
+    fn read_block(&self, block_id: usize, buf: &mut [u8]) {
+        if self.in_table(block_id) {
+            self.inner.read_block(block_id, buf);
+            return;
+        }
+        // a block and its checksum are read with no write in between
+        let _lock = self.table_lock.lock();
+        self.inner.read_block(block_id, buf);
+        let mut damaged = self.damaged.lock();
+        if crc32(buf) == self.checksum(block_id) {
+            damaged.remove(&block_id);
+        } else {
+            damaged.insert(block_id);
+        }
+    }
+    fn write_block(&self, block_id: usize, buf: &[u8]) {
+        if self.in_table(block_id) {
+            self.inner.write_block(block_id, buf);
+            return;
+        }
+        let _lock = self.table_lock.lock();
+        self.inner.write_block(block_id, buf);
+        let (table_block, offset) = self.checksum_pos(block_id);
+        let mut table = [0u8; BLOCK_SZ];
+        self.inner.read_block(table_block, &mut table);
+        table[offset..offset + 4].copy_from_slice(&crc32(buf).to_le_bytes());
+        self.inner.write_block(table_block, &table);
+        self.damaged.lock().remove(&block_id);
+    }
+    fn damaged(&self, block_id: usize) -> bool {
+        self.damaged.lock().contains(&block_id)
+    }
+}
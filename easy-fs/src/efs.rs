@@ -1,5 +1,6 @@
-use alloc::sync::Arc;
-use spin::Mutex;
+use alloc::collections::BTreeMap;
+use alloc::sync::{Arc, Weak};
+use spin::{Mutex, RwLock};
 use super::{
     BlockDevice,
     Bitmap,
@@ -8,7 +9,11 @@ use super::{
     DiskInodeType,
     Inode,
     get_block_cache,
+    get_zeroed_block_cache,
     block_cache_sync_all,
+    block_cache_drop_all,
+    checksum_table_blocks,
+    ChecksumDevice,
 };
 use crate::BLOCK_SZ;
 
@@ -19,8 +24,23 @@ pub struct EasyFileSystem {
     pub data_bitmap: Bitmap,
     pub inode_area_start_block: u32,
     data_area_start_block: u32,
+    /// Number of blocks in the data area, the data bitmap may have more bits
+    data_area_blocks: u32,
+    /// Inodes held open by their users, inode id -> (number of users,
+    /// whether the last link is already gone)
+    opened: BTreeMap<u32, (usize, bool)>,
+    /// The lock of the data of each inode with a vfs inode around,
+    /// shared by all the vfs inodes of the same inode
+    inode_locks: BTreeMap<u32, Weak<RwLock<()>>>,
+    /// The vfs inodes somebody holds, shared by all who look them up
+    inodes: BTreeMap<u32, Weak<Inode>>,
+    /// Source of the timestamps of inodes
+    clock: Clock,
 }
 
+/// A time source returning the current time in nanoseconds
+pub type Clock = fn() -> u64;
+
 /// A data block of block size
 type DataBlock = [u8; BLOCK_SZ];
 
@@ -30,6 +50,23 @@ impl EasyFileSystem {
         block_device: Arc<dyn BlockDevice>,
         total_blocks: u32,
         inode_bitmap_blocks: u32,
+    ) -> Arc<Mutex<Self>> {
+        Self::format(block_device, total_blocks, inode_bitmap_blocks, false)
+    }
+    /// Create a filesystem from a block device keeping a checksum of
+    /// every block, which takes a few blocks from the data area
+    pub fn create_with_checksums(
+        block_device: Arc<dyn BlockDevice>,
+        total_blocks: u32,
+        inode_bitmap_blocks: u32,
+    ) -> Arc<Mutex<Self>> {
+        Self::format(block_device, total_blocks, inode_bitmap_blocks, true)
+    }
+    fn format(
+        block_device: Arc<dyn BlockDevice>,
+        total_blocks: u32,
+        inode_bitmap_blocks: u32,
+        checksums: bool,
     ) -> Arc<Mutex<Self>> {
         // calculate block size of areas & create bitmaps
         let inode_bitmap = Bitmap::new(1, inode_bitmap_blocks as usize);
@@ -37,22 +74,41 @@ impl EasyFileSystem {
         let inode_area_blocks =
             ((inode_num * core::mem::size_of::<DiskInode>() + BLOCK_SZ - 1) / BLOCK_SZ) as u32;
         let inode_total_blocks = inode_bitmap_blocks + inode_area_blocks;
-        let data_total_blocks = total_blocks - 1 - inode_total_blocks;
+        let checksum_blocks = if checksums {
+            checksum_table_blocks(total_blocks)
+        } else {
+            0
+        };
+        let data_total_blocks = total_blocks - 1 - inode_total_blocks - checksum_blocks;
         let data_bitmap_blocks = (data_total_blocks + 4096) / 4097;
         let data_area_blocks = data_total_blocks - data_bitmap_blocks;
         let data_bitmap = Bitmap::new(
-            (1 + inode_bitmap_blocks + inode_area_blocks) as usize,
+            (1 + inode_total_blocks + checksum_blocks) as usize,
             data_bitmap_blocks as usize,
         );
+        let table_start = 1 + inode_total_blocks;
+        // no block cached for an earlier image may be written back over this one
+        block_cache_drop_all();
+        let block_device: Arc<dyn BlockDevice> = if checksums {
+            Arc::new(ChecksumDevice::new(block_device, table_start, checksum_blocks))
+        } else {
+            block_device
+        };
         let mut efs = Self {
             block_device: Arc::clone(&block_device),
             inode_bitmap,
             data_bitmap,
             inode_area_start_block: 1 + inode_bitmap_blocks,
-            data_area_start_block: 1 + inode_total_blocks + data_bitmap_blocks,
+            data_area_start_block: 1 + inode_total_blocks + checksum_blocks + data_bitmap_blocks,
+            data_area_blocks,
+            opened: BTreeMap::new(),
+            inode_locks: BTreeMap::new(),
+            inodes: BTreeMap::new(),
+            clock: || 0,
         };
-        // clear all blocks
-        for i in 0..total_blocks {
+        // clear all blocks, every one of them gets its checksum
+        // as it is written back, the table is left alone
+        for i in (0..total_blocks).filter(|&i| i < table_start || i >= table_start + checksum_blocks) {
             get_block_cache(
                 i as usize,
                 Arc::clone(&block_device)
@@ -72,6 +128,7 @@ impl EasyFileSystem {
                 inode_area_blocks,
                 data_bitmap_blocks,
                 data_area_blocks,
+                checksum_blocks,
             );
         });
         // write back immediately
@@ -86,46 +143,80 @@ impl EasyFileSystem {
         .modify(root_inode_offset, |disk_inode: &mut DiskInode| {
             disk_inode.initialize(DiskInodeType::Directory);
         });
+        let efs = Arc::new(Mutex::new(efs));
+        Self::root_inode(&efs).init_root_dir();
         block_cache_sync_all();
-        Arc::new(Mutex::new(efs))
+        efs
     }
-    /// Open a block device as a filesystem
+    /// Open a block device as a filesystem, checking every block
+    /// read against its checksum if the image keeps checksums
     pub fn open(block_device: Arc<dyn BlockDevice>) -> Arc<Mutex<Self>> {
         // read SuperBlock
-        get_block_cache(0, Arc::clone(&block_device))
-            .lock()
-            .read(0, |super_block: &SuperBlock| {
-                assert!(super_block.is_valid(), "Error loading EFS!");
-                let inode_total_blocks =
-                    super_block.inode_bitmap_blocks + super_block.inode_area_blocks;
-                let efs = Self {
-                    block_device,
-                    inode_bitmap: Bitmap::new(
-                        1,
-                        super_block.inode_bitmap_blocks as usize
-                    ),
-                    data_bitmap: Bitmap::new(
-                        (1 + inode_total_blocks) as usize,
-                        super_block.data_bitmap_blocks as usize,
-                    ),
-                    inode_area_start_block: 1 + super_block.inode_bitmap_blocks,
-                    data_area_start_block: 1 + inode_total_blocks + super_block.data_bitmap_blocks,
-                };
-                Arc::new(Mutex::new(efs))
-            })
-    }
-    /// Get the root inode of the filesystem
-    pub fn root_inode(efs: &Arc<Mutex<Self>>) -> Inode {
-        let block_device = Arc::clone(&efs.lock().block_device);
-        // acquire efs lock temporarily
-        let (block_id, block_offset) = efs.lock().get_disk_inode_pos(0);
-        // release efs lock
-        Inode::new(
+        let (inode_bitmap_blocks, inode_area_blocks, checksum_blocks, data_bitmap_blocks, data_area_blocks) =
+            get_block_cache(0, Arc::clone(&block_device))
+                .lock()
+                .read(0, |super_block: &SuperBlock| {
+                    assert!(super_block.is_valid(), "Error loading EFS!");
+                    (
+                        super_block.inode_bitmap_blocks,
+                        super_block.inode_area_blocks,
+                        super_block.checksum_blocks,
+                        super_block.data_bitmap_blocks,
+                        super_block.data_area_blocks,
+                    )
+                });
+        let inode_total_blocks = inode_bitmap_blocks + inode_area_blocks;
+        let block_device: Arc<dyn BlockDevice> = if checksum_blocks > 0 {
+            // blocks cached before are not checked, read them anew
+            block_cache_drop_all();
+            Arc::new(ChecksumDevice::new(block_device, 1 + inode_total_blocks, checksum_blocks))
+        } else {
+            block_device
+        };
+        let data_bitmap_start = 1 + inode_total_blocks + checksum_blocks;
+        let efs = Self {
+            block_device,
+            inode_bitmap: Bitmap::new(1, inode_bitmap_blocks as usize),
+            data_bitmap: Bitmap::new(data_bitmap_start as usize, data_bitmap_blocks as usize),
+            inode_area_start_block: 1 + inode_bitmap_blocks,
+            data_area_start_block: data_bitmap_start + data_bitmap_blocks,
+            data_area_blocks,
+            opened: BTreeMap::new(),
+            inode_locks: BTreeMap::new(),
+            inodes: BTreeMap::new(),
+            clock: || 0,
+        };
+        Arc::new(Mutex::new(efs))
+    }
+    /// Get the root inode of the filesystem, the same vfs inode
+    /// a lookup of `..` in a directory right under it finds
+    pub fn root_inode(efs: &Arc<Mutex<Self>>) -> Arc<Inode> {
+        let mut fs = efs.lock();
+        if let Some(inode) = fs.cached_inode(0) {
+            return inode;
+        }
+        let (block_id, block_offset) = fs.get_disk_inode_pos(0);
+        let inode = Arc::new(Inode::new(
             block_id,
             block_offset,
             Arc::clone(efs),
-            block_device,
-        )
+            Arc::clone(&fs.block_device),
+            fs.inode_lock(0),
+        ));
+        fs.cache_inode(0, &inode);
+        inode
+    }
+    /// Set the time source of inode timestamps, which are all 0 without one
+    pub fn set_clock(&mut self, clock: Clock) {
+        self.clock = clock;
+    }
+    /// The max size of a file in bytes
+    pub fn max_file_size() -> usize {
+        DiskInode::max_size()
+    }
+    /// Get the current time in nanoseconds
+    pub fn now(&self) -> u64 {
+        (self.clock)()
     }
     /// Get inode by id
     pub fn get_disk_inode_pos(&self, inode_id: u32) -> (u32, usize) {
@@ -134,6 +225,13 @@ impl EasyFileSystem {
         let block_id = self.inode_area_start_block + inode_id / inodes_per_block;
         (block_id, (inode_id % inodes_per_block) as usize * inode_size)
     }
+    /// Get inode id by the position of its disk inode
+    pub fn get_inode_id(&self, block_id: u32, block_offset: usize) -> u32 {
+        let inode_size = core::mem::size_of::<DiskInode>();
+        let inodes_per_block = (BLOCK_SZ / inode_size) as u32;
+        (block_id - self.inode_area_start_block) * inodes_per_block
+            + (block_offset / inode_size) as u32
+    }
     /// Get data block by id
     pub fn get_data_block_id(&self, data_block_id: u32) -> u32 {
         self.data_area_start_block + data_block_id
@@ -142,23 +240,101 @@ impl EasyFileSystem {
     pub fn alloc_inode(&mut self) -> u32 {
         self.inode_bitmap.alloc(&self.block_device).unwrap() as u32
     }
+    /// Deallocate an inode
+    pub fn dealloc_inode(&mut self, inode_id: u32) {
+        self.inode_bitmap.dealloc(&self.block_device, inode_id as usize)
+    }
+    /// Get the lock of the data of an inode
+    pub fn inode_lock(&mut self, inode_id: u32) -> Arc<RwLock<()>> {
+        if let Some(lock) = self.inode_locks.get(&inode_id).and_then(Weak::upgrade) {
+            return lock;
+        }
+        // forget the locks no vfs inode holds any more
+        self.inode_locks.retain(|_, lock| lock.strong_count() > 0);
+        let lock = Arc::new(RwLock::new(()));
+        self.inode_locks.insert(inode_id, Arc::downgrade(&lock));
+        lock
+    }
+    /// Get the vfs inode of an inode if somebody holds it
+    pub fn cached_inode(&self, inode_id: u32) -> Option<Arc<Inode>> {
+        self.inodes.get(&inode_id).and_then(Weak::upgrade)
+    }
+    /// Share a vfs inode with whoever looks up its inode while it is held
+    pub fn cache_inode(&mut self, inode_id: u32, inode: &Arc<Inode>) {
+        // forget the vfs inodes nobody holds any more
+        self.inodes.retain(|_, inode| inode.strong_count() > 0);
+        self.inodes.insert(inode_id, Arc::downgrade(inode));
+    }
+    /// Record one more user of an inode
+    pub fn open_inode(&mut self, inode_id: u32) {
+        self.opened.entry(inode_id).or_insert((0, false)).0 += 1;
+    }
+    /// Record that a user of an inode is gone,
+    /// returns whether the inode should be reclaimed now
+    pub fn close_inode(&mut self, inode_id: u32) -> bool {
+        let (count, unlinked) = self.opened.get_mut(&inode_id).unwrap();
+        *count -= 1;
+        if *count > 0 {
+            return false;
+        }
+        let unlinked = *unlinked;
+        self.opened.remove(&inode_id);
+        unlinked
+    }
+    /// Whether an inode has a user now
+    pub fn is_opened(&self, inode_id: u32) -> bool {
+        self.opened.contains_key(&inode_id)
+    }
+    /// Mark an inode whose last link is gone,
+    /// returns whether its reclamation is deferred to its last user
+    pub fn defer_reclaim(&mut self, inode_id: u32) -> bool {
+        match self.opened.get_mut(&inode_id) {
+            Some((_, unlinked)) => {
+                *unlinked = true;
+                true
+            }
+            None => false,
+        }
+    }
+    /// Get the number of inodes not allocated yet
+    pub fn free_inodes(&self) -> usize {
+        self.inode_bitmap.count_free(&self.block_device)
+    }
+    /// Get the number of data blocks not allocated yet
+    pub fn free_data_blocks(&self) -> usize {
+        // the bits past the data area stand for no real blocks
+        let beyond = self.data_bitmap.maximum() - self.data_area_blocks as usize;
+        self.data_bitmap.count_free(&self.block_device).saturating_sub(beyond)
+    }
+    /// Get the number of inodes in total
+    pub fn total_inodes(&self) -> usize {
+        self.inode_bitmap.maximum()
+    }
+    /// Get the number of data blocks in total
+    pub fn total_data_blocks(&self) -> usize {
+        self.data_area_blocks as usize
+    }
     /// Allocate a data block
     pub fn alloc_data(&mut self) -> u32 {
-        self.data_bitmap.alloc(&self.block_device).unwrap() as u32 + self.data_area_start_block
+        let block_id =
+            self.data_bitmap.alloc(&self.block_device).unwrap() as u32 + self.data_area_start_block;
+        // whatever a removed file left there never shows up in a new one
+        get_zeroed_block_cache(block_id as usize, Arc::clone(&self.block_device));
+        block_id
     }
-    /// Deallocate a data block
+    /// Deallocate a data block, which is zeroed when it is allocated again
     pub fn dealloc_data(&mut self, block_id: u32) {
-        get_block_cache(
-            block_id as usize,
-            Arc::clone(&self.block_device)
-        )
-        .lock()
-        .modify(0, |data_block: &mut DataBlock| {
-            data_block.iter_mut().for_each(|p| { *p = 0; })
-        });
         self.data_bitmap.dealloc(
             &self.block_device,
             (block_id - self.data_area_start_block) as usize
         )
     }
 }
+
+impl Drop for EasyFileSystem {
+    /// The last inode of the filesystem is gone, write back what
+    /// a write-back cache still holds
+    fn drop(&mut self) {
+        block_cache_sync_all();
+    }
+}
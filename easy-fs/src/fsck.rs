@@ -0,0 +1,348 @@
+//! Offline consistency check of an easy-fs image
+//!
+//! The image is read straight from the block device, bypassing the
+//! block cache, so whoever wrote it must have synced it beforehand.
+
+use super::{
+    BlockDevice,
+    DiskInode,
+    DiskInodeType,
+    SuperBlock,
+    BLOCK_SZ,
+    DIRENT_SZ,
+    INODE_INDIRECT1_COUNT,
+    NAME_LENGTH_LIMIT,
+    NO_BLOCK,
+    checksum_table_blocks,
+    crc32,
+};
+use alloc::collections::{BTreeMap, BTreeSet};
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec;
+use alloc::vec::Vec;
+
+/// Number of bits in a block
+const BLOCK_BITS: usize = BLOCK_SZ * 8;
+
+/// An inconsistency found in an image
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum FsError {
+    /// The super block does not carry the easy-fs magic
+    BadMagic,
+    /// The areas in the super block do not fit the image
+    BadLayout,
+    /// Inode 0 is not an allocated directory
+    BadRoot,
+    /// An inode claims more data than an inode can hold
+    BadSize { inode: u32, size: u32 },
+    /// A block of an inode lies outside the data area
+    BlockOutOfRange { inode: u32, block: u32 },
+    /// A block is used twice, by two inodes or by the same one
+    BlockDoubleAllocated { block: u32, inodes: (u32, u32) },
+    /// A block in use is free in the data bitmap
+    BlockNotMarked { block: u32 },
+    /// A block marked in the data bitmap is used by no inode
+    BlockLeaked { block: u32 },
+    /// An allocated inode no directory entry refers to
+    OrphanInode { inode: u32 },
+    /// A directory entry refers to an inode not allocated
+    DanglingEntry { dir: u32, name: String, inode: u32 },
+    /// The link count of an inode differs from the entries referring to it
+    LinkCountMismatch { inode: u32, nlink: u32, entries: u32 },
+    /// A block does not match the checksum recorded for it
+    ChecksumMismatch { block: u32 },
+}
+
+/// A block read from the device, aligned for any on-disk structure
+struct RawBlock([u64; BLOCK_SZ / 8]);
+
+impl RawBlock {
+    fn read(block_device: &Arc<dyn BlockDevice>, block_id: u32) -> Self {
+        let mut block = Self([0; BLOCK_SZ / 8]);
+        block_device.read_block(block_id as usize, block.bytes_mut());
+        block
+    }
+    fn bytes(&self) -> &[u8] {
+        unsafe { core::slice::from_raw_parts(self.0.as_ptr() as *const u8, BLOCK_SZ) }
+    }
+    fn bytes_mut(&mut self) -> &mut [u8] {
+        unsafe { core::slice::from_raw_parts_mut(self.0.as_mut_ptr() as *mut u8, BLOCK_SZ) }
+    }
+    fn get<T>(&self, offset: usize) -> &T {
+        assert!(offset + core::mem::size_of::<T>() <= BLOCK_SZ);
+        unsafe { &*(self.bytes().as_ptr().add(offset) as *const T) }
+    }
+    /// Get the `i`-th block id of an indirect block
+    fn block_id(&self, i: usize) -> u32 {
+        *self.get::<u32>(i * 4)
+    }
+}
+
+/// What the check needs to know about an allocated inode
+struct InodeInfo {
+    type_: DiskInodeType,
+    size: u32,
+    nlink: u32,
+    /// Data blocks in order, `None` for a hole or one out of the data area
+    data: Vec<Option<u32>>,
+}
+
+struct Checker {
+    block_device: Arc<dyn BlockDevice>,
+    inode_bitmap_start: u32,
+    inode_area_start: u32,
+    inode_num: u32,
+    data_bitmap_start: u32,
+    data_area_start: u32,
+    data_area_blocks: u32,
+    /// First block and number of blocks of the checksum table, if any
+    checksum_table: (u32, u32),
+    errors: Vec<FsError>,
+}
+
+impl Checker {
+    /// Check every block but those of the table against its checksum
+    fn check_checksums(&mut self, total_blocks: u32) {
+        let (table_start, table_blocks) = self.checksum_table;
+        let per_block = (BLOCK_SZ / 4) as u32;
+        for table_block in 0..table_blocks {
+            let table = RawBlock::read(&self.block_device, table_start + table_block);
+            for i in 0..per_block {
+                let block = table_block * per_block + i;
+                if block >= total_blocks {
+                    return;
+                }
+                if block >= table_start && block < table_start + table_blocks {
+                    continue;
+                }
+                let data = RawBlock::read(&self.block_device, block);
+                if crc32(data.bytes()) != table.block_id(i as usize) {
+                    self.errors.push(FsError::ChecksumMismatch { block });
+                }
+            }
+        }
+    }
+    /// Read the first `bits` bits of the bitmap starting at `start_block`
+    fn bitmap(&self, start_block: u32, bits: usize) -> Vec<bool> {
+        let mut bitmap = Vec::with_capacity(bits);
+        let mut block_id = start_block;
+        while bitmap.len() < bits {
+            let block = RawBlock::read(&self.block_device, block_id);
+            let count = (bits - bitmap.len()).min(BLOCK_BITS);
+            bitmap.extend((0..count).map(|bit| block.bytes()[bit / 8] & (1u8 << (bit % 8)) != 0));
+            block_id += 1;
+        }
+        bitmap
+    }
+    fn in_data_area(&self, block: u32) -> bool {
+        block >= self.data_area_start && block < self.data_area_start + self.data_area_blocks
+    }
+    /// Check a block referred to by `inode`, returns whether it can be read
+    fn check_block(&mut self, inode: u32, block: u32, owners: &mut BTreeMap<u32, u32>) -> bool {
+        if !self.in_data_area(block) {
+            self.errors.push(FsError::BlockOutOfRange { inode, block });
+            return false;
+        }
+        if let Some(&first) = owners.get(&block) {
+            self.errors.push(FsError::BlockDoubleAllocated { block, inodes: (first, inode) });
+        } else {
+            owners.insert(block, inode);
+        }
+        true
+    }
+    /// Collect the data blocks of `disk_inode`, recording every block
+    /// it uses, index blocks included, in `owners`
+    fn inode_blocks(
+        &mut self,
+        inode: u32,
+        disk_inode: &DiskInode,
+        owners: &mut BTreeMap<u32, u32>,
+    ) -> Vec<Option<u32>> {
+        let mut data = Vec::with_capacity(disk_inode.data_blocks() as usize);
+        for &block in disk_inode.direct.iter() {
+            data.push(Some(block).filter(|&b| b != NO_BLOCK && self.check_block(inode, b, owners)));
+        }
+        // the block ids under an index block, all missing if it is a hole or cannot be read
+        let mut indexed = |checker: &mut Self,
+                           index: u32,
+                           count: usize,
+                           data: &mut Vec<Option<u32>>| {
+            if index == NO_BLOCK || !checker.check_block(inode, index, owners) {
+                data.extend(core::iter::repeat(None).take(count));
+                return None;
+            }
+            let block = RawBlock::read(&checker.block_device, index);
+            for i in 0..count {
+                let id = block.block_id(i);
+                data.push(Some(id).filter(|&b| b != NO_BLOCK && checker.check_block(inode, b, owners)));
+            }
+            Some(block)
+        };
+        // every block the index refers to, preallocated ones past the end included
+        indexed(self, disk_inode.indirect1, INODE_INDIRECT1_COUNT, &mut data);
+        if let Some(indirect2) = indexed(self, disk_inode.indirect2, 0, &mut data) {
+            for a in 0..INODE_INDIRECT1_COUNT {
+                indexed(self, indirect2.block_id(a), INODE_INDIRECT1_COUNT, &mut data);
+            }
+        }
+        while data.last() == Some(&None) {
+            data.pop();
+        }
+        data
+    }
+    /// Read the allocated inodes and check the blocks they use
+    fn check_inodes(&mut self) -> BTreeMap<u32, InodeInfo> {
+        let inode_size = core::mem::size_of::<DiskInode>();
+        let inodes_per_block = (BLOCK_SZ / inode_size) as u32;
+        let mut inodes = BTreeMap::new();
+        let mut owners = BTreeMap::new();
+        let inode_bitmap = self.bitmap(self.inode_bitmap_start, self.inode_num as usize);
+        for inode in 0..self.inode_num {
+            if !inode_bitmap[inode as usize] {
+                continue;
+            }
+            let block = RawBlock::read(
+                &self.block_device,
+                self.inode_area_start + inode / inodes_per_block,
+            );
+            let disk_inode: &DiskInode = block.get((inode % inodes_per_block) as usize * inode_size);
+            let data = if disk_inode.size as usize > DiskInode::max_size() {
+                self.errors.push(FsError::BadSize { inode, size: disk_inode.size });
+                Vec::new()
+            } else {
+                self.inode_blocks(inode, disk_inode, &mut owners)
+            };
+            inodes.insert(inode, InodeInfo {
+                type_: disk_inode.type_(),
+                size: disk_inode.size,
+                nlink: disk_inode.nlink,
+                data,
+            });
+        }
+        // the data bitmap against the blocks actually used
+        let data_bitmap = self.bitmap(self.data_bitmap_start, self.data_area_blocks as usize);
+        for (bit, &marked) in data_bitmap.iter().enumerate() {
+            let block = self.data_area_start + bit as u32;
+            match (marked, owners.contains_key(&block)) {
+                (false, true) => self.errors.push(FsError::BlockNotMarked { block }),
+                (true, false) => self.errors.push(FsError::BlockLeaked { block }),
+                _ => {}
+            }
+        }
+        inodes
+    }
+    /// Get `(name, inode number)` of the entries in a directory,
+    /// leaving out the removed ones and those in unreadable blocks
+    fn dir_entries(&self, info: &InodeInfo) -> Vec<(String, u32)> {
+        let mut entries = Vec::new();
+        let per_block = BLOCK_SZ / DIRENT_SZ;
+        let count = info.size as usize / DIRENT_SZ;
+        for (i, block) in info.data.iter().enumerate() {
+            let block = match block {
+                Some(block) => RawBlock::read(&self.block_device, *block),
+                None => continue,
+            };
+            for j in 0..per_block.min(count.saturating_sub(i * per_block)) {
+                let raw = &block.bytes()[j * DIRENT_SZ..(j + 1) * DIRENT_SZ];
+                let name = &raw[..NAME_LENGTH_LIMIT + 1];
+                let len = name.iter().position(|&b| b == 0).unwrap_or(name.len());
+                if len == 0 {
+                    continue;
+                }
+                let name = String::from_utf8_lossy(&name[..len]).into_owned();
+                entries.push((name, *block.get::<u32>(j * DIRENT_SZ + NAME_LENGTH_LIMIT + 1)));
+            }
+        }
+        entries
+    }
+    /// Walk the directory tree from the root, checking every entry
+    /// and the link count of every inode it reaches
+    fn check_tree(&mut self, inodes: &BTreeMap<u32, InodeInfo>) {
+        match inodes.get(&0) {
+            Some(root) if root.type_ == DiskInodeType::Directory => {}
+            _ => {
+                self.errors.push(FsError::BadRoot);
+                return;
+            }
+        }
+        let mut entries_of: BTreeMap<u32, u32> = BTreeMap::new();
+        let mut reached = BTreeSet::new();
+        reached.insert(0);
+        let mut dirs = vec![0u32];
+        while let Some(dir) = dirs.pop() {
+            for (name, inode) in self.dir_entries(&inodes[&dir]) {
+                let info = match inodes.get(&inode) {
+                    Some(info) => info,
+                    None => {
+                        self.errors.push(FsError::DanglingEntry { dir, name, inode });
+                        continue;
+                    }
+                };
+                *entries_of.entry(inode).or_insert(0) += 1;
+                if name == "." || name == ".." {
+                    continue;
+                }
+                if reached.insert(inode) && info.type_ == DiskInodeType::Directory {
+                    dirs.push(inode);
+                }
+            }
+        }
+        for (&inode, info) in inodes.iter() {
+            if !reached.contains(&inode) {
+                self.errors.push(FsError::OrphanInode { inode });
+                continue;
+            }
+            let entries = entries_of.get(&inode).copied().unwrap_or(0);
+            if entries != info.nlink {
+                self.errors.push(FsError::LinkCountMismatch { inode, nlink: info.nlink, entries });
+            }
+        }
+    }
+}
+
+/// Check the easy-fs image on `block_device` and report every
+/// inconsistency found, an empty list for a sound image
+pub fn check_image(block_device: Arc<dyn BlockDevice>) -> Vec<FsError> {
+    let block = RawBlock::read(&block_device, 0);
+    let super_block: &SuperBlock = block.get(0);
+    if !super_block.is_valid() {
+        return vec![FsError::BadMagic];
+    }
+    // summed up in u64, the areas of a broken super block may overflow
+    let areas = [
+        1,
+        super_block.inode_bitmap_blocks as u64,
+        super_block.inode_area_blocks as u64,
+        super_block.data_bitmap_blocks as u64,
+        super_block.data_area_blocks as u64,
+        super_block.checksum_blocks as u64,
+    ];
+    if areas.iter().sum::<u64>() != super_block.total_blocks as u64
+        || areas[3] * (BLOCK_BITS as u64) < areas[4]
+        || (areas[5] != 0 && areas[5] < checksum_table_blocks(super_block.total_blocks) as u64)
+    {
+        return vec![FsError::BadLayout];
+    }
+    let inodes_per_block = (BLOCK_SZ / core::mem::size_of::<DiskInode>()) as u64;
+    let inode_bitmap_start = 1;
+    let inode_area_start = inode_bitmap_start + super_block.inode_bitmap_blocks;
+    let checksum_start = inode_area_start + super_block.inode_area_blocks;
+    let data_bitmap_start = checksum_start + super_block.checksum_blocks;
+    let data_area_start = data_bitmap_start + super_block.data_bitmap_blocks;
+    let mut checker = Checker {
+        block_device,
+        inode_bitmap_start,
+        inode_area_start,
+        inode_num: (areas[1] * BLOCK_BITS as u64).min(areas[2] * inodes_per_block) as u32,
+        data_bitmap_start,
+        data_area_start,
+        data_area_blocks: super_block.data_area_blocks,
+        checksum_table: (checksum_start, super_block.checksum_blocks),
+        errors: Vec::new(),
+    };
+    checker.check_checksums(super_block.total_blocks);
+    let inodes = checker.check_inodes();
+    checker.check_tree(&inodes);
+    checker.errors
+}
@@ -9,12 +9,12 @@ use alloc::vec::Vec;
 
 /// Magic number for sanity check
 const EFS_MAGIC: u32 = 0x3b800001;
-/// The max number of direct inodes
-const INODE_DIRECT_COUNT: usize = 28;
+/// The max number of direct inodes, chosen to keep a disk inode 128 bytes
+pub const INODE_DIRECT_COUNT: usize = 20;
 /// The max length of inode name
-const NAME_LENGTH_LIMIT: usize = 27;
+pub const NAME_LENGTH_LIMIT: usize = 27;
 /// The max number of indirect1 inodes
-const INODE_INDIRECT1_COUNT: usize = BLOCK_SZ / 4;
+pub const INODE_INDIRECT1_COUNT: usize = BLOCK_SZ / 4;
 /// The max number of indirect2 inodes
 const INODE_INDIRECT2_COUNT: usize = INODE_INDIRECT1_COUNT * INODE_INDIRECT1_COUNT;
 /// The upper bound of direct inode index
@@ -22,8 +22,9 @@ const DIRECT_BOUND: usize = INODE_DIRECT_COUNT;
 /// The upper bound of indirect1 inode index
 const INDIRECT1_BOUND: usize = DIRECT_BOUND + INODE_INDIRECT1_COUNT;
 /// The upper bound of indirect2 inode index
-#[allow(unused)]
 const INDIRECT2_BOUND: usize = INDIRECT1_BOUND + INODE_INDIRECT2_COUNT;
+/// Block id of a hole, block 0 holds the super block and is never data
+pub const NO_BLOCK: u32 = 0;
 
 /// Super block of a filesystem
 #[repr(C)]
@@ -34,6 +35,9 @@ pub struct SuperBlock {
     pub inode_area_blocks: u32,
     pub data_bitmap_blocks: u32,
     pub data_area_blocks: u32,
+    /// Blocks of the checksum table right after the inode area, 0 for an
+    /// image without checksums. Older images have a zero here as well
+    pub checksum_blocks: u32,
 }
 
 impl Debug for SuperBlock {
@@ -44,6 +48,7 @@ impl Debug for SuperBlock {
             .field("inode_area_blocks", &self.inode_area_blocks)
             .field("data_bitmap_blocks", &self.data_bitmap_blocks)
             .field("data_area_blocks", &self.data_area_blocks)
+            .field("checksum_blocks", &self.checksum_blocks)
             .finish()
     }
 }
@@ -57,6 +62,7 @@ impl SuperBlock {
         inode_area_blocks: u32,
         data_bitmap_blocks: u32,
         data_area_blocks: u32,
+        checksum_blocks: u32,
     ) {
         *self = Self {
             magic: EFS_MAGIC,
@@ -65,6 +71,7 @@ impl SuperBlock {
             inode_area_blocks,
             data_bitmap_blocks,
             data_area_blocks,
+            checksum_blocks,
         }
     }
     /// Check if a super block is valid using efs magic
@@ -74,10 +81,12 @@ impl SuperBlock {
 }
 
 /// Type of a disk inode
-#[derive(PartialEq)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum DiskInodeType {
     File,
     Directory,
+    /// Its data is the path it points to
+    Symlink,
 }
 
 /// A indirect block
@@ -92,6 +101,14 @@ pub struct DiskInode {
     pub direct: [u32; INODE_DIRECT_COUNT],
     pub indirect1: u32,
     pub indirect2: u32,
+    /// Number of directory entries referring to this inode
+    pub nlink: u32,
+    /// Time of last access in nanoseconds
+    pub atime: u64,
+    /// Time of last modification of the data in nanoseconds
+    pub mtime: u64,
+    /// Time of last change of the inode in nanoseconds
+    pub ctime: u64,
     type_: DiskInodeType,
 }
 
@@ -103,12 +120,29 @@ impl DiskInode {
         self.direct.iter_mut().for_each(|v| *v = 0);
         self.indirect1 = 0;
         self.indirect2 = 0;
+        self.nlink = 0;
+        self.atime = 0;
+        self.mtime = 0;
+        self.ctime = 0;
         self.type_ = type_;
     }
     /// Whether this inode is a directory
     pub fn is_dir(&self) -> bool {
         self.type_ == DiskInodeType::Directory
     }
+    /// Record that the data of this inode changes at `now`
+    pub fn touch_modified(&mut self, now: u64) {
+        self.mtime = now;
+        self.ctime = now;
+    }
+    /// Get the type of this inode
+    pub fn type_(&self) -> DiskInodeType {
+        self.type_
+    }
+    /// Whether this inode is a symbolic link
+    pub fn is_symlink(&self) -> bool {
+        self.type_ == DiskInodeType::Symlink
+    }
     /// Whether this inode is a file
     #[allow(unused)]
     pub fn is_file(&self) -> bool {
@@ -121,240 +155,287 @@ impl DiskInode {
     fn _data_blocks(size: u32) -> u32 {
         (size + BLOCK_SZ as u32 - 1) / BLOCK_SZ as u32
     }
-    /// Get the number of data blocks required for the given size of data
-    pub fn total_blocks(size: u32) -> u32 {
-        let data_blocks = Self::_data_blocks(size) as usize;
-        let mut total = data_blocks as usize;
-        // indirect1
-        if data_blocks > INODE_DIRECT_COUNT {
-            total += 1;
-        }
-        // indirect2
-        if data_blocks > INDIRECT1_BOUND {
-            total += 1;
-            // sub indirect1
-            total += (data_blocks - INDIRECT1_BOUND + INODE_INDIRECT1_COUNT - 1) / INODE_INDIRECT1_COUNT;
-        }
-        total as u32
-    }
-    /// Get the number of data blocks that have to be allocated given the new size of data
-    pub fn blocks_num_needed(&self, new_size: u32) -> u32 {
-        assert!(new_size >= self.size);
-        Self::total_blocks(new_size) - Self::total_blocks(self.size)
+    /// The max size of data a disk inode can hold
+    pub fn max_size() -> usize {
+        INDIRECT2_BOUND * BLOCK_SZ
     }
-    /// Get id of block given inner id
+    /// Get id of block given inner id, `NO_BLOCK` for a hole
     pub fn get_block_id(&self, inner_id: u32, block_device: &Arc<dyn BlockDevice>) -> u32 {
         let inner_id = inner_id as usize;
         if inner_id < INODE_DIRECT_COUNT {
             self.direct[inner_id]
         } else if inner_id < INDIRECT1_BOUND {
-            get_block_cache(self.indirect1 as usize, Arc::clone(block_device))
-                .lock()
-                .read(0, |indirect_block: &IndirectBlock| {
-                    indirect_block[inner_id - INODE_DIRECT_COUNT]
-                })
+            Self::index_entry(self.indirect1, inner_id - INODE_DIRECT_COUNT, block_device)
         } else {
             let last = inner_id - INDIRECT1_BOUND;
-            let indirect1 = get_block_cache(
-                self.indirect2 as usize,
-                Arc::clone(block_device)
-            )
-            .lock()
-            .read(0, |indirect2: &IndirectBlock| {
-                indirect2[last / INODE_INDIRECT1_COUNT]
-            });
-            get_block_cache(
-                indirect1 as usize,
-                Arc::clone(block_device)
-            )
-            .lock()
-            .read(0, |indirect1: &IndirectBlock| {
-                indirect1[last % INODE_INDIRECT1_COUNT]
-            })
+            let indirect1 = Self::index_entry(
+                self.indirect2,
+                last / INODE_INDIRECT1_COUNT,
+                block_device,
+            );
+            Self::index_entry(indirect1, last % INODE_INDIRECT1_COUNT, block_device)
         }
     }
-    /// Inncrease the size of current disk inode
-    pub fn increase_size(
+    /// Get the `i`-th block id in an index block, `NO_BLOCK` if the index block is missing
+    fn index_entry(index: u32, i: usize, block_device: &Arc<dyn BlockDevice>) -> u32 {
+        if index == NO_BLOCK {
+            return NO_BLOCK;
+        }
+        get_block_cache(index as usize, Arc::clone(block_device))
+            .lock()
+            .read(0, |indirect_block: &IndirectBlock| indirect_block[i])
+    }
+    /// Get id of block given inner id, allocating it and the index blocks
+    /// leading to it with `alloc` if it is a hole
+    fn get_or_alloc_block_id(
         &mut self,
-        new_size: u32,
-        new_blocks: Vec<u32>,
+        inner_id: u32,
+        alloc: &mut dyn FnMut() -> u32,
         block_device: &Arc<dyn BlockDevice>,
-    ) {
-        let mut current_blocks = self.data_blocks();
-        self.size = new_size;
-        let mut total_blocks = self.data_blocks();
-        let mut new_blocks = new_blocks.into_iter();
-        // fill direct
-        while current_blocks < total_blocks.min(INODE_DIRECT_COUNT as u32) {
-            self.direct[current_blocks as usize] = new_blocks.next().unwrap();
-            current_blocks += 1;
-        }
-        // alloc indirect1
-        if total_blocks > INODE_DIRECT_COUNT as u32{
-            if current_blocks == INODE_DIRECT_COUNT as u32 {
-                self.indirect1 = new_blocks.next().unwrap();
+    ) -> u32 {
+        let inner_id = inner_id as usize;
+        if inner_id < INODE_DIRECT_COUNT {
+            if self.direct[inner_id] == NO_BLOCK {
+                self.direct[inner_id] = alloc();
             }
-            current_blocks -= INODE_DIRECT_COUNT as u32;
-            total_blocks -= INODE_DIRECT_COUNT as u32;
-        } else {
-            return;
+            return self.direct[inner_id];
         }
-        // fill indirect1
-        get_block_cache(
-            self.indirect1 as usize,
-            Arc::clone(block_device)
-        )
-        .lock()
-        .modify(0, |indirect1: &mut IndirectBlock| {
-            while current_blocks < total_blocks.min(INODE_INDIRECT1_COUNT as u32) {
-                indirect1[current_blocks as usize] = new_blocks.next().unwrap();
-                current_blocks += 1;
-            }
-        });
-        // alloc indirect2
-        if total_blocks > INODE_INDIRECT1_COUNT as u32 {
-            if current_blocks == INODE_INDIRECT1_COUNT as u32 {
-                self.indirect2 = new_blocks.next().unwrap();
+        if inner_id < INDIRECT1_BOUND {
+            if self.indirect1 == NO_BLOCK {
+                self.indirect1 = alloc();
             }
-            current_blocks -= INODE_INDIRECT1_COUNT as u32;
-            total_blocks -= INODE_INDIRECT1_COUNT as u32;
-        } else {
-            return;
+            return Self::alloc_index_entry(
+                self.indirect1,
+                inner_id - INODE_DIRECT_COUNT,
+                alloc,
+                block_device,
+            );
         }
-        // fill indirect2 from (a0, b0) -> (a1, b1)
-        let mut a0 = current_blocks as usize / INODE_INDIRECT1_COUNT;
-        let mut b0 = current_blocks as usize % INODE_INDIRECT1_COUNT;
-        let a1 = total_blocks as usize / INODE_INDIRECT1_COUNT;
-        let b1 = total_blocks as usize % INODE_INDIRECT1_COUNT;
-        // alloc low-level indirect1
-        get_block_cache(
-            self.indirect2 as usize,
-            Arc::clone(block_device)
-        )
-        .lock()
-        .modify(0, |indirect2: &mut IndirectBlock| {
-            while (a0 < a1) || (a0 == a1 && b0 < b1) {
-                if b0 == 0 {
-                    indirect2[a0] = new_blocks.next().unwrap();
-                }
-                // fill current
-                get_block_cache(
-                    indirect2[a0] as usize,
-                    Arc::clone(block_device)
-                )
-                .lock()
-                .modify(0, |indirect1: &mut IndirectBlock| {
-                    indirect1[b0] = new_blocks.next().unwrap();
-                });
-                // move to next
-                b0 += 1;
-                if b0 == INODE_INDIRECT1_COUNT {
-                    b0 = 0;
-                    a0 += 1;
+        let last = inner_id - INDIRECT1_BOUND;
+        if self.indirect2 == NO_BLOCK {
+            self.indirect2 = alloc();
+        }
+        let indirect1 = Self::alloc_index_entry(
+            self.indirect2,
+            last / INODE_INDIRECT1_COUNT,
+            alloc,
+            block_device,
+        );
+        Self::alloc_index_entry(indirect1, last % INODE_INDIRECT1_COUNT, alloc, block_device)
+    }
+    /// Get the `i`-th block id in an index block, allocating it if missing.
+    /// Allocated blocks are all zeros, so a new index block holds only holes
+    fn alloc_index_entry(
+        index: u32,
+        i: usize,
+        alloc: &mut dyn FnMut() -> u32,
+        block_device: &Arc<dyn BlockDevice>,
+    ) -> u32 {
+        let block_id = Self::index_entry(index, i, block_device);
+        if block_id != NO_BLOCK {
+            return block_id;
+        }
+        let block_id = alloc();
+        get_block_cache(index as usize, Arc::clone(block_device))
+            .lock()
+            .modify(0, |indirect_block: &mut IndirectBlock| {
+                indirect_block[i] = block_id;
+            });
+        block_id
+    }
+    /// Get a copy of the block ids in an index block
+    fn index_entries(index: u32, block_device: &Arc<dyn BlockDevice>) -> IndirectBlock {
+        get_block_cache(index as usize, Arc::clone(block_device))
+            .lock()
+            .read(0, |indirect_block: &IndirectBlock| *indirect_block)
+    }
+    /// Give back the blocks at index `start` or above in an index block
+    fn free_entries_from(
+        index: u32,
+        start: usize,
+        v: &mut Vec<u32>,
+        block_device: &Arc<dyn BlockDevice>,
+    ) {
+        get_block_cache(index as usize, Arc::clone(block_device))
+            .lock()
+            .modify(0, |indirect_block: &mut IndirectBlock| {
+                for block_id in indirect_block.iter_mut().skip(start) {
+                    if *block_id != NO_BLOCK {
+                        v.push(core::mem::replace(block_id, NO_BLOCK));
+                    }
                 }
-            }
-        });
+            });
     }
-    /// Clear size to zero and return blocks that should be deallocated
-    /// and clear the block contents to zero later
-    pub fn clear_size(&mut self, block_device: &Arc<dyn BlockDevice>) -> Vec<u32> {
+    /// Give back every block at inner id `start` or above, including those
+    /// preallocated past the end, along with the index blocks left empty
+    fn free_blocks_from(&mut self, start: usize, block_device: &Arc<dyn BlockDevice>) -> Vec<u32> {
         let mut v: Vec<u32> = Vec::new();
-        let mut data_blocks = self.data_blocks() as usize;
-        self.size = 0;
-        let mut current_blocks = 0usize;
         // direct
-        while current_blocks < data_blocks.min(INODE_DIRECT_COUNT) {
-            v.push(self.direct[current_blocks]);
-            self.direct[current_blocks] = 0;
-            current_blocks += 1;
-        }
-        // indirect1 block
-        if data_blocks > INODE_DIRECT_COUNT {
-            v.push(self.indirect1);
-            data_blocks -= INODE_DIRECT_COUNT;
-            current_blocks = 0;
-        } else {
-            return v;
+        for block_id in self.direct.iter_mut().skip(start) {
+            if *block_id != NO_BLOCK {
+                v.push(core::mem::replace(block_id, NO_BLOCK));
+            }
         }
         // indirect1
-        get_block_cache(
-            self.indirect1 as usize,
-            Arc::clone(block_device),
-        )
-        .lock()
-        .modify(0, |indirect1: &mut IndirectBlock| {
-            while current_blocks < data_blocks.min(INODE_INDIRECT1_COUNT) {
-                v.push(indirect1[current_blocks]);
-                //indirect1[current_blocks] = 0;
-                current_blocks += 1;
+        if self.indirect1 != NO_BLOCK {
+            let first = start.saturating_sub(DIRECT_BOUND);
+            Self::free_entries_from(self.indirect1, first, &mut v, block_device);
+            if start <= DIRECT_BOUND {
+                v.push(core::mem::replace(&mut self.indirect1, NO_BLOCK));
             }
-        });
-        self.indirect1 = 0;
-        // indirect2 block
-        if data_blocks > INODE_INDIRECT1_COUNT {
-            v.push(self.indirect2);
-            data_blocks -= INODE_INDIRECT1_COUNT;
-        } else {
-            return v;
         }
-        // indirect2
-        assert!(data_blocks <= INODE_INDIRECT2_COUNT);
-        let a1 = data_blocks / INODE_INDIRECT1_COUNT;
-        let b1 = data_blocks % INODE_INDIRECT1_COUNT;
-        get_block_cache(
-            self.indirect2 as usize,
-            Arc::clone(block_device),
-        )
-        .lock()
-        .modify(0, |indirect2: &mut IndirectBlock| {
-            // full indirect1 blocks
-            for i in 0..a1 {
-                v.push(indirect2[i]);
-                get_block_cache(
-                    indirect2[i] as usize,
-                    Arc::clone(block_device),
-                )
+        // indirect2 and the low-level indirect1 blocks under it
+        if self.indirect2 != NO_BLOCK {
+            let first = start.saturating_sub(INDIRECT1_BOUND);
+            get_block_cache(self.indirect2 as usize, Arc::clone(block_device))
                 .lock()
-                .modify(0, |indirect1: &mut IndirectBlock| {
-                    for j in 0..INODE_INDIRECT1_COUNT {
-                        v.push(indirect1[j]);
-                        //indirect1[j] = 0;
+                .modify(0, |indirect2: &mut IndirectBlock| {
+                    for (a, indirect1) in indirect2.iter_mut().enumerate() {
+                        let base = a * INODE_INDIRECT1_COUNT;
+                        if *indirect1 == NO_BLOCK || base + INODE_INDIRECT1_COUNT <= first {
+                            continue;
+                        }
+                        Self::free_entries_from(
+                            *indirect1,
+                            first.saturating_sub(base),
+                            &mut v,
+                            block_device,
+                        );
+                        if first <= base {
+                            v.push(core::mem::replace(indirect1, NO_BLOCK));
+                        }
                     }
                 });
-                //indirect2[i] = 0;
+            if start <= INDIRECT1_BOUND {
+                v.push(core::mem::replace(&mut self.indirect2, NO_BLOCK));
             }
-            // last indirect1 block
-            if b1 > 0 {
-                v.push(indirect2[a1]);
-                get_block_cache(
-                    indirect2[a1] as usize,
-                    Arc::clone(block_device),
-                )
-                .lock()
-                .modify(0, |indirect1: &mut IndirectBlock| {
-                    for j in 0..b1 {
-                        v.push(indirect1[j]);
-                        //indirect1[j] = 0;
-                    }
-                });
-                //indirect2[a1] = 0;
-            }
-        });
-        self.indirect2 = 0;
+        }
         v
     }
-    /// Read data from current disk inode
+    /// Increase the size of current disk inode, the bytes past the old
+    /// size are a hole until written, taking no blocks
+    pub fn increase_size(&mut self, new_size: u32) {
+        assert!(new_size as usize <= Self::max_size());
+        self.size = self.size.max(new_size);
+    }
+    /// Decrease the size of current disk inode and return the blocks
+    /// no longer used, data past the new size is zeroed
+    pub fn decrease_size(
+        &mut self,
+        new_size: u32,
+        block_device: &Arc<dyn BlockDevice>,
+    ) -> Vec<u32> {
+        assert!(new_size <= self.size);
+        // zero the tail of the last block kept so that it never shows up again
+        let tail = new_size as usize % BLOCK_SZ;
+        if tail > 0 && new_size < self.size {
+            let block_id = self.get_block_id(new_size / BLOCK_SZ as u32, block_device);
+            if block_id != NO_BLOCK {
+                get_block_cache(block_id as usize, Arc::clone(block_device))
+                    .lock()
+                    .modify(0, |data_block: &mut DataBlock| {
+                        data_block[tail..].iter_mut().for_each(|p| *p = 0);
+                    });
+            }
+        }
+        self.size = new_size;
+        self.free_blocks_from(Self::_data_blocks(new_size) as usize, block_device)
+    }
+    /// Clear size to zero and return blocks that should be deallocated
+    /// and clear the block contents to zero later
+    pub fn clear_size(&mut self, block_device: &Arc<dyn BlockDevice>) -> Vec<u32> {
+        self.decrease_size(0, block_device)
+    }
+    /// Get the number of blocks current disk inode takes,
+    /// index blocks included and holes left out
+    pub fn allocated_blocks(&self, block_device: &Arc<dyn BlockDevice>) -> u32 {
+        let present = |block_id: &&u32| **block_id != NO_BLOCK;
+        let mut count = self.direct.iter().filter(present).count();
+        if self.indirect1 != NO_BLOCK {
+            count += 1 + Self::index_entries(self.indirect1, block_device).iter().filter(present).count();
+        }
+        if self.indirect2 != NO_BLOCK {
+            count += 1;
+            for &indirect1 in Self::index_entries(self.indirect2, block_device).iter().filter(present) {
+                count += 1 + Self::index_entries(indirect1, block_device).iter().filter(present).count();
+            }
+        }
+        count as u32
+    }
+    /// Get the number of blocks, index blocks included,
+    /// filling the holes at inner ids `[start, end)` takes
+    pub fn blocks_to_fill(&self, start: u32, end: u32, block_device: &Arc<dyn BlockDevice>) -> u32 {
+        let (start, end) = (start as usize, end as usize);
+        if start >= end {
+            return 0;
+        }
+        let mut count = (start..end)
+            .filter(|&inner_id| self.get_block_id(inner_id as u32, block_device) == NO_BLOCK)
+            .count();
+        if start < INDIRECT1_BOUND && end > DIRECT_BOUND && self.indirect1 == NO_BLOCK {
+            count += 1;
+        }
+        if end > INDIRECT1_BOUND {
+            if self.indirect2 == NO_BLOCK {
+                count += 1;
+            }
+            let first = (start.max(INDIRECT1_BOUND) - INDIRECT1_BOUND) / INODE_INDIRECT1_COUNT;
+            let last = (end - 1 - INDIRECT1_BOUND) / INODE_INDIRECT1_COUNT;
+            count += (first..=last)
+                .filter(|&a| Self::index_entry(self.indirect2, a, block_device) == NO_BLOCK)
+                .count();
+        }
+        count as u32
+    }
+    /// Fill the holes at inner ids `[start, end)` with blocks from `alloc`,
+    /// the blocks may lie past the end of current disk inode
+    pub fn fill_holes(
+        &mut self,
+        start: u32,
+        end: u32,
+        alloc: &mut dyn FnMut() -> u32,
+        block_device: &Arc<dyn BlockDevice>,
+    ) {
+        for inner_id in start..end {
+            self.get_or_alloc_block_id(inner_id, alloc, block_device);
+        }
+    }
+    /// Read data from current disk inode, holes read as zeros
     pub fn read_at(
         &self,
         offset: usize,
         buf: &mut [u8],
         block_device: &Arc<dyn BlockDevice>,
     ) -> usize {
+        self.read_blocks_at(offset, buf, block_device).0
+    }
+    /// Read data from current disk inode like `read_at`,
+    /// failing with the first block not matching its checksum
+    pub fn try_read_at(
+        &self,
+        offset: usize,
+        buf: &mut [u8],
+        block_device: &Arc<dyn BlockDevice>,
+    ) -> Result<usize, u32> {
+        match self.read_blocks_at(offset, buf, block_device) {
+            (read_size, None) => Ok(read_size),
+            (_, Some(block_id)) => Err(block_id),
+        }
+    }
+    /// Read data from current disk inode, also
+    /// returning the first damaged block read if any
+    fn read_blocks_at(
+        &self,
+        offset: usize,
+        buf: &mut [u8],
+        block_device: &Arc<dyn BlockDevice>,
+    ) -> (usize, Option<u32>) {
         let mut start = offset;
         let end = (offset + buf.len()).min(self.size as usize);
         if start >= end {
-            return 0;
+            return (0, None);
         }
+        let mut damaged = None;
         let mut start_block = start / BLOCK_SZ;
         let mut read_size = 0usize;
         loop {
@@ -364,34 +445,44 @@ impl DiskInode {
             // read and update read size
             let block_read_size = end_current_block - start;
             let dst = &mut buf[read_size..read_size + block_read_size];
-            get_block_cache(
-                self.get_block_id(start_block as u32, block_device) as usize,
-                Arc::clone(block_device),
-            )
-            .lock()
-            .read(0, |data_block: &DataBlock| {
-                let src = &data_block[start % BLOCK_SZ..start % BLOCK_SZ + block_read_size];
-                dst.copy_from_slice(src);
-            });
+            let block_id = self.get_block_id(start_block as u32, block_device);
+            if block_id == NO_BLOCK {
+                dst.iter_mut().for_each(|p| *p = 0);
+            } else {
+                let block_cache = get_block_cache(block_id as usize, Arc::clone(block_device));
+                let block_cache = block_cache.lock();
+                if block_cache.damaged() && damaged.is_none() {
+                    damaged = Some(block_id);
+                }
+                block_cache.read(0, |data_block: &DataBlock| {
+                    let src = &data_block[start % BLOCK_SZ..start % BLOCK_SZ + block_read_size];
+                    dst.copy_from_slice(src);
+                });
+            }
             read_size += block_read_size;
             // move to next block
             if end_current_block == end { break; }
             start_block += 1;
             start = end_current_block;
         }
-        read_size
+        (read_size, damaged)
     }
-    /// Write data into current disk inode
+    /// Write data into current disk inode, filling the holes it
+    /// writes into with blocks from `alloc`.
     /// size must be adjusted properly beforehand
     pub fn write_at(
         &mut self,
         offset: usize,
         buf: &[u8],
+        alloc: &mut dyn FnMut() -> u32,
         block_device: &Arc<dyn BlockDevice>,
     ) -> usize {
         let mut start = offset;
         let end = (offset + buf.len()).min(self.size as usize);
         assert!(start <= end);
+        if start == end {
+            return 0;
+        }
         let mut start_block = start / BLOCK_SZ;
         let mut write_size = 0usize;
         loop {
@@ -401,7 +492,7 @@ impl DiskInode {
             // write and update write size
             let block_write_size = end_current_block - start;
             get_block_cache(
-                self.get_block_id(start_block as u32, block_device) as usize,
+                self.get_or_alloc_block_id(start_block as u32, alloc, block_device) as usize,
                 Arc::clone(block_device)
             )
             .lock()
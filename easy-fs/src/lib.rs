@@ -8,12 +8,32 @@ mod efs;
 mod bitmap;
 mod vfs;
 mod block_cache;
+mod fsck;
+mod checksum;
 
 /// Use a block size of 512 bytes
 pub const BLOCK_SZ: usize = 512;
 pub use block_dev::BlockDevice;
-pub use efs::EasyFileSystem;
-pub use vfs::Inode;
+pub use checksum::crc32;
+pub use efs::{Clock, EasyFileSystem};
+pub use vfs::{Inode, ReadDir};
+pub use layout::DiskInodeType;
+pub use fsck::{check_image, FsError};
 use layout::*;
 use bitmap::Bitmap;
-use block_cache::{get_block_cache, block_cache_sync_all};
+pub use block_cache::{
+    BlockCacheManager,
+    BLOCK_CACHE_SIZE,
+    CacheMode,
+    cache_mode,
+    set_cache_mode,
+    set_block_cache_capacity,
+    set_readahead_window,
+    block_cache_sync_all,
+    block_cache_flush_expired,
+};
+use checksum::{checksum_table_blocks, ChecksumDevice};
+use block_cache::{
+    block_cache_drop_all, block_cache_sync, get_block_cache, get_zeroed_block_cache,
+    prefetch_blocks, readahead_window,
+};
@@ -4,23 +4,50 @@ use super::{
     DiskInodeType,
     DirEntry,
     EasyFileSystem,
+    FsError,
     DIRENT_SZ,
+    NAME_LENGTH_LIMIT,
+    NO_BLOCK,
+    BLOCK_SZ,
     get_block_cache,
-    block_cache_sync_all,
+    block_cache_sync,
+    prefetch_blocks,
+    readahead_window,
 };
+use alloc::collections::BTreeMap;
 use alloc::sync::Arc;
 use alloc::string::String;
+use alloc::vec;
 use alloc::vec::Vec;
-use spin::{Mutex, MutexGuard};
-
-use crate::BLOCK_SZ;
+use spin::{Mutex, MutexGuard, RwLock};
 
 /// Virtual filesystem layer over easy-fs
+///
+/// The data of an inode, its size included, is guarded by a lock shared by
+/// all its vfs inodes, taken for reading by readers and for writing by the
+/// writers of the data. It is always taken before the filesystem lock and
+/// never held while taking the lock of another inode, so that going through
+/// a directory and then changing it can't deadlock. Renaming is the only
+/// exception, taking the locks of two directories in the order of their ids
 pub struct Inode {
     block_id: usize,
     block_offset: usize,
     fs: Arc<Mutex<EasyFileSystem>>,
     block_device: Arc<dyn BlockDevice>,
+    lock: Arc<RwLock<()>>,
+    readahead: Mutex<ReadAhead>,
+    /// The entries of a directory by name, `name -> (index, inode id)`,
+    /// built on the first lookup and kept up to date by whoever changes
+    /// the entries, which takes the lock of the data for writing
+    index: Mutex<Option<BTreeMap<String, (usize, u32)>>>,
+}
+
+/// How far a sequential reader of an inode has got
+struct ReadAhead {
+    /// Offset right after the last read, where a sequential read starts
+    next_offset: Option<usize>,
+    /// Inner id of the first block not read ahead yet
+    ahead: usize,
 }
 
 impl Inode {
@@ -30,12 +57,19 @@ impl Inode {
         block_offset: usize,
         fs: Arc<Mutex<EasyFileSystem>>,
         block_device: Arc<dyn BlockDevice>,
+        lock: Arc<RwLock<()>>,
     ) -> Self {
         Self {
             block_id: block_id as usize,
             block_offset,
             fs,
             block_device,
+            lock,
+            readahead: Mutex::new(ReadAhead {
+                next_offset: None,
+                ahead: 0,
+            }),
+            index: Mutex::new(None),
         }
     }
     /// Call a function over a disk inode to read it
@@ -58,9 +92,29 @@ impl Inode {
         name: &str,
         disk_inode: &DiskInode,
     ) -> Option<u32> {
-        // assert it is a directory
-        assert!(disk_inode.is_dir());
+        self.find_dirent(name, disk_inode).map(|(_, inode_id)| inode_id)
+    }
+    /// Find the entry `name` under a disk inode,
+    /// returns its index and the inode id it refers to
+    fn find_dirent(
+        &self,
+        name: &str,
+        disk_inode: &DiskInode,
+    ) -> Option<(usize, u32)> {
+        // only a directory contains entries
+        if !disk_inode.is_dir() {
+            return None;
+        }
+        let mut index = self.index.lock();
+        index
+            .get_or_insert_with(|| self.build_index(disk_inode))
+            .get(name)
+            .copied()
+    }
+    /// Scan the entries of a directory for its index, leaving out removed ones
+    fn build_index(&self, disk_inode: &DiskInode) -> BTreeMap<String, (usize, u32)> {
         let file_count = (disk_inode.size as usize) / DIRENT_SZ;
+        let mut index = BTreeMap::new();
         let mut dirent = DirEntry::empty();
         for i in 0..file_count {
             assert_eq!(
@@ -71,54 +125,188 @@ impl Inode {
                 ),
                 DIRENT_SZ,
             );
-            if dirent.name() == name {
-                return Some(dirent.inode_number() as u32);
+            if !dirent.name().is_empty() {
+                index.insert(String::from(dirent.name()), (i, dirent.inode_number() as u32));
+            }
+        }
+        index
+    }
+    /// Record in the index, if built, that entry `i` is `name` now
+    fn index_insert(&self, name: &str, i: usize, inode_id: u32) {
+        if let Some(index) = self.index.lock().as_mut() {
+            index.insert(String::from(name), (i, inode_id));
+        }
+    }
+    /// Record in the index, if built, that the entry `name` is removed
+    fn index_remove(&self, name: &str) {
+        if let Some(index) = self.index.lock().as_mut() {
+            index.remove(name);
+        }
+    }
+    /// Move the entries of a directory over the removed ones before them,
+    /// giving back the blocks left past the last entry. Only done while
+    /// nobody has the directory open, as it shifts the entries under the
+    /// offset of a reader going through them
+    fn compact_entries(&self, fs: &mut MutexGuard<EasyFileSystem>) {
+        let compacted = self.modify_disk_inode(|disk_inode| {
+            if !disk_inode.is_dir() {
+                return false;
+            }
+            let file_count = (disk_inode.size as usize) / DIRENT_SZ;
+            let mut live = 0;
+            let mut dirent = DirEntry::empty();
+            for i in 0..file_count {
+                assert_eq!(
+                    disk_inode.read_at(
+                        DIRENT_SZ * i,
+                        dirent.as_bytes_mut(),
+                        &self.block_device,
+                    ),
+                    DIRENT_SZ,
+                );
+                if dirent.name().is_empty() {
+                    continue;
+                }
+                if live < i {
+                    disk_inode.write_at(
+                        DIRENT_SZ * live,
+                        dirent.as_bytes(),
+                        &mut || fs.alloc_data(),
+                        &self.block_device,
+                    );
+                }
+                live += 1;
+            }
+            if live == file_count {
+                return false;
+            }
+            for data_block in disk_inode.decrease_size((live * DIRENT_SZ) as u32, &self.block_device) {
+                fs.dealloc_data(data_block);
             }
+            true
+        });
+        if compacted {
+            *self.index.lock() = None;
+        }
+    }
+    /// Get the vfs inode of an inode, the one somebody already holds
+    /// if any, so that all the users of an inode share it
+    fn get_inode(&self, inode_id: u32, fs: &mut MutexGuard<EasyFileSystem>) -> Arc<Inode> {
+        if let Some(inode) = fs.cached_inode(inode_id) {
+            return inode;
         }
-        None
+        let (block_id, block_offset) = fs.get_disk_inode_pos(inode_id);
+        let inode = Arc::new(Self::new(
+            block_id,
+            block_offset,
+            self.fs.clone(),
+            self.block_device.clone(),
+            fs.inode_lock(inode_id),
+        ));
+        fs.cache_inode(inode_id, &inode);
+        inode
     }
     /// Find inode under current inode by name
     pub fn find(&self, name: &str) -> Option<Arc<Inode>> {
-        let fs = self.fs.lock();
-        self.read_disk_inode(|disk_inode| {
-            self.find_inode_id(name, disk_inode)
-            .map(|inode_id| {
-                let (block_id, block_offset) = fs.get_disk_inode_pos(inode_id);
-                Arc::new(Self::new(
-                    block_id,
-                    block_offset,
-                    self.fs.clone(),
-                    self.block_device.clone(),
-                ))
-            })
-        })
+        let _guard = self.lock.read();
+        let mut fs = self.fs.lock();
+        let inode_id = self.read_disk_inode(|disk_inode| self.find_inode_id(name, disk_inode))?;
+        Some(self.get_inode(inode_id, &mut fs))
     }
-    /// Increase the size of a disk inode
-    fn increase_size(
+    /// Fill an empty directory with the `.` and `..` entries
+    fn init_dir_entries(
         &self,
-        new_size: u32,
         disk_inode: &mut DiskInode,
+        inode_id: u32,
+        parent_inode_id: u32,
         fs: &mut MutexGuard<EasyFileSystem>,
     ) {
-        if new_size < disk_inode.size {
-            return;
+        disk_inode.increase_size((2 * DIRENT_SZ) as u32);
+        disk_inode.write_at(
+            0,
+            DirEntry::new(".", inode_id).as_bytes(),
+            &mut || fs.alloc_data(),
+            &self.block_device,
+        );
+        disk_inode.write_at(
+            DIRENT_SZ,
+            DirEntry::new("..", parent_inode_id).as_bytes(),
+            &mut || fs.alloc_data(),
+            &self.block_device,
+        );
+    }
+    /// Initialize current inode as the root directory, which is its own parent
+    pub(crate) fn init_root_dir(&self) {
+        let _guard = self.lock.write();
+        let mut fs = self.fs.lock();
+        let inode_id = fs.get_inode_id(self.block_id as u32, self.block_offset);
+        self.modify_disk_inode(|disk_inode| {
+            self.init_dir_entries(disk_inode, inode_id, inode_id, &mut fs);
+            // both `.` and `..`
+            disk_inode.nlink = 2;
+            disk_inode.atime = fs.now();
+            disk_inode.touch_modified(fs.now());
+        });
+    }
+    /// Whether current inode is a directory
+    pub fn is_dir(&self) -> bool {
+        let _fs = self.fs.lock();
+        self.read_disk_inode(|disk_inode| disk_inode.is_dir())
+    }
+    /// Whether current inode is a symbolic link
+    pub fn is_symlink(&self) -> bool {
+        let _fs = self.fs.lock();
+        self.read_disk_inode(|disk_inode| disk_inode.is_symlink())
+    }
+    /// Create a regular file under current inode by name
+    pub fn create(&self, name: &str) -> Option<Arc<Inode>> {
+        self.create_inode(name, DiskInodeType::File)
+    }
+    /// Create a directory under current inode by name
+    pub fn create_dir(&self, name: &str) -> Option<Arc<Inode>> {
+        self.create_inode(name, DiskInodeType::Directory)
+    }
+    /// Create a symbolic link under current inode by name,
+    /// which stores `target` as its data
+    pub fn create_symlink(&self, name: &str, target: &str) -> Option<Arc<Inode>> {
+        if target.is_empty() {
+            return None;
+        }
+        // the blocks of the target, an index block for a long one
+        // and a block for the entry in current directory
+        let needed = (target.len() + BLOCK_SZ - 1) / BLOCK_SZ + 2;
+        if self.fs.lock().free_data_blocks() < needed {
+            return None;
+        }
+        let inode = self.create_inode(name, DiskInodeType::Symlink)?;
+        if inode.write_at(0, target.as_bytes()) < target.len() {
+            // too long for an inode, don't leave a truncated target behind
+            self.unlinkat(name);
+            return None;
         }
-        let blocks_needed = disk_inode.blocks_num_needed(new_size);
-        let mut v: Vec<u32> = Vec::new();
-        for _ in 0..blocks_needed {
-            v.push(fs.alloc_data());
+        Some(inode)
+    }
+    /// Get the target of current inode if it is a symbolic link
+    pub fn read_link(&self) -> Option<String> {
+        if !self.is_symlink() {
+            return None;
         }
-        disk_inode.increase_size(new_size, v, &self.block_device);
+        let mut buf = vec![0u8; self.size()];
+        let len = self.read_at(0, &mut buf);
+        buf.truncate(len);
+        String::from_utf8(buf).ok()
     }
-    /// Create inode under current inode by name
-    pub fn create(&self, name: &str) -> Option<Arc<Inode>> {
+    /// Create inode of the given type under current inode by name
+    fn create_inode(&self, name: &str, type_: DiskInodeType) -> Option<Arc<Inode>> {
+        if name.is_empty() || name.len() > NAME_LENGTH_LIMIT || name.contains('/') {
+            return None;
+        }
+        let _guard = self.lock.write();
         let mut fs = self.fs.lock();
-        if self.modify_disk_inode(|root_inode| {
-            // assert it is a directory
-            assert!(root_inode.is_dir());
-            // has the file been created?
-            self.find_inode_id(name, root_inode)
-        }).is_some() {
+        if self.read_disk_inode(|root_inode| {
+            // has the file been created? or is it not a directory at all?
+            !root_inode.is_dir() || self.find_inode_id(name, root_inode).is_some()
+        }) {
             return None;
         }
         // create a new file
@@ -127,40 +315,55 @@ impl Inode {
         // initialize inode
         let (new_inode_block_id, new_inode_block_offset) 
             = fs.get_disk_inode_pos(new_inode_id);
+        let parent_inode_id = fs.get_inode_id(self.block_id as u32, self.block_offset);
+        let now = fs.now();
         get_block_cache(
             new_inode_block_id as usize,
             Arc::clone(&self.block_device)
         ).lock().modify(new_inode_block_offset, |new_inode: &mut DiskInode| {
-            new_inode.initialize(DiskInodeType::File);
+            new_inode.initialize(type_);
+            new_inode.atime = now;
+            new_inode.touch_modified(now);
+            // referred to by its entry in current directory
+            new_inode.nlink = 1;
+            if new_inode.is_dir() {
+                self.init_dir_entries(new_inode, new_inode_id, parent_inode_id, &mut fs);
+                // and by its own `.`
+                new_inode.nlink += 1;
+            }
         });
         self.modify_disk_inode(|root_inode| {
             // append file in the dirent
             let file_count = (root_inode.size as usize) / DIRENT_SZ;
             let new_size = (file_count + 1) * DIRENT_SZ;
             // increase size
-            self.increase_size(new_size as u32, root_inode, &mut fs);
+            root_inode.increase_size(new_size as u32);
             // write dirent
             let dirent = DirEntry::new(name, new_inode_id);
             root_inode.write_at(
                 file_count * DIRENT_SZ,
                 dirent.as_bytes(),
+                &mut || fs.alloc_data(),
                 &self.block_device,
             );
+            self.index_insert(name, file_count, new_inode_id);
+            // the `..` of a new directory refers to current directory
+            if type_ == DiskInodeType::Directory {
+                root_inode.nlink += 1;
+            }
+            root_inode.touch_modified(now);
         });
 
-        let (block_id, block_offset) = fs.get_disk_inode_pos(new_inode_id);
-        block_cache_sync_all();
-        // return inode
-        Some(Arc::new(Self::new(
-            block_id,
-            block_offset,
-            self.fs.clone(),
-            self.block_device.clone(),
-        )))
+        // a vfs inode left over by a removed inode of the same id
+        // indexes the entries it used to have
+        let inode = self.get_inode(new_inode_id, &mut fs);
+        *inode.index.lock() = None;
+        Some(inode)
         // release efs lock automatically by compiler
     }
     /// List inodes under current inode
     pub fn ls(&self) -> Vec<String> {
+        let _guard = self.lock.read();
         let _fs = self.fs.lock();
         self.read_disk_inode(|disk_inode| {
             let file_count = (disk_inode.size as usize) / DIRENT_SZ;
@@ -180,60 +383,262 @@ impl Inode {
             v
         })
     }
+    /// Iterate over the entries of current directory
+    pub fn read_dir(&self) -> ReadDir<'_> {
+        self.read_dir_at(0)
+    }
+    /// Iterate over the entries of current directory,
+    /// starting at byte `offset` returned by [`ReadDir::offset`]
+    pub fn read_dir_at(&self, offset: usize) -> ReadDir<'_> {
+        ReadDir {
+            dir: self,
+            offset,
+        }
+    }
+    /// Get the size of current inode in bytes
+    pub fn size(&self) -> usize {
+        let _guard = self.lock.read();
+        let _fs = self.fs.lock();
+        self.read_disk_inode(|disk_inode| disk_inode.size as usize)
+    }
     /// Read data from current inode
     pub fn read_at(&self, offset: usize, buf: &mut [u8]) -> usize {
-        let _fs = self.fs.lock();
-        self.read_disk_inode(|disk_inode| {
-            disk_inode.read_at(offset, buf, &self.block_device)
+        let _guard = self.lock.read();
+        let fs = self.fs.lock();
+        self.modify_disk_inode(|disk_inode| {
+            disk_inode.atime = fs.now();
+            let size = disk_inode.read_at(offset, buf, &self.block_device);
+            self.read_ahead(offset, size, disk_inode);
+            size
+        })
+    }
+    /// Read data from current inode like `read_at`, failing
+    /// if a block read does not match its checksum
+    pub fn try_read_at(&self, offset: usize, buf: &mut [u8]) -> Result<usize, FsError> {
+        let _guard = self.lock.read();
+        let fs = self.fs.lock();
+        self.modify_disk_inode(|disk_inode| {
+            disk_inode.atime = fs.now();
+            let size = disk_inode
+                .try_read_at(offset, buf, &self.block_device)
+                .map_err(|block| FsError::ChecksumMismatch { block })?;
+            self.read_ahead(offset, size, disk_inode);
+            Ok(size)
         })
     }
-    /// Write data to current inode
+    /// Once a read continues where the last one ends, read the blocks
+    /// following it into the cache before they are asked for, a window
+    /// at a time when the reader gets to the end of the last window
+    fn read_ahead(&self, offset: usize, size: usize, disk_inode: &DiskInode) {
+        let mut readahead = self.readahead.lock();
+        let sequential = readahead.next_offset == Some(offset);
+        readahead.next_offset = Some(offset + size);
+        if !sequential {
+            // a seek, start over
+            readahead.ahead = 0;
+            return;
+        }
+        let window = readahead_window();
+        if size == 0 || window == 0 {
+            return;
+        }
+        let next = (offset + size - 1) / BLOCK_SZ + 1;
+        if next < readahead.ahead {
+            return;
+        }
+        let end = (next + window).min(disk_inode.data_blocks() as usize);
+        let block_ids: Vec<usize> = (next..end)
+            .map(|inner_id| disk_inode.get_block_id(inner_id as u32, &self.block_device))
+            .filter(|&block_id| block_id != NO_BLOCK)
+            .map(|block_id| block_id as usize)
+            .collect();
+        readahead.ahead = next + window;
+        prefetch_blocks(&block_ids, Arc::clone(&self.block_device));
+    }
+    /// Whether there are enough free blocks to back `[offset, offset + len)`
+    fn has_room_for(&self, disk_inode: &DiskInode, offset: usize, len: usize, fs: &EasyFileSystem) -> bool {
+        let start_block = (offset / BLOCK_SZ) as u32;
+        let end_block = ((offset + len + BLOCK_SZ - 1) / BLOCK_SZ) as u32;
+        disk_inode.blocks_to_fill(start_block, end_block, &self.block_device) as usize
+            <= fs.free_data_blocks()
+    }
+    /// Write data to current inode, stopping at the max size of an inode.
+    /// Writes nothing and returns 0 at or past the max size, or if there
+    /// are not enough free blocks for the data
     pub fn write_at(&self, offset: usize, buf: &[u8]) -> usize {
+        if offset >= DiskInode::max_size() {
+            return 0;
+        }
+        let buf = &buf[..buf.len().min(DiskInode::max_size() - offset)];
+        let _guard = self.lock.write();
         let mut fs = self.fs.lock();
         let size = self.modify_disk_inode(|disk_inode| {
-            self.increase_size((offset + buf.len()) as u32, disk_inode, &mut fs);
-            disk_inode.write_at(offset, buf, &self.block_device)
+            if !self.has_room_for(disk_inode, offset, buf.len(), &fs) {
+                return 0;
+            }
+            disk_inode.increase_size((offset + buf.len()) as u32);
+            disk_inode.touch_modified(fs.now());
+            disk_inode.write_at(offset, buf, &mut || fs.alloc_data(), &self.block_device)
         });
-        block_cache_sync_all();
         size
     }
+    /// Write data to the end of current inode, with no other writer
+    /// coming in between finding the end and writing there.
+    /// Returns the offset written at and the number of bytes written,
+    /// none if the inode is full or there are not enough free blocks
+    pub fn append(&self, buf: &[u8]) -> (usize, usize) {
+        let _guard = self.lock.write();
+        let mut fs = self.fs.lock();
+        let ret = self.modify_disk_inode(|disk_inode| {
+            let offset = disk_inode.size as usize;
+            let buf = &buf[..buf.len().min(DiskInode::max_size().saturating_sub(offset))];
+            if !self.has_room_for(disk_inode, offset, buf.len(), &fs) {
+                return (offset, 0);
+            }
+            disk_inode.increase_size((offset + buf.len()) as u32);
+            disk_inode.touch_modified(fs.now());
+            (offset, disk_inode.write_at(offset, buf, &mut || fs.alloc_data(), &self.block_device))
+        });
+        ret
+    }
+    /// Set the size of current inode, extended bytes read as zero
+    /// and the blocks past a smaller size are given back.
+    /// Returns false if the size is beyond the max size of an inode
+    pub fn truncate(&self, new_size: usize) -> bool {
+        if new_size > DiskInode::max_size() {
+            return false;
+        }
+        let _guard = self.lock.write();
+        let mut fs = self.fs.lock();
+        self.modify_disk_inode(|disk_inode| {
+            if new_size >= disk_inode.size as usize {
+                disk_inode.increase_size(new_size as u32);
+            } else {
+                for data_block in disk_inode.decrease_size(new_size as u32, &self.block_device) {
+                    fs.dealloc_data(data_block);
+                }
+            }
+            disk_inode.touch_modified(fs.now());
+        });
+        true
+    }
+    /// Allocate the blocks backing `[offset, offset + len)` up front so
+    /// that writing there takes no more blocks, growing the size to cover
+    /// the range unless `keep_size`. All or nothing, returns false leaving
+    /// current inode untouched if the range is beyond the max size of an
+    /// inode or there are not enough free blocks
+    pub fn fallocate(&self, offset: usize, len: usize, keep_size: bool) -> bool {
+        let end = match offset.checked_add(len) {
+            Some(end) if end <= DiskInode::max_size() => end,
+            _ => return false,
+        };
+        let _guard = self.lock.write();
+        let mut fs = self.fs.lock();
+        let allocated = self.modify_disk_inode(|disk_inode| {
+            let start_block = (offset / BLOCK_SZ) as u32;
+            let end_block = ((end + BLOCK_SZ - 1) / BLOCK_SZ) as u32;
+            let needed = disk_inode.blocks_to_fill(start_block, end_block, &self.block_device);
+            if needed as usize > fs.free_data_blocks() {
+                return false;
+            }
+            disk_inode.fill_holes(start_block, end_block, &mut || fs.alloc_data(), &self.block_device);
+            if !keep_size && end > disk_inode.size as usize {
+                disk_inode.increase_size(end as u32);
+                disk_inode.touch_modified(fs.now());
+            } else {
+                disk_inode.ctime = fs.now();
+            }
+            true
+        });
+        allocated
+    }
     /// Clear the data in current inode
     pub fn clear(&self) {
+        let _guard = self.lock.write();
         let mut fs = self.fs.lock();
         self.modify_disk_inode(|disk_inode| {
-            let size = disk_inode.size;
             let data_blocks_dealloc = disk_inode.clear_size(&self.block_device);
-            assert!(data_blocks_dealloc.len() == DiskInode::total_blocks(size) as usize);
             for data_block in data_blocks_dealloc.into_iter() {
                 fs.dealloc_data(data_block);
             }
+            disk_inode.touch_modified(fs.now());
         });
-        block_cache_sync_all();
+    }
+
+    /// Get the id of current inode
+    pub fn inode_id(&self) -> u32 {
+        self.fs.lock().get_inode_id(self.block_id as u32, self.block_offset)
     }
 
     // custom method
-    pub fn linkat(&self, old_name: &str, new_name: &str) {
+    /// Create a hard link to `inode` named `new_name` under current inode,
+    /// fails if the name is taken already
+    pub fn linkat(&self, new_name: &str, inode: &Inode) -> bool {
         // similar with create method but create no new inode
+        if new_name.is_empty() || new_name.len() > NAME_LENGTH_LIMIT || new_name.contains('/') {
+            return false;
+        }
+        let new_inode_id = inode.inode_id();
+        let _guard = self.lock.write();
         let mut fs = self.fs.lock();
-        self.modify_disk_inode(|root_inode| {
+        let linked = self.modify_disk_inode(|root_inode| {
+            if !root_inode.is_dir() || self.find_inode_id(new_name, root_inode).is_some() {
+                return false;
+            }
             // append file in the dirent
             let file_count = (root_inode.size as usize) / DIRENT_SZ;
             let new_size = (file_count + 1) * DIRENT_SZ;
             // increase size
-            self.increase_size(new_size as u32, root_inode, &mut fs);
+            root_inode.increase_size(new_size as u32);
             // write dirent
-            let new_inode_id = self.find_inode_id(old_name, root_inode).unwrap();
             let dirent = DirEntry::new(new_name, new_inode_id);
             root_inode.write_at(
                 file_count * DIRENT_SZ,
                 dirent.as_bytes(),
+                &mut || fs.alloc_data(),
                 &self.block_device,
             );
+            self.index_insert(new_name, file_count, new_inode_id);
+            root_inode.touch_modified(fs.now());
+            true
         });
+        // `inode` may live in the same block as current inode,
+        // so it is only touched after the block above is released
+        if linked {
+            inode.modify_disk_inode(|disk_inode| {
+                disk_inode.nlink += 1;
+                disk_inode.ctime = fs.now();
+            });
+        }
+        linked
+    }
+    /// Whether current inode is a directory holding nothing but `.` and `..`
+    fn is_empty_dir(&self) -> bool {
+        self.is_dir()
+            && self
+                .ls()
+                .iter()
+                .all(|name| name.is_empty() || name == "." || name == "..")
     }
+    /// Remove the entry `name` from current inode,
+    /// a directory can only be removed when it is empty.
+    /// The inode is reclaimed once the last link is gone
     pub fn unlinkat(&self, name: &str) -> isize {
+        if name == "." || name == ".." {
+            return -1;
+        }
+        let inode = match self.find(name) {
+            Some(inode) => inode,
+            None => return -1,
+        };
+        let is_dir = inode.is_dir();
+        if is_dir && !inode.is_empty_dir() {
+            return -1;
+        }
         // similar with find
         let mut flag: isize = -1;
+        let guard = self.lock.write();
+        let mut fs = self.fs.lock();
         self.modify_disk_inode(|disk_inode| {
             let file_count = (disk_inode.size as usize) / DIRENT_SZ;
             let mut dirent = DirEntry::empty();
@@ -251,48 +656,342 @@ impl Inode {
                     disk_inode.write_at(
                         DIRENT_SZ * i,
                         DirEntry::empty().as_bytes(), 
+                        &mut || fs.alloc_data(),
                         &self.block_device,
                     );
+                    disk_inode.touch_modified(fs.now());
                 }
             }
         });
+        if flag != 0 {
+            return flag;
+        }
+        self.index_remove(name);
+        let nlink = inode.modify_disk_inode(|disk_inode| {
+            disk_inode.nlink -= 1;
+            disk_inode.ctime = fs.now();
+            if is_dir {
+                // its `.` is gone as well
+                disk_inode.nlink -= 1;
+            }
+            disk_inode.nlink
+        });
+        if is_dir {
+            // so is the reference from its `..` to current directory
+            self.modify_disk_inode(|disk_inode| disk_inode.nlink -= 1);
+        }
+        // the removed entry stays in place while a reader may be going
+        // through current directory, and goes once the last one is done
+        let dir_id = fs.get_inode_id(self.block_id as u32, self.block_offset);
+        if !fs.is_opened(dir_id) {
+            self.compact_entries(&mut fs);
+        }
+        // an inode still in use is reclaimed by its last user
+        let inode_id = fs.get_inode_id(inode.block_id as u32, inode.block_offset);
+        let reclaim = nlink == 0 && !fs.defer_reclaim(inode_id);
+        drop(fs);
+        drop(guard);
+        if reclaim {
+            inode.reclaim();
+        }
         flag
     }
-    pub fn stat(&self, root_inode: &Arc<Inode>) -> (u64, u32, u32) {
-        let inode_size = core::mem::size_of::<DiskInode>();
-        let inodes_per_block = (BLOCK_SZ / inode_size) as usize;
+    /// Whether current directory is the directory `inode_id` or lies under it
+    fn is_within(&self, inode_id: u32) -> bool {
+        let mut cur_id = self.inode_id();
+        let mut parent = self.find("..");
+        loop {
+            if cur_id == inode_id {
+                return true;
+            }
+            let dir = match parent {
+                Some(dir) => dir,
+                None => return false,
+            };
+            let parent_id = dir.inode_id();
+            // the root is its own parent
+            if parent_id == cur_id {
+                return false;
+            }
+            cur_id = parent_id;
+            parent = dir.find("..");
+        }
+    }
+    /// Move the entry `old_name` of current inode to `new_name` under
+    /// `new_dir` in one go, replacing the entry already there, whose inode
+    /// is reclaimed once its last link is gone. A directory can only
+    /// replace an empty directory and can't be moved under itself.
+    /// Nothing changes if both names refer to the same inode
+    pub fn rename(&self, old_name: &str, new_dir: &Inode, new_name: &str) -> bool {
+        let is_dot = |name: &str| name == "." || name == "..";
+        if is_dot(old_name)
+            || is_dot(new_name)
+            || new_name.is_empty()
+            || new_name.len() > NAME_LENGTH_LIMIT
+            || new_name.contains('/')
+        {
+            return false;
+        }
+        let inode = match self.find(old_name) {
+            Some(inode) => inode,
+            None => return false,
+        };
+        if !new_dir.is_dir() {
+            return false;
+        }
+        let inode_id = inode.inode_id();
+        let is_dir = inode.is_dir();
+        let target = new_dir.find(new_name);
+        let target_id = target.as_ref().map(|target| target.inode_id());
+        if let Some(target) = &target {
+            if target_id == Some(inode_id) {
+                return true;
+            }
+            if target.is_dir() != is_dir || (is_dir && !target.is_empty_dir()) {
+                return false;
+            }
+        }
+        if is_dir && new_dir.is_within(inode_id) {
+            return false;
+        }
+        let dir_id = self.inode_id();
+        let new_dir_id = new_dir.inode_id();
+        // the locks of the two directories are taken in the order of their ids
+        let guards = if dir_id == new_dir_id {
+            (self.lock.write(), None)
+        } else if dir_id < new_dir_id {
+            let first = self.lock.write();
+            (first, Some(new_dir.lock.write()))
+        } else {
+            let first = new_dir.lock.write();
+            (self.lock.write(), Some(first))
+        };
         let mut fs = self.fs.lock();
-        let ino: u64 = ((self.block_id - fs.inode_area_start_block as usize) * inodes_per_block + self.block_offset / inode_size) as u64;
-    
-        let mut nlink: u32 = 0;
-        root_inode.read_disk_inode(|root_inode| {
-            let file_count = (root_inode.size as usize) / DIRENT_SZ;
-            let mut dirent = DirEntry::empty();
-            for i in 0..file_count {
-                assert_eq!(
-                    root_inode.read_at(
-                        DIRENT_SZ * i, 
-                        dirent.as_bytes_mut(), 
-                        &self.block_device,
-                    ),
-                    DIRENT_SZ,
-                );
-                if dirent.inode_number() == ino as u32 {
-                    nlink += 1;
+        let now = fs.now();
+        // both entries must still be what they were found to be
+        let old_entry = self.read_disk_inode(|disk_inode| self.find_dirent(old_name, disk_inode));
+        let old_index = match old_entry {
+            Some((index, id)) if id == inode_id => index,
+            _ => return false,
+        };
+        let replaced =
+            new_dir.read_disk_inode(|disk_inode| new_dir.find_dirent(new_name, disk_inode));
+        if replaced.map(|(_, id)| id) != target_id {
+            return false;
+        }
+        new_dir.modify_disk_inode(|disk_inode| {
+            let index = match replaced {
+                Some((index, _)) => index,
+                None => {
+                    let index = disk_inode.size as usize / DIRENT_SZ;
+                    disk_inode.increase_size(((index + 1) * DIRENT_SZ) as u32);
+                    index
                 }
+            };
+            disk_inode.write_at(
+                index * DIRENT_SZ,
+                DirEntry::new(new_name, inode_id).as_bytes(),
+                &mut || fs.alloc_data(),
+                &self.block_device,
+            );
+            new_dir.index_insert(new_name, index, inode_id);
+            disk_inode.touch_modified(now);
+        });
+        self.modify_disk_inode(|disk_inode| {
+            disk_inode.write_at(
+                old_index * DIRENT_SZ,
+                DirEntry::empty().as_bytes(),
+                &mut || fs.alloc_data(),
+                &self.block_device,
+            );
+            disk_inode.touch_modified(now);
+        });
+        self.index_remove(old_name);
+        let moved_dir = is_dir && dir_id != new_dir_id;
+        inode.modify_disk_inode(|disk_inode| {
+            disk_inode.ctime = now;
+            if moved_dir {
+                // its `..` now refers to the new parent
+                let (index, _) = inode.find_dirent("..", disk_inode).unwrap();
+                disk_inode.write_at(
+                    index * DIRENT_SZ,
+                    DirEntry::new("..", new_dir_id).as_bytes(),
+                    &mut || fs.alloc_data(),
+                    &self.block_device,
+                );
+                inode.index_insert("..", index, new_dir_id);
             }
         });
-
-        let mode: u32 = self.read_disk_inode(|disk_inode| {
-            if disk_inode.is_dir() == true {
+        if moved_dir {
+            self.modify_disk_inode(|disk_inode| disk_inode.nlink -= 1);
+            new_dir.modify_disk_inode(|disk_inode| disk_inode.nlink += 1);
+        }
+        // the replaced inode loses a link, as if unlinked
+        let reclaim = match &target {
+            Some(target) => {
+                let nlink = target.modify_disk_inode(|disk_inode| {
+                    disk_inode.nlink -= 1;
+                    disk_inode.ctime = now;
+                    if is_dir {
+                        disk_inode.nlink -= 1;
+                    }
+                    disk_inode.nlink
+                });
+                if is_dir {
+                    new_dir.modify_disk_inode(|disk_inode| disk_inode.nlink -= 1);
+                }
+                nlink == 0 && !fs.defer_reclaim(target_id.unwrap())
+            }
+            None => false,
+        };
+        if !fs.is_opened(dir_id) {
+            self.compact_entries(&mut fs);
+        }
+        drop(fs);
+        drop(guards);
+        if reclaim {
+            target.unwrap().reclaim();
+        }
+        true
+    }
+    /// Start using current inode, an unlinked inode is kept
+    /// until every user calls [`Inode::close`]
+    pub fn open(&self) {
+        let mut fs = self.fs.lock();
+        let inode_id = fs.get_inode_id(self.block_id as u32, self.block_offset);
+        fs.open_inode(inode_id);
+    }
+    /// Write back the disk inode of current inode, holding the size and
+    /// the timestamps. With a write-back cache its data is written back
+    /// on a global sync or as its blocks leave the cache
+    pub fn sync(&self) {
+        let _fs = self.fs.lock();
+        block_cache_sync(self.block_id);
+    }
+    /// Stop using current inode. The entries removed from a directory
+    /// while it is in use are compacted away once the last user is gone
+    pub fn close(&self) {
+        let mut fs = self.fs.lock();
+        let inode_id = fs.get_inode_id(self.block_id as u32, self.block_offset);
+        let reclaim = fs.close_inode(inode_id);
+        drop(fs);
+        if reclaim {
+            self.reclaim();
+        } else if self.is_dir() {
+            let _guard = self.lock.write();
+            let mut fs = self.fs.lock();
+            // unless somebody started using it in between
+            if !fs.is_opened(inode_id) {
+                self.compact_entries(&mut fs);
+            }
+        }
+    }
+    /// Give back the data blocks and the inode itself
+    /// once no directory entry refers to current inode
+    fn reclaim(&self) {
+        self.clear();
+        let mut fs = self.fs.lock();
+        let inode_id = fs.get_inode_id(self.block_id as u32, self.block_offset);
+        fs.dealloc_inode(inode_id);
+    }
+    /// Get `(atime, mtime, ctime)` of current inode in nanoseconds
+    pub fn timestamps(&self) -> (u64, u64, u64) {
+        let _fs = self.fs.lock();
+        self.read_disk_inode(|disk_inode| (disk_inode.atime, disk_inode.mtime, disk_inode.ctime))
+    }
+    /// Get the number of blocks current inode takes, holes left out
+    pub fn allocated_blocks(&self) -> usize {
+        let _guard = self.lock.read();
+        let _fs = self.fs.lock();
+        self.read_disk_inode(|disk_inode| disk_inode.allocated_blocks(&self.block_device) as usize)
+    }
+    /// Get the id of the block holding byte `offset` of current inode,
+    /// None for a hole or past the end of file
+    pub fn data_block_id(&self, offset: usize) -> Option<u32> {
+        let _guard = self.lock.read();
+        let _fs = self.fs.lock();
+        self.read_disk_inode(|disk_inode| {
+            if offset >= disk_inode.size as usize {
+                return None;
+            }
+            let block_id = disk_inode.get_block_id((offset / BLOCK_SZ) as u32, &self.block_device);
+            Some(block_id).filter(|&block_id| block_id != NO_BLOCK)
+        })
+    }
+    /// Get `(total data blocks, free data blocks, total inodes, free inodes)`
+    /// of the filesystem holding current inode
+    pub fn fs_usage(&self) -> (usize, usize, usize, usize) {
+        let fs = self.fs.lock();
+        (
+            fs.total_data_blocks(),
+            fs.free_data_blocks(),
+            fs.total_inodes(),
+            fs.free_inodes(),
+        )
+    }
+    /// Get `(inode id, mode, number of links)` of current inode,
+    /// where mode is 0 for a directory, 1 for a regular file
+    /// and 2 for a symbolic link
+    pub fn stat(&self) -> (u64, u32, u32) {
+        let ino = self.inode_id() as u64;
+        let _fs = self.fs.lock();
+        let (mode, nlink) = self.read_disk_inode(|disk_inode| {
+            let mode = if disk_inode.is_dir() {
                 0
-            } else if disk_inode.is_file() == true {
+            } else if disk_inode.is_file() {
                 1
             } else {
                 2
-            }
+            };
+            (mode, disk_inode.nlink)
         });
-        
         (ino, mode, nlink)
     }
 }
+
+/// Iterator over the entries of a directory, yields `(name, inode_id, type)`
+///
+/// Removed entries are skipped. The offsets stay valid across removals
+/// while the directory is open, as its entries only move once nobody uses it
+pub struct ReadDir<'a> {
+    dir: &'a Inode,
+    offset: usize,
+}
+
+impl ReadDir<'_> {
+    /// Byte offset of the next entry to visit inside the directory
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+}
+
+impl Iterator for ReadDir<'_> {
+    type Item = (String, u32, DiskInodeType);
+    fn next(&mut self) -> Option<Self::Item> {
+        let _guard = self.dir.lock.read();
+        let fs = self.dir.fs.lock();
+        let mut dirent = DirEntry::empty();
+        loop {
+            let read_size = self.dir.read_disk_inode(|disk_inode| {
+                if !disk_inode.is_dir() {
+                    return 0;
+                }
+                disk_inode.read_at(self.offset, dirent.as_bytes_mut(), &self.dir.block_device)
+            });
+            if read_size < DIRENT_SZ {
+                return None;
+            }
+            self.offset += DIRENT_SZ;
+            if dirent.name().is_empty() {
+                continue;
+            }
+            let inode_id = dirent.inode_number();
+            let (block_id, block_offset) = fs.get_disk_inode_pos(inode_id);
+            let type_ = get_block_cache(
+                block_id as usize,
+                Arc::clone(&self.dir.block_device)
+            ).lock().read(block_offset, |disk_inode: &DiskInode| disk_inode.type_());
+            return Some((String::from(dirent.name()), inode_id, type_));
+        }
+    }
+}
@@ -1,12 +1,42 @@
 //! Constants used in rCore
 
-pub const USER_STACK_SIZE: usize = 4096 * 2;
+/// The most the user stack grows to, only the pages touched get frames
+pub const USER_STACK_SIZE: usize = 4096 * 16;
+/// Where the search for room to attach shared memory segments starts
+pub const USER_SHM_BASE: usize = 0x4000_0000;
 pub const KERNEL_STACK_SIZE: usize = 4096 * 2;
 pub const KERNEL_HEAP_SIZE: usize = 0x20_0000;
 pub const MEMORY_END: usize = 0x88000000;
 pub const PAGE_SIZE: usize = 0x1000;
 pub const PAGE_SIZE_BITS: usize = 0xc;
+/// Bytes a megapage, mapped by a leaf in the middle level of the page table, spans
+pub const HUGE_PAGE_SIZE: usize = 0x20_0000;
 pub const MAX_SYSCALL_NUM: usize = 500;
+pub const MAX_FD_NUM: usize = 128;
+pub const IOV_MAX: usize = 1024;
+pub const PATH_MAX: usize = 4096;
+pub const MAX_SYMLINK_DEPTH: usize = 8;
+pub const BIG_STRIDE: u32 = u32::MAX;
+pub const DEFAULT_PRIORITY: usize = 16;
+pub const BLOCK_CACHE_CAPACITY: usize = 64;
+pub const READAHEAD_WINDOW: usize = 8;
+/// How often dirty cached blocks are looked for
+pub const DIRTY_FLUSH_INTERVAL_MS: usize = 500;
+/// How long a cached block may stay dirty before it is written back unasked
+pub const DIRTY_EXPIRE_MS: usize = 2000;
+pub const RAMFS_CAPACITY: usize = 0x8_0000;
+pub const SHM_CAPACITY: usize = 0x2_0000;
+/// Bytes of console input held for readers, the line being typed included
+pub const CONSOLE_INPUT_CAPACITY: usize = 256;
+/// Bytes of the last kernel messages kept for `/proc/kmsg`
+pub const KERNEL_LOG_CAPACITY: usize = 0x2000;
+/// The swap area on the block device, right after the filesystem the packer lays down
+pub const SWAP_START_BLOCK: usize = 16384;
+/// Pages the swap area holds
+pub const SWAP_SLOTS: usize = 1024;
+/// Frames kept free for the kernel, a process faulting with fewer left
+/// swaps out its own pages first
+pub const SWAP_LOW_WATERMARK: usize = 64;
 
 pub const TRAMPOLINE: usize = usize::MAX - PAGE_SIZE + 1;
 pub const TRAP_CONTEXT: usize = TRAMPOLINE - PAGE_SIZE;
@@ -1,21 +1,132 @@
-//! SBI console driver, for text output
+//! SBI console driver, for text output, and the kernel log
+//! keeping what the kernel prints
 
+use crate::config::KERNEL_LOG_CAPACITY;
 use crate::sbi::console_putchar;
+use crate::sync::UPSafeCell;
 use core::fmt::{self, Write};
+use lazy_static::*;
 
-struct Stdout;
+/// The console, which also logs what goes through it when `logged`
+struct Stdout {
+    logged: bool,
+}
 
 impl Write for Stdout {
     fn write_str(&mut self, s: &str) -> fmt::Result {
         for c in s.chars() {
             console_putchar(c as usize);
         }
+        if self.logged {
+            KERNEL_LOG.exclusive_access().push(s.as_bytes());
+        }
         Ok(())
     }
 }
 
+/// The last bytes the kernel printed, in a ring. Every byte has a
+/// sequence number counting from boot, so that readers can tell
+/// where they are even after the bytes before are dropped
+struct KernelLog {
+    ring: [u8; KERNEL_LOG_CAPACITY],
+    /// Sequence number of the oldest byte held
+    start: usize,
+    /// Sequence number of the next byte logged
+    end: usize,
+}
+
+impl KernelLog {
+    /// Log `bytes`, dropping the oldest lines to make room
+    fn push(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            if self.end - self.start == KERNEL_LOG_CAPACITY {
+                self.drop_line();
+            }
+            self.ring[self.end % KERNEL_LOG_CAPACITY] = byte;
+            self.end += 1;
+        }
+    }
+    /// Drop the oldest line, all of it if there is no line break
+    fn drop_line(&mut self) {
+        while self.start < self.end {
+            let byte = self.ring[self.start % KERNEL_LOG_CAPACITY];
+            self.start += 1;
+            if byte == b'\n' {
+                break;
+            }
+        }
+    }
+}
+
+lazy_static! {
+    /// The log of the kernel, filled before the heap is there
+    static ref KERNEL_LOG: UPSafeCell<KernelLog> = unsafe {
+        UPSafeCell::new(KernelLog {
+            ring: [0; KERNEL_LOG_CAPACITY],
+            start: 0,
+            end: 0,
+        })
+    };
+}
+
+/// Copy the logged bytes from sequence number `seq` on into `buf`,
+/// from the oldest held if those at `seq` are dropped already, and move
+/// `seq` past them. Returns the number of bytes copied
+pub fn read_kernel_log(seq: &mut usize, buf: &mut [u8]) -> usize {
+    let log = KERNEL_LOG.exclusive_access();
+    let start = (*seq).max(log.start);
+    let len = buf.len().min(log.end - start);
+    for (i, byte) in buf[..len].iter_mut().enumerate() {
+        *byte = log.ring[(start + i) % KERNEL_LOG_CAPACITY];
+    }
+    *seq = start + len;
+    len
+}
+
+/// Sequence number of the next byte logged
+pub fn kernel_log_end() -> usize {
+    KERNEL_LOG.exclusive_access().end
+}
+
+#[allow(unused)]
+/// Fill a log past its capacity and see the oldest lines go as a whole
+pub fn kernel_log_test() {
+    let mut log = KernelLog {
+        ring: [0; KERNEL_LOG_CAPACITY],
+        start: 0,
+        end: 0,
+    };
+    let line = [b'x'; 99];
+    let lines = KERNEL_LOG_CAPACITY / 100;
+    for _ in 0..lines {
+        log.push(&line);
+        log.push(b"\n");
+    }
+    assert_eq!((log.start, log.end), (0, lines * 100));
+    // one more line drops the first two, the room of one not being enough
+    let room = KERNEL_LOG_CAPACITY - lines * 100;
+    log.push(&[b'y'; 150]);
+    log.push(b"\n");
+    assert_eq!(log.start, if room + 100 >= 151 { 100 } else { 200 });
+    assert_eq!(log.end, lines * 100 + 151);
+    assert!(log.end - log.start <= KERNEL_LOG_CAPACITY);
+    assert_eq!(log.ring[log.start % KERNEL_LOG_CAPACITY], b'x');
+    assert_eq!(log.ring[(log.end - 2) % KERNEL_LOG_CAPACITY], b'y');
+    // a line longer than the log leaves only its tail
+    log.push(&[b'z'; KERNEL_LOG_CAPACITY + 10]);
+    assert!(log.end > log.start && log.end - log.start <= KERNEL_LOG_CAPACITY);
+    assert!((log.start..log.end).all(|seq| log.ring[seq % KERNEL_LOG_CAPACITY] == b'z'));
+    info!("kernel_log_test passed!");
+}
+
+/// Print what the kernel has to say, which is logged
 pub fn print(args: fmt::Arguments) {
-    Stdout.write_fmt(args).unwrap();
+    Stdout { logged: true }.write_fmt(args).unwrap();
+}
+
+/// Print on behalf of user programs, which stays out of the kernel log
+pub fn print_unlogged(args: fmt::Arguments) {
+    Stdout { logged: false }.write_fmt(args).unwrap();
 }
 
 #[macro_export]
@@ -61,7 +172,7 @@ pub fn print_colorized(
     foreground_color: impl Into<u8>,
     background_color: impl Into<u8>,
 ) {
-    Stdout
+    Stdout { logged: true }
         .write_fmt(colorize!(args, foreground_color, background_color))
         .unwrap();
 }
@@ -13,6 +13,7 @@ use crate::mm::{
 };
 use super::BlockDevice;
 use crate::sync::UPSafeCell;
+use crate::task::account_block_io;
 use alloc::vec::Vec;
 use lazy_static::*;
 
@@ -32,11 +33,13 @@ impl BlockDevice for VirtIOBlock {
         self.0.exclusive_access()
         .read_block(block_id, buf)
         .expect("Error when reading VirtIOBlk");
+        account_block_io(buf.len(), 0);
     }
     fn write_block(&self, block_id: usize, buf: &[u8]) {
         self.0.exclusive_access()
         .write_block(block_id, buf)
         .expect("Error when writing VirtIOBlk");
+        account_block_io(0, buf.len());
     }
 }
 
@@ -0,0 +1,143 @@
+//! Character devices, which live outside the file system
+//! and take no blocks of it
+
+use super::{File, FileSystem, OpenFlags, Stat, StatMode};
+use crate::mm::UserBuffer;
+use crate::sync::UPSafeCell;
+use crate::timer::get_time;
+use alloc::sync::Arc;
+use lazy_static::*;
+
+/// The golden ratio increment of splitmix64, also the seed of no bytes
+const GOLDEN_GAMMA: u64 = 0x9E37_79B9_7F4A_7C15;
+
+lazy_static! {
+    /// The state of the xorshift64* generator behind `/dev/random`, never 0
+    static ref RANDOM_STATE: UPSafeCell<u64> = unsafe { UPSafeCell::new(GOLDEN_GAMMA) };
+}
+
+/// The splitmix64 finalizer, spreading every input bit over the output
+fn mix(mut z: u64) -> u64 {
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+/// Replace the state of the generator by a hash of `bytes`,
+/// the same bytes always lead to the same sequence
+fn reseed<'a>(bytes: impl Iterator<Item = &'a u8>) {
+    let mut seed = GOLDEN_GAMMA;
+    for &byte in bytes {
+        seed = mix(seed.wrapping_add(GOLDEN_GAMMA) ^ byte as u64);
+    }
+    *RANDOM_STATE.exclusive_access() = if seed == 0 { GOLDEN_GAMMA } else { seed };
+}
+
+/// Step the generator, one byte a time so that the sequence
+/// doesn't depend on how it is split between reads
+fn next_random_byte() -> u8 {
+    let mut state = RANDOM_STATE.exclusive_access();
+    let mut x = *state;
+    x ^= x >> 12;
+    x ^= x << 25;
+    x ^= x >> 27;
+    *state = x;
+    (x.wrapping_mul(0x2545_F491_4F6C_DD1D) >> 56) as u8
+}
+
+/// Seed the generator behind `/dev/random` from the timer
+pub fn init_random() {
+    reseed(get_time().to_le_bytes().iter());
+}
+
+#[derive(PartialEq)]
+enum DeviceKind {
+    /// `/dev/null`, reads nothing
+    Null,
+    /// `/dev/zero`, reads endless zero bytes
+    Zero,
+    /// `/dev/random`, reads pseudo-random bytes, and is reseeded
+    /// by the bytes written
+    Random,
+}
+
+/// An opened character device, which swallows whatever is written
+/// unless it makes use of it
+pub struct CharDevice {
+    kind: DeviceKind,
+    readable: bool,
+    writable: bool,
+}
+
+impl File for CharDevice {
+    fn readable(&self) -> bool { self.readable }
+    fn writable(&self) -> bool { self.writable }
+    fn read(&self, mut buf: UserBuffer) -> usize {
+        match self.kind {
+            DeviceKind::Null => 0,
+            DeviceKind::Zero => {
+                for byte in buf.iter_mut() {
+                    *byte = 0;
+                }
+                buf.len()
+            }
+            DeviceKind::Random => {
+                for byte in buf.iter_mut() {
+                    *byte = next_random_byte();
+                }
+                buf.len()
+            }
+        }
+    }
+    fn write(&self, buf: UserBuffer) -> usize {
+        if self.kind == DeviceKind::Random && buf.len() > 0 {
+            reseed(buf.buffers.iter().flat_map(|slice| slice.iter()));
+        }
+        buf.len()
+    }
+    /// There is no offset to move, it always stays at 0
+    fn seek(&self, _offset: isize, _whence: usize) -> isize {
+        0
+    }
+    fn info(&self, st: *mut Stat) {
+        let mut stat = Stat::new();
+        stat.mode = StatMode::CHR;
+        stat.nlink = 1;
+        unsafe {
+            *st = stat;
+        }
+    }
+}
+
+/// The filesystem of the character devices, one file per device
+pub struct DevFs;
+
+impl DevFs {
+    fn kind(name: &str) -> Option<DeviceKind> {
+        match name {
+            "null" => Some(DeviceKind::Null),
+            "zero" => Some(DeviceKind::Zero),
+            "random" => Some(DeviceKind::Random),
+            _ => None,
+        }
+    }
+}
+
+impl FileSystem for DevFs {
+    /// Open the device `path`, devices are never created nor truncated
+    fn open(&self, path: &str, flags: OpenFlags) -> Option<Arc<dyn File + Send + Sync>> {
+        let kind = Self::kind(path)?;
+        let (readable, writable) = flags.read_write();
+        Some(Arc::new(CharDevice {
+            kind,
+            readable,
+            writable,
+        }))
+    }
+    fn unlink(&self, _path: &str) -> bool {
+        false
+    }
+    fn lookup(&self, path: &str) -> bool {
+        Self::kind(path).is_some()
+    }
+}
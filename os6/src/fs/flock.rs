@@ -0,0 +1,119 @@
+//! Advisory locks on whole files taken with `flock`. A lock is held by an
+//! opened file, shared by the fds referring to it, and goes with the last of
+//! them. Nothing stops a file from being read or written without the lock
+
+use crate::sync::UPSafeCell;
+use crate::task::{block_current_and_run_next, current_task, wakeup_task, TaskControlBlock};
+use alloc::collections::{BTreeMap, VecDeque};
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use lazy_static::*;
+
+/// Take a shared lock, along with other shared ones
+pub const LOCK_SH: usize = 1;
+/// Take an exclusive lock, held by nobody else
+pub const LOCK_EX: usize = 2;
+/// Fail instead of waiting, along with `LOCK_SH` or `LOCK_EX`
+pub const LOCK_NB: usize = 4;
+/// Release the lock held
+pub const LOCK_UN: usize = 8;
+
+/// The locks on an inode, each held by an opened file
+#[derive(Default)]
+struct InodeLock {
+    /// Opened files holding a shared lock
+    shared: Vec<usize>,
+    /// The opened file holding the exclusive lock
+    exclusive: Option<usize>,
+    /// Tasks waiting for the locks to change, the longest waiting one first
+    wait_queue: VecDeque<Arc<TaskControlBlock>>,
+}
+
+impl InodeLock {
+    /// Whether `owner` can have the lock asked for, with the one it holds
+    /// replaced
+    fn grantable(&self, owner: usize, exclusive: bool) -> bool {
+        let exclusive_by_other = self.exclusive.map_or(false, |holder| holder != owner);
+        let shared_by_other = self.shared.iter().any(|&holder| holder != owner);
+        !exclusive_by_other && !(exclusive && shared_by_other)
+    }
+    /// Drop the lock `owner` holds, if any
+    fn release(&mut self, owner: usize) {
+        self.shared.retain(|&holder| holder != owner);
+        if self.exclusive == Some(owner) {
+            self.exclusive = None;
+        }
+    }
+    /// Wake up all the waiters to try again
+    fn wake_all(&mut self) {
+        for task in self.wait_queue.drain(..) {
+            wakeup_task(task);
+        }
+    }
+    fn is_unused(&self) -> bool {
+        self.shared.is_empty() && self.exclusive.is_none() && self.wait_queue.is_empty()
+    }
+}
+
+lazy_static! {
+    /// The locks on each inode somebody holds or waits for
+    static ref INODE_LOCKS: UPSafeCell<BTreeMap<u32, InodeLock>> =
+        unsafe { UPSafeCell::new(BTreeMap::new()) };
+}
+
+/// Lock inode `ino` for the opened file `owner`, shared or exclusive,
+/// replacing the lock it holds. Waits while others hold a conflicting lock
+/// unless `nonblock`. Returns false if the lock is not granted then,
+/// or if a signal comes while waiting
+pub fn lock(ino: u32, owner: usize, exclusive: bool, nonblock: bool) -> bool {
+    let task = current_task().unwrap();
+    loop {
+        let mut locks = INODE_LOCKS.exclusive_access();
+        let lock = locks.entry(ino).or_default();
+        let queued = lock.wait_queue.iter().position(|t| Arc::ptr_eq(t, &task));
+        if lock.grantable(owner, exclusive) {
+            if let Some(pos) = queued {
+                lock.wait_queue.remove(pos);
+            }
+            let downgraded = !exclusive && lock.exclusive == Some(owner);
+            lock.release(owner);
+            if exclusive {
+                lock.exclusive = Some(owner);
+            } else {
+                lock.shared.push(owner);
+                // others may share it from now on
+                if downgraded {
+                    lock.wake_all();
+                }
+            }
+            return true;
+        }
+        if nonblock || task.inner_exclusive_access().has_deliverable_signals() {
+            if let Some(pos) = queued {
+                lock.wait_queue.remove(pos);
+            }
+            if lock.is_unused() {
+                locks.remove(&ino);
+            }
+            return false;
+        }
+        // woken up by a signal before, it is still queued
+        if queued.is_none() {
+            lock.wait_queue.push_back(task.clone());
+        }
+        drop(locks);
+        block_current_and_run_next();
+    }
+}
+
+/// Release the lock the opened file `owner` holds on inode `ino`, if any
+pub fn unlock(ino: u32, owner: usize) {
+    let mut locks = INODE_LOCKS.exclusive_access();
+    if let Some(lock) = locks.get_mut(&ino) {
+        lock.release(owner);
+        lock.wake_all();
+        if lock.is_unused() {
+            locks.remove(&ino);
+        }
+    }
+}
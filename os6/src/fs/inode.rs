@@ -1,15 +1,30 @@
 use easy_fs::{
+    DiskInodeType,
     EasyFileSystem,
     Inode,
+    set_block_cache_capacity,
+    set_readahead_window,
+    block_cache_sync_all,
+    block_cache_flush_expired,
+    BLOCK_SZ,
+};
+use crate::config::{
+    BLOCK_CACHE_CAPACITY, DIRTY_EXPIRE_MS, DIRTY_FLUSH_INTERVAL_MS, MAX_SYMLINK_DEPTH,
+    READAHEAD_WINDOW,
 };
 use crate::drivers::BLOCK_DEVICE;
 use crate::sync::UPSafeCell;
 use alloc::sync::Arc;
 use lazy_static::*;
 use bitflags::*;
+use alloc::string::String;
 use alloc::vec::Vec;
-use super::{File, Stat, StatMode};
+use super::{File, Dirent, Stat, Statfs, StatMode, DT_DIR, DT_LNK, DT_REG, SEEK_SET, SEEK_CUR, SEEK_END};
+use super::{R_OK, W_OK, X_OK};
+use super::find_mount;
+use super::flock::{self, LOCK_EX, LOCK_NB, LOCK_SH, LOCK_UN};
 use crate::mm::UserBuffer;
+use crate::timer::{get_time_ms, get_time_ns};
 
 /// A wrapper around a filesystem inode
 /// to implement File trait atop
@@ -22,6 +37,8 @@ pub struct OSInode {
 /// The OS inode inner in 'UPSafeCell'
 pub struct OSInodeInner {
     offset: usize,
+    /// status flags, every write goes to the end of file with `APPEND`
+    status: OpenFlags,
     inode: Arc<Inode>,
 }
 
@@ -30,17 +47,29 @@ impl OSInode {
     pub fn new(
         readable: bool,
         writable: bool,
+        status: OpenFlags,
         inode: Arc<Inode>,
     ) -> Self {
+        // keep the inode alive even if it is unlinked while opened
+        inode.open();
         Self {
             readable,
             writable,
             inner: unsafe { UPSafeCell::new(OSInodeInner {
                 offset: 0,
+                status: status.status_flags(),
                 inode,
             })},
         }
     }
+    /// Get the current offset of the file cursor
+    pub fn offset(&self) -> usize {
+        self.inner.exclusive_access().offset
+    }
+    /// Move the file cursor, which is allowed to go beyond the end of file
+    pub fn set_offset(&self, offset: usize) {
+        self.inner.exclusive_access().offset = offset;
+    }
     /// Read all data inside a inode into vector
     pub fn read_all(&self) -> Vec<u8> {
         let mut inner = self.inner.exclusive_access();
@@ -58,11 +87,25 @@ impl OSInode {
     }
 }
 
+impl Drop for OSInode {
+    /// The last fd of an opened file is gone, which has been shared
+    /// by dup and fork, so the file is written back right away
+    fn drop(&mut self) {
+        let inner = self.inner.exclusive_access();
+        flock::unlock(inner.inode.inode_id(), self as *const _ as usize);
+        inner.inode.sync();
+        inner.inode.close();
+    }
+}
+
 lazy_static! {
     /// The root of all inodes, or '/' in short
     pub static ref ROOT_INODE: Arc<Inode> = {
+        set_block_cache_capacity(BLOCK_CACHE_CAPACITY);
+        set_readahead_window(READAHEAD_WINDOW);
         let efs = EasyFileSystem::open(BLOCK_DEVICE.clone());
-        Arc::new(EasyFileSystem::root_inode(&efs))
+        efs.lock().set_clock(get_time_ns);
+        EasyFileSystem::root_inode(&efs)
     };
 }
 
@@ -70,129 +113,530 @@ lazy_static! {
 pub fn list_apps() {
     println!("/**** APPS ****");
     for app in ROOT_INODE.ls() {
+        if app == "." || app == ".." {
+            continue;
+        }
         println!("{}", app);
     }
     println!("**************/");
 }
 
+/// Write all dirty cached blocks back to the block device
+pub fn sync() {
+    block_cache_sync_all();
+}
+
+lazy_static! {
+    /// When the flusher looks for dirty blocks next, in milliseconds
+    static ref NEXT_FLUSH_MS: UPSafeCell<usize> = unsafe { UPSafeCell::new(0) };
+}
+
+/// Write back the cached blocks dirty for longer than `DIRTY_EXPIRE_MS`,
+/// once every `DIRTY_FLUSH_INTERVAL_MS`. Called on every timer interrupt,
+/// which only comes from user mode, so no task is inside the cache
+pub fn flush_dirty_blocks() {
+    let now = get_time_ms();
+    let mut next = NEXT_FLUSH_MS.exclusive_access();
+    if now < *next {
+        return;
+    }
+    *next = now + DIRTY_FLUSH_INTERVAL_MS;
+    drop(next);
+    block_cache_flush_expired(now as u64, DIRTY_EXPIRE_MS as u64);
+}
+
+/// Write a file with no sync, and see its data block on the disk change
+/// once the flusher has run past `DIRTY_EXPIRE_MS` of real time
+#[allow(unused)]
+pub fn dirty_flush_test() {
+    let name = "dirty_flush_test";
+    let inode = ROOT_INODE.create(name).unwrap();
+    // unlike what a block freed by an earlier boot may still hold
+    let data = get_time_ns().to_ne_bytes();
+    assert_eq!(inode.write_at(0, &data), data.len());
+    let block_id = inode.data_block_id(0).unwrap() as usize;
+    let start = get_time_ms();
+    let mut block = [0u8; BLOCK_SZ];
+    // the timer interrupts are not on yet, so run the flusher by hand
+    loop {
+        flush_dirty_blocks();
+        BLOCK_DEVICE.read_block(block_id, &mut block);
+        if block[..data.len()] == data {
+            break;
+        }
+        assert!(get_time_ms() < start + DIRTY_EXPIRE_MS + 3 * DIRTY_FLUSH_INTERVAL_MS);
+    }
+    // not written through, but once dirty for long enough
+    assert!(get_time_ms() >= start + DIRTY_EXPIRE_MS);
+    drop(inode);
+    ROOT_INODE.unlinkat(name);
+    block_cache_sync_all();
+    info!("dirty_flush_test passed!");
+}
+
 bitflags! {
     /// Flags for opening files
     pub struct OpenFlags: u32 {
         const RDONLY = 0;
         const WRONLY = 1 << 0;
         const RDWR = 1 << 1;
+        /// with `CREATE`, fail if the file exists already
+        const EXCL = 1 << 7;
         const CREATE = 1 << 9;
         const TRUNC = 1 << 10;
+        const APPEND = 1 << 11;
+        /// reading or writing fails instead of waiting
+        const NONBLOCK = 1 << 12;
+        /// open a symbolic link itself instead of its target
+        const NOFOLLOW = 1 << 17;
+        /// close the fd on exec
+        const CLOEXEC = 1 << 19;
     }
 }
 
 impl OpenFlags {
+    /// The flags kept by an opened file, which `fcntl` may change
+    pub fn status_flags(&self) -> Self {
+        *self & (Self::APPEND | Self::NONBLOCK)
+    }
     /// Get the current read write permission on an inode
     /// does not check validity for simplicity
     /// returns (readable, writable)
     pub fn read_write(&self) -> (bool, bool) {
-        if self.is_empty() {
-            (true, false)
-        } else if self.contains(Self::WRONLY) {
+        if self.contains(Self::WRONLY) {
             (false, true)
-        } else {
+        } else if self.contains(Self::RDWR) {
             (true, true)
+        } else {
+            (true, false)
         }
     }
 }
 
-/// Open a file by path
-pub fn open_file(name: &str, flags: OpenFlags) -> Option<Arc<OSInode>> {
-    let (readable, writable) = flags.read_write();
-    if flags.contains(OpenFlags::CREATE) {
-        if let Some(inode) = ROOT_INODE.find(name) {
-            // clear size
-            inode.clear();
-            Some(Arc::new(OSInode::new(
-                readable,
-                writable,
-                inode,
-            )))
+/// Walk a path component by component starting from `dir`, or from the root
+/// if it is absolute. Symbolic links are followed, except for the last
+/// component when `follow_last` is false. `depth` is the number of links
+/// that may still be followed, fails once it runs out
+fn walk(dir: Arc<Inode>, path: &str, follow_last: bool, depth: &mut usize) -> Option<Arc<Inode>> {
+    let mut cur = if path.starts_with('/') { ROOT_INODE.clone() } else { dir };
+    let mut names = path.split('/').filter(|name| !name.is_empty()).peekable();
+    while let Some(name) = names.next() {
+        let next = cur.find(name)?;
+        let is_last = names.peek().is_none();
+        if next.is_symlink() && (follow_last || !is_last) {
+            if *depth == 0 {
+                return None;
+            }
+            *depth -= 1;
+            // a relative target starts from the directory of the link
+            cur = walk(cur, next.read_link()?.as_str(), true, depth)?;
         } else {
+            cur = next;
+        }
+    }
+    Some(cur)
+}
+
+/// Find the inode of a path relative to `cwd`, following symbolic links
+pub fn find_inode(cwd: &Arc<Inode>, path: &str) -> Option<Arc<Inode>> {
+    let mut depth = MAX_SYMLINK_DEPTH;
+    walk(cwd.clone(), path, true, &mut depth)
+}
+
+/// Find the inode of a path relative to `cwd`, a symbolic link
+/// as the last component is returned itself
+pub fn find_inode_nofollow(cwd: &Arc<Inode>, path: &str) -> Option<Arc<Inode>> {
+    let mut depth = MAX_SYMLINK_DEPTH;
+    walk(cwd.clone(), path, false, &mut depth)
+}
+
+/// The target of the symbolic link at `path` relative to `cwd`, the link
+/// itself is not followed. None if there is no symbolic link there
+pub fn readlink(cwd: &Arc<Inode>, path: &str) -> Option<String> {
+    // no other filesystem has symbolic links
+    if find_mount(cwd, path).is_some() {
+        return None;
+    }
+    find_inode_nofollow(cwd, path)?.read_link()
+}
+
+/// Find the directory of a path relative to `cwd`
+pub fn find_dir(cwd: &Arc<Inode>, path: &str) -> Option<Arc<Inode>> {
+    let dir = find_inode(cwd, path)?;
+    if dir.is_dir() {
+        Some(dir)
+    } else {
+        None
+    }
+}
+
+/// The absolute path of directory `dir`, found by walking `..` up to
+/// the root and looking up the name of each directory in its parent.
+/// None if `dir` has been removed
+pub fn dir_path(dir: &Arc<Inode>) -> Option<String> {
+    let mut names: Vec<String> = Vec::new();
+    let mut cur = dir.clone();
+    loop {
+        let parent = cur.find("..")?;
+        let inode_id = cur.inode_id();
+        // the root is its own parent
+        if parent.inode_id() == inode_id {
+            break;
+        }
+        let (name, _, _) = parent
+            .read_dir()
+            .find(|(name, id, _)| *id == inode_id && name != "." && name != "..")?;
+        names.push(name);
+        cur = parent;
+    }
+    let mut path = String::new();
+    for name in names.iter().rev() {
+        path.push('/');
+        path.push_str(name);
+    }
+    if path.is_empty() {
+        path.push('/');
+    }
+    Some(path)
+}
+
+/// Get the usage of the filesystem holding the path relative to `cwd`
+pub fn statfs(cwd: &Arc<Inode>, path: &str) -> Option<Statfs> {
+    let (blocks, bfree, files, ffree) = find_inode(cwd, path)?.fs_usage();
+    Some(Statfs {
+        bsize: BLOCK_SZ as u64,
+        blocks: blocks as u64,
+        bfree: bfree as u64,
+        files: files as u64,
+        ffree: ffree as u64,
+    })
+}
+
+/// Whether the path relative to `cwd` exists and allows what `mode`
+/// asks for, without opening it. There are no permissions yet,
+/// so anything existing can be read, written and executed
+pub fn access(cwd: &Arc<Inode>, path: &str, mode: usize) -> bool {
+    if mode & !(R_OK | W_OK | X_OK) != 0 {
+        return false;
+    }
+    if let Some((fs, rest)) = find_mount(cwd, path) {
+        return fs.lookup(rest.as_str());
+    }
+    find_inode(cwd, path).is_some()
+}
+
+/// The status of an inode, as `fstat` gives it for a file opened on it
+fn inode_stat(inode: &Inode) -> Stat {
+    let (ino, mode, nlink) = inode.stat();
+    let (atime, mtime, ctime) = inode.timestamps();
+    let mut stat = Stat::new();
+    stat.ino = ino;
+    stat.mode = match mode {
+        0 => StatMode::DIR,
+        1 => StatMode::FILE,
+        2 => StatMode::LNK,
+        _ => StatMode::NULL,
+    };
+    stat.nlink = nlink;
+    stat.size = inode.size() as u64;
+    stat.blocks = inode.allocated_blocks() as u64;
+    stat.atime = atime;
+    stat.mtime = mtime;
+    stat.ctime = ctime;
+    stat
+}
+
+/// Get the status of the path relative to `dir` without opening it,
+/// that of a symbolic link itself rather than its target unless `follow`
+pub fn stat_at(dir: &Arc<Inode>, path: &str, follow: bool) -> Option<Stat> {
+    if let Some((fs, rest)) = find_mount(dir, path) {
+        let mut stat = Stat::new();
+        fs.open(rest.as_str(), OpenFlags::RDONLY)?.info(&mut stat);
+        return Some(stat);
+    }
+    let inode = if follow {
+        find_inode(dir, path)?
+    } else {
+        find_inode_nofollow(dir, path)?
+    };
+    Some(inode_stat(&inode))
+}
+
+/// Find the directory containing the last component of a path relative
+/// to `cwd`, returns the directory inode together with the last component
+pub fn find_parent<'a>(cwd: &Arc<Inode>, path: &'a str) -> Option<(Arc<Inode>, &'a str)> {
+    let path = path.trim_end_matches('/');
+    let (parent, name) = match path.rfind('/') {
+        Some(pos) => (&path[..pos], &path[pos + 1..]),
+        None => ("", path),
+    };
+    if name.is_empty() {
+        return None;
+    }
+    let dir = if path.starts_with('/') && parent.is_empty() {
+        ROOT_INODE.clone()
+    } else {
+        find_dir(cwd, parent)?
+    };
+    Some((dir, name))
+}
+
+/// Create a directory by path, fails if its parent is missing
+/// or the last component already exists
+pub fn mkdir(cwd: &Arc<Inode>, path: &str) -> Option<Arc<Inode>> {
+    let (dir, name) = find_parent(cwd, path)?;
+    dir.create_dir(name)
+}
+
+/// Create a symbolic link at `path` pointing to `target`,
+/// which does not need to exist
+pub fn symlink(cwd: &Arc<Inode>, target: &str, path: &str) -> Option<Arc<Inode>> {
+    let (dir, name) = find_parent(cwd, path)?;
+    dir.create_symlink(name, target)
+}
+
+/// Open a file by path relative to `cwd`
+pub fn open_file(cwd: &Arc<Inode>, path: &str, flags: OpenFlags) -> Option<Arc<OSInode>> {
+    let (readable, writable) = flags.read_write();
+    let truncate = flags.contains(OpenFlags::TRUNC);
+    let follow = !flags.contains(OpenFlags::NOFOLLOW);
+    // truncating needs the write permission
+    if truncate && !writable {
+        return None;
+    }
+    let inode = if flags.contains(OpenFlags::CREATE) {
+        let (dir, name) = find_parent(cwd, path)?;
+        match dir.find(name) {
+            // not even a symbolic link is followed then
+            Some(_) if flags.contains(OpenFlags::EXCL) => return None,
+            Some(inode) if inode.is_dir() => return None,
+            Some(inode) if follow && inode.is_symlink() => find_inode(cwd, path)?,
+            Some(inode) => inode,
             // create file
-            ROOT_INODE.create(name)
-                .map(|inode| {
-                    Arc::new(OSInode::new(
-                        readable,
-                        writable,
-                        inode,
-                    ))
-                })
+            None => dir.create(name)?,
         }
+    } else if follow {
+        find_inode(cwd, path)?
     } else {
-        ROOT_INODE.find(name)
-            .map(|inode| {
-                if flags.contains(OpenFlags::TRUNC) {
-                    inode.clear();
-                }
-                Arc::new(OSInode::new(
-                    readable,
-                    writable,
-                    inode
-                ))
-            })
+        find_inode_nofollow(cwd, path)?
+    };
+    // a directory or a symbolic link can only be read
+    if writable && (inode.is_dir() || inode.is_symlink()) {
+        return None;
+    }
+    if truncate {
+        // clear size, the inode itself stays
+        inode.clear();
     }
+    Some(Arc::new(OSInode::new(
+        readable,
+        writable,
+        flags,
+        inode,
+    )))
 }
 
 impl File for OSInode {
     fn readable(&self) -> bool { self.readable }
     fn writable(&self) -> bool { self.writable }
-    fn read(&self, mut buf: UserBuffer) -> usize {
+    fn read(&self, buf: UserBuffer) -> usize {
+        self.try_read(buf).unwrap_or(0)
+    }
+    fn try_read(&self, mut buf: UserBuffer) -> Result<usize, ()> {
         let mut inner = self.inner.exclusive_access();
         let mut total_read_size = 0usize;
         for slice in buf.buffers.iter_mut() {
-            let read_size = inner.inode.read_at(inner.offset, *slice);
-            if read_size == 0 {
-                break;
-            }
+            let read_size = inner.inode.try_read_at(inner.offset, *slice).map_err(|_| ())?;
             inner.offset += read_size;
             total_read_size += read_size;
+            // the end of file, the rest of the buffer is left as it is
+            if read_size < slice.len() {
+                break;
+            }
         }
-        total_read_size
+        Ok(total_read_size)
     }
     fn write(&self, buf: UserBuffer) -> usize {
         let mut inner = self.inner.exclusive_access();
         let mut total_write_size = 0usize;
         for slice in buf.buffers.iter() {
-            let write_size = inner.inode.write_at(inner.offset, *slice);
-            assert_eq!(write_size, slice.len());
+            let write_size = if inner.status.contains(OpenFlags::APPEND) {
+                let (offset, write_size) = inner.inode.append(*slice);
+                inner.offset = offset;
+                write_size
+            } else {
+                inner.inode.write_at(inner.offset, *slice)
+            };
             inner.offset += write_size;
             total_write_size += write_size;
+            // the file can't grow any more
+            if write_size < slice.len() {
+                break;
+            }
         }
         total_write_size
     }
+    fn status_flags(&self) -> OpenFlags {
+        self.inner.exclusive_access().status
+    }
+    fn set_status_flags(&self, flags: OpenFlags) {
+        self.inner.exclusive_access().status = flags.status_flags();
+    }
+    fn seek(&self, offset: isize, whence: usize) -> isize {
+        let base = match whence {
+            SEEK_SET => 0,
+            SEEK_CUR => self.offset() as isize,
+            SEEK_END => self.inner.exclusive_access().inode.size() as isize,
+            _ => return -1,
+        };
+        match base.checked_add(offset) {
+            Some(new_offset) if new_offset >= 0 => {
+                self.set_offset(new_offset as usize);
+                new_offset
+            }
+            _ => -1,
+        }
+    }
+    fn getdents(&self, mut buf: UserBuffer) -> isize {
+        let mut inner = self.inner.exclusive_access();
+        if !inner.inode.is_dir() {
+            return -1;
+        }
+        let len = buf.len();
+        let mut bytes: Vec<u8> = Vec::new();
+        let mut entries = inner.inode.read_dir_at(inner.offset);
+        // the offset of the first entry not handed out yet
+        let mut offset = entries.offset();
+        let mut full = false;
+        while let Some((name, inode_id, type_)) = entries.next() {
+            let dirent = Dirent {
+                ino: inode_id as u64,
+                off: entries.offset() as i64,
+                type_: match type_ {
+                    DiskInodeType::Directory => DT_DIR,
+                    DiskInodeType::File => DT_REG,
+                    DiskInodeType::Symlink => DT_LNK,
+                },
+                name: name.as_str(),
+            };
+            if bytes.len() + dirent.reclen() > len {
+                full = true;
+                break;
+            }
+            dirent.pack(&mut bytes);
+            offset = entries.offset();
+        }
+        inner.offset = offset;
+        // not even a single entry fits in the buffer
+        if full && bytes.is_empty() {
+            return -1;
+        }
+        buf.copy_from_slice(&bytes) as isize
+    }
+    fn truncate(&self, len: usize) -> isize {
+        let inner = self.inner.exclusive_access();
+        if inner.inode.is_dir() {
+            return -1;
+        }
+        if inner.inode.truncate(len) {
+            0
+        } else {
+            -1
+        }
+    }
+    fn fallocate(&self, offset: usize, len: usize, keep_size: bool) -> isize {
+        let inner = self.inner.exclusive_access();
+        if inner.inode.is_dir() {
+            return -1;
+        }
+        if inner.inode.fallocate(offset, len, keep_size) {
+            0
+        } else {
+            -1
+        }
+    }
+    fn read_at(&self, offset: usize, buf: &mut [u8]) -> isize {
+        let inner = self.inner.exclusive_access();
+        if inner.inode.is_dir() {
+            return -1;
+        }
+        match inner.inode.try_read_at(offset, buf) {
+            Ok(read_size) => read_size as isize,
+            Err(_) => -1,
+        }
+    }
+    fn write_at(&self, offset: usize, buf: &[u8]) -> isize {
+        let inner = self.inner.exclusive_access();
+        if inner.inode.is_dir() {
+            return -1;
+        }
+        inner.inode.write_at(offset, buf) as isize
+    }
     fn info(&self, st: *mut Stat) {
         let inner = self.inner.exclusive_access();
-        let inode = &inner.inode;
-        //let (a, b) = inode.test();
-        //println!("a: {}, b: {}", a, b);
-        let (ino, mode, nlink) = inode.stat(&ROOT_INODE);
-        let mode = match mode {
-            0 => StatMode::DIR,
-            1 => StatMode::FILE,
-            _ => StatMode::NULL,
-        };
         unsafe {
-            *st = Stat {
-                dev: 0,
-                ino: ino,
-                mode: mode,
-                nlink: nlink,
-                pad: [0; 7],
-            };
+            *st = inode_stat(&inner.inode);
+        }
+    }
+    fn dir_inode(&self) -> Option<Arc<Inode>> {
+        let inner = self.inner.exclusive_access();
+        if inner.inode.is_dir() {
+            Some(inner.inode.clone())
+        } else {
+            None
+        }
+    }
+    /// The lock is held by this opened file, whichever fd takes it
+    fn flock(&self, operation: usize) -> isize {
+        let ino = self.inner.exclusive_access().inode.inode_id();
+        let owner = self as *const _ as usize;
+        let nonblock = operation & LOCK_NB != 0;
+        let granted = match operation & !LOCK_NB {
+            LOCK_SH => flock::lock(ino, owner, false, nonblock),
+            LOCK_EX => flock::lock(ino, owner, true, nonblock),
+            LOCK_UN => {
+                flock::unlock(ino, owner);
+                true
+            }
+            _ => false,
         };
+        if granted {
+            0
+        } else {
+            -1
+        }
     }
 }
 
-pub fn linkat(old_name: &str, new_name: &str) {
-    ROOT_INODE.linkat(old_name, new_name);
+/// Create a hard link at `new_path` relative to `new_dir` to the file at
+/// `old_path` relative to `old_dir`. Fails if the file is missing or
+/// a directory, or if something is at `new_path` already
+pub fn linkat(old_dir: &Arc<Inode>, old_path: &str, new_dir: &Arc<Inode>, new_path: &str) -> bool {
+    // a symbolic link is linked itself, like linkat without AT_SYMLINK_FOLLOW
+    match (find_inode_nofollow(old_dir, old_path), find_parent(new_dir, new_path)) {
+        // hard links to directories are not allowed
+        (Some(inode), Some((dir, name))) if !inode.is_dir() => dir.linkat(name, &inode),
+        _ => false,
+    }
+}
+
+/// Move the file at `old_path` to `new_path`, both relative to `cwd`,
+/// replacing what is at `new_path`. A symbolic link is moved itself
+pub fn rename(cwd: &Arc<Inode>, old_path: &str, new_path: &str) -> bool {
+    match (find_parent(cwd, old_path), find_parent(cwd, new_path)) {
+        (Some((old_dir, old_name)), Some((new_dir, new_name))) => {
+            old_dir.rename(old_name, &new_dir, new_name)
+        }
+        _ => false,
+    }
 }
 
-pub fn unlinkat(name: &str) -> isize {
-    ROOT_INODE.unlinkat(name)
-}
\ No newline at end of file
+pub fn unlinkat(cwd: &Arc<Inode>, path: &str) -> isize {
+    if let Some((fs, rest)) = find_mount(cwd, path) {
+        return if fs.unlink(rest.as_str()) { 0 } else { -1 };
+    }
+    match find_parent(cwd, path) {
+        Some((dir, name)) => dir.unlinkat(name),
+        None => -1,
+    }
+}
@@ -1,7 +1,16 @@
 mod stdio;
 mod inode;
+mod pipe;
+mod dev;
+mod ramfs;
+mod proc;
+mod mount;
+mod flock;
 
 use crate::mm::UserBuffer;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use easy_fs::Inode;
 
 /// The common abstraction of all IO resources
 pub trait File : Send + Sync {
@@ -9,22 +18,155 @@ pub trait File : Send + Sync {
     fn writable(&self) -> bool;
     fn read(&self, buf: UserBuffer) -> usize;
     fn write(&self, buf: UserBuffer) -> usize;
+    /// Read like `read`, failing if the data read is found damaged
+    fn try_read(&self, buf: UserBuffer) -> Result<usize, ()> {
+        Ok(self.read(buf))
+    }
+    /// The status flags of the file, shared by all the fds referring to it
+    fn status_flags(&self) -> OpenFlags {
+        OpenFlags::empty()
+    }
+    /// Replace the status flags of the file,
+    /// ignored by files with none of their own
+    fn set_status_flags(&self, _flags: OpenFlags) {}
+    /// Whether reading would not wait now
+    fn read_ready(&self) -> bool {
+        true
+    }
+    /// Whether writing would not wait now
+    fn write_ready(&self) -> bool {
+        true
+    }
+    /// Reposition the offset of the file, returns the new offset or -1
+    fn seek(&self, _offset: isize, _whence: usize) -> isize {
+        -1
+    }
+    /// Pack the entries of a directory into `buf` as [`Dirent`] records,
+    /// returns the number of bytes written or -1
+    fn getdents(&self, _buf: UserBuffer) -> isize {
+        -1
+    }
+    /// Set the size of the file, returns 0 or -1
+    fn truncate(&self, _len: usize) -> isize {
+        -1
+    }
+    /// Allocate the storage backing `len` bytes at `offset` up front,
+    /// growing the file to cover them unless `keep_size`, returns 0 or -1
+    fn fallocate(&self, _offset: usize, _len: usize, _keep_size: bool) -> isize {
+        -1
+    }
+    /// Read at `offset` without moving the offset of the file,
+    /// returns the number of bytes read or -1
+    fn read_at(&self, _offset: usize, _buf: &mut [u8]) -> isize {
+        -1
+    }
+    /// Write at `offset` without moving the offset of the file,
+    /// returns the number of bytes written or -1
+    fn write_at(&self, _offset: usize, _buf: &[u8]) -> isize {
+        -1
+    }
     fn info(&self, _st: *mut Stat) {
         unsafe {
-            *_st = Stat {
-                dev: 0,
-                ino: 0,
-                mode: StatMode::NULL,
-                nlink: 0,
-                pad: [0; 7],
-            }
+            *_st = Stat::new();
         }
     }
+    /// The terminal settings, None for a file that is no terminal
+    fn termios(&self) -> Option<Termios> {
+        None
+    }
+    /// Change the terminal settings, false for a file that is no terminal
+    fn set_termios(&self, _termios: &Termios) -> bool {
+        false
+    }
+    /// The inode of the directory the file is opened on,
+    /// paths relative to the file are resolved from it
+    fn dir_inode(&self) -> Option<Arc<Inode>> {
+        None
+    }
+    /// Take or release an advisory lock on the file as `flock` does,
+    /// returns 0 or -1
+    fn flock(&self, _operation: usize) -> isize {
+        -1
+    }
+}
+
+/// Seek relative to the start of the file
+pub const SEEK_SET: usize = 0;
+/// Seek relative to the current offset
+pub const SEEK_CUR: usize = 1;
+/// Seek relative to the end of the file
+pub const SEEK_END: usize = 2;
+
+/// `fcntl` command reading the flags of an fd
+pub const F_GETFD: usize = 1;
+/// `fcntl` command setting the flags of an fd
+pub const F_SETFD: usize = 2;
+/// The fd is closed on exec
+pub const FD_CLOEXEC: usize = 1;
+/// `fcntl` command duplicating an fd to the lowest free fd from `arg` on
+pub const F_DUPFD: usize = 0;
+/// `fcntl` command reading the access mode and the status flags of a file
+pub const F_GETFL: usize = 3;
+/// `fcntl` command setting the status flags of a file
+pub const F_SETFL: usize = 4;
+
+/// `dirfd` standing for the working directory
+pub const AT_FDCWD: isize = -100;
+/// `fstatat` flag asking for the status of a symbolic link itself
+pub const AT_SYMLINK_NOFOLLOW: u32 = 0x100;
+
+/// `access` mode checking only that the file exists
+pub const F_OK: usize = 0;
+/// `access` mode bit asking for the read permission
+pub const R_OK: usize = 4;
+/// `access` mode bit asking for the write permission
+pub const W_OK: usize = 2;
+/// `access` mode bit asking for the execute permission
+pub const X_OK: usize = 1;
+
+/// Directory entry type of a directory
+pub const DT_DIR: u8 = 4;
+/// Directory entry type of a regular file
+pub const DT_REG: u8 = 8;
+/// Directory entry type of a symbolic link
+pub const DT_LNK: u8 = 10;
+
+/// A `linux_dirent64` record, laid out as `d_ino: u64`, `d_off: i64`,
+/// `d_reclen: u16`, `d_type: u8` and the NUL-terminated name,
+/// padded to a multiple of 8 bytes
+pub struct Dirent<'a> {
+    /// inode number
+    pub ino: u64,
+    /// offset of the next entry in the directory
+    pub off: i64,
+    /// type of the entry, `DT_DIR`, `DT_REG` or `DT_LNK`
+    pub type_: u8,
+    /// name of the entry
+    pub name: &'a str,
+}
+
+impl Dirent<'_> {
+    /// Offset of the name inside a record
+    const NAME_OFFSET: usize = 19;
+    /// Length of the packed record
+    pub fn reclen(&self) -> usize {
+        (Self::NAME_OFFSET + self.name.len() + 1 + 7) & !7
+    }
+    /// Append the packed record to `bytes`
+    pub fn pack(&self, bytes: &mut Vec<u8>) {
+        let start = bytes.len();
+        bytes.extend_from_slice(&self.ino.to_le_bytes());
+        bytes.extend_from_slice(&self.off.to_le_bytes());
+        bytes.extend_from_slice(&(self.reclen() as u16).to_le_bytes());
+        bytes.push(self.type_);
+        bytes.extend_from_slice(self.name.as_bytes());
+        bytes.resize(start + self.reclen(), 0);
+    }
 }
 
 /// The stat of a inode
 #[repr(C)]
-#[derive(Debug)]
+#[derive(Clone, Copy, Debug)]
 pub struct Stat {
     /// ID of device containing file
     pub dev: u64,
@@ -34,8 +176,104 @@ pub struct Stat {
     pub mode: StatMode,
     /// number of hard links
     pub nlink: u32,
+    /// size of file in bytes
+    pub size: u64,
+    /// time of last access in nanoseconds
+    pub atime: u64,
+    /// time of last modification in nanoseconds
+    pub mtime: u64,
+    /// time of last status change in nanoseconds
+    pub ctime: u64,
+    /// number of 512-byte blocks allocated, holes left out
+    pub blocks: u64,
     /// unused pad
-    pad: [u64; 7],
+    pad: [u64; 2],
+}
+
+impl Stat {
+    /// A stat with all fields zeroed
+    pub fn new() -> Self {
+        Self {
+            dev: 0,
+            ino: 0,
+            mode: StatMode::NULL,
+            nlink: 0,
+            size: 0,
+            atime: 0,
+            mtime: 0,
+            ctime: 0,
+            blocks: 0,
+            pad: [0; 2],
+        }
+    }
+}
+
+/// A buffer of vectored I/O in user space
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+pub struct IoVec {
+    /// start address of the buffer
+    pub base: usize,
+    /// length of the buffer in bytes
+    pub len: usize,
+}
+
+/// An fd watched by `poll`
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+pub struct PollFd {
+    /// the fd, ignored if negative
+    pub fd: i32,
+    /// the events asked for
+    pub events: i16,
+    /// the events that happened, filled by `poll`
+    pub revents: i16,
+}
+
+/// `poll` event: there is data to read
+pub const POLLIN: i16 = 0x1;
+/// `poll` event: writing would not wait
+pub const POLLOUT: i16 = 0x4;
+/// `poll` event: the fd is not open
+pub const POLLNVAL: i16 = 0x20;
+
+/// Terminal settings, as `ioctl` reads and writes them
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Termios {
+    /// input modes, kept but not acted on
+    pub iflag: u32,
+    /// output modes, kept but not acted on
+    pub oflag: u32,
+    /// control modes, kept but not acted on
+    pub cflag: u32,
+    /// local modes, `ICANON` and `ECHO`
+    pub lflag: u32,
+}
+
+/// Local mode: input is edited and released a line at a time
+pub const ICANON: u32 = 0o2;
+/// Local mode: keys typed are echoed
+pub const ECHO: u32 = 0o10;
+/// `ioctl` request reading the terminal settings
+pub const TCGETS: usize = 0x5401;
+/// `ioctl` request changing the terminal settings at once
+pub const TCSETS: usize = 0x5402;
+
+/// Usage of the filesystem holding a path
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Statfs {
+    /// size of a block in bytes
+    pub bsize: u64,
+    /// data blocks in total
+    pub blocks: u64,
+    /// data blocks not allocated
+    pub bfree: u64,
+    /// inodes in total
+    pub files: u64,
+    /// inodes not allocated
+    pub ffree: u64,
 }
 
 bitflags! {
@@ -47,11 +285,20 @@ bitflags! {
         const DIR   = 0o040000;
         /// ordinary regular file
         const FILE  = 0o100000;
+        /// symbolic link
+        const LNK   = 0o120000;
+        /// character device
+        const CHR   = 0o020000;
     }
 }    
 
-pub use stdio::{Stdin, Stdout};
+pub use stdio::{Stdin, Stdout, console_input_test};
+pub use pipe::{Pipe, make_pipe};
+pub use dev::init_random;
+pub use flock::{LOCK_EX, LOCK_NB, LOCK_SH, LOCK_UN};
+pub use mount::{FileSystem, find_mount, init_mounts, mount, open};
 pub use inode::{
     OSInode, open_file, OpenFlags, list_apps, 
-    linkat, unlinkat
+    linkat, unlinkat, mkdir, symlink, readlink, sync, flush_dirty_blocks, dirty_flush_test,
+    find_dir, dir_path, statfs, stat_at, access, rename, ROOT_INODE,
 };
@@ -0,0 +1,112 @@
+//! The mount table, routing paths under a mount point to the filesystem
+//! mounted there instead of the easy-fs root
+
+use super::dev::DevFs;
+use super::proc::ProcFs;
+use super::ramfs::RamFs;
+use super::{open_file, File, OpenFlags, ROOT_INODE};
+use crate::config::{RAMFS_CAPACITY, SHM_CAPACITY};
+use crate::sync::UPSafeCell;
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use easy_fs::Inode;
+use lazy_static::*;
+
+/// A filesystem mounted somewhere below the root, given paths
+/// relative to its mount point
+pub trait FileSystem: Send + Sync {
+    /// Open the file at `path`, None if it can't be opened
+    fn open(&self, path: &str, flags: OpenFlags) -> Option<Arc<dyn File + Send + Sync>>;
+    /// Remove the file at `path`, false if there is none
+    fn unlink(&self, path: &str) -> bool;
+    /// Whether there is a file at `path`
+    fn lookup(&self, path: &str) -> bool;
+}
+
+/// `dev` of the files in `/tmp`
+const TMPFS_DEV: u64 = 1;
+/// `dev` of the files in `/dev/shm`
+const SHMFS_DEV: u64 = 2;
+
+lazy_static! {
+    /// The mount points, as absolute paths without the leading `/`,
+    /// and the filesystems mounted there
+    static ref MOUNTS: UPSafeCell<Vec<(String, Arc<dyn FileSystem>)>> = unsafe {
+        UPSafeCell::new(Vec::new())
+    };
+}
+
+/// Mount the default filesystems
+pub fn init_mounts() {
+    mount("/dev", Arc::new(DevFs));
+    mount("/proc", Arc::new(ProcFs));
+    mount("/tmp", Arc::new(RamFs::new(TMPFS_DEV, RAMFS_CAPACITY)));
+    mount("/dev/shm", Arc::new(RamFs::new(SHMFS_DEV, SHM_CAPACITY)));
+}
+
+/// Mount `fs` at the absolute path `point`, hiding what was mounted there
+pub fn mount(point: &str, fs: Arc<dyn FileSystem>) {
+    let point = normalize(point);
+    let mut mounts = MOUNTS.exclusive_access();
+    mounts.retain(|(mounted, _)| *mounted != point);
+    mounts.push((point, fs));
+}
+
+/// Split `path` into its components, dropping `.` and resolving `..`
+/// lexically, and join them back without the leading `/`
+fn normalize(path: &str) -> String {
+    let mut components: Vec<&str> = Vec::new();
+    for component in path.split('/') {
+        match component {
+            "" | "." => {}
+            ".." => {
+                components.pop();
+            }
+            component => components.push(component),
+        }
+    }
+    components.join("/")
+}
+
+/// The filesystem mounted at the longest mount point `path` relative to
+/// `cwd` is under, together with the rest of the path below it.
+/// The mount points are only seen from the root, so a relative path
+/// counts only when `cwd` is the root
+pub fn find_mount(cwd: &Arc<Inode>, path: &str) -> Option<(Arc<dyn FileSystem>, String)> {
+    if !path.starts_with('/') && cwd.inode_id() != ROOT_INODE.inode_id() {
+        return None;
+    }
+    let path = normalize(path);
+    let mounts = MOUNTS.exclusive_access();
+    let mut found: Option<(usize, &Arc<dyn FileSystem>)> = None;
+    for (point, fs) in mounts.iter() {
+        let under = match path.strip_prefix(point.as_str()) {
+            Some(rest) => rest.is_empty() || rest.starts_with('/'),
+            None => false,
+        };
+        if under && found.map_or(true, |(len, _)| point.len() > len) {
+            found = Some((point.len(), fs));
+        }
+    }
+    let (len, fs) = found?;
+    let rest = path[len..].trim_start_matches('/');
+    Some((fs.clone(), String::from(rest)))
+}
+
+/// Open the file at `path` relative to `cwd`, on the filesystem
+/// mounted there or else on the easy-fs root
+pub fn open(cwd: &Arc<Inode>, path: &str, flags: OpenFlags) -> Option<Arc<dyn File + Send + Sync>> {
+    match find_mount(cwd, path) {
+        Some((fs, rest)) => {
+            if flags.contains(OpenFlags::CREATE | OpenFlags::EXCL) && fs.lookup(rest.as_str()) {
+                return None;
+            }
+            fs.open(rest.as_str(), flags)
+        }
+        None => {
+            let inode: Arc<dyn File + Send + Sync> = open_file(cwd, path, flags)?;
+            Some(inode)
+        }
+    }
+}
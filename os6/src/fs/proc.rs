@@ -0,0 +1,179 @@
+//! Files telling about the kernel and the processes, made up as they are read
+
+use super::{File, FileSystem, OpenFlags, Stat, StatMode};
+use crate::console::{kernel_log_end, read_kernel_log};
+use crate::mm::UserBuffer;
+use crate::sync::UPSafeCell;
+use crate::task::{current_task, pid2task, suspend_current_and_run_next, TaskStatus};
+use alloc::format;
+use alloc::string::String;
+use alloc::sync::Arc;
+
+/// `/proc/kmsg` opened, streaming the kernel log from the oldest
+/// message held on. Reading at the end waits for more messages,
+/// or reads nothing with `NONBLOCK`
+pub struct KmsgFile {
+    inner: UPSafeCell<KmsgFileInner>,
+}
+
+struct KmsgFileInner {
+    /// Sequence number in the kernel log of the next byte to read
+    seq: usize,
+    status: OpenFlags,
+}
+
+impl File for KmsgFile {
+    fn readable(&self) -> bool { true }
+    fn writable(&self) -> bool { false }
+    fn read(&self, mut buf: UserBuffer) -> usize {
+        loop {
+            let mut inner = self.inner.exclusive_access();
+            let mut read_size = 0usize;
+            for slice in buf.buffers.iter_mut() {
+                let len = read_kernel_log(&mut inner.seq, slice);
+                read_size += len;
+                if len < slice.len() {
+                    break;
+                }
+            }
+            if read_size > 0 || buf.len() == 0 || inner.status.contains(OpenFlags::NONBLOCK) {
+                return read_size;
+            }
+            drop(inner);
+            suspend_current_and_run_next();
+        }
+    }
+    fn write(&self, _buf: UserBuffer) -> usize {
+        panic!("Cannot write to /proc/kmsg!");
+    }
+    fn status_flags(&self) -> OpenFlags {
+        self.inner.exclusive_access().status
+    }
+    fn set_status_flags(&self, flags: OpenFlags) {
+        self.inner.exclusive_access().status = flags.status_flags();
+    }
+    /// There are messages not read yet
+    fn read_ready(&self) -> bool {
+        self.inner.exclusive_access().seq < kernel_log_end()
+    }
+    fn info(&self, st: *mut Stat) {
+        let mut stat = Stat::new();
+        stat.mode = StatMode::FILE;
+        stat.nlink = 1;
+        unsafe {
+            *st = stat;
+        }
+    }
+}
+
+/// Render the status of the process `pid` as lines of `Name:\tvalue`,
+/// None if there is no such process or it has exited
+fn render_status(pid: usize) -> Option<String> {
+    let task = pid2task(pid)?;
+    let frames = task.usage().resident_frames;
+    let inner = task.inner_exclusive_access();
+    let ppid = match inner.parent.as_ref().and_then(|parent| parent.upgrade()) {
+        Some(parent) => parent.getpid(),
+        None => 0,
+    };
+    let state = match inner.task_status {
+        TaskStatus::UnInit => "I (idle)",
+        TaskStatus::Ready => "R (runnable)",
+        TaskStatus::Running => "R (running)",
+        TaskStatus::Blocked => "S (sleeping)",
+        TaskStatus::Zombie => "Z (zombie)",
+    };
+    Some(format!(
+        "Pid:\t{}\nPPid:\t{}\nState:\t{}\nFrames:\t{}\n",
+        pid, ppid, state, frames
+    ))
+}
+
+/// `/proc/<pid>/status` opened, rendered anew on every read
+/// so that it tells how the process is at the time
+pub struct StatusFile {
+    pid: usize,
+    offset: UPSafeCell<usize>,
+}
+
+impl File for StatusFile {
+    fn readable(&self) -> bool { true }
+    fn writable(&self) -> bool { false }
+    fn read(&self, buf: UserBuffer) -> usize {
+        self.try_read(buf).unwrap_or(0)
+    }
+    /// Fails once the process has exited
+    fn try_read(&self, mut buf: UserBuffer) -> Result<usize, ()> {
+        let status = render_status(self.pid).ok_or(())?;
+        let mut offset = self.offset.exclusive_access();
+        let rest = status.as_bytes().get(*offset..).unwrap_or(&[]);
+        let read_size = buf.copy_from_slice(rest);
+        *offset += read_size;
+        Ok(read_size)
+    }
+    fn write(&self, _buf: UserBuffer) -> usize {
+        panic!("Cannot write to /proc/<pid>/status!");
+    }
+    fn info(&self, st: *mut Stat) {
+        let mut stat = Stat::new();
+        stat.mode = StatMode::FILE;
+        stat.nlink = 1;
+        unsafe {
+            *st = stat;
+        }
+    }
+}
+
+/// A file of the filesystem, found by its path
+enum ProcEntry {
+    Kmsg,
+    /// `<pid>/status`, where `self` stands for the calling process
+    Status(usize),
+}
+
+/// The filesystem of the files telling about the kernel
+/// and the processes alive
+pub struct ProcFs;
+
+impl ProcFs {
+    fn entry(path: &str) -> Option<ProcEntry> {
+        if path == "kmsg" {
+            return Some(ProcEntry::Kmsg);
+        }
+        let pid = match path.strip_suffix("/status")? {
+            "self" => current_task()?.getpid(),
+            pid => pid.parse().ok()?,
+        };
+        pid2task(pid)?;
+        Some(ProcEntry::Status(pid))
+    }
+}
+
+impl FileSystem for ProcFs {
+    /// Open a file, only for reading
+    fn open(&self, path: &str, flags: OpenFlags) -> Option<Arc<dyn File + Send + Sync>> {
+        if flags.read_write() != (true, false) {
+            return None;
+        }
+        Some(match Self::entry(path)? {
+            ProcEntry::Kmsg => Arc::new(KmsgFile {
+                inner: unsafe {
+                    UPSafeCell::new(KmsgFileInner {
+                        seq: 0,
+                        status: flags.status_flags(),
+                    })
+                },
+            }),
+            ProcEntry::Status(pid) => Arc::new(StatusFile {
+                pid,
+                offset: unsafe { UPSafeCell::new(0) },
+            }),
+        })
+    }
+    fn unlink(&self, _path: &str) -> bool {
+        false
+    }
+    fn lookup(&self, path: &str) -> bool {
+        Self::entry(path).is_some()
+    }
+}
@@ -0,0 +1,305 @@
+//! An in-memory filesystem holding plain files in the kernel heap.
+//! They never reach the block device and are gone on shutdown
+
+use super::{File, FileSystem, OpenFlags, Stat, StatMode, SEEK_CUR, SEEK_END, SEEK_SET};
+use crate::mm::UserBuffer;
+use crate::sync::UPSafeCell;
+use crate::timer::get_time_ns;
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::sync::{Arc, Weak};
+use alloc::vec::Vec;
+
+/// A file in memory
+struct RamInode {
+    /// The filesystem whose room the data takes
+    fs: Weak<UPSafeCell<RamFsInner>>,
+    ino: u64,
+    /// 1 while the file has a name, 0 once unlinked
+    nlink: u32,
+    data: Vec<u8>,
+    atime: u64,
+    mtime: u64,
+    ctime: u64,
+}
+
+impl RamInode {
+    /// Bytes the data may grow by
+    fn room(&self) -> usize {
+        match self.fs.upgrade() {
+            Some(fs) => {
+                let fs = fs.exclusive_access();
+                fs.capacity - fs.used
+            }
+            None => 0,
+        }
+    }
+    /// Record that the data grows by `grown` bytes and shrinks by `shrunk`
+    fn account(&self, grown: usize, shrunk: usize) {
+        if let Some(fs) = self.fs.upgrade() {
+            let mut fs = fs.exclusive_access();
+            fs.used = fs.used + grown - shrunk;
+        }
+    }
+    /// Resize the data to `len` bytes, extended bytes read as zero.
+    /// Returns false if the filesystem has no room for it
+    fn resize(&mut self, len: usize) -> bool {
+        let old_len = self.data.len();
+        if len > old_len {
+            if len - old_len > self.room() {
+                return false;
+            }
+            self.account(len - old_len, 0);
+        } else {
+            self.account(0, old_len - len);
+        }
+        self.data.resize(len, 0);
+        self.mtime = get_time_ns();
+        self.ctime = self.mtime;
+        true
+    }
+    /// Write at `offset`, as much as there is room for
+    fn write_at(&mut self, offset: usize, buf: &[u8]) -> usize {
+        let room = (self.room() + self.data.len()).saturating_sub(offset);
+        let buf = &buf[..buf.len().min(room)];
+        let end = offset + buf.len();
+        if end > self.data.len() {
+            self.resize(end);
+        }
+        self.data[offset..end].copy_from_slice(buf);
+        self.mtime = get_time_ns();
+        self.ctime = self.mtime;
+        buf.len()
+    }
+    /// Read at `offset`, up to the end of file
+    fn read_at(&mut self, offset: usize, buf: &mut [u8]) -> usize {
+        self.atime = get_time_ns();
+        if offset >= self.data.len() {
+            return 0;
+        }
+        let len = buf.len().min(self.data.len() - offset);
+        buf[..len].copy_from_slice(&self.data[offset..offset + len]);
+        len
+    }
+}
+
+impl Drop for RamInode {
+    /// The last link and the last opened file are gone
+    fn drop(&mut self) {
+        self.account(0, self.data.len());
+    }
+}
+
+struct RamFsInner {
+    files: BTreeMap<String, Arc<UPSafeCell<RamInode>>>,
+    /// Max bytes of data held by all the files
+    capacity: usize,
+    /// Bytes of data held by all the files
+    used: usize,
+    next_ino: u64,
+}
+
+/// An in-memory filesystem with a single directory of files
+pub struct RamFs {
+    /// `dev` of the files, telling filesystems apart
+    dev: u64,
+    inner: Arc<UPSafeCell<RamFsInner>>,
+}
+
+impl RamFs {
+    /// An empty filesystem holding at most `capacity` bytes of data
+    pub fn new(dev: u64, capacity: usize) -> Self {
+        Self {
+            dev,
+            inner: Arc::new(unsafe {
+                UPSafeCell::new(RamFsInner {
+                    files: BTreeMap::new(),
+                    capacity,
+                    used: 0,
+                    next_ino: 1,
+                })
+            }),
+        }
+    }
+}
+
+impl FileSystem for RamFs {
+    /// Open the file `path`, creating it with `CREATE`
+    fn open(&self, path: &str, flags: OpenFlags) -> Option<Arc<dyn File + Send + Sync>> {
+        if path.is_empty() || path.contains('/') {
+            return None;
+        }
+        let (readable, writable) = flags.read_write();
+        if flags.contains(OpenFlags::TRUNC) && !writable {
+            return None;
+        }
+        let mut inner = self.inner.exclusive_access();
+        let inode = match inner.files.get(path) {
+            Some(inode) => inode.clone(),
+            None if flags.contains(OpenFlags::CREATE) => {
+                let now = get_time_ns();
+                let inode = Arc::new(unsafe {
+                    UPSafeCell::new(RamInode {
+                        fs: Arc::downgrade(&self.inner),
+                        ino: inner.next_ino,
+                        nlink: 1,
+                        data: Vec::new(),
+                        atime: now,
+                        mtime: now,
+                        ctime: now,
+                    })
+                });
+                inner.next_ino += 1;
+                inner.files.insert(String::from(path), inode.clone());
+                inode
+            }
+            None => return None,
+        };
+        drop(inner);
+        if flags.contains(OpenFlags::TRUNC) {
+            inode.exclusive_access().resize(0);
+        }
+        Some(Arc::new(RamFile {
+            dev: self.dev,
+            readable,
+            writable,
+            inner: unsafe {
+                UPSafeCell::new(RamFileInner {
+                    offset: 0,
+                    status: flags.status_flags(),
+                    inode,
+                })
+            },
+        }))
+    }
+    /// Remove the file `path`, its data stays
+    /// until the files opened on it are closed
+    fn unlink(&self, path: &str) -> bool {
+        let inode = self.inner.exclusive_access().files.remove(path);
+        // dropped out of the borrow above, giving back its data if not opened
+        match inode {
+            Some(inode) => {
+                inode.exclusive_access().nlink = 0;
+                true
+            }
+            None => false,
+        }
+    }
+    fn lookup(&self, path: &str) -> bool {
+        self.inner.exclusive_access().files.contains_key(path)
+    }
+}
+
+/// An opened file of the filesystem
+pub struct RamFile {
+    dev: u64,
+    readable: bool,
+    writable: bool,
+    inner: UPSafeCell<RamFileInner>,
+}
+
+struct RamFileInner {
+    offset: usize,
+    /// status flags, every write goes to the end of file with `APPEND`
+    status: OpenFlags,
+    inode: Arc<UPSafeCell<RamInode>>,
+}
+
+impl File for RamFile {
+    fn readable(&self) -> bool { self.readable }
+    fn writable(&self) -> bool { self.writable }
+    fn read(&self, mut buf: UserBuffer) -> usize {
+        let mut inner = self.inner.exclusive_access();
+        let mut inode = inner.inode.exclusive_access();
+        let mut total_read_size = 0usize;
+        for slice in buf.buffers.iter_mut() {
+            let read_size = inode.read_at(inner.offset + total_read_size, *slice);
+            total_read_size += read_size;
+            if read_size < slice.len() {
+                break;
+            }
+        }
+        drop(inode);
+        inner.offset += total_read_size;
+        total_read_size
+    }
+    fn write(&self, buf: UserBuffer) -> usize {
+        let mut inner = self.inner.exclusive_access();
+        let mut inode = inner.inode.exclusive_access();
+        let mut offset = if inner.status.contains(OpenFlags::APPEND) {
+            inode.data.len()
+        } else {
+            inner.offset
+        };
+        let mut total_write_size = 0usize;
+        for slice in buf.buffers.iter() {
+            let write_size = inode.write_at(offset, *slice);
+            offset += write_size;
+            total_write_size += write_size;
+            // the filesystem is full
+            if write_size < slice.len() {
+                break;
+            }
+        }
+        drop(inode);
+        inner.offset = offset;
+        total_write_size
+    }
+    fn status_flags(&self) -> OpenFlags {
+        self.inner.exclusive_access().status
+    }
+    fn set_status_flags(&self, flags: OpenFlags) {
+        self.inner.exclusive_access().status = flags.status_flags();
+    }
+    fn seek(&self, offset: isize, whence: usize) -> isize {
+        let mut inner = self.inner.exclusive_access();
+        let base = match whence {
+            SEEK_SET => 0,
+            SEEK_CUR => inner.offset as isize,
+            SEEK_END => inner.inode.exclusive_access().data.len() as isize,
+            _ => return -1,
+        };
+        match base.checked_add(offset) {
+            Some(new_offset) if new_offset >= 0 => {
+                inner.offset = new_offset as usize;
+                new_offset
+            }
+            _ => -1,
+        }
+    }
+    fn truncate(&self, len: usize) -> isize {
+        let inner = self.inner.exclusive_access();
+        if inner.inode.exclusive_access().resize(len) {
+            0
+        } else {
+            -1
+        }
+    }
+    fn read_at(&self, offset: usize, buf: &mut [u8]) -> isize {
+        let inner = self.inner.exclusive_access();
+        let read_size = inner.inode.exclusive_access().read_at(offset, buf);
+        read_size as isize
+    }
+    fn write_at(&self, offset: usize, buf: &[u8]) -> isize {
+        let inner = self.inner.exclusive_access();
+        let write_size = inner.inode.exclusive_access().write_at(offset, buf);
+        write_size as isize
+    }
+    fn info(&self, st: *mut Stat) {
+        let inner = self.inner.exclusive_access();
+        let inode = inner.inode.exclusive_access();
+        let mut stat = Stat::new();
+        stat.dev = self.dev;
+        stat.ino = inode.ino;
+        stat.mode = StatMode::FILE;
+        stat.nlink = inode.nlink;
+        stat.size = inode.data.len() as u64;
+        stat.blocks = ((inode.data.len() + 511) / 512) as u64;
+        stat.atime = inode.atime;
+        stat.mtime = inode.mtime;
+        stat.ctime = inode.ctime;
+        unsafe {
+            *st = stat;
+        }
+    }
+}
@@ -1,7 +1,237 @@
-use super::File;
+use super::{File, Termios, ECHO, ICANON};
+use crate::config::CONSOLE_INPUT_CAPACITY;
+use crate::console::print_unlogged;
 use crate::mm::{UserBuffer};
 use crate::sbi::console_getchar;
+use crate::sync::UPSafeCell;
 use crate::task::suspend_current_and_run_next;
+use alloc::collections::VecDeque;
+use alloc::vec::Vec;
+use lazy_static::*;
+
+const LF: u8 = b'\n';
+const CR: u8 = b'\r';
+const BS: u8 = 0x08;
+const DL: u8 = 0x7f;
+/// Ctrl-D, the end of input on an empty line
+const EOT: u8 = 0x04;
+
+/// Keys typed on the console on their way to readers. In canonical mode
+/// they are edited until Enter releases the line, in raw mode each key
+/// is released as it comes. Keys are echoed with `ECHO` only
+pub struct ConsoleInput {
+    /// the line being typed
+    line: Vec<u8>,
+    /// bytes released, waiting for a reader
+    ready: VecDeque<u8>,
+    /// Ctrl-D was typed on an empty line, the next read returns 0
+    eof: bool,
+    termios: Termios,
+}
+
+impl ConsoleInput {
+    /// Empty input in canonical mode with echo
+    pub fn new() -> Self {
+        Self {
+            line: Vec::new(),
+            ready: VecDeque::new(),
+            eof: false,
+            termios: Termios {
+                lflag: ICANON | ECHO,
+                ..Termios::default()
+            },
+        }
+    }
+    fn canonical(&self) -> bool {
+        self.termios.lflag & ICANON != 0
+    }
+    pub fn termios(&self) -> Termios {
+        self.termios
+    }
+    /// Change the settings, a line being typed is
+    /// released as it is when leaving canonical mode
+    pub fn set_termios(&mut self, termios: Termios) {
+        self.termios = termios;
+        if !self.canonical() {
+            self.release();
+        }
+    }
+    /// Move the line typed so far to the bytes released, as much as fits
+    fn release(&mut self) {
+        let room = CONSOLE_INPUT_CAPACITY - self.ready.len();
+        let len = self.line.len().min(room);
+        self.ready.extend(self.line.drain(..len));
+        self.line.clear();
+    }
+    /// Take a typed key, appending what it shows on the console to
+    /// `echo`. Keys typed while there is no room are dropped
+    pub fn feed(&mut self, key: u8, echo: &mut Vec<u8>) {
+        let shown = self.termios.lflag & ECHO != 0;
+        if !self.canonical() {
+            if self.ready.len() < CONSOLE_INPUT_CAPACITY {
+                self.ready.push_back(key);
+                if shown {
+                    echo.push(key);
+                }
+            }
+            return;
+        }
+        let mut show = |bytes: &[u8]| {
+            if shown {
+                echo.extend_from_slice(bytes);
+            }
+        };
+        match key {
+            CR | LF => {
+                if self.line.len() + 1 > CONSOLE_INPUT_CAPACITY - self.ready.len() {
+                    return;
+                }
+                self.line.push(LF);
+                self.release();
+                show(&[LF]);
+            }
+            BS | DL => {
+                if self.line.pop().is_some() {
+                    show(&[BS, b' ', BS]);
+                }
+            }
+            EOT => {
+                if self.line.is_empty() {
+                    self.eof = true;
+                } else {
+                    self.release();
+                }
+            }
+            key => {
+                // the newline ending the line must still fit
+                if self.ready.len() + self.line.len() + 1 < CONSOLE_INPUT_CAPACITY {
+                    self.line.push(key);
+                    show(&[key]);
+                }
+            }
+        }
+    }
+    /// Whether a read would not wait
+    pub fn readable(&self) -> bool {
+        !self.ready.is_empty() || self.eof
+    }
+    /// Take the bytes released into `buf`, up to the end of a line in
+    /// canonical mode. `Some(0)` at the end of input, None if there is nothing yet
+    pub fn take(&mut self, buf: &mut [u8]) -> Option<usize> {
+        if self.ready.is_empty() {
+            if self.eof {
+                self.eof = false;
+                return Some(0);
+            }
+            return None;
+        }
+        let mut len = 0;
+        while len < buf.len() {
+            let byte = match self.ready.pop_front() {
+                Some(byte) => byte,
+                None => break,
+            };
+            buf[len] = byte;
+            len += 1;
+            if byte == LF && self.canonical() {
+                break;
+            }
+        }
+        Some(len)
+    }
+}
+
+lazy_static! {
+    /// The keys typed on the console
+    static ref CONSOLE_INPUT: UPSafeCell<ConsoleInput> = unsafe { UPSafeCell::new(ConsoleInput::new()) };
+}
+
+/// Take the keys typed on the console so far into `CONSOLE_INPUT`, echoing them
+fn pump_keys() {
+    let mut echo = Vec::new();
+    let mut input = CONSOLE_INPUT.exclusive_access();
+    loop {
+        match console_getchar() {
+            // no key typed
+            0 => break,
+            c if c > u8::MAX as usize => break,
+            c => input.feed(c as u8, &mut echo),
+        }
+    }
+    drop(input);
+    if !echo.is_empty() {
+        print_unlogged(format_args!("{}", core::str::from_utf8(&echo).unwrap_or("")));
+    }
+}
+
+/// Feed keys into a line being typed and take it back as it is edited
+pub fn console_input_test() {
+    let mut input = ConsoleInput::new();
+    let mut echo = Vec::new();
+    let mut buf = [0u8; 16];
+    for &key in b"lx\x08ine\r" {
+        input.feed(key, &mut echo);
+        if key != CR {
+            assert!(!input.readable());
+        }
+    }
+    assert_eq!(&echo[..], b"lx\x08 \x08ine\n");
+    assert_eq!(input.take(&mut buf), Some(5));
+    assert_eq!(&buf[..5], b"line\n");
+    assert_eq!(input.take(&mut buf), None);
+    // a read stops at the end of a line
+    for &key in b"a\rbc\r" {
+        input.feed(key, &mut echo);
+    }
+    assert_eq!(input.take(&mut buf[..1]), Some(1));
+    assert_eq!(input.take(&mut buf), Some(1));
+    assert_eq!(input.take(&mut buf), Some(3));
+    assert_eq!(&buf[..3], b"bc\n");
+    // backspace on an empty line does nothing, Ctrl-D there is the end of input
+    echo.clear();
+    input.feed(DL, &mut echo);
+    input.feed(EOT, &mut echo);
+    assert!(echo.is_empty());
+    assert_eq!(input.take(&mut buf), Some(0));
+    assert_eq!(input.take(&mut buf), None);
+    // a line too long is cut short, its newline still fits
+    for _ in 0..CONSOLE_INPUT_CAPACITY + 10 {
+        input.feed(b'x', &mut echo);
+    }
+    input.feed(CR, &mut echo);
+    let mut long = [0u8; CONSOLE_INPUT_CAPACITY + 10];
+    assert_eq!(input.take(&mut long), Some(CONSOLE_INPUT_CAPACITY));
+    assert_eq!(long[CONSOLE_INPUT_CAPACITY - 1], LF);
+    // a line typed without echo, as a password is
+    input.set_termios(Termios { lflag: ICANON, ..input.termios() });
+    echo.clear();
+    for &key in b"pw\x08d\r" {
+        input.feed(key, &mut echo);
+    }
+    assert!(echo.is_empty());
+    assert_eq!(input.take(&mut buf), Some(3));
+    assert_eq!(&buf[..3], b"pd\n");
+    // raw mode releases the pending line and then every key, unechoed
+    input.feed(b'p', &mut echo);
+    input.set_termios(Termios { lflag: 0, ..input.termios() });
+    assert_eq!(input.take(&mut buf), Some(1));
+    assert_eq!(input.take(&mut buf), None);
+    input.feed(b'k', &mut echo);
+    assert!(input.readable());
+    assert_eq!(input.take(&mut buf), Some(1));
+    assert_eq!(buf[0], b'k');
+    input.feed(BS, &mut echo);
+    input.feed(EOT, &mut echo);
+    assert!(echo.is_empty());
+    assert_eq!(input.take(&mut buf), Some(2));
+    assert_eq!(&buf[..2], &[BS, EOT]);
+    // and back to canonical mode with echo
+    input.set_termios(Termios { lflag: ICANON | ECHO, ..input.termios() });
+    input.feed(b'z', &mut echo);
+    assert_eq!(&echo[..], b"z");
+    assert!(!input.readable());
+    info!("console_input_test passed!");
+}
 
 /// The standard input
 pub struct Stdin;
@@ -11,26 +241,36 @@ pub struct Stdout;
 impl File for Stdin {
     fn readable(&self) -> bool { true }
     fn writable(&self) -> bool { false }
+    /// Wait for a line, or for any key in raw mode, and read what
+    /// there is of it. 0 at the end of input
     fn read(&self, mut user_buf: UserBuffer) -> usize {
-        assert_eq!(user_buf.len(), 1);
-        // busy loop
-        let mut c: usize;
+        let mut kept = Vec::new();
         loop {
-            c = console_getchar();
-            if c == 0 {
-                suspend_current_and_run_next();
-                continue;
-            } else {
+            pump_keys();
+            kept.resize(user_buf.len(), 0);
+            if let Some(len) = CONSOLE_INPUT.exclusive_access().take(&mut kept) {
+                kept.truncate(len);
                 break;
             }
+            suspend_current_and_run_next();
         }
-        let ch = c as u8;
-        unsafe { user_buf.buffers[0].as_mut_ptr().write_volatile(ch); }
-        1
+        user_buf.copy_from_slice(&kept)
     }
     fn write(&self, _user_buf: UserBuffer) -> usize {
         panic!("Cannot write to stdin!");
     }
+    /// A line, or a key in raw mode, is there to read
+    fn read_ready(&self) -> bool {
+        pump_keys();
+        CONSOLE_INPUT.exclusive_access().readable()
+    }
+    fn termios(&self) -> Option<Termios> {
+        Some(CONSOLE_INPUT.exclusive_access().termios())
+    }
+    fn set_termios(&self, termios: &Termios) -> bool {
+        CONSOLE_INPUT.exclusive_access().set_termios(*termios);
+        true
+    }
 }
 
 impl File for Stdout {
@@ -41,7 +281,7 @@ impl File for Stdout {
     }
     fn write(&self, user_buf: UserBuffer) -> usize {
         for buffer in user_buf.buffers.iter() {
-            print!("{}", core::str::from_utf8(*buffer).unwrap());
+            print_unlogged(format_args!("{}", core::str::from_utf8(*buffer).unwrap()));
         }
         user_buf.len()
     }
@@ -25,5 +25,5 @@ fn panic(info: &PanicInfo) -> ! {
             info.message().unwrap()
         );
     }
-    shutdown()
+    shutdown(true)
 }
@@ -57,16 +57,29 @@ fn clear_bss() {
 }
 
 #[no_mangle]
-/// the rust entry-point of os
-pub fn rust_main() -> ! {
+/// the rust entry-point of os, the bootloader passes the hart id
+/// and the address of the device tree along
+pub fn rust_main(_hartid: usize, dtb: usize) -> ! {
     clear_bss();
+    timer::init_timebase(dtb);
     logging::init();
     println!("[kernel] Hello, world!");
     mm::init();
     mm::remap_test();
+    mm::frame_share_test();
+    mm::cow_test();
+    mm::lazy_test();
+    mm::swap_test();
+    mm::huge_page_test();
+    mm::user_buffer_test();
+    fs::console_input_test();
+    fs::dirty_flush_test();
+    console::kernel_log_test();
     trap::init();
     trap::enable_timer_interrupt();
     timer::set_next_trigger();
+    fs::init_random();
+    fs::init_mounts();
     fs::list_apps();
     task::add_initproc();
     task::run_tasks();
@@ -2,8 +2,9 @@
 //! controls all the frames in the operating system.
 
 use super::{PhysAddr, PhysPageNum};
-use crate::config::MEMORY_END;
+use crate::config::{HUGE_PAGE_SIZE, MEMORY_END, PAGE_SIZE};
 use crate::sync::UPSafeCell;
+use alloc::vec;
 use alloc::vec::Vec;
 use core::fmt::{self, Debug, Formatter};
 use lazy_static::*;
@@ -22,6 +23,12 @@ impl FrameTracker {
         }
         Self { ppn }
     }
+    /// Another tracker of the same frame without cleaning it,
+    /// the frame is freed when the last of its trackers drops
+    pub fn share(&self) -> Self {
+        FRAME_ALLOCATOR.exclusive_access().inc_ref(self.ppn);
+        Self { ppn: self.ppn }
+    }
 }
 
 impl Debug for FrameTracker {
@@ -39,7 +46,12 @@ impl Drop for FrameTracker {
 trait FrameAllocator {
     fn new() -> Self;
     fn alloc(&mut self) -> Option<PhysPageNum>;
+    fn alloc_huge(&mut self) -> Option<PhysPageNum>;
     fn dealloc(&mut self, ppn: PhysPageNum);
+    fn inc_ref(&mut self, ppn: PhysPageNum);
+    fn dec_ref(&mut self, ppn: PhysPageNum) -> usize;
+    fn ref_count(&self, ppn: PhysPageNum) -> usize;
+    fn remaining(&self) -> usize;
 }
 
 /// an implementation for frame allocator
@@ -47,14 +59,27 @@ pub struct StackFrameAllocator {
     current: usize,
     end: usize,
     recycled: Vec<usize>,
+    /// the first frame managed
+    start: usize,
+    /// number of trackers of each frame from `start`
+    ref_counts: Vec<u16>,
 }
 
 impl StackFrameAllocator {
     pub fn init(&mut self, l: PhysPageNum, r: PhysPageNum) {
         self.current = l.0;
         self.end = r.0;
+        self.start = l.0;
+        self.ref_counts = vec![0; r.0 - l.0];
         info!("last {} Physical Frames.", self.end - self.current);
     }
+    fn ref_count_mut(&mut self, ppn: usize) -> &mut u16 {
+        // validity check
+        if ppn < self.start || ppn >= self.current || self.ref_counts[ppn - self.start] == 0 {
+            panic!("Frame ppn={:#x} has not been allocated!", ppn);
+        }
+        &mut self.ref_counts[ppn - self.start]
+    }
 }
 impl FrameAllocator for StackFrameAllocator {
     fn new() -> Self {
@@ -62,26 +87,70 @@ impl FrameAllocator for StackFrameAllocator {
             current: 0,
             end: 0,
             recycled: Vec::new(),
+            start: 0,
+            ref_counts: Vec::new(),
         }
     }
     fn alloc(&mut self) -> Option<PhysPageNum> {
-        if let Some(ppn) = self.recycled.pop() {
-            Some(ppn.into())
+        let ppn = if let Some(ppn) = self.recycled.pop() {
+            ppn
         } else if self.current == self.end {
-            None
+            return None;
         } else {
             self.current += 1;
-            Some((self.current - 1).into())
+            self.current - 1
+        };
+        self.ref_counts[ppn - self.start] = 1;
+        Some(ppn.into())
+    }
+    /// Allocate the frames of a megapage, free, contiguous and aligned to it
+    fn alloc_huge(&mut self) -> Option<PhysPageNum> {
+        let pages = HUGE_PAGE_SIZE / PAGE_SIZE;
+        let first = (self.start + pages - 1) / pages * pages;
+        let ppn = (first..self.end)
+            .step_by(pages)
+            .take_while(|ppn| ppn + pages <= self.end)
+            .find(|ppn| {
+                let from = ppn - self.start;
+                self.ref_counts[from..from + pages].iter().all(|&count| count == 0)
+            })?;
+        // the frames never allocated below the megapage are recycled
+        if ppn + pages > self.current {
+            self.recycled.extend(self.current..ppn);
+            self.current = ppn + pages;
         }
+        self.recycled.retain(|&recycled| recycled < ppn || recycled >= ppn + pages);
+        let from = ppn - self.start;
+        self.ref_counts[from..from + pages].fill(1);
+        Some(ppn.into())
     }
     fn dealloc(&mut self, ppn: PhysPageNum) {
-        let ppn = ppn.0;
-        // validity check
-        if ppn >= self.current || self.recycled.iter().any(|v| *v == ppn) {
-            panic!("Frame ppn={:#x} has not been allocated!", ppn);
+        // recycle when nobody refers to it
+        if self.dec_ref(ppn) == 0 {
+            self.recycled.push(ppn.0);
+        }
+    }
+    fn inc_ref(&mut self, ppn: PhysPageNum) {
+        let count = self.ref_count_mut(ppn.0);
+        *count = count
+            .checked_add(1)
+            .unwrap_or_else(|| panic!("Frame ppn={:#x} has too many references!", ppn.0));
+    }
+    /// Drop a reference to the frame, returns how many are left
+    fn dec_ref(&mut self, ppn: PhysPageNum) -> usize {
+        let count = self.ref_count_mut(ppn.0);
+        *count -= 1;
+        *count as usize
+    }
+    fn ref_count(&self, ppn: PhysPageNum) -> usize {
+        if ppn.0 < self.start || ppn.0 >= self.current {
+            0
+        } else {
+            self.ref_counts[ppn.0 - self.start] as usize
         }
-        // recycle
-        self.recycled.push(ppn);
+    }
+    fn remaining(&self) -> usize {
+        self.end - self.current + self.recycled.len()
     }
 }
 
@@ -111,11 +180,58 @@ pub fn frame_alloc() -> Option<FrameTracker> {
         .map(FrameTracker::new)
 }
 
-/// deallocate a frame
+/// allocate the frames of a megapage, contiguous and aligned to it
+pub fn frame_alloc_huge() -> Option<Vec<FrameTracker>> {
+    let start = FRAME_ALLOCATOR.exclusive_access().alloc_huge()?;
+    Some(
+        (0..HUGE_PAGE_SIZE / PAGE_SIZE)
+            .map(|i| FrameTracker::new(PhysPageNum(start.0 + i)))
+            .collect(),
+    )
+}
+
+/// deallocate a frame, which is freed when nobody else refers to it
 pub fn frame_dealloc(ppn: PhysPageNum) {
     FRAME_ALLOCATOR.exclusive_access().dealloc(ppn);
 }
 
+/// number of trackers referring to a frame
+pub fn frame_ref_count(ppn: PhysPageNum) -> usize {
+    FRAME_ALLOCATOR.exclusive_access().ref_count(ppn)
+}
+
+/// number of frames left to allocate
+pub fn frame_remaining() -> usize {
+    FRAME_ALLOCATOR.exclusive_access().remaining()
+}
+
+/// Share a frame between two trackers, it stays allocated
+/// until the last of them drops
+pub fn frame_share_test() {
+    let before = frame_remaining();
+    let first = frame_alloc().unwrap();
+    let ppn = first.ppn;
+    ppn.get_bytes_array()[0] = 0x5a;
+    let second = first.share();
+    assert_eq!(second.ppn, ppn);
+    assert_eq!(frame_ref_count(ppn), 2);
+    assert_eq!(frame_remaining(), before - 1);
+    drop(first);
+    // still allocated, with what was written
+    assert_eq!(frame_ref_count(ppn), 1);
+    assert_eq!(frame_remaining(), before - 1);
+    assert_eq!(second.ppn.get_bytes_array()[0], 0x5a);
+    drop(second);
+    assert_eq!(frame_ref_count(ppn), 0);
+    assert_eq!(frame_remaining(), before);
+    // handed out again, and cleaned
+    let again = frame_alloc().unwrap();
+    assert_eq!(again.ppn, ppn);
+    assert_eq!(again.ppn.get_bytes_array()[0], 0);
+    drop(again);
+    info!("frame_share_test passed!");
+}
+
 #[allow(unused)]
 /// a simple test for frame allocator
 pub fn frame_allocator_test() {
@@ -1,14 +1,20 @@
 //! Implementation of [`MapArea`] and [`MemorySet`].
 
-use super::{frame_alloc, FrameTracker};
-use super::{PTEFlags, PageTable, PageTableEntry};
+use super::{frame_alloc, frame_alloc_huge, frame_ref_count, frame_remaining, FrameTracker};
+use super::{translated_byte_buffer_checked, translated_byte_buffer_mut, PTEFlags, PageTable, PageTableEntry, UserBuffer};
 use super::{PhysAddr, PhysPageNum, VirtAddr, VirtPageNum};
 use super::{StepByOne, VPNRange};
+use super::ShmSegment;
+use super::swap::{swap_ins, swap_slots_used, SwapSlot};
 use crate::config::{MEMORY_END, PAGE_SIZE, TRAMPOLINE, TRAP_CONTEXT, USER_STACK_SIZE, MMIO};
+use crate::config::{HUGE_PAGE_SIZE, PATH_MAX, SWAP_LOW_WATERMARK};
+use crate::fs::{File, OSInode, OpenFlags, Stat, ROOT_INODE};
 use crate::sync::UPSafeCell;
 use alloc::collections::BTreeMap;
+use alloc::string::String;
 use alloc::sync::Arc;
 use alloc::vec::Vec;
+use core::ops::Bound;
 use lazy_static::*;
 use riscv::register::satp;
 
@@ -40,6 +46,20 @@ pub fn kernel_token() -> usize {
 pub struct MemorySet {
     page_table: PageTable,
     areas: Vec<MapArea>,
+    /// where the heap starts, the break never goes below it
+    heap_bottom: usize,
+    /// the program break, or the end of the heap
+    brk: usize,
+    /// the pages the user stack may grow down over, up to its top.
+    /// The page right below is left unmapped as a guard,
+    /// which a stack overflow runs into first
+    stack_range: Option<VPNRange>,
+    /// page faults served, the programs exec replaced included
+    page_faults: usize,
+    /// the most frames mapped at once, the programs exec replaced included
+    peak_frames: usize,
+    /// the page the last search for a page to swap out stopped at
+    swap_hand: VirtPageNum,
 }
 
 impl MemorySet {
@@ -47,8 +67,36 @@ impl MemorySet {
         Self {
             page_table: PageTable::new(),
             areas: Vec::new(),
+            heap_bottom: 0,
+            brk: 0,
+            stack_range: None,
+            page_faults: 0,
+            peak_frames: 0,
+            swap_hand: VirtPageNum(0),
         }
     }
+    /// Number of frames mapped in the areas, shared ones included
+    pub fn resident_frames(&self) -> usize {
+        self.areas.iter().map(|area| area.data_frames.len()).sum()
+    }
+    /// Get the number of page faults served
+    pub fn page_faults(&self) -> usize {
+        self.page_faults
+    }
+    /// Get the most frames mapped at once
+    pub fn peak_frames(&self) -> usize {
+        self.peak_frames
+    }
+    /// Carry on counting from where `old`, the address space this one
+    /// replaces on exec, left off
+    pub fn inherit_usage(&mut self, old: &MemorySet) {
+        self.page_faults += old.page_faults;
+        self.peak_frames = self.peak_frames.max(old.peak_frames);
+    }
+    /// Record how many frames are mapped now if it is the most yet
+    fn note_resident(&mut self) {
+        self.peak_frames = self.peak_frames.max(self.resident_frames());
+    }
     pub fn token(&self) -> usize {
         self.page_table.token()
     }
@@ -64,6 +112,104 @@ impl MemorySet {
             None,
         );
     }
+    /// Assume that no conflicts, no frame is taken before the first access.
+    pub fn insert_lazy_area(
+        &mut self,
+        start_va: VirtAddr,
+        end_va: VirtAddr,
+        permission: MapPermission,
+        backing: Option<FileBacking>,
+    ) {
+        let mut map_area = MapArea::new(start_va, end_va, MapType::Lazy, permission);
+        map_area.backing = backing;
+        self.push(map_area, None);
+    }
+    /// Assume that no conflicts, zeroed memory given frames on the first
+    /// access like a lazy area, but by the megapage where one fits
+    pub fn insert_huge_area(
+        &mut self,
+        start_va: VirtAddr,
+        end_va: VirtAddr,
+        permission: MapPermission,
+    ) {
+        let mut map_area = MapArea::new(start_va, end_va, MapType::Lazy, permission);
+        map_area.huge = true;
+        self.push(map_area, None);
+    }
+    /// Whether no area nor page is mapped in `[start_vpn, end_vpn)`,
+    /// and it leaves the user stack room to grow and its guard alone
+    pub fn is_free(&self, start_vpn: VirtPageNum, end_vpn: VirtPageNum) -> bool {
+        let overlapped = self.areas.iter().any(|area| {
+            area.vpn_range.get_start() < end_vpn && start_vpn < area.vpn_range.get_end()
+        });
+        let reserved = self.stack_range.map_or(false, |stack| {
+            let guard = VirtPageNum(stack.get_start().0 - 1);
+            guard < end_vpn && start_vpn < stack.get_end()
+        });
+        !overlapped
+            && !reserved
+            && VPNRange::new(start_vpn, end_vpn)
+                .into_iter()
+                .all(|vpn| !self.translate(vpn).map_or(false, |pte| pte.is_valid()))
+    }
+    /// Whether `vpn` is the stack guard, so a fault on it is a stack overflow
+    pub fn is_stack_guard(&self, vpn: VirtPageNum) -> bool {
+        self.stack_range
+            .map_or(false, |stack| stack.get_start().0 == vpn.0 + 1)
+    }
+    /// Grow the user stack down to `vpn` if it is above the lowest the stack
+    /// may reach, returns false if it is not. The pages get frames on access
+    fn grow_stack(&mut self, vpn: VirtPageNum) -> bool {
+        let stack = match self.stack_range {
+            Some(stack) if stack.get_start() <= vpn && vpn < stack.get_end() => stack,
+            _ => return false,
+        };
+        // the lowest part of the stack, which grows down
+        match self
+            .areas
+            .iter_mut()
+            .filter(|area| stack.contains(area.vpn_range.get_start()))
+            .min_by_key(|area| area.vpn_range.get_start())
+        {
+            Some(area) if vpn < area.vpn_range.get_start() => {
+                area.grow_down(vpn);
+                true
+            }
+            _ => false,
+        }
+    }
+    /// Whether `vpn` is inside an area, even if it has no frame yet
+    pub fn covers(&self, vpn: VirtPageNum) -> bool {
+        self.areas.iter().any(|area| area.vpn_range.contains(vpn))
+    }
+    /// Map the frames of `segment` from `start_vpn` on, shared with
+    /// the other processes attaching it. Assume that no conflicts
+    pub fn attach_shm(&mut self, start_vpn: VirtPageNum, segment: Arc<ShmSegment>) {
+        let start_va: VirtAddr = start_vpn.into();
+        let end_va: VirtAddr = VirtPageNum(start_vpn.0 + segment.frames().len()).into();
+        let map_perm = MapPermission::R | MapPermission::W | MapPermission::U;
+        let mut map_area = MapArea::new(start_va, end_va, MapType::Framed, map_perm);
+        let pte_flags = PTEFlags::from_bits(map_perm.bits).unwrap();
+        for (vpn, frame) in map_area.vpn_range.into_iter().zip(segment.frames()) {
+            self.page_table.map(vpn, frame.ppn, pte_flags);
+            map_area.data_frames.insert(vpn, frame.share());
+        }
+        map_area.shm = Some(segment);
+        self.areas.push(map_area);
+        self.note_resident();
+    }
+    /// Detach the shared memory segment attached at `start_vpn`,
+    /// returns false if there is none
+    pub fn detach_shm(&mut self, start_vpn: VirtPageNum) -> bool {
+        let attached = self
+            .areas
+            .iter()
+            .any(|area| area.shm.is_some() && area.vpn_range.get_start() == start_vpn);
+        if attached {
+            self.remove_area_with_start_vpn(start_vpn);
+        }
+        attached
+    }
     pub fn remove_area_with_start_vpn(&mut self, start_vpn: VirtPageNum) {
         if let Some((idx, area)) = self
             .areas
@@ -81,6 +227,7 @@ impl MemorySet {
             map_area.copy_data(&mut self.page_table, data);
         }
         self.areas.push(map_area);
+        self.note_resident();
     }
     /// Mention that trampoline is not collected by areas.
     fn map_trampoline(&mut self) {
@@ -168,22 +315,36 @@ impl MemorySet {
     }
     /// Include sections in elf and trampoline and TrapContext and user stack,
     /// also returns user_sp and entry point.
-    pub fn from_elf(elf_data: &[u8]) -> (Self, usize, usize) {
+    /// Returns None if `elf_data` is not a valid elf.
+    pub fn from_elf(elf_data: &[u8]) -> Option<(Self, usize, usize)> {
         let mut memory_set = Self::new_bare();
         // map trampoline
         memory_set.map_trampoline();
         // map program headers of elf, with U flag
-        let elf = xmas_elf::ElfFile::new(elf_data).unwrap();
+        let elf = xmas_elf::ElfFile::new(elf_data).ok()?;
         let elf_header = elf.header;
         let magic = elf_header.pt1.magic;
-        assert_eq!(magic, [0x7f, 0x45, 0x4c, 0x46], "invalid elf!");
+        if magic != [0x7f, 0x45, 0x4c, 0x46] {
+            return None;
+        }
         let ph_count = elf_header.pt2.ph_count();
         let mut max_end_vpn = VirtPageNum(0);
         for i in 0..ph_count {
-            let ph = elf.program_header(i).unwrap();
-            if ph.get_type().unwrap() == xmas_elf::program::Type::Load {
+            let ph = elf.program_header(i).ok()?;
+            if ph.get_type() == Ok(xmas_elf::program::Type::Load) {
+                let end = ph.virtual_addr().checked_add(ph.mem_size())?;
+                if ph.file_size() > ph.mem_size() || end as usize >= TRAP_CONTEXT {
+                    return None;
+                }
                 let start_va: VirtAddr = (ph.virtual_addr() as usize).into();
-                let end_va: VirtAddr = ((ph.virtual_addr() + ph.mem_size()) as usize).into();
+                let end_va: VirtAddr = (end as usize).into();
+                // segments must not overlap each other
+                if !memory_set.is_free(start_va.floor(), end_va.ceil()) {
+                    return None;
+                }
+                let data_start = ph.offset() as usize;
+                let data_end = data_start.checked_add(ph.file_size() as usize)?;
+                let data = elf.input.get(data_start..data_end)?;
                 let mut map_perm = MapPermission::U;
                 let ph_flags = ph.flags();
                 if ph_flags.is_read() {
@@ -199,21 +360,38 @@ impl MemorySet {
                 max_end_vpn = map_area.vpn_range.get_end();
                 memory_set.push(
                     map_area,
-                    Some(&elf.input[ph.offset() as usize..(ph.offset() + ph.file_size()) as usize]),
+                    Some(data),
                 );
             }
         }
-        // map user stack with U flags
+        // map user stack with U flags, a page to begin with
+        // and growing down on faults below it
         let max_end_va: VirtAddr = max_end_vpn.into();
         let mut user_stack_bottom: usize = max_end_va.into();
         // guard page
         user_stack_bottom += PAGE_SIZE;
         let user_stack_top = user_stack_bottom + USER_STACK_SIZE;
+        memory_set.stack_range = Some(VPNRange::new(
+            VirtAddr::from(user_stack_bottom).floor(),
+            VirtAddr::from(user_stack_top).floor(),
+        ));
         memory_set.push(
             MapArea::new(
-                user_stack_bottom.into(),
+                (user_stack_top - PAGE_SIZE).into(),
                 user_stack_top.into(),
-                MapType::Framed,
+                MapType::Lazy,
+                MapPermission::R | MapPermission::W | MapPermission::U,
+            ),
+            None,
+        );
+        // map an empty heap above the user stack, which grows with the break
+        memory_set.heap_bottom = user_stack_top;
+        memory_set.brk = user_stack_top;
+        memory_set.push(
+            MapArea::new(
+                user_stack_top.into(),
+                user_stack_top.into(),
+                MapType::Lazy,
                 MapPermission::R | MapPermission::W | MapPermission::U,
             ),
             None,
@@ -228,32 +406,296 @@ impl MemorySet {
             ),
             None,
         );
-        (
+        Some((
             memory_set,
             user_stack_top,
             elf.header.pt2.entry_point() as usize,
-        )
+        ))
     }
-    /// Copy an identical user_space
-    pub fn from_existed_user(user_space: &MemorySet) -> MemorySet {
+    /// Copy an identical user_space, where the pages user can reach are
+    /// shared copy-on-write and only the rest is copied at once
+    pub fn from_existed_user(user_space: &mut MemorySet) -> MemorySet {
         let mut memory_set = Self::new_bare();
+        memory_set.heap_bottom = user_space.heap_bottom;
+        memory_set.brk = user_space.brk;
+        memory_set.stack_range = user_space.stack_range;
         // map trampoline
         memory_set.map_trampoline();
-        // copy data sections/trap_context/user_stack
-        for area in user_space.areas.iter() {
-            let new_area = MapArea::from_another(area);
+        for area in user_space.areas.iter_mut() {
+            let mut new_area = MapArea::from_another(area);
+            if area.map_perm.contains(MapPermission::U) {
+                // share data sections/user_stack
+                area.share_cow(&mut user_space.page_table, &mut new_area, &mut memory_set.page_table);
+                memory_set.areas.push(new_area);
+                continue;
+            }
+            // copy trap_context, which the kernel writes without faults
             memory_set.push(new_area, None);
-            // copy data from another space
             for vpn in area.vpn_range {
-                let src_ppn = user_space.translate(vpn).unwrap().ppn();
+                let src_ppn = user_space.page_table.translate(vpn).unwrap().ppn();
                 let dst_ppn = memory_set.translate(vpn).unwrap().ppn();
                 dst_ppn
                     .get_bytes_array()
                     .copy_from_slice(src_ppn.get_bytes_array());
             }
         }
+        memory_set.note_resident();
         memory_set
     }
+    /// Get the program break
+    pub fn brk(&self) -> usize {
+        self.brk
+    }
+    /// Move the program break, the pages above it are freed. Returns false
+    /// if it would go below the start of the heap or into another area
+    pub fn set_brk(&mut self, new_brk: usize) -> bool {
+        if new_brk < self.heap_bottom {
+            return false;
+        }
+        let heap_start_vpn = VirtAddr::from(self.heap_bottom).floor();
+        let old_end = VirtAddr::from(self.brk).ceil();
+        let new_end = VirtAddr::from(new_brk).ceil();
+        if new_end > old_end && !self.is_free(old_end, new_end) {
+            return false;
+        }
+        // the heap may have been split by mprotect into the areas
+        // between its bottom and the old break
+        let mut idx = 0;
+        while idx < self.areas.len() {
+            let area = &mut self.areas[idx];
+            let start = area.vpn_range.get_start();
+            let end = area.vpn_range.get_end();
+            if start < heap_start_vpn || end > old_end {
+                idx += 1;
+                continue;
+            }
+            // the bottom one stays even if the heap turns empty
+            if start != heap_start_vpn && start >= new_end {
+                area.unmap(&mut self.page_table);
+                self.areas.remove(idx);
+                continue;
+            }
+            if end > new_end || end == old_end {
+                area.resize(&mut self.page_table, new_end);
+            }
+            idx += 1;
+        }
+        self.brk = new_brk;
+        true
+    }
+    /// Change the permission of the user pages in `[start_vpn, end_vpn)`,
+    /// splitting the areas across the bounds. Returns false if any of the
+    /// pages is not mapped
+    pub fn mprotect(
+        &mut self,
+        start_vpn: VirtPageNum,
+        end_vpn: VirtPageNum,
+        map_perm: MapPermission,
+    ) -> bool {
+        if !self.user_mapped(start_vpn, end_vpn) {
+            return false;
+        }
+        self.split_areas_at(start_vpn);
+        self.split_areas_at(end_vpn);
+        for area in self.areas.iter_mut() {
+            let range = area.vpn_range;
+            if start_vpn <= range.get_start() && range.get_end() <= end_vpn {
+                area.set_perm(&mut self.page_table, map_perm);
+            }
+        }
+        // the TLB is flushed on the way back to user space
+        true
+    }
+    /// Unmap the user pages in `[start_vpn, end_vpn)`, splitting the areas
+    /// across the bounds, so that the rest of them stays mapped. The pages
+    /// written of a shared file mapping in the range go back to the file.
+    /// Returns false if any of the pages is not mapped
+    pub fn unmap_range(&mut self, start_vpn: VirtPageNum, end_vpn: VirtPageNum) -> bool {
+        if !self.user_mapped(start_vpn, end_vpn) {
+            return false;
+        }
+        self.split_areas_at(start_vpn);
+        self.split_areas_at(end_vpn);
+        let mut idx = 0;
+        while idx < self.areas.len() {
+            let range = self.areas[idx].vpn_range;
+            if start_vpn <= range.get_start() && range.get_end() <= end_vpn {
+                self.areas[idx].unmap(&mut self.page_table);
+                self.areas.remove(idx);
+            } else {
+                idx += 1;
+            }
+        }
+        // the TLB is flushed on the way back to user space
+        true
+    }
+    /// Whether every page in `[start_vpn, end_vpn)` is inside a user area
+    pub fn user_mapped(&self, start_vpn: VirtPageNum, end_vpn: VirtPageNum) -> bool {
+        VPNRange::new(start_vpn, end_vpn).into_iter().all(|vpn| {
+            self.areas.iter().any(|area| {
+                area.vpn_range.contains(vpn) && area.map_perm.contains(MapPermission::U)
+            })
+        })
+    }
+    /// Drop the frames of the pages in `[start_vpn, end_vpn)` of the areas
+    /// given frames on access, so that the pages read as they did before
+    /// the first access the next time. The other areas keep their frames
+    pub fn discard(&mut self, start_vpn: VirtPageNum, end_vpn: VirtPageNum) {
+        for area in self.areas.iter_mut() {
+            let start = start_vpn.max(area.vpn_range.get_start());
+            let end = end_vpn.min(area.vpn_range.get_end());
+            if area.map_type == MapType::Lazy && start < end {
+                area.discard(&mut self.page_table, start, end);
+            }
+        }
+    }
+    /// Give the pages in `[start_vpn, end_vpn)` read from a file or
+    /// from swap their frames before they are accessed
+    pub fn prefetch(&mut self, start_vpn: VirtPageNum, end_vpn: VirtPageNum) {
+        for vpn in VPNRange::new(start_vpn, end_vpn) {
+            if self.translate(vpn).map_or(false, |pte| pte.is_valid()) {
+                continue;
+            }
+            self.reclaim();
+            if let Some(area) = self
+                .areas
+                .iter_mut()
+                .find(|area| area.vpn_range.contains(vpn))
+            {
+                area.prefetch(&mut self.page_table, vpn);
+            }
+        }
+        self.note_resident();
+    }
+    /// Split the area `vpn` is in the middle of, so that an area starts there
+    fn split_areas_at(&mut self, vpn: VirtPageNum) {
+        if let Some(area) = self.areas.iter_mut().find(|area| {
+            area.vpn_range.get_start() < vpn && vpn < area.vpn_range.get_end()
+        }) {
+            let tail = area.split_off(vpn);
+            self.areas.push(tail);
+        }
+    }
+    /// Handle a page fault on `vpn`, which might be a page not accessed yet,
+    /// a page swapped out or a write to a copy-on-write page,
+    /// returns false if it is none of them. When there is no frame left
+    /// for the page even after swapping, it returns false as well, so that
+    /// only the process faulting is killed
+    pub fn handle_page_fault(&mut self, vpn: VirtPageNum, write: bool) -> bool {
+        if !self.covers(vpn) && !self.grow_stack(vpn) {
+            return false;
+        }
+        self.reclaim();
+        if let Some(area) = self
+            .areas
+            .iter_mut()
+            .find(|area| area.vpn_range.contains(vpn))
+        {
+            if !area.page_fault(&mut self.page_table, vpn, write) {
+                return false;
+            }
+        } else {
+            return false;
+        }
+        self.page_faults += 1;
+        self.note_resident();
+        true
+    }
+    /// Swap out pages of this address space while too few frames are free
+    fn reclaim(&mut self) {
+        while frame_remaining() < SWAP_LOW_WATERMARK && self.evict_one() {}
+    }
+    /// Swap out a page not accessed lately. The resident pages are gone
+    /// through like a clock hand, clearing the accessed bits on the way,
+    /// so that the page picked is one of the least recently used.
+    /// Returns false if there is none to swap out
+    fn evict_one(&mut self) -> bool {
+        // a page accessed is passed over once at most
+        for _ in 0..2 * self.resident_frames() + 1 {
+            let (idx, vpn) = match self
+                .next_swappable(Bound::Excluded(self.swap_hand))
+                .or_else(|| self.next_swappable(Bound::Unbounded))
+            {
+                Some(page) => page,
+                None => return false,
+            };
+            self.swap_hand = vpn;
+            let pte = self.page_table.translate(vpn).unwrap();
+            // copy-on-write pages are left to the processes sharing them
+            if frame_ref_count(pte.ppn()) > 1 {
+                continue;
+            }
+            if pte.accessed() {
+                self.page_table.remap(vpn, pte.ppn(), pte.flags() - PTEFlags::A);
+                continue;
+            }
+            if self.areas[idx].swap_out(&mut self.page_table, vpn) {
+                return true;
+            }
+        }
+        false
+    }
+    /// The lowest resident page past `after` in the areas whose pages may be
+    /// swapped out, along with the index of its area
+    fn next_swappable(&self, after: Bound<VirtPageNum>) -> Option<(usize, VirtPageNum)> {
+        self.areas
+            .iter()
+            .enumerate()
+            .filter(|(_, area)| area.swappable())
+            .filter_map(|(idx, area)| {
+                area.data_frames
+                    .range((after, Bound::Unbounded))
+                    .next()
+                    .map(|(vpn, _)| (idx, *vpn))
+            })
+            .min_by_key(|(_, vpn)| *vpn)
+    }
+    /// Handle the faults a user access to `[start, start + len)` would raise,
+    /// before the kernel accesses it through physical addresses
+    pub fn fault_in(&mut self, start: usize, len: usize, write: bool) {
+        let end = match start.checked_add(len) {
+            Some(end) => end,
+            None => return,
+        };
+        let start_va = VirtAddr::from(start);
+        let end_va = VirtAddr::from(end);
+        for vpn in VPNRange::new(start_va.floor(), end_va.ceil()) {
+            let pte = self.translate(vpn).filter(|pte| pte.is_valid());
+            let present = pte.is_some();
+            // accessed by the kernel soon, so not swapped out by the faults
+            // on the pages after it
+            if let Some(pte) = pte.filter(|pte| !pte.accessed()) {
+                self.page_table.remap(vpn, pte.ppn(), pte.flags() | PTEFlags::A);
+            }
+            if present && !write {
+                continue;
+            }
+            // the access fails at this unmapped page anyway
+            if !self.handle_page_fault(vpn, write) && !present {
+                break;
+            }
+        }
+    }
+    /// Translate a NUL terminated string of at most `PATH_MAX` bytes at `ptr`,
+    /// faulting in each page as the scan reaches it. Fails if it runs into
+    /// a page not mapped for the user or is too long
+    pub fn translated_str(&mut self, ptr: *const u8) -> Result<String, ()> {
+        let mut string = String::new();
+        let mut va = ptr as usize;
+        while string.len() < PATH_MAX {
+            let len = (PATH_MAX - string.len()).min(PAGE_SIZE - va % PAGE_SIZE);
+            self.fault_in(va, len, false);
+            let buffers = translated_byte_buffer_checked(self.token(), va as *const u8, len)?;
+            for &ch in buffers.iter().flat_map(|buffer| buffer.iter()) {
+                if ch == 0 {
+                    return Ok(string);
+                }
+                string.push(ch as char);
+            }
+            va = va.checked_add(len).ok_or(())?;
+        }
+        Err(())
+    }
     pub fn activate(&self) {
         let satp = self.page_table.token();
         unsafe {
@@ -266,16 +708,42 @@ impl MemorySet {
     }
     pub fn recycle_data_pages(&mut self) {
         //*self = Self::new_bare();
+        for area in self.areas.iter() {
+            area.sync(&self.page_table);
+        }
         self.areas.clear();
     }
 }
 
+impl Drop for MemorySet {
+    fn drop(&mut self) {
+        self.recycle_data_pages();
+    }
+}
+
+/// The file a map area shows, from `offset` of it
+#[derive(Clone)]
+pub struct FileBacking {
+    pub file: Arc<dyn File + Send + Sync>,
+    pub offset: usize,
+    /// whether the writes go back to the file
+    pub shared: bool,
+}
+
 /// map area structure, controls a contiguous piece of virtual memory
 pub struct MapArea {
     vpn_range: VPNRange,
     data_frames: BTreeMap<VirtPageNum, FrameTracker>,
+    /// the pages swapped out, whose slots are shared with
+    /// the processes forked before
+    swapped: BTreeMap<VirtPageNum, Arc<SwapSlot>>,
     map_type: MapType,
     map_perm: MapPermission,
+    /// whether the pages are mapped by the megapage where one fits
+    huge: bool,
+    backing: Option<FileBacking>,
+    /// the shared memory segment whose frames the area maps
+    shm: Option<Arc<ShmSegment>>,
 }
 
 impl MapArea {
@@ -290,16 +758,24 @@ impl MapArea {
         Self {
             vpn_range: VPNRange::new(start_vpn, end_vpn),
             data_frames: BTreeMap::new(),
+            swapped: BTreeMap::new(),
             map_type,
             map_perm,
+            huge: false,
+            backing: None,
+            shm: None,
         }
     }
     pub fn from_another(another: &MapArea) -> Self {
         Self {
             vpn_range: VPNRange::new(another.vpn_range.get_start(), another.vpn_range.get_end()),
             data_frames: BTreeMap::new(),
+            swapped: BTreeMap::new(),
             map_type: another.map_type,
             map_perm: another.map_perm,
+            huge: another.huge,
+            backing: another.backing.clone(),
+            shm: another.shm.clone(),
         }
     }
     pub fn map_one(&mut self, page_table: &mut PageTable, vpn: VirtPageNum) {
@@ -313,29 +789,347 @@ impl MapArea {
                 ppn = frame.ppn;
                 self.data_frames.insert(vpn, frame);
             }
+            MapType::Lazy => return,
         }
         let pte_flags = PTEFlags::from_bits(self.map_perm.bits).unwrap();
         page_table.map(vpn, ppn, pte_flags);
     }
 
+    /// Map the frames into `another` as well, the writable pages of a
+    /// private area turn read-only on both sides until one of them writes
+    pub fn share_cow(
+        &mut self,
+        page_table: &mut PageTable,
+        another: &mut MapArea,
+        another_page_table: &mut PageTable,
+    ) {
+        assert_ne!(self.map_type, MapType::Identical);
+        let pte_flags = PTEFlags::from_bits(self.map_perm.bits).unwrap() - PTEFlags::W;
+        for (vpn, frame) in self.data_frames.iter() {
+            if self.is_shared() {
+                let pte_flags = page_table.translate(*vpn).unwrap().flags();
+                another_page_table.map(*vpn, frame.ppn, pte_flags);
+            } else {
+                // a page of a private file mapping still tells if it is
+                // the same as in the file
+                let dirty = page_table.translate(*vpn).unwrap().flags() & PTEFlags::D;
+                page_table.remap(*vpn, frame.ppn, pte_flags | dirty);
+                another_page_table.map(*vpn, frame.ppn, pte_flags | dirty);
+            }
+            another.data_frames.insert(*vpn, frame.share());
+        }
+        for (vpn, slot) in self.swapped.iter() {
+            another_page_table.map_swapped(*vpn, slot.id());
+            another.swapped.insert(*vpn, slot.clone());
+        }
+    }
+    /// Whether the pages of the area may be swapped out
+    fn swappable(&self) -> bool {
+        self.map_type == MapType::Lazy
+            && self.map_perm.contains(MapPermission::U)
+            && self.shm.is_none()
+    }
+    /// Whether the writes go back to a file or to a shared memory segment
+    fn is_shared(&self) -> bool {
+        self.shm.is_some() || self.backing.as_ref().map_or(false, |backing| backing.shared)
+    }
+    /// The offset in the backing file of a page
+    fn file_offset(&self, backing: &FileBacking, vpn: VirtPageNum) -> usize {
+        backing.offset + (vpn.0 - self.vpn_range.get_start().0) * PAGE_SIZE
+    }
+    /// Give a page of a lazy area its frame, zeroed or read from the file.
+    /// It counts as accessed, even if only the kernel accesses it.
+    /// Returns false if there is no frame left for it
+    fn populate(&mut self, page_table: &mut PageTable, vpn: VirtPageNum, write: bool) -> bool {
+        let frame = match frame_alloc() {
+            Some(frame) => frame,
+            None => return false,
+        };
+        let mut pte_flags = PTEFlags::from_bits(self.map_perm.bits).unwrap() | PTEFlags::A;
+        if let Some(backing) = &self.backing {
+            let offset = self.file_offset(backing, vpn);
+            backing.file.read_at(offset, frame.ppn.get_bytes_array());
+            // a page read from the file turns writable, which means dirty,
+            // on the first write
+            if write {
+                pte_flags |= PTEFlags::D;
+            } else {
+                pte_flags -= PTEFlags::W;
+            }
+        }
+        page_table.map(vpn, frame.ppn, pte_flags);
+        self.data_frames.insert(vpn, frame);
+        true
+    }
+    /// Give the whole megapage `vpn` is in its frames, if the megapage
+    /// is inside the area with no page of it mapped yet and there are
+    /// frames for it. Returns false if not
+    fn populate_huge(&mut self, page_table: &mut PageTable, vpn: VirtPageNum) -> bool {
+        let start = VirtPageNum(vpn.0 - vpn.0 % HUGE_PAGE_PAGES);
+        let end = VirtPageNum(start.0 + HUGE_PAGE_PAGES);
+        if start < self.vpn_range.get_start()
+            || end > self.vpn_range.get_end()
+            || self.backing.is_some()
+            || self.data_frames.range(start..end).next().is_some()
+            || self.swapped.range(start..end).next().is_some()
+            || !page_table.can_map_huge(start)
+        {
+            return false;
+        }
+        let frames = match frame_alloc_huge() {
+            Some(frames) => frames,
+            None => return false,
+        };
+        let pte_flags = PTEFlags::from_bits(self.map_perm.bits).unwrap() | PTEFlags::A;
+        page_table.map_huge(start, frames[0].ppn, pte_flags);
+        for (i, frame) in frames.into_iter().enumerate() {
+            self.data_frames.insert(VirtPageNum(start.0 + i), frame);
+        }
+        true
+    }
+    /// Handle a page fault on `vpn` inside the area, returns false
+    /// if the access is not allowed or there is no frame left for the page
+    pub fn page_fault(&mut self, page_table: &mut PageTable, vpn: VirtPageNum, write: bool) -> bool {
+        if write && !self.map_perm.contains(MapPermission::W) {
+            return false;
+        }
+        let frame = match self.data_frames.get(&vpn) {
+            Some(frame) => frame,
+            None if self.map_type == MapType::Lazy => {
+                return match page_table.translate(vpn).and_then(|pte| pte.swap_slot()) {
+                    Some(slot) => self.swap_in(page_table, vpn, slot),
+                    None if self.huge && self.populate_huge(page_table, vpn) => true,
+                    None => self.populate(page_table, vpn, write),
+                };
+            }
+            None => return false,
+        };
+        // what is left is a write to a page mapped read-only
+        match page_table.translate(vpn) {
+            Some(pte) if write && pte.is_valid() && !pte.writable() => {}
+            _ => return false,
+        }
+        // written from now on, dirty whether the hardware tells or not
+        let pte_flags = PTEFlags::from_bits(self.map_perm.bits).unwrap() | PTEFlags::D;
+        if self.is_shared() || frame_ref_count(frame.ppn) == 1 {
+            page_table.remap(vpn, frame.ppn, pte_flags);
+        } else {
+            let new_frame = match frame_alloc() {
+                Some(frame) => frame,
+                None => return false,
+            };
+            new_frame
+                .ppn
+                .get_bytes_array()
+                .copy_from_slice(frame.ppn.get_bytes_array());
+            page_table.remap(vpn, new_frame.ppn, pte_flags);
+            self.data_frames.insert(vpn, new_frame);
+        }
+        true
+    }
+
+    /// Give a page of a lazy area with no frame its frame, if it is read
+    /// from the file or from swap
+    fn prefetch(&mut self, page_table: &mut PageTable, vpn: VirtPageNum) {
+        if self.map_type != MapType::Lazy || self.data_frames.contains_key(&vpn) {
+            return;
+        }
+        // left to fault in if there is no frame for it
+        match page_table.translate(vpn).and_then(|pte| pte.swap_slot()) {
+            Some(slot) => {
+                self.swap_in(page_table, vpn, slot);
+            }
+            None if self.backing.is_some() => {
+                self.populate(page_table, vpn, false);
+            }
+            None => {}
+        }
+    }
+    /// Read a page back from swap slot `slot` into a new frame,
+    /// returns false if there is no frame left for it
+    fn swap_in(&mut self, page_table: &mut PageTable, vpn: VirtPageNum, slot: usize) -> bool {
+        let frame = match frame_alloc() {
+            Some(frame) => frame,
+            None => return false,
+        };
+        let swapped = self.swapped.remove(&vpn).unwrap();
+        assert_eq!(swapped.id(), slot);
+        swapped.read(frame.ppn.get_bytes_array());
+        // the frame is its own, so a write needs no copy
+        let pte_flags = PTEFlags::from_bits(self.map_perm.bits).unwrap() | PTEFlags::A;
+        page_table.map(vpn, frame.ppn, pte_flags);
+        self.data_frames.insert(vpn, frame);
+        true
+    }
+    /// Take the frame of a resident page away. A page of a shared file
+    /// mapping goes back to the file if written and is read from it again
+    /// on access, so does a page of a private file mapping never written.
+    /// Any other goes to swap. Returns false if swap is full
+    fn swap_out(&mut self, page_table: &mut PageTable, vpn: VirtPageNum) -> bool {
+        let frame = &self.data_frames[&vpn];
+        let pte = page_table.translate(vpn).unwrap();
+        match &self.backing {
+            Some(backing) if backing.shared => {
+                self.sync_page(page_table, backing, file_size(backing), vpn, frame);
+                page_table.unmap(vpn);
+            }
+            Some(_) if !pte.writable() && !pte.dirty() => {
+                page_table.unmap(vpn);
+            }
+            _ => {
+                let slot = match SwapSlot::write(frame.ppn.get_bytes_array()) {
+                    Some(slot) => slot,
+                    None => return false,
+                };
+                page_table.unmap(vpn);
+                page_table.map_swapped(vpn, slot.id());
+                self.swapped.insert(vpn, Arc::new(slot));
+            }
+        }
+        self.data_frames.remove(&vpn);
+        true
+    }
+
+    /// Write the pages written back to the file of a shared area
+    pub fn sync(&self, page_table: &PageTable) {
+        let backing = match &self.backing {
+            Some(backing) if backing.shared => backing,
+            _ => return,
+        };
+        let file_size = file_size(backing);
+        for (vpn, frame) in self.data_frames.iter() {
+            self.sync_page(page_table, backing, file_size, *vpn, frame);
+        }
+    }
+    /// Write a page of a shared area back to the file if written,
+    /// up to the end of file
+    fn sync_page(
+        &self,
+        page_table: &PageTable,
+        backing: &FileBacking,
+        file_size: usize,
+        vpn: VirtPageNum,
+        frame: &FrameTracker,
+    ) {
+        let offset = self.file_offset(backing, vpn);
+        // clean pages are kept read-only
+        if offset >= file_size || !page_table.translate(vpn).unwrap().writable() {
+            return;
+        }
+        let len = PAGE_SIZE.min(file_size - offset);
+        backing.file.write_at(offset, &frame.ppn.get_bytes_array()[..len]);
+    }
+
     pub fn unmap_one(&mut self, page_table: &mut PageTable, vpn: VirtPageNum) {
-        #[allow(clippy::single_match)]
         match self.map_type {
             MapType::Framed => {
                 self.data_frames.remove(&vpn);
             }
+            MapType::Lazy => {
+                if self.data_frames.remove(&vpn).is_none() {
+                    if self.swapped.remove(&vpn).is_some() {
+                        page_table.unmap_swapped(vpn);
+                    }
+                    // or never accessed
+                    return;
+                }
+            }
             _ => {}
         }
         page_table.unmap(vpn);
     }
+    /// Split the area at `at`, keeping `[start, at)` and returning `[at, end)`
+    pub fn split_off(&mut self, at: VirtPageNum) -> MapArea {
+        let start = self.vpn_range.get_start();
+        let mut tail = MapArea::from_another(self);
+        tail.vpn_range = VPNRange::new(at, self.vpn_range.get_end());
+        tail.data_frames = self.data_frames.split_off(&at);
+        tail.swapped = self.swapped.split_off(&at);
+        if let Some(backing) = tail.backing.as_mut() {
+            backing.offset += (at.0 - start.0) * PAGE_SIZE;
+        }
+        self.vpn_range = VPNRange::new(start, at);
+        tail
+    }
+    /// Change the permission of the area. A page that was not writable stays
+    /// so in the page table until the next write faults, so that copy-on-write
+    /// and dirty pages of shared areas keep working
+    pub fn set_perm(&mut self, page_table: &mut PageTable, map_perm: MapPermission) {
+        // shared pages can't be told dirty any more once read-only
+        if !map_perm.contains(MapPermission::W) {
+            self.sync(page_table);
+        }
+        self.map_perm = map_perm;
+        let pte_flags = PTEFlags::from_bits(map_perm.bits).unwrap();
+        for (vpn, frame) in self.data_frames.iter() {
+            let pte = page_table.translate(*vpn).unwrap();
+            let dirty = pte.flags() & PTEFlags::D;
+            let pte_flags = if pte.writable() {
+                pte_flags | dirty
+            } else {
+                (pte_flags - PTEFlags::W) | dirty
+            };
+            page_table.remap(*vpn, frame.ppn, pte_flags);
+        }
+    }
+    /// Move the start of a lazy area down to `new_start`,
+    /// the pages added get their frames on access
+    pub fn grow_down(&mut self, new_start: VirtPageNum) {
+        assert_eq!(self.map_type, MapType::Lazy);
+        assert!(self.backing.is_none());
+        self.vpn_range = VPNRange::new(new_start, self.vpn_range.get_end());
+    }
+    /// Move the end of a lazy area, unmapping the pages beyond a lower end
+    pub fn resize(&mut self, page_table: &mut PageTable, new_end: VirtPageNum) {
+        assert_eq!(self.map_type, MapType::Lazy);
+        let start = self.vpn_range.get_start();
+        let old_end = self.vpn_range.get_end();
+        if new_end < old_end {
+            for vpn in VPNRange::new(new_end, old_end) {
+                self.unmap_one(page_table, vpn);
+            }
+        }
+        self.vpn_range = VPNRange::new(start, new_end);
+    }
     pub fn map(&mut self, page_table: &mut PageTable) {
         for vpn in self.vpn_range {
             self.map_one(page_table, vpn);
         }
     }
     pub fn unmap(&mut self, page_table: &mut PageTable) {
-        for vpn in self.vpn_range {
+        self.sync(page_table);
+        self.unmap_pages(page_table, self.vpn_range.get_start(), self.vpn_range.get_end());
+    }
+    /// Drop the frames of the pages in `[start, end)` of a lazy area, the
+    /// pages written of a shared file mapping going back to the file first
+    pub fn discard(&mut self, page_table: &mut PageTable, start: VirtPageNum, end: VirtPageNum) {
+        assert_eq!(self.map_type, MapType::Lazy);
+        if let Some(backing) = self.backing.as_ref().filter(|backing| backing.shared) {
+            let file_size = file_size(backing);
+            for (vpn, frame) in self.data_frames.range(start..end) {
+                self.sync_page(page_table, backing, file_size, *vpn, frame);
+            }
+        }
+        self.unmap_pages(page_table, start, end);
+    }
+    /// Unmap the pages in `[start, end)`, a megapage inside as a whole
+    fn unmap_pages(&mut self, page_table: &mut PageTable, start: VirtPageNum, end: VirtPageNum) {
+        let mut vpn = start;
+        while vpn < end {
+            // a megapage goes as a whole
+            if vpn.0 % HUGE_PAGE_PAGES == 0
+                && vpn.0 + HUGE_PAGE_PAGES <= end.0
+                && page_table.is_huge(vpn)
+            {
+                page_table.unmap_huge(vpn);
+                for _ in 0..HUGE_PAGE_PAGES {
+                    self.data_frames.remove(&vpn);
+                    vpn.step();
+                }
+                continue;
+            }
             self.unmap_one(page_table, vpn);
+            vpn.step();
         }
     }
     /// data: start-aligned but maybe with shorter length
@@ -362,11 +1156,22 @@ impl MapArea {
     }
 }
 
+/// Pages a megapage spans
+const HUGE_PAGE_PAGES: usize = HUGE_PAGE_SIZE / PAGE_SIZE;
+
+/// Get the size of the file backing an area
+fn file_size(backing: &FileBacking) -> usize {
+    let mut stat = Stat::new();
+    backing.file.info(&mut stat);
+    stat.size as usize
+}
+
 #[derive(Copy, Clone, PartialEq, Debug)]
-/// map type for memory set: identical or framed
+/// map type for memory set: identical, framed, or framed on the first access
 pub enum MapType {
     Identical,
     Framed,
+    Lazy,
 }
 
 bitflags! {
@@ -402,3 +1207,312 @@ pub fn remap_test() {
         .executable());
     info!("remap_test passed!");
 }
+
+#[allow(unused)]
+pub fn cow_test() {
+    let pages = 16;
+    let before = frame_remaining();
+    let start_va: VirtAddr = 0x1000_0000.into();
+    let end_va: VirtAddr = (0x1000_0000 + pages * PAGE_SIZE).into();
+    let vpn_range = VPNRange::new(start_va.floor(), end_va.ceil());
+    let mut parent = MemorySet::new_bare();
+    parent.insert_framed_area(
+        start_va,
+        end_va,
+        MapPermission::R | MapPermission::W | MapPermission::U,
+    );
+    let first_vpn = vpn_range.get_start();
+    let ppn_of = |memory_set: &MemorySet, vpn: VirtPageNum| memory_set.translate(vpn).unwrap().ppn();
+    ppn_of(&parent, first_vpn).get_bytes_array()[0] = 0x5a;
+    // a chain of forks without writes shares a single copy
+    let remaining = frame_remaining();
+    let mut child = MemorySet::from_existed_user(&mut parent);
+    let mut grandchild = MemorySet::from_existed_user(&mut child);
+    // only the page tables take frames
+    assert!(remaining - frame_remaining() < pages);
+    for vpn in vpn_range {
+        let ppn = ppn_of(&parent, vpn);
+        assert_eq!(ppn_of(&child, vpn).0, ppn.0);
+        assert_eq!(ppn_of(&grandchild, vpn).0, ppn.0);
+        assert_eq!(frame_ref_count(ppn), 3);
+        assert!(!parent.translate(vpn).unwrap().writable());
+    }
+    // the first write of the child copies the page
+    assert!(child.handle_page_fault(first_vpn, true));
+    assert!(child.translate(first_vpn).unwrap().writable());
+    let child_ppn = ppn_of(&child, first_vpn);
+    assert_ne!(child_ppn.0, ppn_of(&parent, first_vpn).0);
+    child_ppn.get_bytes_array()[0] = 0xa5;
+    assert_eq!(ppn_of(&parent, first_vpn).get_bytes_array()[0], 0x5a);
+    assert_eq!(ppn_of(&grandchild, first_vpn).get_bytes_array()[0], 0x5a);
+    assert_eq!(frame_ref_count(ppn_of(&parent, first_vpn)), 2);
+    // a writable page is not a copy-on-write one
+    assert!(!child.handle_page_fault(first_vpn, true));
+    // the last one sharing a frame takes it over without a copy
+    drop(grandchild);
+    let ppn = ppn_of(&parent, first_vpn);
+    assert!(parent.handle_page_fault(first_vpn, true));
+    assert_eq!(ppn_of(&parent, first_vpn).0, ppn.0);
+    drop(child);
+    drop(parent);
+    assert_eq!(frame_remaining(), before);
+    info!("cow_test passed!");
+}
+
+#[allow(unused)]
+pub fn lazy_test() {
+    let pages = 16;
+    let start_va: VirtAddr = 0x1000_0000.into();
+    let end_va: VirtAddr = (0x1000_0000 + pages * PAGE_SIZE).into();
+    let vpn_range = VPNRange::new(start_va.floor(), end_va.ceil());
+    let before = frame_remaining();
+    let mut memory_set = MemorySet::new_bare();
+    memory_set.insert_lazy_area(
+        start_va,
+        end_va,
+        MapPermission::R | MapPermission::W | MapPermission::U,
+        None,
+    );
+    let first_vpn = vpn_range.get_start();
+    assert!(memory_set.translate(first_vpn).map_or(true, |pte| !pte.is_valid()));
+    assert!(memory_set.handle_page_fault(first_vpn, false));
+    // the page tables are there from now on, so each page takes one frame
+    let remaining = frame_remaining();
+    for vpn in vpn_range.into_iter().skip(1).step_by(5) {
+        assert!(memory_set.handle_page_fault(vpn, true));
+        let ppn = memory_set.translate(vpn).unwrap().ppn();
+        assert!(ppn.get_bytes_array().iter().all(|&b| b == 0));
+    }
+    assert_eq!(remaining - frame_remaining(), 3);
+    // outside of the area is not handled
+    assert!(!memory_set.handle_page_fault(vpn_range.get_end(), false));
+    memory_set.remove_area_with_start_vpn(first_vpn);
+    assert_eq!(frame_remaining(), remaining + 4);
+    drop(memory_set);
+    assert_eq!(frame_remaining(), before);
+    info!("lazy_test passed!");
+}
+
+#[allow(unused)]
+pub fn swap_test() {
+    let pages = 4 * SWAP_LOW_WATERMARK;
+    let start_va: VirtAddr = 0x1000_0000.into();
+    let end_va: VirtAddr = (0x1000_0000 + pages * PAGE_SIZE).into();
+    let vpn_range = VPNRange::new(start_va.floor(), end_va.ceil());
+    let before = frame_remaining();
+    let mut memory_set = MemorySet::new_bare();
+    memory_set.insert_lazy_area(
+        start_va,
+        end_va,
+        MapPermission::R | MapPermission::W | MapPermission::U,
+        None,
+    );
+    // the page tables are there from now on
+    assert!(memory_set.handle_page_fault(vpn_range.get_start(), false));
+    // leave the area far fewer frames than its pages
+    let mut taken = Vec::new();
+    while frame_remaining() > SWAP_LOW_WATERMARK + pages / 4 {
+        taken.push(frame_alloc().unwrap());
+    }
+    let ins = swap_ins();
+    let touch = |memory_set: &mut MemorySet, vpn: VirtPageNum| {
+        if !memory_set.translate(vpn).unwrap().is_valid() {
+            assert!(memory_set.handle_page_fault(vpn, true));
+        }
+        memory_set.translate(vpn).unwrap().ppn()
+    };
+    for (i, vpn) in vpn_range.into_iter().enumerate() {
+        let ppn = touch(&mut memory_set, vpn);
+        ppn.get_bytes_array().fill(i as u8);
+        assert!(frame_remaining() >= SWAP_LOW_WATERMARK - 1);
+    }
+    assert!(swap_slots_used() > 0);
+    // each page comes back as written, twice over to read those
+    // swapped out by the first pass as well
+    for _ in 0..2 {
+        for (i, vpn) in vpn_range.into_iter().enumerate() {
+            let ppn = touch(&mut memory_set, vpn);
+            assert!(ppn.get_bytes_array().iter().all(|&b| b == i as u8));
+        }
+    }
+    assert!(swap_ins() > ins);
+    // the copy of a fork shares the slots until either reads them back
+    let slots = swap_slots_used();
+    let child = MemorySet::from_existed_user(&mut memory_set);
+    assert_eq!(swap_slots_used(), slots);
+    drop(memory_set);
+    assert_eq!(swap_slots_used(), slots);
+    drop(child);
+    assert_eq!(swap_slots_used(), 0);
+    // with no frame left and nothing to swap out, the fault fails
+    // instead of panicking, which kills only the process faulting
+    let mut memory_set = MemorySet::new_bare();
+    memory_set.insert_lazy_area(start_va, end_va, MapPermission::R | MapPermission::W, None);
+    assert!(memory_set.handle_page_fault(vpn_range.get_start(), true));
+    while let Some(frame) = frame_alloc() {
+        taken.push(frame);
+    }
+    let vpn = VirtPageNum(vpn_range.get_start().0 + 1);
+    assert!(!memory_set.handle_page_fault(vpn, true));
+    assert!(!memory_set.translate(vpn).unwrap().is_valid());
+    drop(memory_set);
+    drop(taken);
+    assert_eq!(frame_remaining(), before);
+
+    // of a private file mapping only the pages written go to swap,
+    // the others are read from the file again
+    let name = "swap_test";
+    let inode = ROOT_INODE.create(name).unwrap();
+    let file_pages = SWAP_LOW_WATERMARK;
+    for i in 0..file_pages {
+        inode.write_at(i * PAGE_SIZE, &[i as u8; PAGE_SIZE]);
+    }
+    let file = Arc::new(OSInode::new(true, false, OpenFlags::RDONLY, inode));
+    let end_va: VirtAddr = (0x1000_0000 + file_pages * PAGE_SIZE).into();
+    let vpn_range = VPNRange::new(start_va.floor(), end_va.ceil());
+    let mut memory_set = MemorySet::new_bare();
+    memory_set.insert_lazy_area(
+        start_va,
+        end_va,
+        MapPermission::R | MapPermission::W | MapPermission::U,
+        Some(FileBacking { file, offset: 0, shared: false }),
+    );
+    assert!(memory_set.handle_page_fault(vpn_range.get_start(), false));
+    let mut taken = Vec::new();
+    while frame_remaining() > SWAP_LOW_WATERMARK + file_pages / 4 {
+        taken.push(frame_alloc().unwrap());
+    }
+    // the odd pages only read
+    for (i, vpn) in vpn_range.into_iter().enumerate() {
+        let write = i % 2 == 0;
+        let pte = memory_set.translate(vpn).unwrap();
+        if !pte.is_valid() || write && !pte.writable() {
+            assert!(memory_set.handle_page_fault(vpn, write));
+        }
+        if write {
+            memory_set.translate(vpn).unwrap().ppn().get_bytes_array().fill(!i as u8);
+        }
+    }
+    assert!(swap_slots_used() <= file_pages / 2);
+    assert!(vpn_range.into_iter().skip(1).step_by(2).any(|vpn| {
+        let pte = memory_set.translate(vpn).unwrap();
+        !pte.is_valid() && pte.swap_slot().is_none()
+    }));
+    for (i, vpn) in vpn_range.into_iter().enumerate() {
+        if !memory_set.translate(vpn).unwrap().is_valid() {
+            assert!(memory_set.handle_page_fault(vpn, false));
+        }
+        let expected = if i % 2 == 0 { !i as u8 } else { i as u8 };
+        let ppn = memory_set.translate(vpn).unwrap().ppn();
+        assert!(ppn.get_bytes_array().iter().all(|&b| b == expected));
+    }
+    drop(memory_set);
+    assert_eq!(swap_slots_used(), 0);
+    ROOT_INODE.unlinkat(name);
+    drop(taken);
+    assert_eq!(frame_remaining(), before);
+    info!("swap_test passed!");
+}
+
+#[allow(unused)]
+pub fn huge_page_test() {
+    let before = frame_remaining();
+    let mut memory_set = MemorySet::new_bare();
+    let perm = MapPermission::R | MapPermission::W | MapPermission::U;
+    // two megapages and a page after them, which takes a page of its own
+    let start_va: VirtAddr = 0x1000_0000.into();
+    let end_va: VirtAddr = (0x1000_0000 + 2 * HUGE_PAGE_SIZE + PAGE_SIZE).into();
+    let first_vpn = start_va.floor();
+    let last_vpn = VirtPageNum(first_vpn.0 + 2 * HUGE_PAGE_PAGES);
+    memory_set.insert_huge_area(start_va, end_va, perm);
+    let remaining = frame_remaining();
+    assert!(memory_set.handle_page_fault(VirtPageNum(first_vpn.0 + 5), true));
+    // the frames of the megapage and the middle level table, with no table
+    // below, so that a single leaf covers the whole megapage
+    assert_eq!(remaining - frame_remaining(), HUGE_PAGE_PAGES + 1);
+    let base_ppn = memory_set.translate(first_vpn).unwrap().ppn();
+    assert_eq!(base_ppn.0 % HUGE_PAGE_PAGES, 0);
+    for i in 0..HUGE_PAGE_PAGES {
+        let vpn = VirtPageNum(first_vpn.0 + i);
+        assert!(memory_set.page_table.is_huge(vpn));
+        let pte = memory_set.translate(vpn).unwrap();
+        assert!(pte.is_valid() && pte.writable());
+        assert_eq!(pte.ppn().0, base_ppn.0 + i);
+    }
+    assert!(!memory_set.page_table.is_huge(VirtPageNum(first_vpn.0 + HUGE_PAGE_PAGES)));
+    // the page that does not fit a megapage falls back to a page
+    assert!(memory_set.handle_page_fault(last_vpn, true));
+    assert!(!memory_set.page_table.is_huge(last_vpn));
+    assert!(memory_set.translate(last_vpn).unwrap().is_valid());
+    // a page of the megapage changed on its own splits it into pages
+    let second_vpn = VirtPageNum(first_vpn.0 + HUGE_PAGE_PAGES);
+    assert!(memory_set.handle_page_fault(second_vpn, true));
+    assert!(memory_set.page_table.is_huge(second_vpn));
+    let ppn = memory_set.translate(VirtPageNum(second_vpn.0 + 7)).unwrap().ppn();
+    ppn.get_bytes_array()[0] = 0x5a;
+    let read_only = MapPermission::R | MapPermission::U;
+    assert!(memory_set.mprotect(second_vpn, VirtPageNum(second_vpn.0 + 1), read_only));
+    assert!(!memory_set.page_table.is_huge(second_vpn));
+    assert!(!memory_set.translate(second_vpn).unwrap().writable());
+    let pte = memory_set.translate(VirtPageNum(second_vpn.0 + 7)).unwrap();
+    assert!(pte.writable());
+    assert_eq!(pte.ppn().0, ppn.0);
+    assert_eq!(ppn.get_bytes_array()[0], 0x5a);
+    // the first megapage is freed as a whole
+    let remaining = frame_remaining();
+    memory_set.remove_area_with_start_vpn(first_vpn);
+    assert_eq!(frame_remaining(), remaining + HUGE_PAGE_PAGES);
+    assert!(memory_set.translate(first_vpn).map_or(true, |pte| !pte.is_valid()));
+    drop(memory_set);
+    assert_eq!(frame_remaining(), before);
+    info!("huge_page_test passed!");
+}
+
+#[allow(unused)]
+/// Copy through a UserBuffer whose bytes span two pages
+pub fn user_buffer_test() {
+    let mut memory_set = MemorySet::new_bare();
+    let perm = MapPermission::R | MapPermission::W | MapPermission::U;
+    let start_va: VirtAddr = 0x1000_0000.into();
+    let end_va: VirtAddr = (0x1000_0000 + 2 * PAGE_SIZE).into();
+    memory_set.insert_framed_area(start_va, end_va, perm);
+    let ptr = (0x1000_0000 + PAGE_SIZE - 5) as *const u8;
+    let mut buf = UserBuffer::new(translated_byte_buffer_checked(memory_set.token(), ptr, 16).unwrap());
+    assert_eq!(buf.buffers.len(), 2);
+    let pattern: Vec<u8> = (0..20).map(|i| i as u8 + 1).collect();
+    // as much as fits, or as much as there is
+    assert_eq!(buf.copy_from_slice(&pattern), 16);
+    let first = memory_set.translate(start_va.floor()).unwrap().ppn().get_bytes_array();
+    let second = memory_set.translate(VirtPageNum(start_va.floor().0 + 1)).unwrap().ppn().get_bytes_array();
+    assert_eq!(&first[PAGE_SIZE - 5..], &pattern[..5]);
+    assert_eq!(&second[..11], &pattern[5..16]);
+    assert_eq!(second[11], 0);
+    assert_eq!(buf.copy_from_slice(&[0xaa; 3]), 3);
+    assert_eq!(&first[PAGE_SIZE - 5..PAGE_SIZE - 2], &[0xaa; 3]);
+    assert_eq!(first[PAGE_SIZE - 2], pattern[3]);
+    let mut out = [0u8; 8];
+    assert_eq!(buf.copy_to_slice(&mut out), 8);
+    assert_eq!(&out[..3], &[0xaa; 3]);
+    assert_eq!(&out[3..], &pattern[3..8]);
+    let mut out = [0u8; 32];
+    assert_eq!(buf.copy_to_slice(&mut out), 16);
+    assert_eq!(&out[3..16], &pattern[3..16]);
+    // the iterator goes across the pages in order
+    for (i, byte) in buf.iter_mut().enumerate() {
+        *byte = 0x80 + i as u8;
+    }
+    assert_eq!(buf.iter_mut().count(), 16);
+    assert_eq!(first[PAGE_SIZE - 1], 0x84);
+    assert_eq!(second[0], 0x85);
+    assert_eq!(second[10], 0x8f);
+    assert_eq!(second[11], 0);
+    // a buffer written to by the kernel must be writable for the user
+    let ro_start: VirtAddr = (0x1000_0000 + 2 * PAGE_SIZE).into();
+    let ro_end: VirtAddr = (0x1000_0000 + 3 * PAGE_SIZE).into();
+    memory_set.insert_framed_area(ro_start, ro_end, MapPermission::R | MapPermission::U);
+    let ptr = (0x1000_0000 + 2 * PAGE_SIZE - 5) as *mut u8;
+    assert!(translated_byte_buffer_checked(memory_set.token(), ptr, 16).is_ok());
+    assert!(translated_byte_buffer_mut(memory_set.token(), ptr, 16).is_err());
+    assert!(translated_byte_buffer_mut(memory_set.token(), ptr, 5).is_ok());
+    info!("user_buffer_test passed!");
+}
@@ -12,14 +12,20 @@ mod frame_allocator;
 mod heap_allocator;
 mod memory_set;
 mod page_table;
+mod shm;
+mod swap;
 
 pub use address::{PhysAddr, PhysPageNum, VirtAddr, VirtPageNum};
 pub use address::{StepByOne, VPNRange};
-pub use frame_allocator::{frame_alloc, frame_dealloc, FrameTracker};
-pub use memory_set::{remap_test, kernel_token};
-pub use memory_set::{MapPermission, MemorySet, KERNEL_SPACE};
-pub use page_table::{translated_byte_buffer, translated_refmut, translated_ref, translated_str, PageTableEntry};
+pub use frame_allocator::{
+    frame_alloc, frame_alloc_huge, frame_dealloc, frame_ref_count, frame_remaining,
+    frame_share_test, FrameTracker,
+};
+pub use memory_set::{remap_test, cow_test, lazy_test, swap_test, huge_page_test, user_buffer_test, kernel_token};
+pub use memory_set::{FileBacking, MapPermission, MemorySet, KERNEL_SPACE};
+pub use page_table::{copy_from_user, copy_to_user, translated_byte_buffer_checked, translated_byte_buffer_mut, PageTableEntry};
 pub use page_table::{PTEFlags, PageTable, UserBuffer};
+pub use shm::{shm_get, shm_remove, shm_segment, ShmSegment, IPC_PRIVATE};
 
 /// initiate heap allocator, frame allocator and kernel space
 pub fn init() {
@@ -1,7 +1,7 @@
 //! Implementation of [`PageTableEntry`] and [`PageTable`].
 
 use super::{frame_alloc, FrameTracker, PhysAddr, PhysPageNum, StepByOne, VirtAddr, VirtPageNum};
-use alloc::string::String;
+use crate::config::{HUGE_PAGE_SIZE, PAGE_SIZE};
 use alloc::vec;
 use alloc::vec::Vec;
 use bitflags::*;
@@ -20,6 +20,13 @@ bitflags! {
     }
 }
 
+/// Pages a megapage, mapped by a leaf in the middle level, spans
+const HUGE_PAGE_PAGES: usize = HUGE_PAGE_SIZE / PAGE_SIZE;
+
+/// A software bit of an invalid entry, telling that the page is in swap
+/// and the ppn field holds its slot
+const PTE_SWAPPED: usize = 1 << 8;
+
 #[derive(Copy, Clone)]
 #[repr(C)]
 /// page table entry structure
@@ -54,6 +61,24 @@ impl PageTableEntry {
     pub fn executable(&self) -> bool {
         (self.flags() & PTEFlags::X) != PTEFlags::empty()
     }
+    /// Whether the entry maps a page rather than pointing to the next level
+    pub fn is_leaf(&self) -> bool {
+        self.is_valid() && (self.readable() || self.writable() || self.executable())
+    }
+    pub fn accessed(&self) -> bool {
+        (self.flags() & PTEFlags::A) != PTEFlags::empty()
+    }
+    pub fn dirty(&self) -> bool {
+        (self.flags() & PTEFlags::D) != PTEFlags::empty()
+    }
+    /// The swap slot the page is in, if it is swapped out
+    pub fn swap_slot(&self) -> Option<usize> {
+        if !self.is_valid() && self.bits & PTE_SWAPPED != 0 {
+            Some(self.bits >> 10)
+        } else {
+            None
+        }
+    }
 }
 
 /// page table structure
@@ -92,27 +117,51 @@ impl PageTable {
                 let frame = frame_alloc().unwrap();
                 *pte = PageTableEntry::new(frame.ppn, PTEFlags::V);
                 self.frames.push(frame);
+            } else if i == 1 && pte.is_leaf() {
+                // a megapage is split into pages to change one of them
+                let frame = frame_alloc().unwrap();
+                let flags = pte.flags();
+                for (i, page_pte) in frame.ppn.get_pte_array().iter_mut().enumerate() {
+                    *page_pte = PageTableEntry::new(PhysPageNum(pte.ppn().0 + i), flags);
+                }
+                *pte = PageTableEntry::new(frame.ppn, PTEFlags::V);
+                self.frames.push(frame);
             }
             ppn = pte.ppn();
         }
         result
     }
-    fn find_pte(&self, vpn: VirtPageNum) -> Option<&PageTableEntry> {
+    /// Get the middle level entry of `vpn`, where a megapage is mapped
+    fn find_huge_pte_create(&mut self, vpn: VirtPageNum) -> &mut PageTableEntry {
+        let idxs = vpn.indexes();
+        let pte = &mut self.root_ppn.get_pte_array()[idxs[0]];
+        if !pte.is_valid() {
+            let frame = frame_alloc().unwrap();
+            *pte = PageTableEntry::new(frame.ppn, PTEFlags::V);
+            self.frames.push(frame);
+        }
+        &mut pte.ppn().get_pte_array()[idxs[1]]
+    }
+    /// Get the entry of `vpn`. Inside a megapage it is made up
+    /// from the middle level leaf, as if the page were mapped alone
+    fn find_pte(&self, vpn: VirtPageNum) -> Option<PageTableEntry> {
         let idxs = vpn.indexes();
         let mut ppn = self.root_ppn;
-        let mut result: Option<&PageTableEntry> = None;
         for (i, idx) in idxs.iter().enumerate() {
-            let pte = &ppn.get_pte_array()[*idx];
+            let pte = ppn.get_pte_array()[*idx];
             if i == 2 {
-                result = Some(pte);
-                break;
+                return Some(pte);
             }
             if !pte.is_valid() {
                 return None;
             }
+            if i == 1 && pte.is_leaf() {
+                let page_ppn = PhysPageNum(pte.ppn().0 + vpn.0 % HUGE_PAGE_PAGES);
+                return Some(PageTableEntry::new(page_ppn, pte.flags()));
+            }
             ppn = pte.ppn();
         }
-        result
+        None
     }
     #[allow(unused)]
     pub fn map(&mut self, vpn: VirtPageNum, ppn: PhysPageNum, flags: PTEFlags) {
@@ -126,8 +175,54 @@ impl PageTable {
         assert!(pte.is_valid(), "vpn {:?} is invalid before unmapping", vpn);
         *pte = PageTableEntry::empty();
     }
+    /// Whether the megapage from `vpn` on can be mapped,
+    /// with no page mapped in its range
+    pub fn can_map_huge(&self, vpn: VirtPageNum) -> bool {
+        let idxs = vpn.indexes();
+        let pte = self.root_ppn.get_pte_array()[idxs[0]];
+        vpn.0 % HUGE_PAGE_PAGES == 0
+            && (!pte.is_valid() || !pte.ppn().get_pte_array()[idxs[1]].is_valid())
+    }
+    /// Map the megapage from `vpn` on to the frames from `ppn` on,
+    /// both aligned to a megapage
+    pub fn map_huge(&mut self, vpn: VirtPageNum, ppn: PhysPageNum, flags: PTEFlags) {
+        assert!(vpn.0 % HUGE_PAGE_PAGES == 0 && ppn.0 % HUGE_PAGE_PAGES == 0);
+        let pte = self.find_huge_pte_create(vpn);
+        assert!(!pte.is_valid(), "vpn {:?} is mapped before mapping a megapage", vpn);
+        *pte = PageTableEntry::new(ppn, flags | PTEFlags::V);
+    }
+    /// Unmap the megapage from `vpn` on as a whole
+    pub fn unmap_huge(&mut self, vpn: VirtPageNum) {
+        let pte = self.find_huge_pte_create(vpn);
+        assert!(pte.is_leaf(), "vpn {:?} is not a megapage before unmapping", vpn);
+        *pte = PageTableEntry::empty();
+    }
+    /// Whether `vpn` is inside a megapage
+    pub fn is_huge(&self, vpn: VirtPageNum) -> bool {
+        let idxs = vpn.indexes();
+        let pte = self.root_ppn.get_pte_array()[idxs[0]];
+        pte.is_valid() && pte.ppn().get_pte_array()[idxs[1]].is_leaf()
+    }
+    /// Mark a page swapped out to `slot`, faulting on access
+    pub fn map_swapped(&mut self, vpn: VirtPageNum, slot: usize) {
+        let pte = self.find_pte_create(vpn).unwrap();
+        assert!(!pte.is_valid(), "vpn {:?} is mapped before swapping out", vpn);
+        pte.bits = slot << 10 | PTE_SWAPPED;
+    }
+    /// Forget the slot of a page swapped out
+    pub fn unmap_swapped(&mut self, vpn: VirtPageNum) {
+        let pte = self.find_pte_create(vpn).unwrap();
+        assert!(pte.swap_slot().is_some(), "vpn {:?} is not swapped out", vpn);
+        *pte = PageTableEntry::empty();
+    }
+    /// Point a mapped page to another frame or with other flags
+    pub fn remap(&mut self, vpn: VirtPageNum, ppn: PhysPageNum, flags: PTEFlags) {
+        let pte = self.find_pte_create(vpn).unwrap();
+        assert!(pte.is_valid(), "vpn {:?} is invalid before remapping", vpn);
+        *pte = PageTableEntry::new(ppn, flags | PTEFlags::V);
+    }
     pub fn translate(&self, vpn: VirtPageNum) -> Option<PageTableEntry> {
-        self.find_pte(vpn).copied()
+        self.find_pte(vpn)
     }
     pub fn translate_va(&self, va: VirtAddr) -> Option<PhysAddr> {
         self.find_pte(va.clone().floor()).map(|pte| {
@@ -144,16 +239,46 @@ impl PageTable {
     }
 }
 
-/// translate a pointer to a mutable u8 Vec through page table
-pub fn translated_byte_buffer(token: usize, ptr: *const u8, len: usize) -> Vec<&'static mut [u8]> {
+/// translate a pointer to a mutable u8 Vec through page table,
+/// fails if any of the pages is not mapped for the user
+pub fn translated_byte_buffer_checked(
+    token: usize,
+    ptr: *const u8,
+    len: usize,
+) -> Result<Vec<&'static mut [u8]>, ()> {
+    translated_pages(token, ptr, len, PTEFlags::U)
+}
+
+/// translate a buffer the kernel writes to, fails if any of the pages
+/// is not mapped writable for the user. The pages shared copy-on-write
+/// are to be faulted in for writing before
+pub fn translated_byte_buffer_mut(
+    token: usize,
+    ptr: *mut u8,
+    len: usize,
+) -> Result<Vec<&'static mut [u8]>, ()> {
+    translated_pages(token, ptr, len, PTEFlags::U | PTEFlags::W)
+}
+
+/// translate `[ptr, ptr + len)` page by page, fails if any of the pages
+/// is not mapped with all of `flags`
+fn translated_pages(
+    token: usize,
+    ptr: *const u8,
+    len: usize,
+    flags: PTEFlags,
+) -> Result<Vec<&'static mut [u8]>, ()> {
     let page_table = PageTable::from_token(token);
     let mut start = ptr as usize;
-    let end = start + len;
+    let end = start.checked_add(len).ok_or(())?;
     let mut v = Vec::new();
     while start < end {
         let start_va = VirtAddr::from(start);
         let mut vpn = start_va.floor();
-        let ppn = page_table.translate(vpn).unwrap().ppn();
+        let ppn = match page_table.translate(vpn) {
+            Some(pte) if pte.is_valid() && pte.flags().contains(flags) => pte.ppn(),
+            _ => return Err(()),
+        };
         vpn.step();
         let mut end_va: VirtAddr = vpn.into();
         end_va = end_va.min(VirtAddr::from(end));
@@ -164,42 +289,36 @@ pub fn translated_byte_buffer(token: usize, ptr: *const u8, len: usize) -> Vec<&
         }
         start = end_va.into();
     }
-    v
+    Ok(v)
 }
 
-pub fn translated_str(token: usize, ptr: *const u8) -> String {
-    let page_table = PageTable::from_token(token);
-    let mut string = String::new();
-    let mut va = ptr as usize;
-    loop {
-        let ch: u8 = *(page_table
-            .translate_va(VirtAddr::from(va))
-            .unwrap()
-            .get_mut());
-        if ch == 0 {
-            break;
-        } else {
-            string.push(ch as char);
-            va += 1;
-        }
+/// Copy a `T` in from `ptr` through page table, where it may straddle
+/// two pages. Fails if `ptr` is misaligned or any of the `T` is not
+/// mapped for the user
+pub fn copy_from_user<T: Copy>(token: usize, ptr: *const T) -> Result<T, ()> {
+    if ptr as usize % core::mem::align_of::<T>() != 0 {
+        return Err(());
     }
-    string
+    let len = core::mem::size_of::<T>();
+    let buffers = translated_byte_buffer_checked(token, ptr as *const u8, len)?;
+    let mut value = core::mem::MaybeUninit::<T>::uninit();
+    let bytes = unsafe { core::slice::from_raw_parts_mut(value.as_mut_ptr() as *mut u8, len) };
+    UserBuffer::new(buffers).copy_to_slice(bytes);
+    Ok(unsafe { value.assume_init() })
 }
 
-pub fn translated_ref<T>(token: usize, ptr: *const T) -> &'static T {
-    let page_table = PageTable::from_token(token);
-    page_table.translate_va(VirtAddr::from(ptr as usize)).unwrap().get_mut()
-}
-
-pub fn translated_refmut<T>(token: usize, ptr: *mut T) -> &'static mut T {
-    //println!("into translated_refmut!");
-    let page_table = PageTable::from_token(token);
-    let va = ptr as usize;
-    //println!("translated_refmut: before translate_va");
-    page_table
-        .translate_va(VirtAddr::from(va))
-        .unwrap()
-        .get_mut()
+/// Copy `value` out to `ptr` through page table, where it may straddle
+/// two pages. Fails if `ptr` is misaligned or any of the `T` is not
+/// mapped writable for the user, with nothing written then
+pub fn copy_to_user<T: Copy>(token: usize, ptr: *mut T, value: T) -> Result<(), ()> {
+    if ptr as usize % core::mem::align_of::<T>() != 0 {
+        return Err(());
+    }
+    let len = core::mem::size_of::<T>();
+    let buffers = translated_byte_buffer_mut(token, ptr as *mut u8, len)?;
+    let bytes = unsafe { core::slice::from_raw_parts(&value as *const T as *const u8, len) };
+    UserBuffer::new(buffers).copy_from_slice(bytes);
+    Ok(())
 }
 
 /// An abstraction over a buffer passed from user space to kernel space
@@ -220,6 +339,67 @@ impl UserBuffer {
         }
         total
     }
+    /// Iterate over the bytes, across the pages
+    pub fn iter_mut(&mut self) -> UserBufferIterMut<'_> {
+        UserBufferIterMut {
+            buffers: self.buffers.iter_mut(),
+            current: (&mut []).iter_mut(),
+        }
+    }
+    /// Copy `src` to the start of the buffer, as much as fits,
+    /// returns the number of bytes copied
+    pub fn copy_from_slice(&mut self, src: &[u8]) -> usize {
+        let mut copied = 0;
+        for slice in self.buffers.iter_mut() {
+            let len = slice.len().min(src.len() - copied);
+            slice[..len].copy_from_slice(&src[copied..copied + len]);
+            copied += len;
+            if copied == src.len() {
+                break;
+            }
+        }
+        copied
+    }
+    /// Copy the start of the buffer to `dst`, as much as fits,
+    /// returns the number of bytes copied
+    pub fn copy_to_slice(&self, dst: &mut [u8]) -> usize {
+        let mut copied = 0;
+        for slice in self.buffers.iter() {
+            let len = slice.len().min(dst.len() - copied);
+            dst[copied..copied + len].copy_from_slice(&slice[..len]);
+            copied += len;
+            if copied == dst.len() {
+                break;
+            }
+        }
+        copied
+    }
+}
+
+impl<'a> IntoIterator for &'a mut UserBuffer {
+    type Item = &'a mut u8;
+    type IntoIter = UserBufferIterMut<'a>;
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter_mut()
+    }
+}
+
+/// An iterator over the bytes of a borrowed UserBuffer
+pub struct UserBufferIterMut<'a> {
+    buffers: core::slice::IterMut<'a, &'static mut [u8]>,
+    current: core::slice::IterMut<'a, u8>,
+}
+
+impl<'a> Iterator for UserBufferIterMut<'a> {
+    type Item = &'a mut u8;
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(byte) = self.current.next() {
+                return Some(byte);
+            }
+            self.current = self.buffers.next()?.iter_mut();
+        }
+    }
 }
 
 impl IntoIterator for UserBuffer {
@@ -0,0 +1,81 @@
+//! Shared memory segments, whose frames every process attaching
+//! a segment maps, until the last of them detaches
+
+use super::{frame_alloc, FrameTracker};
+use crate::sync::UPSafeCell;
+use alloc::collections::BTreeMap;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use lazy_static::*;
+
+/// The key of a segment nobody else can look up
+pub const IPC_PRIVATE: usize = 0;
+
+/// A shared memory segment, kept alive by the table
+/// until removed and by the areas attaching it
+pub struct ShmSegment {
+    key: usize,
+    frames: Vec<FrameTracker>,
+}
+
+impl ShmSegment {
+    /// The frames of the segment, in order
+    pub fn frames(&self) -> &[FrameTracker] {
+        &self.frames
+    }
+}
+
+struct ShmTable {
+    segments: BTreeMap<usize, Arc<ShmSegment>>,
+    next_id: usize,
+}
+
+lazy_static! {
+    /// The segments not removed yet, by id
+    static ref SHM_TABLE: UPSafeCell<ShmTable> = unsafe {
+        UPSafeCell::new(ShmTable {
+            segments: BTreeMap::new(),
+            next_id: 1,
+        })
+    };
+}
+
+/// The id of the segment of `key` with at least `pages` pages, created
+/// if there is none or `key` is `IPC_PRIVATE`. None if the segment of
+/// `key` is smaller, or there are not enough frames for a new one
+pub fn shm_get(key: usize, pages: usize) -> Option<usize> {
+    let mut table = SHM_TABLE.exclusive_access();
+    if key != IPC_PRIVATE {
+        if let Some((id, segment)) = table.segments.iter().find(|(_, seg)| seg.key == key) {
+            return if segment.frames.len() >= pages {
+                Some(*id)
+            } else {
+                None
+            };
+        }
+    }
+    if pages == 0 {
+        return None;
+    }
+    let mut frames = Vec::new();
+    for _ in 0..pages {
+        frames.push(frame_alloc()?);
+    }
+    let id = table.next_id;
+    table.next_id += 1;
+    table.segments.insert(id, Arc::new(ShmSegment { key, frames }));
+    Some(id)
+}
+
+/// The segment `id`, if not removed
+pub fn shm_segment(id: usize) -> Option<Arc<ShmSegment>> {
+    SHM_TABLE.exclusive_access().segments.get(&id).cloned()
+}
+
+/// Remove the segment `id` so that nobody can look it up or attach it
+/// any more, its frames are freed once the last process detaches it
+pub fn shm_remove(id: usize) -> bool {
+    let segment = SHM_TABLE.exclusive_access().segments.remove(&id);
+    // dropped out of the borrow above, with the frames if not attached
+    segment.is_some()
+}
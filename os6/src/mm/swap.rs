@@ -0,0 +1,94 @@
+//! Slots of the swap area on the block device, each holding a page
+//! taken away from a process when frames run low
+
+use crate::config::{PAGE_SIZE, SWAP_SLOTS, SWAP_START_BLOCK};
+use crate::drivers::BLOCK_DEVICE;
+use crate::sync::UPSafeCell;
+use alloc::vec::Vec;
+use easy_fs::BLOCK_SZ;
+use lazy_static::*;
+
+/// Blocks a page takes in the swap area
+const BLOCKS_PER_SLOT: usize = PAGE_SIZE / BLOCK_SZ;
+
+/// A slot holding a swapped out page, freed when dropped
+pub struct SwapSlot {
+    id: usize,
+}
+
+impl SwapSlot {
+    pub fn id(&self) -> usize {
+        self.id
+    }
+    /// Write a page to a free slot, returns None if the swap area is full
+    pub fn write(page: &[u8]) -> Option<Self> {
+        let id = SWAP_ALLOCATOR.exclusive_access().alloc()?;
+        for (i, block) in page.chunks(BLOCK_SZ).enumerate() {
+            BLOCK_DEVICE.write_block(SWAP_START_BLOCK + id * BLOCKS_PER_SLOT + i, block);
+        }
+        Some(Self { id })
+    }
+    /// Read the page back
+    pub fn read(&self, page: &mut [u8]) {
+        for (i, block) in page.chunks_mut(BLOCK_SZ).enumerate() {
+            BLOCK_DEVICE.read_block(SWAP_START_BLOCK + self.id * BLOCKS_PER_SLOT + i, block);
+        }
+        SWAP_ALLOCATOR.exclusive_access().swap_ins += 1;
+    }
+}
+
+impl Drop for SwapSlot {
+    fn drop(&mut self) {
+        SWAP_ALLOCATOR.exclusive_access().dealloc(self.id);
+    }
+}
+
+/// Hands out the slots like the frame allocator hands out frames
+struct SwapAllocator {
+    current: usize,
+    recycled: Vec<usize>,
+    /// pages read back from the swap area
+    swap_ins: usize,
+}
+
+impl SwapAllocator {
+    fn alloc(&mut self) -> Option<usize> {
+        if let Some(id) = self.recycled.pop() {
+            Some(id)
+        } else if self.current == SWAP_SLOTS {
+            None
+        } else {
+            self.current += 1;
+            Some(self.current - 1)
+        }
+    }
+    fn dealloc(&mut self, id: usize) {
+        assert!(
+            id < self.current && !self.recycled.contains(&id),
+            "Swap slot {} has not been allocated!",
+            id
+        );
+        self.recycled.push(id);
+    }
+}
+
+lazy_static! {
+    static ref SWAP_ALLOCATOR: UPSafeCell<SwapAllocator> = unsafe {
+        UPSafeCell::new(SwapAllocator {
+            current: 0,
+            recycled: Vec::new(),
+            swap_ins: 0,
+        })
+    };
+}
+
+/// Number of pages read back from the swap area so far
+pub fn swap_ins() -> usize {
+    SWAP_ALLOCATOR.exclusive_access().swap_ins
+}
+
+/// Number of slots holding a page
+pub fn swap_slots_used() -> usize {
+    let allocator = SWAP_ALLOCATOR.exclusive_access();
+    allocator.current - allocator.recycled.len()
+}
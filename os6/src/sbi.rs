@@ -6,6 +6,11 @@ const SBI_SET_TIMER: usize = 0;
 const SBI_CONSOLE_PUTCHAR: usize = 1;
 const SBI_CONSOLE_GETCHAR: usize = 2;
 const SBI_SHUTDOWN: usize = 8;
+/// System Reset extension, whose function 0 resets or shuts down
+const SBI_EXT_SRST: usize = 0x5352_5354;
+const SRST_TYPE_SHUTDOWN: usize = 0;
+const SRST_REASON_NONE: usize = 0;
+const SRST_REASON_FAILURE: usize = 1;
 
 #[inline(always)]
 /// general sbi call
@@ -39,8 +44,11 @@ pub fn console_getchar() -> usize {
     sbi_call(SBI_CONSOLE_GETCHAR, 0, 0, 0)
 }
 
-/// use sbi call to shutdown the kernel
-pub fn shutdown() -> ! {
+/// use sbi call to shutdown the kernel, telling the machine whether
+/// it is a failure, with the legacy call if there is no System Reset
+pub fn shutdown(failure: bool) -> ! {
+    let reason = if failure { SRST_REASON_FAILURE } else { SRST_REASON_NONE };
+    sbi_call(SBI_EXT_SRST, SRST_TYPE_SHUTDOWN, reason, 0);
     sbi_call(SBI_SHUTDOWN, 0, 0, 0);
     panic!("It should shutdown!");
 }
@@ -0,0 +1,63 @@
+//! Condition variables waited on together with the mutexes of a process
+
+use super::{Mutex, UPSafeCell};
+use crate::task::{block_current_and_run_next, current_task, wakeup_task, TaskControlBlock};
+use alloc::collections::VecDeque;
+use alloc::sync::Arc;
+
+/// A condition variable waking its waiters one by one in arrival order
+pub struct Condvar {
+    inner: UPSafeCell<CondvarInner>,
+}
+
+pub struct CondvarInner {
+    /// Tasks waiting to be signaled, the longest waiting one first
+    wait_queue: VecDeque<Arc<TaskControlBlock>>,
+}
+
+impl Condvar {
+    pub fn new() -> Self {
+        Self {
+            inner: unsafe {
+                UPSafeCell::new(CondvarInner {
+                    wait_queue: VecDeque::new(),
+                })
+            },
+        }
+    }
+
+    /// Wake the longest waiting task, does nothing if there is none
+    pub fn signal(&self) {
+        let mut inner = self.inner.exclusive_access();
+        // a task woken up by a signal before still takes it once it runs
+        if let Some(task) = inner.wait_queue.pop_front() {
+            drop(inner);
+            wakeup_task(task);
+        }
+    }
+
+    /// Release `mutex` held by current task and wait to be signaled,
+    /// then take `mutex` again. Returns false if a signal comes before
+    /// that, in which case `mutex` is not held
+    pub fn wait(&self, mutex: Arc<dyn Mutex>) -> bool {
+        let task = current_task().unwrap();
+        // queued before the release, a signal right after it is not lost
+        self.inner.exclusive_access().wait_queue.push_back(task.clone());
+        mutex.unlock();
+        loop {
+            let mut inner = self.inner.exclusive_access();
+            // dequeued by `signal`
+            let pos = match inner.wait_queue.iter().position(|t| Arc::ptr_eq(t, &task)) {
+                Some(pos) => pos,
+                None => break,
+            };
+            if task.inner_exclusive_access().has_deliverable_signals() {
+                inner.wait_queue.remove(pos);
+                return false;
+            }
+            drop(inner);
+            block_current_and_run_next();
+        }
+        mutex.lock()
+    }
+}
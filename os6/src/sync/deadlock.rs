@@ -0,0 +1,129 @@
+//! Deadlock detection with the banker's algorithm
+//!
+//! Every mutex and semaphore is a resource, identified by its address,
+//! with one or `res_count` units. The units held and waited for are
+//! recorded by the pid of the task whatever its deadlock detection is,
+//! so that a process turning it on sees what the others are doing.
+
+use super::UPSafeCell;
+use alloc::collections::{BTreeMap, BTreeSet};
+use lazy_static::*;
+
+struct DeadlockDetector {
+    /// Free units of each resource
+    available: BTreeMap<usize, usize>,
+    /// Units of each resource held by each task, keyed by `(pid, resource)`
+    allocation: BTreeMap<(usize, usize), usize>,
+    /// Units of each resource each task waits for, keyed by `(pid, resource)`
+    need: BTreeMap<(usize, usize), usize>,
+}
+
+impl DeadlockDetector {
+    /// Whether all the tasks can finish, each taking what it waits for
+    /// and giving back all it holds afterwards
+    fn is_safe(&self) -> bool {
+        let mut work = self.available.clone();
+        let mut unfinished: BTreeSet<usize> = self
+            .allocation
+            .keys()
+            .chain(self.need.keys())
+            .map(|&(pid, _)| pid)
+            .collect();
+        loop {
+            let finishing = unfinished.iter().copied().find(|&pid| {
+                self.need
+                    .range((pid, 0)..=(pid, usize::MAX))
+                    .all(|(&(_, res), &count)| work.get(&res).copied().unwrap_or(0) >= count)
+            });
+            let pid = match finishing {
+                Some(pid) => pid,
+                None => return unfinished.is_empty(),
+            };
+            for (&(_, res), &count) in self.allocation.range((pid, 0)..=(pid, usize::MAX)) {
+                *work.entry(res).or_insert(0) += count;
+            }
+            unfinished.remove(&pid);
+        }
+    }
+}
+
+/// Decrease `map[key]` by one, removing it when it drops to 0
+fn decrease(map: &mut BTreeMap<(usize, usize), usize>, key: (usize, usize)) {
+    match map.get_mut(&key) {
+        Some(count) if *count > 1 => *count -= 1,
+        Some(_) => {
+            map.remove(&key);
+        }
+        None => {}
+    }
+}
+
+lazy_static! {
+    static ref DEADLOCK_DETECTOR: UPSafeCell<DeadlockDetector> = unsafe {
+        UPSafeCell::new(DeadlockDetector {
+            available: BTreeMap::new(),
+            allocation: BTreeMap::new(),
+            need: BTreeMap::new(),
+        })
+    };
+}
+
+/// Record a new resource `res` with `count` free units
+pub fn add_resource(res: usize, count: usize) {
+    let mut detector = DEADLOCK_DETECTOR.exclusive_access();
+    detector.available.insert(res, count);
+}
+
+/// Forget resource `res`, its address may be reused after
+pub fn remove_resource(res: usize) {
+    let mut detector = DEADLOCK_DETECTOR.exclusive_access();
+    detector.available.remove(&res);
+    detector.allocation.retain(|&(_, r), _| r != res);
+    detector.need.retain(|&(_, r), _| r != res);
+}
+
+/// Record that task `pid` waits for a unit of `res`. If `check`,
+/// refuse it and return false if it could end up in a deadlock
+pub fn request_resource(pid: usize, res: usize, check: bool) -> bool {
+    let mut detector = DEADLOCK_DETECTOR.exclusive_access();
+    *detector.need.entry((pid, res)).or_insert(0) += 1;
+    if check && !detector.is_safe() {
+        decrease(&mut detector.need, (pid, res));
+        return false;
+    }
+    true
+}
+
+/// Task `pid` stops waiting for a unit of `res` without getting it
+pub fn cancel_request(pid: usize, res: usize) {
+    let mut detector = DEADLOCK_DETECTOR.exclusive_access();
+    decrease(&mut detector.need, (pid, res));
+}
+
+/// Task `pid` gets the unit of `res` it has been waiting for
+pub fn acquire_resource(pid: usize, res: usize) {
+    let mut detector = DEADLOCK_DETECTOR.exclusive_access();
+    decrease(&mut detector.need, (pid, res));
+    if let Some(available) = detector.available.get_mut(&res) {
+        *available = available.saturating_sub(1);
+    }
+    *detector.allocation.entry((pid, res)).or_insert(0) += 1;
+}
+
+/// Task `pid` releases a unit of `res`, which it may not hold
+/// as semaphores can be posted by anyone
+pub fn release_resource(pid: usize, res: usize) {
+    let mut detector = DEADLOCK_DETECTOR.exclusive_access();
+    decrease(&mut detector.allocation, (pid, res));
+    if let Some(available) = detector.available.get_mut(&res) {
+        *available += 1;
+    }
+}
+
+/// Forget what task `pid` holds and waits for,
+/// it has exited or replaced its program
+pub fn remove_task(pid: usize) {
+    let mut detector = DEADLOCK_DETECTOR.exclusive_access();
+    detector.allocation.retain(|&(p, _), _| p != pid);
+    detector.need.retain(|&(p, _), _| p != pid);
+}
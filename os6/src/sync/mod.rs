@@ -1,5 +1,15 @@
 //! Synchronization and interior mutability primitives
 
+mod condvar;
+mod deadlock;
+mod mutex;
+mod semaphore;
 mod up;
 
+pub use condvar::Condvar;
+pub use deadlock::{
+    acquire_resource, add_resource, cancel_request, release_resource, remove_task, request_resource,
+};
+pub use mutex::{Mutex, MutexBlocking, MutexBlockingInner, MutexSpin};
+pub use semaphore::Semaphore;
 pub use up::UPSafeCell;
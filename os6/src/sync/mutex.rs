@@ -0,0 +1,214 @@
+//! Mutexes shared by the tasks of a process and its children
+
+use super::deadlock::remove_resource;
+use super::UPSafeCell;
+use crate::task::{
+    block_current_and_run_next, current_task, suspend_current_and_run_next, wakeup_task,
+    TaskControlBlock,
+};
+use alloc::collections::VecDeque;
+use alloc::sync::{Arc, Weak};
+use alloc::vec::Vec;
+
+/// A lock a task holds from `lock` to `unlock`
+pub trait Mutex: Sync + Send {
+    /// Wait until the lock is taken by current task,
+    /// returns false if a signal comes before that
+    fn lock(&self) -> bool;
+    /// Release the lock, which is expected to be held by current task
+    fn unlock(&self);
+    /// Release the lock if `task` holds it, as the task exits or execs.
+    /// Only a blocking mutex knows who holds it
+    fn release_held_by(&self, _task: &TaskControlBlock) {}
+}
+
+/// A mutex yielding the CPU while it is held by another task
+pub struct MutexSpin {
+    locked: UPSafeCell<bool>,
+}
+
+impl MutexSpin {
+    pub fn new() -> Self {
+        Self {
+            locked: unsafe { UPSafeCell::new(false) },
+        }
+    }
+}
+
+impl Drop for MutexSpin {
+    fn drop(&mut self) {
+        remove_resource(self as *const _ as usize);
+    }
+}
+
+impl Mutex for MutexSpin {
+    fn lock(&self) -> bool {
+        loop {
+            let mut locked = self.locked.exclusive_access();
+            if !*locked {
+                *locked = true;
+                return true;
+            }
+            drop(locked);
+            if has_deliverable_signals() {
+                return false;
+            }
+            suspend_current_and_run_next();
+        }
+    }
+
+    fn unlock(&self) {
+        *self.locked.exclusive_access() = false;
+    }
+}
+
+/// A mutex blocking the tasks waiting for it until it is released.
+/// Its owner runs with the highest effective priority among the waiters
+/// while holding it, so that a task of lower priority doesn't keep the
+/// waiters off the lock
+pub struct MutexBlocking {
+    inner: Arc<UPSafeCell<MutexBlockingInner>>,
+}
+
+pub struct MutexBlockingInner {
+    /// The task holding the lock, if any. It releases the lock as it
+    /// exits, so that it is not kept from being reaped
+    owner: Option<Weak<TaskControlBlock>>,
+    wait_queue: VecDeque<Arc<TaskControlBlock>>,
+}
+
+impl MutexBlocking {
+    pub fn new() -> Self {
+        Self {
+            inner: Arc::new(unsafe {
+                UPSafeCell::new(MutexBlockingInner {
+                    owner: None,
+                    wait_queue: VecDeque::new(),
+                })
+            }),
+        }
+    }
+}
+
+impl Drop for MutexBlocking {
+    fn drop(&mut self) {
+        remove_resource(self as *const _ as usize);
+    }
+}
+
+impl Mutex for MutexBlocking {
+    fn lock(&self) -> bool {
+        let task = current_task().unwrap();
+        let mut waited = false;
+        loop {
+            let mut mutex_inner = self.inner.exclusive_access();
+            match mutex_inner.owner.as_ref().and_then(Weak::upgrade) {
+                None => {
+                    mutex_inner.owner = Some(Arc::downgrade(&task));
+                    // woken up by a signal before, it is still queued
+                    mutex_inner.wait_queue.retain(|t| !Arc::ptr_eq(t, &task));
+                    drop(mutex_inner);
+                    task.inner_exclusive_access().blocked_on = None;
+                    update_inheritance(self.inner.clone());
+                    return true;
+                }
+                // handed over by `unlock`
+                Some(owner)
+                    if waited
+                        && Arc::ptr_eq(&owner, &task)
+                        && task.inner_exclusive_access().blocked_on.is_none() =>
+                {
+                    return true;
+                }
+                _ => {}
+            }
+            if has_deliverable_signals() {
+                mutex_inner.wait_queue.retain(|t| !Arc::ptr_eq(t, &task));
+                drop(mutex_inner);
+                task.inner_exclusive_access().blocked_on = None;
+                update_inheritance(self.inner.clone());
+                return false;
+            }
+            if !mutex_inner.wait_queue.iter().any(|t| Arc::ptr_eq(t, &task)) {
+                mutex_inner.wait_queue.push_back(task.clone());
+            }
+            drop(mutex_inner);
+            task.inner_exclusive_access().blocked_on = Some(self.inner.clone());
+            update_inheritance(self.inner.clone());
+            waited = true;
+            block_current_and_run_next();
+        }
+    }
+
+    fn unlock(&self) {
+        let key = Arc::as_ptr(&self.inner) as usize;
+        let mut mutex_inner = self.inner.exclusive_access();
+        if let Some(owner) = mutex_inner.owner.take().and_then(|owner| owner.upgrade()) {
+            owner.inner_exclusive_access().inherited.retain(|&(mutex, _)| mutex != key);
+        }
+        // hand the lock over to the longest waiting task still blocked,
+        // so that it inherits from the other waiters at once
+        while let Some(task) = mutex_inner.wait_queue.pop_front() {
+            if wakeup_task(task.clone()) {
+                task.inner_exclusive_access().blocked_on = None;
+                mutex_inner.owner = Some(Arc::downgrade(&task));
+                break;
+            }
+        }
+        drop(mutex_inner);
+        update_inheritance(self.inner.clone());
+    }
+
+    fn release_held_by(&self, task: &TaskControlBlock) {
+        let held = self
+            .inner
+            .exclusive_access()
+            .owner
+            .as_ref()
+            .map_or(false, |owner| Weak::as_ptr(owner) == task as *const _);
+        if held {
+            self.unlock();
+        }
+    }
+}
+
+/// Let the owner of `mutex` inherit the highest effective priority among
+/// its waiters, or drop what it inherited from it if none is waiting.
+/// A change goes on to the owner of the mutex the owner is blocked on
+/// and so on, until an effective priority stays the same
+fn update_inheritance(mut mutex: Arc<UPSafeCell<MutexBlockingInner>>) {
+    loop {
+        let mutex_inner = mutex.exclusive_access();
+        let owner = match mutex_inner.owner.as_ref().and_then(Weak::upgrade) {
+            Some(owner) => owner,
+            None => return,
+        };
+        let waiters: Vec<_> = mutex_inner.wait_queue.iter().cloned().collect();
+        drop(mutex_inner);
+        let inherited = waiters
+            .iter()
+            .map(|task| task.inner_exclusive_access().effective_priority())
+            .max();
+        let key = Arc::as_ptr(&mutex) as usize;
+        let mut owner_inner = owner.inner_exclusive_access();
+        let before = owner_inner.effective_priority();
+        owner_inner.inherited.retain(|&(mutex, _)| mutex != key);
+        if let Some(priority) = inherited {
+            owner_inner.inherited.push((key, priority));
+        }
+        if owner_inner.effective_priority() == before {
+            return;
+        }
+        match owner_inner.blocked_on.clone() {
+            Some(next) => mutex = next,
+            None => return,
+        }
+    }
+}
+
+fn has_deliverable_signals() -> bool {
+    current_task()
+        .unwrap()
+        .inner_exclusive_access()
+        .has_deliverable_signals()
+}
@@ -0,0 +1,84 @@
+//! Counting semaphores shared by the tasks of a process and its children
+
+use super::deadlock::remove_resource;
+use super::UPSafeCell;
+use crate::task::{block_current_and_run_next, current_task, wakeup_task, TaskControlBlock};
+use alloc::collections::VecDeque;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+
+/// A semaphore handing the resources released to its waiters in turn
+pub struct Semaphore {
+    inner: UPSafeCell<SemaphoreInner>,
+}
+
+pub struct SemaphoreInner {
+    /// Resources not taken by anyone
+    count: usize,
+    /// Tasks waiting for a resource, the longest waiting one first
+    wait_queue: VecDeque<Arc<TaskControlBlock>>,
+    /// Tasks handed a resource which have not run since
+    granted: Vec<Arc<TaskControlBlock>>,
+}
+
+impl Semaphore {
+    pub fn new(res_count: usize) -> Self {
+        Self {
+            inner: unsafe {
+                UPSafeCell::new(SemaphoreInner {
+                    count: res_count,
+                    wait_queue: VecDeque::new(),
+                    granted: Vec::new(),
+                })
+            },
+        }
+    }
+
+    /// Release a resource to the longest waiting task, or back to the count
+    pub fn up(&self) {
+        let mut inner = self.inner.exclusive_access();
+        if let Some(task) = inner.wait_queue.pop_front() {
+            inner.granted.push(task.clone());
+            drop(inner);
+            wakeup_task(task);
+        } else {
+            inner.count += 1;
+        }
+    }
+
+    /// Take a resource, waiting until one is handed over if there is none.
+    /// Returns false if a signal comes before that
+    pub fn down(&self) -> bool {
+        let task = current_task().unwrap();
+        loop {
+            let mut inner = self.inner.exclusive_access();
+            if let Some(pos) = inner.granted.iter().position(|t| Arc::ptr_eq(t, &task)) {
+                inner.granted.swap_remove(pos);
+                return true;
+            }
+            let queued = inner.wait_queue.iter().position(|t| Arc::ptr_eq(t, &task));
+            if queued.is_none() && inner.count > 0 {
+                inner.count -= 1;
+                return true;
+            }
+            if task.inner_exclusive_access().has_deliverable_signals() {
+                if let Some(pos) = queued {
+                    inner.wait_queue.remove(pos);
+                }
+                return false;
+            }
+            // woken up by a signal before, it is still queued
+            if queued.is_none() {
+                inner.wait_queue.push_back(task.clone());
+            }
+            drop(inner);
+            block_current_and_run_next();
+        }
+    }
+}
+
+impl Drop for Semaphore {
+    fn drop(&mut self) {
+        remove_resource(self as *const _ as usize);
+    }
+}
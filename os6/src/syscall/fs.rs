@@ -1,31 +1,48 @@
 //! File and filesystem-related syscalls
 
-use crate::mm::translated_byte_buffer;
-use crate::mm::translated_str;
-use crate::mm::translated_refmut;
+use crate::mm::{copy_from_user, copy_to_user};
+use crate::mm::{translated_byte_buffer_checked, translated_byte_buffer_mut};
+use super::translated_str;
 use crate::task::current_user_token;
 use crate::task::current_task;
-use crate::fs::open_file;
+use crate::task::current_cwd;
+use crate::task::block_current_and_run_next;
+use crate::timer::{add_timer, get_time_ms};
+use crate::fs::open;
 use crate::fs::OpenFlags;
-use crate::fs::Stat;
+use crate::fs::{IoVec, PollFd, Stat, Statfs, POLLIN, POLLNVAL, POLLOUT};
+use crate::fs::{FD_CLOEXEC, F_DUPFD, F_GETFD, F_GETFL, F_SETFD, F_SETFL, SEEK_CUR};
+use crate::fs::make_pipe;
+use crate::fs::{LOCK_EX, LOCK_NB, LOCK_SH, LOCK_UN};
 use crate::mm::UserBuffer;
 use alloc::sync::Arc;
-use crate::fs::{linkat, unlinkat};
+use easy_fs::Inode;
+use crate::fs::{linkat, unlinkat, mkdir, symlink, readlink, sync, find_dir, dir_path, statfs, stat_at, access, rename};
+use crate::fs::{AT_FDCWD, AT_SYMLINK_NOFOLLOW, TCGETS, TCSETS, Termios};
+use crate::config::{IOV_MAX, MAX_FD_NUM, PAGE_SIZE};
+use alloc::vec;
+use alloc::vec::Vec;
 
 pub fn sys_write(fd: usize, buf: *const u8, len: usize) -> isize {
     let token = current_user_token();
     let task = current_task().unwrap();
-    let inner = task.inner_exclusive_access();
+    let mut inner = task.inner_exclusive_access();
     if fd >= inner.fd_table.len() {
         return -1;
     }
     if let Some(file) = &inner.fd_table[fd] {
         let file = file.clone();
+        if !file.writable() {
+            return -1;
+        }
+        // the buffer is read through physical addresses
+        inner.memory_set.fault_in(buf as usize, len, false);
         // release current task TCB manually to avoid multi-borrow
         drop(inner);
-        file.write(
-            UserBuffer::new(translated_byte_buffer(token, buf, len))
-        ) as isize
+        match translated_byte_buffer_checked(token, buf, len) {
+            Ok(buffers) => file.write(UserBuffer::new(buffers)) as isize,
+            Err(_) => -1,
+        }
     } else {
         -1
     }
@@ -34,39 +51,290 @@ pub fn sys_write(fd: usize, buf: *const u8, len: usize) -> isize {
 pub fn sys_read(fd: usize, buf: *const u8, len: usize) -> isize {
     let token = current_user_token();
     let task = current_task().unwrap();
-    let inner = task.inner_exclusive_access();
+    let mut inner = task.inner_exclusive_access();
     if fd >= inner.fd_table.len() {
         return -1;
     }
     if let Some(file) = &inner.fd_table[fd] {
         let file = file.clone();
+        if !file.readable() {
+            return -1;
+        }
+        // the buffer is written through physical addresses
+        inner.memory_set.fault_in(buf as usize, len, true);
         // release current task TCB manually to avoid multi-borrow
         drop(inner);
-        file.read(
-            UserBuffer::new(translated_byte_buffer(token, buf, len))
-        ) as isize
+        match translated_byte_buffer_mut(token, buf as *mut u8, len) {
+            Ok(buffers) => match file.try_read(UserBuffer::new(buffers)) {
+                Ok(read_size) => read_size as isize,
+                Err(_) => -1,
+            },
+            Err(_) => -1,
+        }
     } else {
         -1
     }
 }
 
-pub fn sys_open(path: *const u8, flags: u32) -> isize {
+/// Gather the buffers described by the `iovcnt` [`IoVec`]s at `iov`,
+/// faulting them in for writing if `write`. Fails if any of them
+/// is not mapped, before anything is transferred
+fn iovec_buffers(
+    iov: *const IoVec,
+    iovcnt: usize,
+    write: bool,
+) -> Result<Vec<&'static mut [u8]>, ()> {
+    if iovcnt > IOV_MAX {
+        return Err(());
+    }
+    let token = current_user_token();
+    let task = current_task().unwrap();
+    let mut inner = task.inner_exclusive_access();
+    let size = core::mem::size_of::<IoVec>();
+    inner.memory_set.fault_in(iov as usize, iovcnt * size, false);
+    // copied out first, an entry may straddle two pages
+    let bytes: Vec<u8> = translated_byte_buffer_checked(token, iov as *const u8, iovcnt * size)?
+        .iter()
+        .flat_map(|slice| slice.iter().copied())
+        .collect();
+    let mut buffers = Vec::new();
+    for chunk in bytes.chunks(size) {
+        let iovec = unsafe { core::ptr::read_unaligned(chunk.as_ptr() as *const IoVec) };
+        inner.memory_set.fault_in(iovec.base, iovec.len, write);
+        let iov_buffers = if write {
+            translated_byte_buffer_mut(token, iovec.base as *mut u8, iovec.len)?
+        } else {
+            translated_byte_buffer_checked(token, iovec.base as *const u8, iovec.len)?
+        };
+        buffers.extend(iov_buffers);
+    }
+    Ok(buffers)
+}
+
+/// Read from the file `fd` into the `iovcnt` buffers described at `iov`,
+/// filling them one after another. Returns the number of bytes read in
+/// total, or -1 if any of the buffers is not mapped
+pub fn sys_readv(fd: usize, iov: *const IoVec, iovcnt: usize) -> isize {
+    let task = current_task().unwrap();
+    let inner = task.inner_exclusive_access();
+    let file = match inner.fd_table.get(fd) {
+        Some(Some(file)) if file.readable() => file.clone(),
+        _ => return -1,
+    };
+    drop(inner);
+    match iovec_buffers(iov, iovcnt, true) {
+        Ok(buffers) => match file.try_read(UserBuffer::new(buffers)) {
+            Ok(read_size) => read_size as isize,
+            Err(_) => -1,
+        },
+        Err(_) => -1,
+    }
+}
+
+/// Write the `iovcnt` buffers described at `iov` to the file `fd` one after
+/// another. Returns the number of bytes written in total, or -1 without
+/// writing anything if any of the buffers is not mapped
+pub fn sys_writev(fd: usize, iov: *const IoVec, iovcnt: usize) -> isize {
     let task = current_task().unwrap();
+    let inner = task.inner_exclusive_access();
+    let file = match inner.fd_table.get(fd) {
+        Some(Some(file)) if file.writable() => file.clone(),
+        _ => return -1,
+    };
+    drop(inner);
+    match iovec_buffers(iov, iovcnt, false) {
+        Ok(buffers) => file.write(UserBuffer::new(buffers)) as isize,
+        Err(_) => -1,
+    }
+}
+
+/// A [`UserBuffer`] over a kernel buffer, which must outlive its use
+fn kernel_buffer(buf: &mut [u8]) -> UserBuffer {
+    UserBuffer::new(vec![unsafe { core::slice::from_raw_parts_mut(buf.as_mut_ptr(), buf.len()) }])
+}
+
+/// Copy up to `count` bytes from the file `in_fd` to the file `out_fd`
+/// through a kernel buffer. The data is read from the offset of `in_fd`,
+/// which moves past what is copied, or if `offset` is not null, from
+/// `*offset`, which moves instead. Returns the number of bytes copied or -1
+pub fn sys_sendfile(out_fd: usize, in_fd: usize, offset: *mut usize, count: usize) -> isize {
     let token = current_user_token();
-    let path = translated_str(token, path);
-    if let Some(inode) = open_file(
-        path.as_str(),
-        OpenFlags::from_bits(flags).unwrap()
-    ) {
-        let mut inner = task.inner_exclusive_access();
-        let fd = inner.alloc_fd();
-        inner.fd_table[fd] = Some(inode);
-        fd as isize
+    let task = current_task().unwrap();
+    let mut inner = task.inner_exclusive_access();
+    let (out_file, in_file) = match (inner.fd_table.get(out_fd), inner.fd_table.get(in_fd)) {
+        (Some(Some(out_file)), Some(Some(in_file)))
+            if out_file.writable() && in_file.readable() =>
+        {
+            (out_file.clone(), in_file.clone())
+        }
+        _ => return -1,
+    };
+    let len = core::mem::size_of::<usize>();
+    let offset_buffers = if offset.is_null() {
+        None
+    } else {
+        inner.memory_set.fault_in(offset as usize, len, true);
+        match translated_byte_buffer_mut(token, offset as *mut u8, len) {
+            Ok(buffers) => Some(UserBuffer::new(buffers)),
+            Err(_) => return -1,
+        }
+    };
+    drop(inner);
+    let mut pos = offset_buffers.as_ref().map(|buffers| {
+        let mut bytes = [0u8; core::mem::size_of::<usize>()];
+        buffers.copy_to_slice(&mut bytes);
+        usize::from_ne_bytes(bytes)
+    });
+    let mut buffer = vec![0u8; PAGE_SIZE];
+    let mut total = 0usize;
+    while total < count {
+        let chunk = &mut buffer[..(count - total).min(PAGE_SIZE)];
+        let read_size = match pos {
+            Some(pos) => in_file.read_at(pos, chunk),
+            None => in_file.read(kernel_buffer(chunk)) as isize,
+        };
+        if read_size < 0 {
+            return -1;
+        }
+        let read_size = read_size as usize;
+        if read_size == 0 {
+            break;
+        }
+        // a pipe with no reader left takes nothing
+        let written = match out_file.write(kernel_buffer(&mut chunk[..read_size])) {
+            written if written == -1isize as usize => 0,
+            written => written,
+        };
+        total += written;
+        match pos.as_mut() {
+            Some(pos) => *pos += written,
+            // give back what is read but not written
+            None if written < read_size => {
+                in_file.seek(-((read_size - written) as isize), SEEK_CUR);
+            }
+            None => {}
+        }
+        if written < read_size {
+            if total == 0 {
+                return -1;
+            }
+            break;
+        }
+    }
+    if let (Some(pos), Some(mut buffers)) = (pos, offset_buffers) {
+        buffers.copy_from_slice(&pos.to_ne_bytes());
+    }
+    total as isize
+}
+
+/// Read up to `len` bytes at `offset` of the file `fd` into `buf`, leaving
+/// the offset of the file alone. Returns the number of bytes read, which
+/// is 0 past the end, or -1 if the file can't be read at an offset
+pub fn sys_pread(fd: usize, buf: *const u8, len: usize, offset: usize) -> isize {
+    let token = current_user_token();
+    let task = current_task().unwrap();
+    let mut inner = task.inner_exclusive_access();
+    let file = match inner.fd_table.get(fd) {
+        Some(Some(file)) if file.readable() => file.clone(),
+        _ => return -1,
+    };
+    if offset.checked_add(len).is_none() {
+        return -1;
+    }
+    inner.memory_set.fault_in(buf as usize, len, true);
+    drop(inner);
+    let buffers = match translated_byte_buffer_mut(token, buf as *mut u8, len) {
+        Ok(buffers) => buffers,
+        Err(_) => return -1,
+    };
+    let mut total_read_size = 0usize;
+    for slice in buffers {
+        let read_size = file.read_at(offset + total_read_size, slice);
+        if read_size < 0 {
+            return -1;
+        }
+        total_read_size += read_size as usize;
+        if (read_size as usize) < slice.len() {
+            break;
+        }
+    }
+    total_read_size as isize
+}
+
+/// Write `len` bytes of `buf` at `offset` of the file `fd`, leaving the
+/// offset of the file alone. Writing past the end leaves a hole of zeros
+/// in between. Returns the number of bytes written or -1
+pub fn sys_pwrite(fd: usize, buf: *const u8, len: usize, offset: usize) -> isize {
+    let token = current_user_token();
+    let task = current_task().unwrap();
+    let mut inner = task.inner_exclusive_access();
+    let file = match inner.fd_table.get(fd) {
+        Some(Some(file)) if file.writable() => file.clone(),
+        _ => return -1,
+    };
+    if offset.checked_add(len).is_none() {
+        return -1;
+    }
+    inner.memory_set.fault_in(buf as usize, len, false);
+    drop(inner);
+    let buffers = match translated_byte_buffer_checked(token, buf, len) {
+        Ok(buffers) => buffers,
+        Err(_) => return -1,
+    };
+    let mut total_write_size = 0usize;
+    for slice in buffers {
+        let write_size = file.write_at(offset + total_write_size, slice);
+        if write_size < 0 {
+            return -1;
+        }
+        total_write_size += write_size as usize;
+        // the file can't grow any more
+        if (write_size as usize) < slice.len() {
+            break;
+        }
+    }
+    total_write_size as isize
+}
+
+pub fn sys_lseek(fd: usize, offset: isize, whence: usize) -> isize {
+    let task = current_task().unwrap();
+    let inner = task.inner_exclusive_access();
+    if fd >= inner.fd_table.len() {
+        return -1;
+    }
+    if let Some(file) = &inner.fd_table[fd] {
+        let file = file.clone();
+        drop(inner);
+        file.seek(offset, whence)
     } else {
         -1
     }
 }
 
+/// Open the file or device at `path` on the lowest free fd, returns -1
+/// if it can't be opened or every fd below the `RLIMIT_NOFILE` soft limit
+/// is open already
+pub fn sys_open(path: *const u8, flags: u32) -> isize {
+    let task = current_task().unwrap();
+    let path = match translated_str(path) {
+        Ok(path) => path,
+        Err(_) => return -1,
+    };
+    let flags = OpenFlags::from_bits(flags).unwrap();
+    let file = match open(&current_cwd(), path.as_str(), flags) {
+        Some(file) => file,
+        None => return -1,
+    };
+    let mut inner = task.inner_exclusive_access();
+    let fd = match inner.alloc_fd() {
+        Some(fd) => fd,
+        None => return -1,
+    };
+    inner.fd_table[fd] = Some(file);
+    inner.fd_cloexec[fd] = flags.contains(OpenFlags::CLOEXEC);
+    fd as isize
+}
+
 pub fn sys_close(fd: usize) -> isize {
     let task = current_task().unwrap();
     let mut inner = task.inner_exclusive_access();
@@ -80,6 +348,344 @@ pub fn sys_close(fd: usize) -> isize {
     0
 }
 
+/// Create a pipe and write its read and write fds to `pipe`. Both ends
+/// are opened with `flags`, which can only be `NONBLOCK` and `CLOEXEC`.
+/// Returns -1 with no fd left open if `pipe` is not mapped writable
+pub fn sys_pipe(pipe: *mut usize, flags: u32) -> isize {
+    let flags = match OpenFlags::from_bits(flags) {
+        Some(flags) if (flags - OpenFlags::NONBLOCK - OpenFlags::CLOEXEC).is_empty() => flags,
+        _ => return -1,
+    };
+    let task = current_task().unwrap();
+    let token = current_user_token();
+    let mut inner = task.inner_exclusive_access();
+    let (pipe_read, pipe_write) = make_pipe();
+    pipe_read.set_status_flags(flags);
+    pipe_write.set_status_flags(flags);
+    let read_fd = match inner.alloc_fd() {
+        Some(fd) => fd,
+        None => return -1,
+    };
+    inner.fd_table[read_fd] = Some(pipe_read);
+    let write_fd = match inner.alloc_fd() {
+        Some(fd) => fd,
+        None => {
+            inner.fd_table[read_fd] = None;
+            return -1;
+        }
+    };
+    inner.fd_table[write_fd] = Some(pipe_write);
+    let cloexec = flags.contains(OpenFlags::CLOEXEC);
+    inner.fd_cloexec[read_fd] = cloexec;
+    inner.fd_cloexec[write_fd] = cloexec;
+    inner.memory_set.fault_in(pipe as usize, 2 * core::mem::size_of::<usize>(), true);
+    // both fds at once, they may straddle two pages
+    if copy_to_user(token, pipe as *mut [usize; 2], [read_fd, write_fd]).is_err() {
+        for &fd in [read_fd, write_fd].iter() {
+            inner.fd_table[fd] = None;
+            inner.fd_cloexec[fd] = false;
+        }
+        return -1;
+    }
+    0
+}
+
+pub fn sys_dup(fd: usize) -> isize {
+    let task = current_task().unwrap();
+    let mut inner = task.inner_exclusive_access();
+    if fd >= inner.fd_table.len() {
+        return -1;
+    }
+    if inner.fd_table[fd].is_none() {
+        return -1;
+    }
+    let new_fd = match inner.alloc_fd() {
+        Some(fd) => fd,
+        None => return -1,
+    };
+    inner.fd_table[new_fd] = Some(Arc::clone(inner.fd_table[fd].as_ref().unwrap()));
+    new_fd as isize
+}
+
+/// Make `new_fd` refer to the same file as `old_fd`, closing the file
+/// previously held by `new_fd` if any. Fails when `old_fd` equals `new_fd`,
+/// and `new_fd` is closed on exec if `flags` is `CLOEXEC`
+pub fn sys_dup3(old_fd: usize, new_fd: usize, flags: u32) -> isize {
+    let cloexec = match OpenFlags::from_bits(flags) {
+        Some(flags) if flags.is_empty() => false,
+        Some(OpenFlags::CLOEXEC) => true,
+        _ => return -1,
+    };
+    let task = current_task().unwrap();
+    let mut inner = task.inner_exclusive_access();
+    if old_fd >= inner.fd_table.len() || inner.fd_table[old_fd].is_none() {
+        return -1;
+    }
+    if old_fd == new_fd || new_fd >= inner.nofile.cur {
+        return -1;
+    }
+    inner.reserve_fd(new_fd);
+    inner.fd_table[new_fd] = Some(Arc::clone(inner.fd_table[old_fd].as_ref().unwrap()));
+    // the copy doesn't share the flag with `old_fd`
+    inner.fd_cloexec[new_fd] = cloexec;
+    new_fd as isize
+}
+
+/// Read the settings of the terminal `fd` into `argp` with `TCGETS`,
+/// or change them to those at `argp` with `TCSETS`, switching the
+/// console between canonical and raw mode and turning echo on or off.
+/// Returns -1 if `fd` is not a terminal or `request` is not supported
+pub fn sys_ioctl(fd: usize, request: usize, argp: usize) -> isize {
+    let task = current_task().unwrap();
+    let inner = task.inner_exclusive_access();
+    let file = match inner.fd_table.get(fd) {
+        Some(Some(file)) => file.clone(),
+        _ => return -1,
+    };
+    drop(inner);
+    match request {
+        TCGETS => match file.termios() {
+            Some(termios) => copy_out(argp as *mut Termios, &termios),
+            None => -1,
+        },
+        TCSETS => match copy_in(argp as *const Termios) {
+            Some(termios) if file.set_termios(&termios) => 0,
+            _ => -1,
+        },
+        _ => -1,
+    }
+}
+
+/// Read the flags of `fd` with `F_GETFD`, or set them to `arg` with
+/// `F_SETFD`. Read the access mode and the status flags of the file with
+/// `F_GETFL`, or set the status flags to `arg` with `F_SETFL`. Duplicate
+/// `fd` to the lowest free fd not below `arg` with `F_DUPFD`.
+/// Returns -1 if `fd` is not open or `cmd` is not supported
+pub fn sys_fcntl(fd: usize, cmd: usize, arg: usize) -> isize {
+    let task = current_task().unwrap();
+    let mut inner = task.inner_exclusive_access();
+    if fd >= inner.fd_table.len() || inner.fd_table[fd].is_none() {
+        return -1;
+    }
+    let file = inner.fd_table[fd].clone().unwrap();
+    match cmd {
+        F_DUPFD => {
+            let new_fd = match (arg..inner.nofile.cur)
+                .find(|&new_fd| new_fd >= inner.fd_table.len() || inner.fd_table[new_fd].is_none())
+            {
+                Some(new_fd) => new_fd,
+                None => return -1,
+            };
+            inner.reserve_fd(new_fd);
+            inner.fd_table[new_fd] = Some(file);
+            inner.fd_cloexec[new_fd] = false;
+            new_fd as isize
+        }
+        F_GETFL => {
+            let mode = match (file.readable(), file.writable()) {
+                (true, true) => OpenFlags::RDWR,
+                (false, true) => OpenFlags::WRONLY,
+                _ => OpenFlags::RDONLY,
+            };
+            (mode | file.status_flags()).bits() as isize
+        }
+        F_SETFL => {
+            // the access mode and the flags for opening are left as they are
+            file.set_status_flags(OpenFlags::from_bits_truncate(arg as u32));
+            0
+        }
+        F_GETFD => {
+            if inner.fd_cloexec[fd] {
+                FD_CLOEXEC as isize
+            } else {
+                0
+            }
+        }
+        F_SETFD => {
+            inner.fd_cloexec[fd] = arg & FD_CLOEXEC != 0;
+            0
+        }
+        _ => -1,
+    }
+}
+
+/// Wait until any of the `nfds` [`PollFd`]s at `fds` is ready for the
+/// events it asks for, or until `timeout` ms pass, forever if negative.
+/// The events that happen are written back to each `revents`, an fd not
+/// open gets `POLLNVAL`. Returns the number of ready fds, 0 once the time
+/// is up, or -1 if the entries are not mapped or a signal comes
+pub fn sys_poll(fds: *mut PollFd, nfds: usize, timeout: isize) -> isize {
+    if nfds > MAX_FD_NUM {
+        return -1;
+    }
+    let token = current_user_token();
+    let task = current_task().unwrap();
+    let len = nfds * core::mem::size_of::<PollFd>();
+    task.inner_exclusive_access().memory_set.fault_in(fds as usize, len, true);
+    let mut buffers = match translated_byte_buffer_mut(token, fds as *mut u8, len) {
+        Ok(buffers) => UserBuffer::new(buffers),
+        Err(_) => return -1,
+    };
+    // copied out first, an entry may straddle two pages
+    let mut bytes = vec![0u8; len];
+    buffers.copy_to_slice(&mut bytes);
+    let mut poll_fds: Vec<PollFd> = bytes
+        .chunks(core::mem::size_of::<PollFd>())
+        .map(|chunk| unsafe { core::ptr::read_unaligned(chunk.as_ptr() as *const PollFd) })
+        .collect();
+    let expire_ms = get_time_ms() + timeout.max(0) as usize;
+    let ready = loop {
+        let inner = task.inner_exclusive_access();
+        let mut ready = 0;
+        for poll_fd in poll_fds.iter_mut() {
+            poll_fd.revents = 0;
+            if poll_fd.fd < 0 {
+                continue;
+            }
+            match inner.fd_table.get(poll_fd.fd as usize) {
+                Some(Some(file)) => {
+                    if poll_fd.events & POLLIN != 0 && file.readable() && file.read_ready() {
+                        poll_fd.revents |= POLLIN;
+                    }
+                    if poll_fd.events & POLLOUT != 0 && file.writable() && file.write_ready() {
+                        poll_fd.revents |= POLLOUT;
+                    }
+                }
+                _ => poll_fd.revents = POLLNVAL,
+            }
+            if poll_fd.revents != 0 {
+                ready += 1;
+            }
+        }
+        let signaled = inner.has_deliverable_signals();
+        drop(inner);
+        if ready > 0 || timeout == 0 || (timeout > 0 && get_time_ms() >= expire_ms) {
+            break ready;
+        }
+        if signaled {
+            return -1;
+        }
+        // the files have no one to wake a waiter up, so sleep until
+        // the next timer tick and check again
+        add_timer(get_time_ms() + 1, task.clone());
+        block_current_and_run_next();
+    };
+    let bytes = unsafe { core::slice::from_raw_parts(poll_fds.as_ptr() as *const u8, len) };
+    buffers.copy_from_slice(bytes);
+    ready
+}
+
+/// Read the entries of directory `fd` into `buf` as `linux_dirent64` records,
+/// resuming where the last call on `fd` stopped.
+/// Returns the number of bytes written, 0 at the end of the directory, or -1
+pub fn sys_getdents64(fd: usize, buf: *mut u8, len: usize) -> isize {
+    let token = current_user_token();
+    let task = current_task().unwrap();
+    let mut inner = task.inner_exclusive_access();
+    if fd >= inner.fd_table.len() {
+        return -1;
+    }
+    if let Some(file) = &inner.fd_table[fd] {
+        let file = file.clone();
+        // the buffer is written through physical addresses
+        inner.memory_set.fault_in(buf as usize, len, true);
+        // release current task TCB manually to avoid multi-borrow
+        drop(inner);
+        match translated_byte_buffer_mut(token, buf, len) {
+            Ok(buffers) => file.getdents(UserBuffer::new(buffers)),
+            Err(_) => -1,
+        }
+    } else {
+        -1
+    }
+}
+
+/// Take a shared lock on the file `fd` refers to with `LOCK_SH`, an exclusive
+/// one with `LOCK_EX`, replacing the lock it holds, or release it with
+/// `LOCK_UN`. Waits for a conflicting lock to go unless `LOCK_NB` is added.
+/// The lock goes along with the last fd referring to the file.
+/// Returns -1 if it is not granted or the file can't be locked
+pub fn sys_flock(fd: usize, operation: usize) -> isize {
+    match operation & !LOCK_NB {
+        LOCK_SH | LOCK_EX | LOCK_UN => {}
+        _ => return -1,
+    }
+    let task = current_task().unwrap();
+    let inner = task.inner_exclusive_access();
+    let file = match inner.fd_table.get(fd) {
+        Some(Some(file)) => file.clone(),
+        _ => return -1,
+    };
+    // others get to run while it waits
+    drop(inner);
+    file.flock(operation)
+}
+
+/// Set the size of the file `fd` refers to, bytes beyond the old end read as zero
+pub fn sys_ftruncate(fd: usize, length: isize) -> isize {
+    if length < 0 {
+        return -1;
+    }
+    let task = current_task().unwrap();
+    let inner = task.inner_exclusive_access();
+    if fd >= inner.fd_table.len() {
+        return -1;
+    }
+    if let Some(file) = &inner.fd_table[fd] {
+        let file = file.clone();
+        if !file.writable() {
+            return -1;
+        }
+        drop(inner);
+        file.truncate(length as usize)
+    } else {
+        -1
+    }
+}
+
+/// Set the size of the file at `path` like `sys_ftruncate`, with no fd
+/// needed. Returns -1 if there is no such file or it is a directory
+pub fn sys_truncate(path: *const u8, length: isize) -> isize {
+    if length < 0 {
+        return -1;
+    }
+    let path = match translated_str(path) {
+        Ok(path) => path,
+        Err(_) => return -1,
+    };
+    // opened for writing only for the while, which a directory can't be
+    match open(&current_cwd(), path.as_str(), OpenFlags::WRONLY) {
+        Some(file) => file.truncate(length as usize),
+        None => -1,
+    }
+}
+
+/// Keep the size of the file as it is in `sys_fallocate`
+pub const FALLOC_FL_KEEP_SIZE: usize = 1;
+
+/// Allocate the blocks backing `len` bytes at `offset` of file `fd`,
+/// all of them or none
+pub fn sys_fallocate(fd: usize, mode: usize, offset: isize, len: isize) -> isize {
+    if offset < 0 || len <= 0 || mode & !FALLOC_FL_KEEP_SIZE != 0 {
+        return -1;
+    }
+    let task = current_task().unwrap();
+    let inner = task.inner_exclusive_access();
+    if fd >= inner.fd_table.len() {
+        return -1;
+    }
+    if let Some(file) = &inner.fd_table[fd] {
+        let file = file.clone();
+        if !file.writable() {
+            return -1;
+        }
+        drop(inner);
+        file.fallocate(offset as usize, len as usize, mode & FALLOC_FL_KEEP_SIZE != 0)
+    } else {
+        -1
+    }
+}
+
 // YOUR JOB: 扩展 easy-fs 和内核以实现以下三个 syscall
 
 /*
@@ -94,18 +700,85 @@ Rust 接口： fn fstat(fd: i32, st: *mut Stat) -> i32
         st: 文件状态结构体
 */
 pub fn sys_fstat(_fd: usize, _st: *mut Stat) -> isize {
-    let token = current_user_token();
     let task = current_task().unwrap();
     let inner = task.inner_exclusive_access();
-    if _fd >= inner.fd_table.len() {
+    let file = match inner.fd_table.get(_fd) {
+        Some(Some(file)) => file.clone(),
+        _ => return -1,
+    };
+    drop(inner);
+    let mut stat = Stat::new();
+    file.info(&mut stat);
+    copy_out(_st, &stat)
+}
+
+/// Fill `st` with the status of `path` relative to the directory `dirfd`,
+/// or to the working directory if `dirfd` is `AT_FDCWD`, without opening
+/// it. A symbolic link is looked at itself with `AT_SYMLINK_NOFOLLOW`.
+/// Returns -1 if there is no such path
+pub fn sys_fstatat(dirfd: isize, path: *const u8, st: *mut Stat, flags: u32) -> isize {
+    if flags & !AT_SYMLINK_NOFOLLOW != 0 {
         return -1;
     }
-    if let Some(file) = &inner.fd_table[_fd] {
-        let _st: *mut Stat = translated_refmut(token, _st);
-        file.info(_st);
-        0
-    } else {
-        -1
+    let path = match translated_str(path) {
+        Ok(path) => path,
+        Err(_) => return -1,
+    };
+    let dir = match dir_of(dirfd) {
+        Some(dir) => dir,
+        None => return -1,
+    };
+    match stat_at(&dir, path.as_str(), flags & AT_SYMLINK_NOFOLLOW == 0) {
+        Some(stat) => copy_out(st, &stat),
+        None => -1,
+    }
+}
+
+/// The directory paths relative to `dirfd` start from, the working
+/// directory for `AT_FDCWD`. None if `dirfd` is no open directory
+fn dir_of(dirfd: isize) -> Option<Arc<Inode>> {
+    if dirfd == AT_FDCWD {
+        return Some(current_cwd());
+    }
+    let task = current_task().unwrap();
+    let inner = task.inner_exclusive_access();
+    match inner.fd_table.get(dirfd as usize) {
+        Some(Some(file)) => file.dir_inode(),
+        _ => None,
+    }
+}
+
+/// Copy `value` out to `ptr` in the current task, faulting in
+/// its pages first. Returns -1 if `ptr` is misaligned or not mapped
+fn copy_out<T: Copy>(ptr: *mut T, value: &T) -> isize {
+    let len = core::mem::size_of::<T>();
+    let task = current_task().unwrap();
+    task.inner_exclusive_access().memory_set.fault_in(ptr as usize, len, true);
+    match copy_to_user(current_user_token(), ptr, *value) {
+        Ok(()) => 0,
+        Err(()) => -1,
+    }
+}
+
+/// Copy a `T` in from `ptr` in the current task, faulting in
+/// its pages first. None if `ptr` is misaligned or not mapped
+fn copy_in<T: Copy>(ptr: *const T) -> Option<T> {
+    let len = core::mem::size_of::<T>();
+    let task = current_task().unwrap();
+    task.inner_exclusive_access().memory_set.fault_in(ptr as usize, len, false);
+    copy_from_user(current_user_token(), ptr).ok()
+}
+
+/// Fill `buf` with the usage of the filesystem holding `path`,
+/// returns -1 if there is no such path
+pub fn sys_statfs(path: *const u8, buf: *mut Statfs) -> isize {
+    let path = match translated_str(path) {
+        Ok(path) => path,
+        Err(_) => return -1,
+    };
+    match statfs(&current_cwd(), path.as_str()) {
+        Some(stat) => copy_out(buf, &stat),
+        None => -1,
     }
 }
 
@@ -117,31 +790,47 @@ pub fn sys_fstat(_fd: usize, _st: *mut Stat) -> isize {
 Rust 接口： fn linkat(olddirfd: i32, oldpath: *const u8, newdirfd: i32, newpath: *const u8, flags: u32) -> i32
 
 参数：
-        olddirfd，newdirfd: 仅为了兼容性考虑，本次实验中始终为 AT_FDCWD (-100)，可以忽略。
-        flags: 仅为了兼容性考虑，本次实验中始终为 0，可以忽略。
+        olddirfd，newdirfd: oldpath 与 newpath 相对的目录 fd，AT_FDCWD (-100) 表示当前工作目录。
+        flags: 目前不支持任何标志，必须为 0。
         oldpath：原有文件路径
         newpath: 新的链接文件路径。
 
 说明：
-        为了方便，不考虑新文件路径已经存在的情况（属于未定义行为），除非链接同名文件。
         返回值：如果出现了错误则返回 -1，否则返回 0。
 
 可能的错误
 
-        链接同名文件。
+        原有文件不存在或是目录。
+        新的链接文件路径已经存在，包括链接同名文件。
+        olddirfd 或 newdirfd 既不是 AT_FDCWD 也不是打开的目录。
+        flags 不为 0。
 */
 
-pub fn sys_linkat(_old_name: *const u8, _new_name: *const u8) -> isize {
-    let token = current_user_token();
-    let old_name = translated_str(token, _old_name);
-    let old_name = old_name.as_str();
-    let new_name = translated_str(token, _new_name);
-    let new_name = new_name.as_str();
-    if old_name == new_name {
-        -1
-    } else {
-        linkat(old_name, new_name);
+pub fn sys_linkat(
+    old_dirfd: isize,
+    _old_name: *const u8,
+    new_dirfd: isize,
+    _new_name: *const u8,
+    flags: u32,
+) -> isize {
+    if flags != 0 {
+        return -1;
+    }
+    let (old_dir, new_dir) = match (dir_of(old_dirfd), dir_of(new_dirfd)) {
+        (Some(old_dir), Some(new_dir)) => (old_dir, new_dir),
+        _ => return -1,
+    };
+    let (old_name, new_name) = match (
+        translated_str(_old_name),
+        translated_str(_new_name),
+    ) {
+        (Ok(old_name), Ok(new_name)) => (old_name, new_name),
+        _ => return -1,
+    };
+    if linkat(&old_dir, old_name.as_str(), &new_dir, new_name.as_str()) {
         0
+    } else {
+        -1
     }
 }
 
@@ -166,8 +855,147 @@ Rust 接口： fn unlinkat(dirfd: i32, path: *const u8, flags: u32) -> i32
         文件不存在。
 */
 pub fn sys_unlinkat(_name: *const u8) -> isize {
-    let token = current_user_token();
-    let name = translated_str(token, _name);
+    let name = match translated_str(_name) {
+        Ok(name) => name,
+        Err(_) => return -1,
+    };
     let name = name.as_str();
-    unlinkat(name)
+    unlinkat(&current_cwd(), name)
+}
+
+/// Move the file at `old_path` to `new_path` in one go, replacing the file
+/// already there. Renaming a file onto itself does nothing and succeeds
+pub fn sys_rename(old_path: *const u8, new_path: *const u8) -> isize {
+    let (old_path, new_path) = match (
+        translated_str(old_path),
+        translated_str(new_path),
+    ) {
+        (Ok(old_path), Ok(new_path)) => (old_path, new_path),
+        _ => return -1,
+    };
+    if rename(&current_cwd(), old_path.as_str(), new_path.as_str()) {
+        0
+    } else {
+        -1
+    }
+}
+
+/// Permission bits accepted by `sys_mkdir`
+const MKDIR_MODE_MASK: u32 = 0o7777;
+
+/// Create a directory at `path`, `mode` is only checked against
+/// the permission bits for now
+pub fn sys_mkdir(path: *const u8, mode: u32) -> isize {
+    if mode & !MKDIR_MODE_MASK != 0 {
+        return -1;
+    }
+    let path = match translated_str(path) {
+        Ok(path) => path,
+        Err(_) => return -1,
+    };
+    match mkdir(&current_cwd(), path.as_str()) {
+        Some(_) => 0,
+        None => -1,
+    }
+}
+
+/// Create a symbolic link at `link_path` pointing to `target`
+pub fn sys_symlink(target: *const u8, link_path: *const u8) -> isize {
+    let (target, link_path) = match (
+        translated_str(target),
+        translated_str(link_path),
+    ) {
+        (Ok(target), Ok(link_path)) => (target, link_path),
+        _ => return -1,
+    };
+    match symlink(&current_cwd(), target.as_str(), link_path.as_str()) {
+        Some(_) => 0,
+        None => -1,
+    }
+}
+
+/// Check whether `path` exists and allows the `R_OK`, `W_OK` and `X_OK`
+/// bits of `mode`, returns 0 if so and -1 otherwise
+pub fn sys_access(path: *const u8, mode: usize) -> isize {
+    let path = match translated_str(path) {
+        Ok(path) => path,
+        Err(_) => return -1,
+    };
+    if access(&current_cwd(), path.as_str(), mode) {
+        0
+    } else {
+        -1
+    }
+}
+
+/// Make directory `path` the working directory of current task,
+/// returns -1 if it is not a directory
+pub fn sys_chdir(path: *const u8) -> isize {
+    let path = match translated_str(path) {
+        Ok(path) => path,
+        Err(_) => return -1,
+    };
+    match find_dir(&current_cwd(), path.as_str()) {
+        Some(dir) => {
+            current_task().unwrap().inner_exclusive_access().cwd = dir;
+            0
+        }
+        None => -1,
+    }
+}
+
+/// Write the absolute path of the working directory with a trailing NUL
+/// to `buf` of `size` bytes and return its length including the NUL.
+/// Returns -1 if it doesn't fit or the directory has been removed
+pub fn sys_getcwd(buf: *mut u8, size: usize) -> isize {
+    let mut path = match dir_path(&current_cwd()) {
+        Some(path) => path,
+        None => return -1,
+    };
+    path.push('\0');
+    if path.len() > size {
+        return -1;
+    }
+    let token = current_user_token();
+    let task = current_task().unwrap();
+    // the buffer is written through physical addresses
+    task.inner_exclusive_access().memory_set.fault_in(buf as usize, path.len(), true);
+    let buffers = match translated_byte_buffer_mut(token, buf, path.len()) {
+        Ok(buffers) => buffers,
+        Err(_) => return -1,
+    };
+    UserBuffer::new(buffers).copy_from_slice(path.as_bytes());
+    path.len() as isize
+}
+
+/// Copy the target of the symbolic link at `path` to `buf`, cut short to
+/// `size` bytes with no NUL added. Returns the number of bytes copied,
+/// or -1 if there is no symbolic link at `path`
+pub fn sys_readlink(path: *const u8, buf: *mut u8, size: usize) -> isize {
+    if size == 0 {
+        return -1;
+    }
+    let token = current_user_token();
+    let path = match translated_str(path) {
+        Ok(path) => path,
+        Err(_) => return -1,
+    };
+    let target = match readlink(&current_cwd(), path.as_str()) {
+        Some(target) => target,
+        None => return -1,
+    };
+    let len = target.len().min(size);
+    let task = current_task().unwrap();
+    task.inner_exclusive_access().memory_set.fault_in(buf as usize, len, true);
+    let buffers = match translated_byte_buffer_mut(token, buf, len) {
+        Ok(buffers) => buffers,
+        Err(_) => return -1,
+    };
+    UserBuffer::new(buffers).copy_from_slice(&target.as_bytes()[..len]) as isize
+}
+
+/// Write all dirty cached blocks back to the disk
+pub fn sys_sync() -> isize {
+    sync();
+    0
 }
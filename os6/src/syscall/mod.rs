@@ -10,55 +10,203 @@
 //! `sys_` then the name of the syscall. You can find functions like this in
 //! submodules, and you should also implement syscalls this way.
 
+const SYSCALL_GETCWD: usize = 17;
+const SYSCALL_DUP: usize = 24;
+const SYSCALL_FCNTL: usize = 25;
+const SYSCALL_IOCTL: usize = 29;
+const SYSCALL_FLOCK: usize = 32;
+const SYSCALL_MKDIRAT: usize = 34;
 const SYSCALL_UNLINKAT: usize = 35;
+const SYSCALL_SYMLINKAT: usize = 36;
 const SYSCALL_LINKAT: usize = 37;
+const SYSCALL_STATFS: usize = 43;
+const SYSCALL_TRUNCATE: usize = 45;
+const SYSCALL_FTRUNCATE: usize = 46;
+const SYSCALL_FALLOCATE: usize = 47;
+const SYSCALL_FACCESSAT: usize = 48;
+const SYSCALL_CHDIR: usize = 49;
 const SYSCALL_OPEN: usize = 56;
 const SYSCALL_CLOSE: usize = 57;
+const SYSCALL_PIPE: usize = 59;
+const SYSCALL_GETDENTS64: usize = 61;
+const SYSCALL_LSEEK: usize = 62;
 const SYSCALL_READ: usize = 63;
 const SYSCALL_WRITE: usize = 64;
+const SYSCALL_READV: usize = 65;
+const SYSCALL_WRITEV: usize = 66;
+const SYSCALL_PREAD: usize = 67;
+const SYSCALL_PWRITE: usize = 68;
+const SYSCALL_SENDFILE: usize = 71;
+const SYSCALL_PPOLL: usize = 73;
+const SYSCALL_READLINKAT: usize = 78;
+const SYSCALL_FSTATAT: usize = 79;
 const SYSCALL_FSTAT: usize = 80;
+const SYSCALL_SYNC: usize = 81;
 const SYSCALL_EXIT: usize = 93;
+const SYSCALL_SLEEP: usize = 101;
+const SYSCALL_CLOCK_GETTIME: usize = 113;
+const SYSCALL_TIMES: usize = 153;
+const SYSCALL_UNAME: usize = 160;
+const SYSCALL_GETRLIMIT: usize = 163;
+const SYSCALL_SETRLIMIT: usize = 164;
+const SYSCALL_GETRUSAGE: usize = 165;
 const SYSCALL_YIELD: usize = 124;
-const SYSCALL_GET_TIME: usize = 169;
+const SYSCALL_KILL: usize = 129;
+const SYSCALL_SIGACTION: usize = 134;
+const SYSCALL_SIGPROCMASK: usize = 135;
+const SYSCALL_SIGRETURN: usize = 139;
+const SYSCALL_GETTIMEOFDAY: usize = 169;
 const SYSCALL_GETPID: usize = 172;
+const SYSCALL_GETPPID: usize = 173;
+const SYSCALL_SHMGET: usize = 194;
+const SYSCALL_SHMCTL: usize = 195;
+const SYSCALL_SHMAT: usize = 196;
+const SYSCALL_SHMDT: usize = 197;
 const SYSCALL_FORK: usize = 220;
 const SYSCALL_EXEC: usize = 221;
 const SYSCALL_WAITPID: usize = 260;
+const SYSCALL_RENAMEAT2: usize = 276;
 const SYSCALL_SPAWN: usize = 400;
+// Linux gives 24 to `dup3`, which the rCore user programs take for `dup`,
+// so it is numbered along with the other rCore extensions. `dup2` is left
+// to the user library on top of it, the same as on Linux riscv64
+const SYSCALL_DUP3: usize = 404;
+const SYSCALL_BRK: usize = 214;
 const SYSCALL_MUNMAP: usize = 215;
 const SYSCALL_MMAP: usize = 222;
+const SYSCALL_MPROTECT: usize = 226;
+const SYSCALL_MADVISE: usize = 233;
 const SYSCALL_SET_PRIORITY: usize = 140;
+const SYSCALL_SHUTDOWN: usize = 142;
 const SYSCALL_TASK_INFO: usize = 410;
+const SYSCALL_ENVIRON: usize = 411;
+const SYSCALL_SETENV: usize = 412;
+const SYSCALL_MUTEX_CREATE: usize = 463;
+const SYSCALL_MUTEX_LOCK: usize = 464;
+const SYSCALL_MUTEX_UNLOCK: usize = 466;
+const SYSCALL_SEMAPHORE_CREATE: usize = 467;
+const SYSCALL_SEMAPHORE_UP: usize = 468;
+const SYSCALL_ENABLE_DEADLOCK_DETECT: usize = 469;
+const SYSCALL_SEMAPHORE_DOWN: usize = 470;
+const SYSCALL_CONDVAR_CREATE: usize = 471;
+const SYSCALL_CONDVAR_SIGNAL: usize = 472;
+const SYSCALL_CONDVAR_WAIT: usize = 473;
 
 mod fs;
 pub mod process;
+mod sync;
 
 use fs::*;
 use process::*;
-use crate::fs::Stat;
+use sync::*;
+use crate::fs::{IoVec, PollFd, Stat, Statfs};
+use crate::task::{count_current_syscall, current_task, SignalAction};
+use alloc::string::String;
 
 /// handle syscall exception with `syscall_id` and other arguments
-pub fn syscall(syscall_id: usize, args: [usize; 4]) -> isize {
+pub fn syscall(syscall_id: usize, args: [usize; 6]) -> isize {
+    count_current_syscall(syscall_id);
     match syscall_id {
-        SYSCALL_LINKAT => sys_linkat(args[1] as *const u8, args[3] as *const u8),
+        SYSCALL_GETCWD => sys_getcwd(args[0] as *mut u8, args[1]),
+        SYSCALL_DUP => sys_dup(args[0]),
+        SYSCALL_FCNTL => sys_fcntl(args[0], args[1], args[2]),
+        SYSCALL_IOCTL => sys_ioctl(args[0], args[1], args[2]),
+        SYSCALL_DUP3 => sys_dup3(args[0], args[1], args[2] as u32),
+        SYSCALL_STATFS => sys_statfs(args[0] as *const u8, args[1] as *mut Statfs),
+        SYSCALL_FACCESSAT => sys_access(args[1] as *const u8, args[2]),
+        SYSCALL_CHDIR => sys_chdir(args[0] as *const u8),
+        SYSCALL_LINKAT => sys_linkat(
+            args[0] as isize,
+            args[1] as *const u8,
+            args[2] as isize,
+            args[3] as *const u8,
+            args[4] as u32,
+        ),
+        SYSCALL_RENAMEAT2 => sys_rename(args[1] as *const u8, args[3] as *const u8),
+        SYSCALL_MKDIRAT => sys_mkdir(args[1] as *const u8, args[2] as u32),
         SYSCALL_UNLINKAT => sys_unlinkat(args[1] as *const u8),
+        SYSCALL_SYMLINKAT => sys_symlink(args[0] as *const u8, args[2] as *const u8),
+        SYSCALL_TRUNCATE => sys_truncate(args[0] as *const u8, args[1] as isize),
+        SYSCALL_FLOCK => sys_flock(args[0], args[1]),
+        SYSCALL_FTRUNCATE => sys_ftruncate(args[0], args[1] as isize),
+        SYSCALL_FALLOCATE => sys_fallocate(args[0], args[1], args[2] as isize, args[3] as isize),
         SYSCALL_OPEN => sys_open(args[1] as *const u8, args[2] as u32),
         SYSCALL_CLOSE => sys_close(args[0]),
+        SYSCALL_PIPE => sys_pipe(args[0] as *mut usize, args[1] as u32),
+        SYSCALL_GETDENTS64 => sys_getdents64(args[0], args[1] as *mut u8, args[2]),
+        SYSCALL_LSEEK => sys_lseek(args[0], args[1] as isize, args[2]),
         SYSCALL_READ => sys_read(args[0], args[1] as *const u8, args[2]),
         SYSCALL_WRITE => sys_write(args[0], args[1] as *const u8, args[2]),
+        SYSCALL_READV => sys_readv(args[0], args[1] as *const IoVec, args[2]),
+        SYSCALL_WRITEV => sys_writev(args[0], args[1] as *const IoVec, args[2]),
+        SYSCALL_SENDFILE => sys_sendfile(args[0], args[1], args[2] as *mut usize, args[3]),
+        SYSCALL_PPOLL => sys_poll(args[0] as *mut PollFd, args[1], args[2] as isize),
+        SYSCALL_PREAD => sys_pread(args[0], args[1] as *const u8, args[2], args[3]),
+        SYSCALL_PWRITE => sys_pwrite(args[0], args[1] as *const u8, args[2], args[3]),
+        SYSCALL_READLINKAT => sys_readlink(args[1] as *const u8, args[2] as *mut u8, args[3]),
+        SYSCALL_FSTATAT => sys_fstatat(args[0] as isize, args[1] as *const u8, args[2] as *mut Stat, args[3] as u32),
         SYSCALL_FSTAT => sys_fstat(args[0], args[1] as *mut Stat),
+        SYSCALL_SYNC => sys_sync(),
         SYSCALL_EXIT => sys_exit(args[0] as i32),
+        SYSCALL_SHUTDOWN => sys_shutdown(args[0]),
+        SYSCALL_SLEEP => sys_sleep(args[0]),
         SYSCALL_YIELD => sys_yield(),
+        SYSCALL_KILL => sys_kill(args[0], args[1]),
+        SYSCALL_SIGACTION => sys_sigaction(
+            args[0],
+            args[1] as *const SignalAction,
+            args[2] as *mut SignalAction,
+        ),
+        SYSCALL_SIGPROCMASK => sys_sigprocmask(args[0] as u32),
+        SYSCALL_SIGRETURN => sys_sigreturn(),
         SYSCALL_GETPID => sys_getpid(),
+        SYSCALL_GETPPID => sys_getppid(),
         SYSCALL_FORK => sys_fork(),
-        SYSCALL_EXEC => sys_exec(args[0] as *const u8),
-        SYSCALL_WAITPID => sys_waitpid(args[0] as isize, args[1] as *mut i32),
-        SYSCALL_GET_TIME => sys_get_time(args[0] as *mut TimeVal, args[1]),
-        SYSCALL_MMAP => sys_mmap(args[0], args[1], args[2]),
+        SYSCALL_EXEC => sys_exec(
+            args[0] as *const u8,
+            args[1] as *const usize,
+            args[2] as *const usize,
+        ),
+        SYSCALL_WAITPID => sys_waitpid(args[0] as isize, args[1] as *mut i32, args[2]),
+        SYSCALL_GETTIMEOFDAY => sys_gettimeofday(args[0] as *mut TimeVal, args[1]),
+        SYSCALL_CLOCK_GETTIME => sys_clock_gettime(args[0], args[1] as *mut TimeSpec),
+        SYSCALL_TIMES => sys_times(args[0] as *mut Tms),
+        SYSCALL_UNAME => sys_uname(args[0] as *mut Utsname),
+        SYSCALL_GETRLIMIT => sys_getrlimit(args[0], args[1] as *mut RLimit),
+        SYSCALL_SETRLIMIT => sys_setrlimit(args[0], args[1] as *const RLimit),
+        SYSCALL_GETRUSAGE => sys_getrusage(args[0] as isize, args[1] as *mut Rusage),
+        SYSCALL_MMAP => sys_mmap(args[0], args[1], args[2], args[3], args[4], args[5]),
         SYSCALL_MUNMAP => sys_munmap(args[0], args[1]),
+        SYSCALL_BRK => sys_brk(args[0]),
+        SYSCALL_MPROTECT => sys_mprotect(args[0], args[1], args[2]),
+        SYSCALL_MADVISE => sys_madvise(args[0], args[1], args[2]),
+        SYSCALL_SHMGET => sys_shmget(args[0], args[1]),
+        SYSCALL_SHMCTL => sys_shmctl(args[0], args[1]),
+        SYSCALL_SHMAT => sys_shmat(args[0]),
+        SYSCALL_SHMDT => sys_shmdt(args[0]),
         SYSCALL_SET_PRIORITY => sys_set_priority(args[0] as isize),
         SYSCALL_TASK_INFO => sys_task_info(args[0] as *mut TaskInfo),
+        SYSCALL_ENVIRON => sys_environ(args[0] as *mut u8, args[1]),
+        SYSCALL_SETENV => sys_setenv(args[0] as *const u8, args[1] as *const u8),
         SYSCALL_SPAWN => sys_spawn(args[0] as *const u8),
+        SYSCALL_MUTEX_CREATE => sys_mutex_create(args[0] != 0),
+        SYSCALL_MUTEX_LOCK => sys_mutex_lock(args[0]),
+        SYSCALL_MUTEX_UNLOCK => sys_mutex_unlock(args[0]),
+        SYSCALL_SEMAPHORE_CREATE => sys_semaphore_create(args[0] as isize),
+        SYSCALL_SEMAPHORE_UP => sys_semaphore_up(args[0]),
+        SYSCALL_ENABLE_DEADLOCK_DETECT => sys_enable_deadlock_detect(args[0]),
+        SYSCALL_SEMAPHORE_DOWN => sys_semaphore_down(args[0]),
+        SYSCALL_CONDVAR_CREATE => sys_condvar_create(),
+        SYSCALL_CONDVAR_SIGNAL => sys_condvar_signal(args[0]),
+        SYSCALL_CONDVAR_WAIT => sys_condvar_wait(args[0], args[1]),
         _ => panic!("Unsupported syscall_id: {}", syscall_id),
     }
 }
+
+/// Translate the NUL terminated string at `ptr` in current task,
+/// its pages faulted in as the scan reaches them
+fn translated_str(ptr: *const u8) -> Result<String, ()> {
+    let task = current_task().unwrap();
+    let mut inner = task.inner_exclusive_access();
+    inner.memory_set.translated_str(ptr)
+}
@@ -1,27 +1,143 @@
 //! Process management syscalls
 
 use crate::mm::{
-    translated_refmut, translated_str, 
-    MapPermission, VirtAddr, VPNRange, PageTable
+    translated_byte_buffer_checked, translated_byte_buffer_mut, UserBuffer,
+    MapPermission, VirtAddr, VPNRange, PageTable, FileBacking
 };
+use crate::mm::{copy_from_user, copy_to_user, shm_get, shm_remove, shm_segment, StepByOne, VirtPageNum};
+use crate::config::{PAGE_SIZE, TRAP_CONTEXT, USER_SHM_BASE, USER_STACK_SIZE};
 use crate::task::{
-    add_task, current_task, current_user_token, exit_current_and_run_next,
-    suspend_current_and_run_next, TaskStatus,
+    add_task, block_current_and_run_next, current_cwd, current_task, current_user_token,
+    exit_current_and_run_next, pid2task, power_off, suspend_current_and_run_next, wakeup_task,
+    SignalAction, SignalFlags, TaskStatus, INITPROC,
 };
 use crate::fs::{open_file, OpenFlags};
-use crate::timer::get_time_us;
+use super::translated_str;
+use crate::timer::{
+    add_timer, get_time, get_time_monotonic_ns, get_time_ms, get_time_ns, get_time_us,
+    to_clock_ticks,
+};
 use alloc::sync::Arc;
 use alloc::vec::Vec;
-use crate::config::MAX_SYSCALL_NUM;
+use crate::config::{MAX_FD_NUM, MAX_SYSCALL_NUM};
 use alloc::string::String;
 
 #[repr(C)]
-#[derive(Debug)]
+#[derive(Clone, Copy, Debug)]
 pub struct TimeVal {
     pub sec: usize,
     pub usec: usize,
 }
 
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+pub struct TimeSpec {
+    pub sec: usize,
+    pub nsec: usize,
+}
+
+/// CPU times of a process and of its reaped children, in clock ticks
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+pub struct Tms {
+    pub utime: usize,
+    pub stime: usize,
+    pub cutime: usize,
+    pub cstime: usize,
+}
+
+/// Memory and block device usage of a process, or of its reaped children
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Rusage {
+    /// page faults served
+    pub page_faults: u64,
+    /// frames mapped now, 0 for the children
+    pub resident_frames: u64,
+    /// the most frames mapped at once, for the children
+    /// that of the one which mapped the most
+    pub peak_frames: u64,
+    /// bytes read from the block device
+    pub block_read_bytes: u64,
+    /// bytes written to the block device
+    pub block_written_bytes: u64,
+}
+
+impl Rusage {
+    /// Add the usage of a reaped child
+    pub fn add_child(&mut self, child: &Rusage) {
+        self.page_faults += child.page_faults;
+        self.peak_frames = self.peak_frames.max(child.peak_frames);
+        self.block_read_bytes += child.block_read_bytes;
+        self.block_written_bytes += child.block_written_bytes;
+    }
+}
+
+/// The soft and the hard limit on a resource of a process
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+pub struct RLimit {
+    /// the limit enforced
+    pub cur: usize,
+    /// how far `cur` may be raised
+    pub max: usize,
+}
+
+/// Limit on the fds of a process, every fd opened is below it
+pub const RLIMIT_NOFILE: usize = 7;
+
+/// Bytes of every field of a `Utsname`, the NUL included
+const UTSNAME_LEN: usize = 65;
+
+/// Identity of the kernel and of the machine it runs on,
+/// every field a NUL terminated string
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct Utsname {
+    pub sysname: [u8; UTSNAME_LEN],
+    pub nodename: [u8; UTSNAME_LEN],
+    pub release: [u8; UTSNAME_LEN],
+    pub version: [u8; UTSNAME_LEN],
+    pub machine: [u8; UTSNAME_LEN],
+    pub domainname: [u8; UTSNAME_LEN],
+}
+
+impl Utsname {
+    /// The identity of this kernel
+    fn new() -> Self {
+        Self {
+            sysname: uts_field("rCore"),
+            nodename: uts_field("rcore"),
+            release: uts_field("0.6.0"),
+            version: uts_field("#1 SMP"),
+            machine: uts_field("riscv64"),
+            domainname: uts_field("(none)"),
+        }
+    }
+}
+
+/// A field of a `Utsname` holding `s`, cut short to leave room for the NUL
+fn uts_field(s: &str) -> [u8; UTSNAME_LEN] {
+    let mut field = [0u8; UTSNAME_LEN];
+    let len = s.len().min(UTSNAME_LEN - 1);
+    field[..len].copy_from_slice(&s.as_bytes()[..len]);
+    field
+}
+
+/// `getrusage` of the calling process
+pub const RUSAGE_SELF: isize = 0;
+/// `getrusage` of the children reaped, including what they reaped
+pub const RUSAGE_CHILDREN: isize = -1;
+
+/// Remove a shared memory segment
+pub const IPC_RMID: usize = 0;
+
+/// The time since boot, which stands in for the wall-clock time
+pub const CLOCK_REALTIME: usize = 0;
+/// The time since boot, never going backward
+pub const CLOCK_MONOTONIC: usize = 1;
+
+#[repr(C)]
 #[derive(Clone, Copy)]
 pub struct TaskInfo {
     pub status: TaskStatus,
@@ -35,6 +151,15 @@ pub fn sys_exit(exit_code: i32) -> ! {
     panic!("Unreachable in sys_exit!");
 }
 
+/// Write back the dirty blocks and power off, as a failure if `failure`
+/// is not 0. Only the initial process may, returns -1 for any other
+pub fn sys_shutdown(failure: usize) -> isize {
+    if !Arc::ptr_eq(&current_task().unwrap(), &INITPROC) {
+        return -1;
+    }
+    power_off(failure != 0)
+}
+
 /// current task gives up resources for other tasks
 pub fn sys_yield() -> isize {
     suspend_current_and_run_next();
@@ -45,6 +170,18 @@ pub fn sys_getpid() -> isize {
     current_task().unwrap().pid.0 as isize
 }
 
+/// Get the pid of the parent of current task. A task whose parent
+/// exits is handed to the initial process, whose pid it gets from then
+/// on. The initial process has no parent and gets 0
+pub fn sys_getppid() -> isize {
+    let task = current_task().unwrap();
+    let inner = task.inner_exclusive_access();
+    match inner.parent.as_ref().and_then(|parent| parent.upgrade()) {
+        Some(parent) => parent.getpid() as isize,
+        None => 0,
+    }
+}
+
 /// Syscall Fork which returns 0 for child process and child_pid for parent process
 pub fn sys_fork() -> isize {
     let current_task = current_task().unwrap();
@@ -61,23 +198,271 @@ pub fn sys_fork() -> isize {
 }
 
 /// Syscall Exec which accepts the elf path
-pub fn sys_exec(path: *const u8) -> isize {
-    let token = current_user_token();
-    let path = translated_str(token, path);
-    if let Some(app_inode) = open_file(path.as_str(), OpenFlags::RDONLY) {
+/// The strings of the null terminated array of string pointers `ptr`,
+/// none if `ptr` itself is null. Fails on a bad pointer or if the strings
+/// are too many to fit in a user stack
+fn translated_str_array(ptr: *const usize) -> Result<Vec<String>, ()> {
+    let mut strings = Vec::new();
+    if ptr.is_null() {
+        return Ok(strings);
+    }
+    let word = core::mem::size_of::<usize>();
+    let mut total_len = 0;
+    let mut va = ptr as usize;
+    let task = current_task().unwrap();
+    loop {
+        let mut bytes = [0u8; core::mem::size_of::<usize>()];
+        // the pointers are faulted in as the scan reaches them, like the strings
+        let mut inner = task.inner_exclusive_access();
+        inner.memory_set.fault_in(va, word, false);
+        let buffers = translated_byte_buffer_checked(inner.memory_set.token(), va as *const u8, word)?;
+        drop(inner);
+        UserBuffer::new(buffers).copy_to_slice(&mut bytes);
+        let str_ptr = usize::from_ne_bytes(bytes);
+        if str_ptr == 0 {
+            return Ok(strings);
+        }
+        let string = translated_str(str_ptr as *const u8)?;
+        total_len += string.len() + 1 + word;
+        if total_len > USER_STACK_SIZE {
+            return Err(());
+        }
+        strings.push(string);
+        va = va.checked_add(word).ok_or(())?;
+    }
+}
+
+/// Replace the program of current task with the one at `path`, passing it
+/// the null terminated arrays `argv` and `envp`, where null is an empty array
+pub fn sys_exec(path: *const u8, argv: *const usize, envp: *const usize) -> isize {
+    let path = match translated_str(path) {
+        Ok(path) => path,
+        Err(_) => return -1,
+    };
+    let args = match translated_str_array(argv) {
+        Ok(args) => args,
+        Err(_) => return -1,
+    };
+    let envs = match translated_str_array(envp) {
+        Ok(envs) => envs,
+        Err(_) => return -1,
+    };
+    if let Some(app_inode) = open_file(&current_cwd(), path.as_str(), OpenFlags::RDONLY) {
         let all_data = app_inode.read_all();
         let task = current_task().unwrap();
-        task.exec(all_data.as_slice());
-        0
+        if task.exec(all_data.as_slice(), &args, &envs) {
+            0
+        } else {
+            -1
+        }
     } else {
         -1
     }
 }
 
 
+/// Copy the environment of current task to `buf` as `NAME=value` strings,
+/// each ended by a NUL, as much as fits in `len` bytes. Returns the length
+/// of the whole environment, which is larger than `len` if it is cut short
+pub fn sys_environ(buf: *mut u8, len: usize) -> isize {
+    let token = current_user_token();
+    let task = current_task().unwrap();
+    let mut inner = task.inner_exclusive_access();
+    let mut bytes = Vec::new();
+    for env in inner.environ.iter() {
+        bytes.extend_from_slice(env.as_bytes());
+        bytes.push(0);
+    }
+    let copy_len = len.min(bytes.len());
+    inner.memory_set.fault_in(buf as usize, copy_len, true);
+    drop(inner);
+    let buffers = match translated_byte_buffer_mut(token, buf, copy_len) {
+        Ok(buffers) => buffers,
+        Err(_) => return -1,
+    };
+    UserBuffer::new(buffers).copy_from_slice(&bytes);
+    bytes.len() as isize
+}
+
+/// Set the environment variable `name` of current task to `value`, or
+/// remove it if `value` is null. The name must be non-empty without `=`,
+/// and the environment must still fit in a user stack
+pub fn sys_setenv(name: *const u8, value: *const u8) -> isize {
+    let name = match translated_str(name) {
+        Ok(name) if !name.is_empty() && !name.contains('=') => name,
+        _ => return -1,
+    };
+    let value = if value.is_null() {
+        None
+    } else {
+        match translated_str(value) {
+            Ok(value) => Some(value),
+            Err(_) => return -1,
+        }
+    };
+    let task = current_task().unwrap();
+    let mut inner = task.inner_exclusive_access();
+    let prefix = name + "=";
+    let others_len: usize = inner
+        .environ
+        .iter()
+        .filter(|env| !env.starts_with(prefix.as_str()))
+        .map(|env| env.len() + 1)
+        .sum();
+    if let Some(value) = value.as_ref() {
+        if others_len + prefix.len() + value.len() + 1 > USER_STACK_SIZE {
+            return -1;
+        }
+    }
+    inner.environ.retain(|env| !env.starts_with(prefix.as_str()));
+    if let Some(value) = value {
+        inner.environ.push(prefix + value.as_str());
+    }
+    0
+}
+
+/// Send signal `signum` to the task `pid`. A signal 0 only checks that the
+/// task exists. `SIGCONT` discards a pending `SIGSTOP` and vice versa
+pub fn sys_kill(pid: usize, signum: usize) -> isize {
+    let task = match pid2task(pid) {
+        Some(task) => task,
+        None => return -1,
+    };
+    if signum == 0 {
+        return 0;
+    }
+    let signal = match SignalFlags::from_signum(signum) {
+        Some(signal) => signal,
+        None => return -1,
+    };
+    let mut inner = task.inner_exclusive_access();
+    if signal == SignalFlags::SIGCONT {
+        inner.signals.remove(SignalFlags::SIGSTOP | SignalFlags::SIGTSTP);
+        // resumes even if it is caught or masked
+        inner.frozen = false;
+    } else if signal.intersects(SignalFlags::SIGSTOP | SignalFlags::SIGTSTP) {
+        inner.signals.remove(SignalFlags::SIGCONT);
+    }
+    inner.signals.insert(signal);
+    drop(inner);
+    // a sleeping task wakes up to handle it
+    wakeup_task(task);
+    0
+}
+
+/// Block current task for `ms` milliseconds, or just yield if it is 0.
+/// Returns -1 if a signal comes before that
+pub fn sys_sleep(ms: usize) -> isize {
+    if ms == 0 {
+        suspend_current_and_run_next();
+        return 0;
+    }
+    let expire_ms = get_time_ms() + ms;
+    loop {
+        if get_time_ms() >= expire_ms {
+            return 0;
+        }
+        let task = current_task().unwrap();
+        if task.inner_exclusive_access().has_deliverable_signals() {
+            return -1;
+        }
+        add_timer(expire_ms, task);
+        block_current_and_run_next();
+    }
+}
+
+/// Install `action` for signal `signum` unless it is null, and save
+/// the previous one to `old_action` unless it is null.
+/// The actions of `SIGKILL` and `SIGSTOP` can't be changed
+pub fn sys_sigaction(
+    signum: usize,
+    action: *const SignalAction,
+    old_action: *mut SignalAction,
+) -> isize {
+    let signal = match SignalFlags::from_signum(signum) {
+        Some(signal) if !SignalFlags::unmaskable().contains(signal) => signal,
+        _ => return -1,
+    };
+    let token = current_user_token();
+    let task = current_task().unwrap();
+    let mut inner = task.inner_exclusive_access();
+    let len = core::mem::size_of::<SignalAction>();
+    // copied in and out in whole, the actions may straddle two pages
+    let new_action = if action.is_null() {
+        None
+    } else {
+        inner.memory_set.fault_in(action as usize, len, false);
+        match copy_from_user(token, action) {
+            Ok(action) => Some(action),
+            Err(()) => return -1,
+        }
+    };
+    if !old_action.is_null() {
+        inner.memory_set.fault_in(old_action as usize, len, true);
+        // nothing is installed unless the old action is saved
+        if copy_to_user(token, old_action, inner.signal_actions[signum]).is_err() {
+            return -1;
+        }
+    }
+    if let Some(action) = new_action {
+        // the signal itself is always blocked while its handler runs
+        inner.signal_actions[signum] = SignalAction {
+            handler: action.handler,
+            mask: action.mask | signal,
+        };
+    }
+    0
+}
+
+/// Set the signals kept pending for current task, returns the old mask
+pub fn sys_sigprocmask(mask: u32) -> isize {
+    let task = current_task().unwrap();
+    let mut inner = task.inner_exclusive_access();
+    let old_mask = inner.signal_mask;
+    inner.signal_mask = SignalFlags::from_bits_truncate(mask);
+    old_mask.bits() as isize
+}
+
+/// Return from a user signal handler to the interrupted context
+pub fn sys_sigreturn() -> isize {
+    let task = current_task().unwrap();
+    let mut inner = task.inner_exclusive_access();
+    match inner.trap_ctx_backup.take() {
+        Some(backup) => {
+            inner.handling_sig = None;
+            *inner.get_trap_cx() = backup;
+            // the return value goes to a0, keep it as it was
+            backup.x[10] as isize
+        }
+        None => -1,
+    }
+}
+
+/// Return at once instead of waiting for a running child
+pub const WNOHANG: usize = 1;
+
+/// Wait for the child `pid`, or any child if it is -1, to exit and reap it.
+/// If there is not a child process whose pid is same as given, return -1.
+/// Else if there is a child process but it is still running, return 0 with
+/// `WNOHANG`, or wait until it exits without. The exit code is written to
+/// `exit_code_ptr` unless it is null, the child is left unreaped and -1
+/// returned if it can't be written there.
+pub fn sys_waitpid(pid: isize, exit_code_ptr: *mut i32, options: usize) -> isize {
+    if options & !WNOHANG != 0 {
+        return -1;
+    }
+    loop {
+        match try_waitpid(pid, exit_code_ptr) {
+            -2 if options & WNOHANG != 0 => return 0,
+            -2 => suspend_current_and_run_next(),
+            ret => return ret,
+        }
+    }
+}
+
 /// If there is not a child process whose pid is same as given, return -1.
 /// Else if there is a child process but it is still running, return -2.
-pub fn sys_waitpid(pid: isize, exit_code_ptr: *mut i32) -> isize {
+fn try_waitpid(pid: isize, exit_code_ptr: *mut i32) -> isize {
     let task = current_task().unwrap();
     // find a child process
 
@@ -97,14 +482,28 @@ pub fn sys_waitpid(pid: isize, exit_code_ptr: *mut i32) -> isize {
         // ++++ release child PCB
     });
     if let Some((idx, _)) = pair {
+        let exit_code = inner.children[idx].inner_exclusive_access().exit_code;
+        // the child stays unreaped unless its exit code is written
+        if !exit_code_ptr.is_null() {
+            let token = inner.memory_set.token();
+            inner.memory_set.fault_in(exit_code_ptr as usize, core::mem::size_of::<i32>(), true);
+            if copy_to_user(token, exit_code_ptr, exit_code).is_err() {
+                return -1;
+            }
+        }
         let child = inner.children.remove(idx);
         // confirm that child will be deallocated after removing from children list
         assert_eq!(Arc::strong_count(&child), 1);
         let found_pid = child.getpid();
+        let child_usage = child.usage();
         // ++++ temporarily access child TCB exclusively
-        let exit_code = child.inner_exclusive_access().exit_code;
+        let child_inner = child.inner_exclusive_access();
+        inner.cutime += child_inner.utime + child_inner.cutime;
+        inner.cstime += child_inner.stime + child_inner.cstime;
+        inner.children_usage.add_child(&child_usage);
+        inner.children_usage.add_child(&child_inner.children_usage);
+        drop(child_inner);
         // ++++ release child PCB
-        *translated_refmut(inner.memory_set.token(), exit_code_ptr) = exit_code;
         found_pid as isize
     } else {
         -2
@@ -113,31 +512,222 @@ pub fn sys_waitpid(pid: isize, exit_code_ptr: *mut i32) -> isize {
 }
 
 // YOUR JOB: 引入虚地址后重写 sys_get_time
-pub fn sys_get_time(_ts: *mut TimeVal, _tz: usize) -> isize {
+/// Write the time since boot to `_ts`, the timezone `_tz` is ignored.
+/// Returns -1 if `_ts` is misaligned or not mapped writable
+pub fn sys_gettimeofday(_ts: *mut TimeVal, _tz: usize) -> isize {
     let _us = get_time_us();
     let token = current_user_token();
-    let ts = translated_refmut(token, _ts);
-    unsafe {
-        *ts = TimeVal {
-            sec: _us / 1_000_000,
-            usec: _us % 1_000_000,
-        };
+    current_task()
+        .unwrap()
+        .inner_exclusive_access()
+        .memory_set
+        .fault_in(_ts as usize, core::mem::size_of::<TimeVal>(), true);
+    let tv = TimeVal {
+        sec: _us / 1_000_000,
+        usec: _us % 1_000_000,
+    };
+    // it may straddle two pages
+    copy_to_user(token, _ts, tv).map_or(-1, |_| 0)
+}
+
+/// Write the time of `clock` to `tp`, where it may straddle two pages.
+/// Returns -1 if `tp` is misaligned or not mapped writable
+pub fn sys_clock_gettime(clock: usize, tp: *mut TimeSpec) -> isize {
+    let ns = match clock {
+        CLOCK_REALTIME => get_time_ns(),
+        CLOCK_MONOTONIC => get_time_monotonic_ns(),
+        _ => return -1,
+    };
+    let token = current_user_token();
+    current_task()
+        .unwrap()
+        .inner_exclusive_access()
+        .memory_set
+        .fault_in(tp as usize, core::mem::size_of::<TimeSpec>(), true);
+    let ts = TimeSpec {
+        sec: (ns / 1_000_000_000) as usize,
+        nsec: (ns % 1_000_000_000) as usize,
+    };
+    match copy_to_user(token, tp, ts) {
+        Ok(()) => 0,
+        Err(()) => -1,
+    }
+}
+
+/// Write the CPU times of current process and of its reaped children
+/// to `buf`, returns the clock ticks since boot. Returns -1 if `buf`
+/// is misaligned or not mapped writable
+pub fn sys_times(buf: *mut Tms) -> isize {
+    let token = current_user_token();
+    let task = current_task().unwrap();
+    let mut inner = task.inner_exclusive_access();
+    // up to now, not just the last time the task left the kernel
+    inner.leave_kernel();
+    let tms = Tms {
+        utime: to_clock_ticks(inner.utime),
+        stime: to_clock_ticks(inner.stime),
+        cutime: to_clock_ticks(inner.cutime),
+        cstime: to_clock_ticks(inner.cstime),
+    };
+    inner.memory_set.fault_in(buf as usize, core::mem::size_of::<Tms>(), true);
+    drop(inner);
+    // it may straddle two pages
+    if copy_to_user(token, buf, tms).is_err() {
+        return -1;
+    }
+    to_clock_ticks(get_time()) as isize
+}
+
+/// Fill `usage` with the memory and block device usage of current task
+/// with `RUSAGE_SELF`, or of its reaped children with `RUSAGE_CHILDREN`.
+/// Returns -1 for any other `who`, or if `usage` is misaligned or
+/// not mapped writable
+pub fn sys_getrusage(who: isize, usage: *mut Rusage) -> isize {
+    let token = current_user_token();
+    let task = current_task().unwrap();
+    let rusage = match who {
+        RUSAGE_SELF => task.usage(),
+        RUSAGE_CHILDREN => task.inner_exclusive_access().children_usage,
+        _ => return -1,
+    };
+    let mut inner = task.inner_exclusive_access();
+    inner.memory_set.fault_in(usage as usize, core::mem::size_of::<Rusage>(), true);
+    drop(inner);
+    // it may straddle two pages
+    match copy_to_user(token, usage, rusage) {
+        Ok(()) => 0,
+        Err(()) => -1,
+    }
+}
+
+/// Fill `buf` with the identity of the kernel, where it may straddle
+/// two pages. Returns -1 if `buf` is not mapped writable
+pub fn sys_uname(buf: *mut Utsname) -> isize {
+    let token = current_user_token();
+    let task = current_task().unwrap();
+    let mut inner = task.inner_exclusive_access();
+    inner.memory_set.fault_in(buf as usize, core::mem::size_of::<Utsname>(), true);
+    drop(inner);
+    match copy_to_user(token, buf, Utsname::new()) {
+        Ok(()) => 0,
+        Err(()) => -1,
+    }
+}
+
+/// Fill `rlim` with the limits of current task on `resource`.
+/// Returns -1 for a resource other than `RLIMIT_NOFILE`
+pub fn sys_getrlimit(resource: usize, rlim: *mut RLimit) -> isize {
+    if resource != RLIMIT_NOFILE {
+        return -1;
+    }
+    let token = current_user_token();
+    let task = current_task().unwrap();
+    let mut inner = task.inner_exclusive_access();
+    let nofile = inner.nofile;
+    inner.memory_set.fault_in(rlim as usize, core::mem::size_of::<RLimit>(), true);
+    drop(inner);
+    match copy_to_user(token, rlim, nofile) {
+        Ok(()) => 0,
+        Err(()) => -1,
+    }
+}
+
+/// Set the limits of current task on `resource` to `rlim`, inherited by the
+/// children forked or spawned after. The soft limit can't go over the hard
+/// one, and only the initial process may raise the hard limit, never past
+/// `MAX_FD_NUM`. The fds already open stay open, the soft limit only
+/// applies to the next ones. Returns -1 if the limits are refused
+pub fn sys_setrlimit(resource: usize, rlim: *const RLimit) -> isize {
+    if resource != RLIMIT_NOFILE {
+        return -1;
+    }
+    let token = current_user_token();
+    let task = current_task().unwrap();
+    let mut inner = task.inner_exclusive_access();
+    inner.memory_set.fault_in(rlim as usize, core::mem::size_of::<RLimit>(), false);
+    let new = match copy_from_user(token, rlim) {
+        Ok(new) => new,
+        Err(()) => return -1,
+    };
+    if new.cur > new.max || new.max > MAX_FD_NUM {
+        return -1;
     }
+    if new.max > inner.nofile.max && !Arc::ptr_eq(&task, &INITPROC) {
+        return -1;
+    }
+    inner.nofile = new;
     0
 }
 
 // YOUR JOB: 引入虚地址后重写 sys_task_info
+/// Copy the status, syscall counts and running time in milliseconds
+/// of current task to `ti`
 pub fn sys_task_info(ti: *mut TaskInfo) -> isize {
-    -1
+    let token = current_user_token();
+    let task = current_task().unwrap();
+    let mut inner = task.inner_exclusive_access();
+    let len = core::mem::size_of::<TaskInfo>();
+    inner.memory_set.fault_in(ti as usize, len, true);
+    let mut info = TaskInfo {
+        status: inner.task_status,
+        syscall_times: [0; MAX_SYSCALL_NUM],
+        time: inner.running_time_us() / 1000,
+    };
+    info.syscall_times.copy_from_slice(&inner.syscall_times);
+    drop(inner);
+    // much larger than a Stat, it is likely to straddle two pages
+    let buffers = match translated_byte_buffer_mut(token, ti as *mut u8, len) {
+        Ok(buffers) => buffers,
+        Err(_) => return -1,
+    };
+    let bytes = unsafe {
+        core::slice::from_raw_parts(&info as *const TaskInfo as *const u8, len)
+    };
+    UserBuffer::new(buffers).copy_from_slice(bytes);
+    0
 }
 
 // YOUR JOB: 实现sys_set_priority，为任务添加优先级
+/// Set the priority of current task, which must be at least 2
 pub fn sys_set_priority(_prio: isize) -> isize {
-    -1
+    if _prio < 2 {
+        return -1;
+    }
+    let task = current_task().unwrap();
+    task.inner_exclusive_access().priority = _prio as usize;
+    _prio
 }
 
+/// Writes to the mapping go back to the file
+pub const MAP_SHARED: usize = 0x01;
+/// Writes to the mapping stay in the process
+pub const MAP_PRIVATE: usize = 0x02;
+/// The mapping is backed by zeroed memory instead of a file
+pub const MAP_ANONYMOUS: usize = 0x20;
+/// The anonymous mapping takes 2MiB megapages where they fit
+pub const MAP_HUGETLB: usize = 0x40000;
+
 // YOUR JOB: 扩展内核以实现 sys_mmap 和 sys_munmap
-pub fn sys_mmap(_start: usize, _len: usize, _port: usize) -> isize {
+/// Map `[start, start + len)` with the permission `port`. Without flags fresh
+/// memory is mapped at once and 0 returned, with `MAP_SHARED` or `MAP_PRIVATE`
+/// the file `fd` from `offset` is mapped page by page on the first access and
+/// `start` returned, so is zeroed memory with `MAP_PRIVATE | MAP_ANONYMOUS`,
+/// by the megapage where one fits with `MAP_HUGETLB` as well.
+/// Returns -1 on failure
+pub fn sys_mmap(
+    _start: usize,
+    _len: usize,
+    _port: usize,
+    flags: usize,
+    fd: usize,
+    offset: usize,
+) -> isize {
+    if flags & MAP_ANONYMOUS != 0 {
+        return mmap_anonymous(_start, _len, _port, flags);
+    }
+    if flags != 0 {
+        return mmap_file(_start, _len, _port, flags, fd, offset);
+    }
     let start_va = VirtAddr::from(_start);
     let end_va = VirtAddr::from(_start+_len);
     let task = current_task().unwrap();
@@ -153,17 +743,10 @@ pub fn sys_mmap(_start: usize, _len: usize, _port: usize) -> isize {
         return -1;
     }
     let vpn_range = VPNRange::new(start_va.floor(), end_va.ceil());
-    // check if mapped
-    for vpn in vpn_range {
-        match inner.memory_set.translate(vpn) {
-            Some(pte) => {
-                if pte.is_valid() {
-                    println!("already exist mapped page!");
-                    return -1;
-                }
-            },
-            None => (),
-        }
+    // check if mapped, pages not accessed yet count as well
+    if !inner.memory_set.is_free(vpn_range.get_start(), vpn_range.get_end()) {
+        println!("already exist mapped page!");
+        return -1;
     }
     // map
     let mut map_perm = MapPermission::U;
@@ -186,6 +769,73 @@ pub fn sys_mmap(_start: usize, _len: usize, _port: usize) -> isize {
     0
 }
 
+/// Whether a lazy mapping can be placed at `[start, start + len)` with `port`
+fn lazy_mmap_valid(start: usize, len: usize, port: usize) -> bool {
+    start != 0
+        && VirtAddr::from(start).aligned()
+        && len != 0
+        && port & !0x7 == 0
+        && port & 0x7 != 0
+}
+
+fn mmap_anonymous(start: usize, len: usize, port: usize, flags: usize) -> isize {
+    if !lazy_mmap_valid(start, len, port) || flags & !MAP_HUGETLB != MAP_PRIVATE | MAP_ANONYMOUS {
+        return -1;
+    }
+    let start_va = VirtAddr::from(start);
+    let end_va = VirtAddr::from(start + len);
+    let task = current_task().unwrap();
+    let mut inner = task.inner_exclusive_access();
+    if !inner.memory_set.is_free(start_va.floor(), end_va.ceil()) {
+        return -1;
+    }
+    let map_perm = MapPermission::U | MapPermission::from_bits((port as u8) << 1).unwrap();
+    if flags & MAP_HUGETLB != 0 {
+        inner.memory_set.insert_huge_area(start_va, end_va, map_perm);
+    } else {
+        inner.memory_set.insert_lazy_area(start_va, end_va, map_perm, None);
+    }
+    start as isize
+}
+
+fn mmap_file(start: usize, len: usize, port: usize, flags: usize, fd: usize, offset: usize) -> isize {
+    if !lazy_mmap_valid(start, len, port) || offset % PAGE_SIZE != 0 {
+        return -1;
+    }
+    let start_va = VirtAddr::from(start);
+    let end_va = VirtAddr::from(start + len);
+    let shared = match flags {
+        MAP_SHARED => true,
+        MAP_PRIVATE => false,
+        _ => return -1,
+    };
+    let task = current_task().unwrap();
+    let mut inner = task.inner_exclusive_access();
+    let file = match inner.fd_table.get(fd) {
+        Some(Some(file)) => file.clone(),
+        _ => return -1,
+    };
+    // a shared mapping writes with the permission of the file
+    if !file.readable() || (shared && port & 0x2 != 0 && !file.writable()) {
+        return -1;
+    }
+    // only files read at an offset can be mapped
+    if file.read_at(offset, &mut []) < 0 {
+        return -1;
+    }
+    if !inner.memory_set.is_free(start_va.floor(), end_va.ceil()) {
+        return -1;
+    }
+    let map_perm = MapPermission::U | MapPermission::from_bits((port as u8) << 1).unwrap();
+    inner.memory_set.insert_lazy_area(
+        start_va,
+        end_va,
+        map_perm,
+        Some(FileBacking { file, offset, shared }),
+    );
+    start as isize
+}
+
 pub fn sys_munmap(_start: usize, _len: usize) -> isize {
     let start_va = VirtAddr::from(_start);
     let end_va = VirtAddr::from(_start+_len);
@@ -197,43 +847,163 @@ pub fn sys_munmap(_start: usize, _len: usize) -> isize {
         println!("va aligned fail!");
         return -1;
     }
-    let vpn_range = VPNRange::new(start_va.floor(), end_va.ceil());
-    // check unmapped
-    for vpn in vpn_range {
-        match inner.memory_set.translate(vpn) {
-            Some(pte) => {
-                if !pte.is_valid() {
-                    println!("unmapped!");
-                    return -1;
-                }
-            },
-            None => {
-                println!("sys_mmap fail!");
-                return -1;
-            },
+    // unmap only the pages asked for, pages not accessed yet are mapped as well
+    if !inner.memory_set.unmap_range(start_va.floor(), end_va.ceil()) {
+        println!("unmapped!");
+        return -1;
+    }
+    0
+}
+
+/// The id of the shared memory segment of `key` with at least `size`
+/// bytes, created if there is none or `key` is `IPC_PRIVATE`
+pub fn sys_shmget(key: usize, size: usize) -> isize {
+    let pages = match size.checked_add(PAGE_SIZE - 1) {
+        Some(end) => end / PAGE_SIZE,
+        None => return -1,
+    };
+    match shm_get(key, pages) {
+        Some(id) => id as isize,
+        None => -1,
+    }
+}
+
+/// Attach the shared memory segment `id` at the lowest free address
+/// from `USER_SHM_BASE` on, returns the address
+pub fn sys_shmat(id: usize) -> isize {
+    let segment = match shm_segment(id) {
+        Some(segment) => segment,
+        None => return -1,
+    };
+    let pages = segment.frames().len();
+    let task = current_task().unwrap();
+    let mut inner = task.inner_exclusive_access();
+    let mut start_vpn = VirtAddr::from(USER_SHM_BASE).floor();
+    let end_vpn = VirtAddr::from(TRAP_CONTEXT).floor();
+    while start_vpn.0 + pages <= end_vpn.0 {
+        let seg_end = VirtPageNum(start_vpn.0 + pages);
+        if inner.memory_set.is_free(start_vpn, seg_end) {
+            inner.memory_set.attach_shm(start_vpn, segment);
+            return VirtAddr::from(start_vpn).0 as isize;
         }
+        start_vpn.step();
     }
-    // unmap
-    for vpn in vpn_range {
-        inner.memory_set.remove_area_with_start_vpn(vpn);
+    -1
+}
+
+/// Detach the shared memory segment attached at `addr`
+pub fn sys_shmdt(addr: usize) -> isize {
+    let start_va = VirtAddr::from(addr);
+    if !start_va.aligned() {
+        return -1;
+    }
+    let task = current_task().unwrap();
+    let mut inner = task.inner_exclusive_access();
+    if inner.memory_set.detach_shm(start_va.floor()) {
+        0
+    } else {
+        -1
+    }
+}
+
+/// Control the shared memory segment `id`, only `IPC_RMID` is supported,
+/// which removes it once every process detaches it
+pub fn sys_shmctl(id: usize, cmd: usize) -> isize {
+    if cmd != IPC_RMID {
+        return -1;
+    }
+    if shm_remove(id) {
+        0
+    } else {
+        -1
+    }
+}
+
+/// Change the permission of the pages in `[addr, addr + len)` to `prot`,
+/// the range must be page aligned and mapped
+pub fn sys_mprotect(addr: usize, len: usize, prot: usize) -> isize {
+    let start_va = VirtAddr::from(addr);
+    let end_va = VirtAddr::from(addr + len);
+    if !start_va.aligned() || (prot & !0x7 != 0) || (prot & 0x7 == 0) {
+        return -1;
+    }
+    let map_perm = MapPermission::U | MapPermission::from_bits((prot as u8) << 1).unwrap();
+    let task = current_task().unwrap();
+    let mut inner = task.inner_exclusive_access();
+    if inner.memory_set.mprotect(start_va.floor(), end_va.ceil(), map_perm) {
+        0
+    } else {
+        -1
+    }
+}
+
+/// Expect the pages to be accessed soon, reading them ahead
+pub const MADV_WILLNEED: usize = 3;
+/// Expect the pages not to be accessed soon, dropping their frames
+pub const MADV_DONTNEED: usize = 4;
+
+/// Advise how the pages in `[addr, addr + len)` are going to be used.
+/// With `MADV_DONTNEED` the frames of the pages given frames on access
+/// are freed, so that they read as zeros or from the file again.
+/// With `MADV_WILLNEED` the pages read from a file or from swap are read
+/// ahead. Returns -1 if any of the pages is not mapped
+pub fn sys_madvise(addr: usize, len: usize, advice: usize) -> isize {
+    let start_va = VirtAddr::from(addr);
+    let end_va = match addr.checked_add(len) {
+        Some(end) => VirtAddr::from(end),
+        None => return -1,
+    };
+    if !start_va.aligned() {
+        return -1;
+    }
+    let task = current_task().unwrap();
+    let mut inner = task.inner_exclusive_access();
+    if !inner.memory_set.user_mapped(start_va.floor(), end_va.ceil()) {
+        return -1;
+    }
+    match advice {
+        MADV_DONTNEED => inner.memory_set.discard(start_va.floor(), end_va.ceil()),
+        MADV_WILLNEED => inner.memory_set.prefetch(start_va.floor(), end_va.ceil()),
+        _ => return -1,
     }
     0
 }
 
+/// Move the program break to `addr`, returns the new break,
+/// the current one if `addr` is 0, or -1 on failure
+pub fn sys_brk(addr: usize) -> isize {
+    let task = current_task().unwrap();
+    let mut inner = task.inner_exclusive_access();
+    if addr == 0 {
+        return inner.memory_set.brk() as isize;
+    }
+    if inner.memory_set.set_brk(addr) {
+        addr as isize
+    } else {
+        -1
+    }
+}
+
 //
 // YOUR JOB: 实现 sys_spawn 系统调用
 // ALERT: 注意在实现 SPAWN 时不需要复制父进程地址空间，SPAWN != FORK + EXEC 
 pub fn sys_spawn(_path: *const u8) -> isize {
-    let token = current_user_token();
-    let path = translated_str(token, _path);
-    if let Some(app_inode) = open_file(path.as_str(), OpenFlags::RDONLY) {
+    let path = match translated_str(_path) {
+        Ok(path) => path,
+        Err(_) => return -1,
+    };
+    if let Some(app_inode) = open_file(&current_cwd(), path.as_str(), OpenFlags::RDONLY) {
         let all_data = app_inode.read_all();
         let current_task = current_task().unwrap();
 
-        let new_task = current_task.spawn(all_data.as_slice());
-        let pid = new_task.pid.0;
-        add_task(new_task);
-        pid as isize
+        match current_task.spawn(all_data.as_slice()) {
+            Some(new_task) => {
+                let pid = new_task.pid.0;
+                add_task(new_task);
+                pid as isize
+            }
+            None => -1,
+        }
     } else {
         -1
     }
@@ -0,0 +1,188 @@
+use crate::sync::{
+    acquire_resource, add_resource, cancel_request, release_resource, request_resource, Condvar,
+    Mutex, MutexBlocking, MutexSpin, Semaphore,
+};
+use crate::task::current_task;
+use alloc::sync::Arc;
+
+/// Returned when taking a resource could deadlock
+const EDEADLOCK: isize = -0xDEAD;
+
+/// Create a mutex, blocking the tasks waiting for it if `blocking`
+/// or yielding otherwise, and return its id
+pub fn sys_mutex_create(blocking: bool) -> isize {
+    let task = current_task().unwrap();
+    let mut inner = task.inner_exclusive_access();
+    let mutex: Arc<dyn Mutex> = if blocking {
+        Arc::new(MutexBlocking::new())
+    } else {
+        Arc::new(MutexSpin::new())
+    };
+    add_resource(Arc::as_ptr(&mutex) as *const () as usize, 1);
+    if let Some(id) = inner.mutex_list.iter().position(|mutex| mutex.is_none()) {
+        inner.mutex_list[id] = Some(mutex);
+        id as isize
+    } else {
+        inner.mutex_list.push(Some(mutex));
+        inner.mutex_list.len() as isize - 1
+    }
+}
+
+/// Take mutex `mutex_id`, waiting while another task holds it.
+/// Returns -1 if there is no such mutex or a signal comes first,
+/// or -0xDEAD if it could deadlock and the detection is enabled
+pub fn sys_mutex_lock(mutex_id: usize) -> isize {
+    let mutex = match get_mutex(mutex_id) {
+        Some(mutex) => mutex,
+        None => return -1,
+    };
+    acquire(Arc::as_ptr(&mutex) as *const () as usize, || mutex.lock())
+}
+
+/// Release mutex `mutex_id`, returns -1 if there is no such mutex
+pub fn sys_mutex_unlock(mutex_id: usize) -> isize {
+    let mutex = match get_mutex(mutex_id) {
+        Some(mutex) => mutex,
+        None => return -1,
+    };
+    mutex.unlock();
+    release(Arc::as_ptr(&mutex) as *const () as usize);
+    0
+}
+
+fn get_mutex(mutex_id: usize) -> Option<Arc<dyn Mutex>> {
+    let task = current_task().unwrap();
+    let inner = task.inner_exclusive_access();
+    inner.mutex_list.get(mutex_id)?.clone()
+}
+
+/// Create a semaphore with `res_count` resources and return its id,
+/// returns -1 if `res_count` is negative
+pub fn sys_semaphore_create(res_count: isize) -> isize {
+    if res_count < 0 {
+        return -1;
+    }
+    let task = current_task().unwrap();
+    let mut inner = task.inner_exclusive_access();
+    let semaphore = Arc::new(Semaphore::new(res_count as usize));
+    add_resource(Arc::as_ptr(&semaphore) as usize, res_count as usize);
+    if let Some(id) = inner.semaphore_list.iter().position(|sem| sem.is_none()) {
+        inner.semaphore_list[id] = Some(semaphore);
+        id as isize
+    } else {
+        inner.semaphore_list.push(Some(semaphore));
+        inner.semaphore_list.len() as isize - 1
+    }
+}
+
+/// Release a resource of semaphore `sem_id`, returns -1 if there is no such semaphore
+pub fn sys_semaphore_up(sem_id: usize) -> isize {
+    let semaphore = match get_semaphore(sem_id) {
+        Some(semaphore) => semaphore,
+        None => return -1,
+    };
+    semaphore.up();
+    release(Arc::as_ptr(&semaphore) as usize);
+    0
+}
+
+/// Take a resource of semaphore `sem_id`, waiting while there is none.
+/// Returns -1 if there is no such semaphore or a signal comes first,
+/// or -0xDEAD if it could deadlock and the detection is enabled
+pub fn sys_semaphore_down(sem_id: usize) -> isize {
+    let semaphore = match get_semaphore(sem_id) {
+        Some(semaphore) => semaphore,
+        None => return -1,
+    };
+    acquire(Arc::as_ptr(&semaphore) as usize, || semaphore.down())
+}
+
+fn get_semaphore(sem_id: usize) -> Option<Arc<Semaphore>> {
+    let task = current_task().unwrap();
+    let inner = task.inner_exclusive_access();
+    inner.semaphore_list.get(sem_id)?.clone()
+}
+
+/// Create a condition variable and return its id
+pub fn sys_condvar_create() -> isize {
+    let task = current_task().unwrap();
+    let mut inner = task.inner_exclusive_access();
+    let condvar = Arc::new(Condvar::new());
+    if let Some(id) = inner.condvar_list.iter().position(|condvar| condvar.is_none()) {
+        inner.condvar_list[id] = Some(condvar);
+        id as isize
+    } else {
+        inner.condvar_list.push(Some(condvar));
+        inner.condvar_list.len() as isize - 1
+    }
+}
+
+/// Wake a task waiting on condition variable `condvar_id`,
+/// returns -1 if there is no such condition variable
+pub fn sys_condvar_signal(condvar_id: usize) -> isize {
+    let condvar = match get_condvar(condvar_id) {
+        Some(condvar) => condvar,
+        None => return -1,
+    };
+    condvar.signal();
+    0
+}
+
+/// Release mutex `mutex_id` and wait on condition variable `condvar_id`
+/// until signaled, then take the mutex again. Returns -1 if either of them
+/// doesn't exist or a signal comes first, the mutex is not held then
+pub fn sys_condvar_wait(condvar_id: usize, mutex_id: usize) -> isize {
+    let (condvar, mutex) = match (get_condvar(condvar_id), get_mutex(mutex_id)) {
+        (Some(condvar), Some(mutex)) => (condvar, mutex),
+        _ => return -1,
+    };
+    let res = Arc::as_ptr(&mutex) as *const () as usize;
+    release(res);
+    if condvar.wait(mutex) {
+        acquire_resource(current_task().unwrap().getpid(), res);
+        0
+    } else {
+        -1
+    }
+}
+
+fn get_condvar(condvar_id: usize) -> Option<Arc<Condvar>> {
+    let task = current_task().unwrap();
+    let inner = task.inner_exclusive_access();
+    inner.condvar_list.get(condvar_id)?.clone()
+}
+
+/// Turn deadlock detection of current process on if `enabled` is 1
+/// or off if it is 0, returns -1 for anything else
+pub fn sys_enable_deadlock_detect(enabled: usize) -> isize {
+    let task = current_task().unwrap();
+    let mut inner = task.inner_exclusive_access();
+    match enabled {
+        0 => inner.deadlock_detect = false,
+        1 => inner.deadlock_detect = true,
+        _ => return -1,
+    }
+    0
+}
+
+/// Take a unit of resource `res` with `take`, refusing it first
+/// if it could deadlock and the detection is enabled
+fn acquire(res: usize, take: impl FnOnce() -> bool) -> isize {
+    let task = current_task().unwrap();
+    let pid = task.getpid();
+    let check = task.inner_exclusive_access().deadlock_detect;
+    if !request_resource(pid, res, check) {
+        return EDEADLOCK;
+    }
+    if take() {
+        acquire_resource(pid, res);
+        0
+    } else {
+        cancel_request(pid, res);
+        -1
+    }
+}
+
+fn release(res: usize) {
+    release_resource(current_task().unwrap().getpid(), res);
+}
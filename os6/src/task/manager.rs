@@ -6,7 +6,7 @@
 
 use super::TaskControlBlock;
 use crate::sync::UPSafeCell;
-use alloc::collections::VecDeque;
+use alloc::collections::{BTreeMap, VecDeque};
 use alloc::sync::Arc;
 use lazy_static::*;
 
@@ -14,8 +14,15 @@ pub struct TaskManager {
     ready_queue: VecDeque<Arc<TaskControlBlock>>,
 }
 
-// YOUR JOB: FIFO->Stride
-/// A simple FIFO scheduler.
+/// Whether stride `a` is behind stride `b`. Strides of runnable tasks are
+/// never more than `BIG_STRIDE / 2` apart, so the wrapped difference tells
+/// even after one of them overflows
+fn stride_before(a: u32, b: u32) -> bool {
+    (a.wrapping_sub(b) as i32) < 0
+}
+
+/// A stride scheduler, the task with the smallest stride runs next
+/// and ties are broken in FIFO order.
 impl TaskManager {
     pub fn new() -> Self {
         Self {
@@ -26,9 +33,42 @@ impl TaskManager {
     pub fn add(&mut self, task: Arc<TaskControlBlock>) {
         self.ready_queue.push_back(task);
     }
-    /// Take a process out of the ready queue
+    /// Add a process woken up to ready queue. Its stride is lifted to the
+    /// smallest one in the queue, so that it neither gets the CPU for long
+    /// after blocking nor falls more than `BIG_STRIDE / 2` behind
+    pub fn add_woken(&mut self, task: Arc<TaskControlBlock>) {
+        let min_stride = self
+            .ready_queue
+            .iter()
+            .map(|task| task.inner_exclusive_access().stride)
+            .fold(None, |min: Option<u32>, stride| match min {
+                Some(min) if !stride_before(stride, min) => Some(min),
+                _ => Some(stride),
+            });
+        if let Some(min_stride) = min_stride {
+            let mut inner = task.inner_exclusive_access();
+            if stride_before(inner.stride, min_stride) {
+                inner.stride = min_stride;
+            }
+        }
+        self.ready_queue.push_back(task);
+    }
+    /// Take the process with the smallest stride out of the ready queue
     pub fn fetch(&mut self) -> Option<Arc<TaskControlBlock>> {
-        self.ready_queue.pop_front()
+        let mut next = 0;
+        let mut min_stride = self.ready_queue.front()?.inner_exclusive_access().stride;
+        for (i, task) in self.ready_queue.iter().enumerate().skip(1) {
+            let stride = task.inner_exclusive_access().stride;
+            if stride_before(stride, min_stride) {
+                next = i;
+                min_stride = stride;
+            }
+        }
+        let task = self.ready_queue.remove(next)?;
+        let mut inner = task.inner_exclusive_access();
+        inner.stride = inner.stride.wrapping_add(inner.pass());
+        drop(inner);
+        Some(task)
     }
 }
 
@@ -36,12 +76,32 @@ lazy_static! {
     /// TASK_MANAGER instance through lazy_static!
     pub static ref TASK_MANAGER: UPSafeCell<TaskManager> =
         unsafe { UPSafeCell::new(TaskManager::new()) };
+    /// Every task not exited yet by pid, ready or not
+    pub static ref PID2TCB: UPSafeCell<BTreeMap<usize, Arc<TaskControlBlock>>> =
+        unsafe { UPSafeCell::new(BTreeMap::new()) };
 }
 
 pub fn add_task(task: Arc<TaskControlBlock>) {
     TASK_MANAGER.exclusive_access().add(task);
 }
 
+pub fn add_woken_task(task: Arc<TaskControlBlock>) {
+    TASK_MANAGER.exclusive_access().add_woken(task);
+}
+
 pub fn fetch_task() -> Option<Arc<TaskControlBlock>> {
     TASK_MANAGER.exclusive_access().fetch()
 }
+
+/// Find a task not exited yet by its pid
+pub fn pid2task(pid: usize) -> Option<Arc<TaskControlBlock>> {
+    PID2TCB.exclusive_access().get(&pid).cloned()
+}
+
+pub fn insert_into_pid2task(pid: usize, task: Arc<TaskControlBlock>) {
+    PID2TCB.exclusive_access().insert(pid, task);
+}
+
+pub fn remove_from_pid2task(pid: usize) {
+    PID2TCB.exclusive_access().remove(&pid);
+}
@@ -13,6 +13,7 @@ mod context;
 mod manager;
 mod pid;
 mod processor;
+mod signal;
 mod switch;
 #[allow(clippy::module_inception)]
 mod task;
@@ -25,15 +26,21 @@ use crate::mm::VirtAddr;
 use crate::mm::MapPermission;
 use crate::config::PAGE_SIZE;
 use crate::timer::get_time_us;
+use crate::sbi::shutdown;
 pub use crate::syscall::process::TaskInfo;
-use crate::fs::{open_file, OpenFlags};
+use crate::fs::{open_file, sync, OpenFlags, ROOT_INODE};
+use crate::sync::remove_task;
+use core::sync::atomic::Ordering;
 pub use task::{TaskControlBlock, TaskStatus};
 
 pub use context::TaskContext;
-pub use manager::add_task;
+pub use manager::{add_task, pid2task};
+use manager::{add_woken_task, insert_into_pid2task, remove_from_pid2task};
+pub use signal::{handle_signals, SignalAction, SignalFlags, MAX_SIG};
 pub use pid::{pid_alloc, KernelStack, PidHandle};
 pub use processor::{
-    current_task, current_trap_cx, current_user_token, run_tasks, schedule, take_current_task,
+    current_cwd, current_task, current_trap_cx, current_user_token, run_tasks, schedule,
+    take_current_task,
 };
 
 /// Make current task suspended and switch to the next task
@@ -44,6 +51,7 @@ pub fn suspend_current_and_run_next() {
     // ---- access current TCB exclusively
     let mut task_inner = task.inner_exclusive_access();
     let task_cx_ptr = &mut task_inner.task_cx as *mut TaskContext;
+    task_inner.stop_running();
     // Change status to Ready
     task_inner.task_status = TaskStatus::Ready;
     drop(task_inner);
@@ -55,17 +63,60 @@ pub fn suspend_current_and_run_next() {
     schedule(task_cx_ptr);
 }
 
+/// Block current task until [`wakeup_task`] is called on it
+/// and switch to the next task
+pub fn block_current_and_run_next() {
+    let task = take_current_task().unwrap();
+    let mut task_inner = task.inner_exclusive_access();
+    let task_cx_ptr = &mut task_inner.task_cx as *mut TaskContext;
+    task_inner.stop_running();
+    task_inner.task_status = TaskStatus::Blocked;
+    drop(task_inner);
+    // kept alive by whoever is going to wake it up
+    drop(task);
+    schedule(task_cx_ptr);
+}
+
+/// Put a blocked task back to the ready queue, does nothing and
+/// returns false if it is not blocked any more
+pub fn wakeup_task(task: Arc<TaskControlBlock>) -> bool {
+    let mut task_inner = task.inner_exclusive_access();
+    if task_inner.task_status != TaskStatus::Blocked {
+        return false;
+    }
+    task_inner.task_status = TaskStatus::Ready;
+    drop(task_inner);
+    add_woken_task(task);
+    true
+}
+
+/// Write back the dirty blocks, then power off, telling the
+/// machine whether it is a failure
+pub fn power_off(failure: bool) -> ! {
+    sync();
+    shutdown(failure)
+}
+
 /// Exit current task, recycle process resources and switch to the next task
 pub fn exit_current_and_run_next(exit_code: i32) {
     // take from Processor
     let task = take_current_task().unwrap();
+    // nobody is left to take in and reap the orphans of the initial process
+    if Arc::ptr_eq(&task, &INITPROC) {
+        println!("[kernel] Initial process exited with code {}, shutting down", exit_code);
+        power_off(exit_code != 0);
+    }
     // **** access current TCB exclusively
     let mut inner = task.inner_exclusive_access();
     // Change status to Zombie
     inner.task_status = TaskStatus::Zombie;
+    // no more signals can be sent to it
+    remove_from_pid2task(task.getpid());
     // Record exit code
     inner.exit_code = exit_code;
-    // do not move to its parent but under initproc
+    inner.stop_running();
+    // do not move to its parent but under initproc, which reaps
+    // the children, zombies and all, as it reaps its own
 
     // ++++++ access initproc TCB exclusively
     {
@@ -78,9 +129,18 @@ pub fn exit_current_and_run_next(exit_code: i32) {
     // ++++++ release parent PCB
 
     inner.children.clear();
+    remove_task(task.getpid());
     // deallocate user space
     inner.memory_set.recycle_data_pages();
+    // the files are closed now instead of when the zombie is reaped,
+    // giving up the locks on them
+    let fd_table = core::mem::take(&mut inner.fd_table);
+    inner.fd_cloexec.clear();
     drop(inner);
+    drop(fd_table);
+    // the mutexes held go to their waiters, and the process no longer
+    // keeps its share of them, which could keep it from being reaped
+    task.release_sync_objects();
     // **** release current PCB
     // drop task manually to maintain rc correctly
     drop(task);
@@ -95,12 +155,42 @@ lazy_static! {
     /// the name "initproc" may be changed to any other app name like "usertests",
     /// but we have user_shell, so we don't need to change it.
     pub static ref INITPROC: Arc<TaskControlBlock> = Arc::new({
-        let inode = open_file("ch6b_initproc", OpenFlags::RDONLY).unwrap();
+        let inode = open_file(&ROOT_INODE, "ch6b_initproc", OpenFlags::RDONLY).unwrap();
         let v = inode.read_all();
-        TaskControlBlock::new(v.as_slice())
+        TaskControlBlock::new(v.as_slice()).unwrap()
     });
 }
 
+/// Count an invocation of syscall `id` by current task
+pub fn count_current_syscall(id: usize) {
+    if let Some(task) = current_task() {
+        task.inner_exclusive_access().count_syscall(id);
+    }
+}
+
+/// Charge the time current task ran in user mode, as it traps into the kernel
+pub fn current_enter_kernel() {
+    if let Some(task) = current_task() {
+        task.inner_exclusive_access().enter_kernel();
+    }
+}
+
+/// Charge the time current task ran in the kernel, as it returns to user mode
+pub fn current_leave_kernel() {
+    if let Some(task) = current_task() {
+        task.inner_exclusive_access().leave_kernel();
+    }
+}
+
+/// Charge the bytes moved to and from the block device to current task
+pub fn account_block_io(read: usize, written: usize) {
+    if let Some(task) = current_task() {
+        task.block_io.read_bytes.fetch_add(read, Ordering::Relaxed);
+        task.block_io.written_bytes.fetch_add(written, Ordering::Relaxed);
+    }
+}
+
 pub fn add_initproc() {
+    insert_into_pid2task(INITPROC.getpid(), INITPROC.clone());
     add_task(INITPROC.clone());
 }
@@ -6,11 +6,13 @@
 
 
 use super::__switch;
-use super::{fetch_task, TaskStatus};
+use super::fetch_task;
 use super::{TaskContext, TaskControlBlock};
 use crate::sync::UPSafeCell;
+use crate::timer::check_timer;
 use crate::trap::TrapContext;
 use alloc::sync::Arc;
+use easy_fs::Inode;
 use lazy_static::*;
 
 /// Processor management structure
@@ -56,7 +58,7 @@ pub fn run_tasks() {
             // access coming task TCB exclusively
             let mut task_inner = task.inner_exclusive_access();
             let next_task_cx_ptr = &task_inner.task_cx as *const TaskContext;
-            task_inner.task_status = TaskStatus::Running;
+            task_inner.start_running();
             drop(task_inner);
             // release coming task TCB manually
             processor.current = Some(task);
@@ -65,6 +67,10 @@ pub fn run_tasks() {
             unsafe {
                 __switch(idle_task_cx_ptr, next_task_cx_ptr);
             }
+        } else {
+            drop(processor);
+            // every task may be sleeping, no timer interrupt comes in the kernel
+            check_timer();
         }
     }
 }
@@ -86,6 +92,11 @@ pub fn current_user_token() -> usize {
     token
 }
 
+/// Get the working directory of current task
+pub fn current_cwd() -> Arc<Inode> {
+    current_task().unwrap().inner_exclusive_access().cwd.clone()
+}
+
 /// Get the mutable reference to trap context of current task
 pub fn current_trap_cx() -> &'static mut TrapContext {
     current_task()
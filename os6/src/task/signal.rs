@@ -0,0 +1,187 @@
+//! Signals and their default actions
+//!
+//! A signal sent by [`crate::syscall::process::sys_kill`] stays pending in the
+//! target task until it is about to return to user mode, where
+//! [`handle_signals`] carries out the default action of each signal not masked,
+//! or runs the handler installed by [`crate::syscall::process::sys_sigaction`].
+
+use super::{current_task, exit_current_and_run_next, suspend_current_and_run_next};
+use bitflags::*;
+
+/// The largest signal number
+pub const MAX_SIG: usize = 31;
+/// Take the default action of a signal
+pub const SIG_DFL: usize = 0;
+/// Ignore a signal
+pub const SIG_IGN: usize = 1;
+/// Room left below the interrupted user stack for a handler
+const SIGNAL_FRAME_SIZE: usize = 128;
+
+bitflags! {
+    /// A set of signals, signal `n` is bit `n`
+    pub struct SignalFlags: u32 {
+        const SIGHUP    = 1 << 1;
+        const SIGINT    = 1 << 2;
+        const SIGQUIT   = 1 << 3;
+        const SIGILL    = 1 << 4;
+        const SIGTRAP   = 1 << 5;
+        const SIGABRT   = 1 << 6;
+        const SIGBUS    = 1 << 7;
+        const SIGFPE    = 1 << 8;
+        const SIGKILL   = 1 << 9;
+        const SIGUSR1   = 1 << 10;
+        const SIGSEGV   = 1 << 11;
+        const SIGUSR2   = 1 << 12;
+        const SIGPIPE   = 1 << 13;
+        const SIGALRM   = 1 << 14;
+        const SIGTERM   = 1 << 15;
+        const SIGSTKFLT = 1 << 16;
+        const SIGCHLD   = 1 << 17;
+        const SIGCONT   = 1 << 18;
+        const SIGSTOP   = 1 << 19;
+        const SIGTSTP   = 1 << 20;
+        const SIGTTIN   = 1 << 21;
+        const SIGTTOU   = 1 << 22;
+        const SIGURG    = 1 << 23;
+        const SIGXCPU   = 1 << 24;
+        const SIGXFSZ   = 1 << 25;
+        const SIGVTALRM = 1 << 26;
+        const SIGPROF   = 1 << 27;
+        const SIGWINCH  = 1 << 28;
+        const SIGIO     = 1 << 29;
+        const SIGPWR    = 1 << 30;
+        const SIGSYS    = 1 << 31;
+    }
+}
+
+impl SignalFlags {
+    /// The set holding only signal `signum`, None if there is no such signal
+    pub fn from_signum(signum: usize) -> Option<Self> {
+        if signum == 0 || signum > MAX_SIG {
+            return None;
+        }
+        Self::from_bits(1 << signum)
+    }
+    /// Signals that can neither be masked nor handled by the task
+    pub fn unmaskable() -> Self {
+        Self::SIGKILL | Self::SIGSTOP
+    }
+}
+
+/// What to do when a signal is delivered, shared with the user
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+pub struct SignalAction {
+    /// Address of the user handler, or `SIG_DFL` / `SIG_IGN`
+    pub handler: usize,
+    /// Signals blocked while the handler runs
+    pub mask: SignalFlags,
+}
+
+impl Default for SignalAction {
+    fn default() -> Self {
+        Self {
+            handler: SIG_DFL,
+            mask: SignalFlags::empty(),
+        }
+    }
+}
+
+/// Run the user handler of a pending signal `signum` on return to user mode,
+/// the interrupted trap context is saved until `sys_sigreturn`
+fn call_user_handler(signum: usize, handler: usize) {
+    let task = current_task().unwrap();
+    let mut inner = task.inner_exclusive_access();
+    inner.signals.remove(SignalFlags::from_signum(signum).unwrap());
+    inner.handling_sig = Some(signum);
+    let trap_cx = inner.get_trap_cx();
+    let backup = *trap_cx;
+    trap_cx.sepc = handler;
+    trap_cx.x[10] = signum;
+    // a fresh frame below the interrupted one
+    trap_cx.x[2] = (backup.x[2] - SIGNAL_FRAME_SIZE) & !0xf;
+    inner.trap_ctx_backup = Some(backup);
+}
+
+/// Carry out the default action of a pending signal `signum` for current task
+fn default_action(signum: usize) {
+    let task = current_task().unwrap();
+    let mut inner = task.inner_exclusive_access();
+    let signal = SignalFlags::from_signum(signum).unwrap();
+    inner.signals.remove(signal);
+    if signal == SignalFlags::SIGSTOP || signal == SignalFlags::SIGTSTP {
+        inner.frozen = true;
+    } else if signal == SignalFlags::SIGCONT {
+        inner.frozen = false;
+    } else if signal == SignalFlags::SIGCHLD || signal == SignalFlags::SIGURG
+        || signal == SignalFlags::SIGWINCH
+    {
+        // ignored by default
+    } else {
+        // terminate
+        inner.killed = Some(signum);
+    }
+}
+
+/// Handle the pending signals of current task which are not masked.
+/// Only one user handler runs at a time, the other signals with a handler
+/// stay pending until it returns, and so do those in the mask of its action
+fn check_pending_signals() {
+    for signum in 1..=MAX_SIG {
+        let signal = SignalFlags::from_signum(signum).unwrap();
+        let (deliverable, handler) = {
+            let task = current_task().unwrap();
+            let inner = task.inner_exclusive_access();
+            let mut blocked = inner.signal_mask;
+            if let Some(handling) = inner.handling_sig {
+                blocked |= inner.signal_actions[handling].mask;
+            }
+            let unmaskable = SignalFlags::unmaskable().contains(signal);
+            let handler = if unmaskable {
+                SIG_DFL
+            } else {
+                inner.signal_actions[signum].handler
+            };
+            // there is only one saved trap context for a user handler
+            let busy = handler != SIG_DFL && handler != SIG_IGN && inner.handling_sig.is_some();
+            let deliverable =
+                inner.signals.contains(signal) && (unmaskable || !blocked.contains(signal)) && !busy;
+            (deliverable, handler)
+        };
+        if !deliverable {
+            continue;
+        }
+        match handler {
+            SIG_DFL => default_action(signum),
+            SIG_IGN => {
+                let task = current_task().unwrap();
+                task.inner_exclusive_access().signals.remove(signal);
+            }
+            _ => {
+                call_user_handler(signum, handler);
+                return;
+            }
+        }
+    }
+}
+
+/// Deliver the pending signals of current task before it returns to user mode.
+/// A stopped task is kept off the CPU until it gets `SIGCONT`, and a task
+/// killed by signal `n` exits with code `-n`
+pub fn handle_signals() {
+    loop {
+        check_pending_signals();
+        let (frozen, killed) = {
+            let task = current_task().unwrap();
+            let inner = task.inner_exclusive_access();
+            (inner.frozen, inner.killed)
+        };
+        if let Some(signum) = killed {
+            exit_current_and_run_next(-(signum as i32));
+        }
+        if !frozen {
+            break;
+        }
+        suspend_current_and_run_next();
+    }
+}
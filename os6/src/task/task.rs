@@ -1,17 +1,73 @@
 //! Types related to task management & Functions for completely changing TCB
 
 use super::TaskContext;
-use super::{pid_alloc, KernelStack, PidHandle};
-use crate::config::TRAP_CONTEXT;
+use super::{pid_alloc, KernelStack, PidHandle, SignalAction, SignalFlags, MAX_SIG};
+use super::manager::insert_into_pid2task;
+use crate::config::{
+    TRAP_CONTEXT, BIG_STRIDE, DEFAULT_PRIORITY, MAX_FD_NUM, MAX_SYSCALL_NUM, USER_STACK_SIZE,
+};
 use crate::mm::{MemorySet, PhysPageNum, VirtAddr, KERNEL_SPACE};
-use crate::sync::UPSafeCell;
+use crate::sync::{remove_task, Condvar, Mutex, MutexBlockingInner, Semaphore, UPSafeCell};
 use crate::trap::{trap_handler, TrapContext};
 use alloc::sync::{Arc, Weak};
 use alloc::vec::Vec;
 use core::cell::RefMut;
-use crate::fs::{File, Stdin, Stdout};
+use crate::fs::{File, Stdin, Stdout, ROOT_INODE};
+use easy_fs::Inode;
 use alloc::string::String;
-use crate::mm::translated_refmut;
+use crate::mm::{translated_byte_buffer_checked, UserBuffer};
+use crate::timer::{get_time, get_time_us};
+use crate::syscall::process::{RLimit, Rusage};
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+/// Push `args` and `envs` onto the user stack of `memory_set` whose top is
+/// `user_sp`: the strings at the top, and below them `argc`, the argv pointers
+/// and the envp pointers, each array ended by a null pointer. Returns the new
+/// stack pointer, argv and envp, or None if they don't fit in the stack
+fn push_args(
+    memory_set: &mut MemorySet,
+    user_sp: usize,
+    args: &[String],
+    envs: &[String],
+) -> Option<(usize, usize, usize)> {
+    let word = core::mem::size_of::<usize>();
+    let strings_len: usize = args.iter().chain(envs).map(|s| s.len() + 1).sum();
+    let strings_base = user_sp.checked_sub(strings_len)?;
+    let words = 1 + (args.len() + 1) + (envs.len() + 1);
+    // the stack pointer stays 16 bytes aligned
+    let sp = strings_base.checked_sub(words * word)? & !0xf;
+    if user_sp - sp > USER_STACK_SIZE {
+        return None;
+    }
+    let mut stack = alloc::vec![0u8; user_sp - sp];
+    let mut put_word = |offset: usize, value: usize| {
+        stack[offset..offset + word].copy_from_slice(&value.to_ne_bytes());
+    };
+    put_word(0, args.len());
+    let argv = sp + word;
+    let envp = argv + (args.len() + 1) * word;
+    let mut string_va = strings_base;
+    for (i, s) in args.iter().enumerate() {
+        put_word(argv - sp + i * word, string_va);
+        string_va += s.len() + 1;
+    }
+    for (i, s) in envs.iter().enumerate() {
+        put_word(envp - sp + i * word, string_va);
+        string_va += s.len() + 1;
+    }
+    // the null pointers ending the arrays are already there
+    let mut offset = strings_base - sp;
+    for s in args.iter().chain(envs) {
+        stack[offset..offset + s.len()].copy_from_slice(s.as_bytes());
+        offset += s.len() + 1;
+    }
+    // the stack grows over the pages on the way
+    memory_set.fault_in(sp, stack.len(), true);
+    let buffers =
+        translated_byte_buffer_checked(memory_set.token(), sp as *const u8, stack.len()).ok()?;
+    UserBuffer::new(buffers).copy_from_slice(&stack);
+    Some((sp, argv, envp))
+}
 
 /// Task control block structure
 ///
@@ -22,10 +78,20 @@ pub struct TaskControlBlock {
     pub pid: PidHandle,
     /// Kernel stack corresponding to PID
     pub kernel_stack: KernelStack,
+    /// Bytes moved to and from the block device for the task,
+    /// counted by the driver, which may run with `inner` borrowed
+    pub block_io: BlockIo,
     // mutable
     inner: UPSafeCell<TaskControlBlockInner>,
 }
 
+/// Bytes read from and written to the block device
+#[derive(Default)]
+pub struct BlockIo {
+    pub read_bytes: AtomicUsize,
+    pub written_bytes: AtomicUsize,
+}
+
 /// Structure containing more process content
 ///
 /// Store the contents that will change during operation
@@ -50,6 +116,65 @@ pub struct TaskControlBlockInner {
     /// It is set when active exit or execution error occurs
     pub exit_code: i32,
     pub fd_table: Vec<Option<Arc<dyn File + Send + Sync>>>,
+    /// Whether each fd of `fd_table` is closed on exec
+    pub fd_cloexec: Vec<bool>,
+    /// Limits on the fds, none is allocated at or above the soft limit
+    pub nofile: RLimit,
+    /// Where relative paths start from
+    pub cwd: Arc<Inode>,
+    /// The environment as `NAME=value` strings, given at exec and inherited
+    pub environ: Vec<String>,
+    /// Scheduling priority, at least 2
+    pub priority: usize,
+    /// Priorities inherited from the waiters of each blocking mutex held,
+    /// keyed by the mutex
+    pub inherited: Vec<(usize, usize)>,
+    /// The blocking mutex the task is waiting for, if any
+    pub blocked_on: Option<Arc<UPSafeCell<MutexBlockingInner>>>,
+    /// How far the task has run in stride scheduling, wrapping around
+    pub stride: u32,
+    /// How many times the task invoked each syscall
+    pub syscall_times: Vec<u32>,
+    /// Time spent running before it was last scheduled, in microseconds
+    pub run_time_us: usize,
+    /// When the task was last scheduled, in microseconds
+    pub run_start_us: usize,
+    /// CPU time spent in user mode, in ticks of the `mtime` register
+    pub utime: usize,
+    /// CPU time spent in the kernel for the task, in ticks of the `mtime` register
+    pub stime: usize,
+    /// `utime` of the children reaped, including what they reaped
+    pub cutime: usize,
+    /// `stime` of the children reaped, including what they reaped
+    pub cstime: usize,
+    /// Memory and block device usage of the children reaped,
+    /// including what they reaped
+    pub children_usage: Rusage,
+    /// When the task last got the CPU or crossed between user mode
+    /// and the kernel, in ticks of the `mtime` register
+    pub mode_start: usize,
+    /// Signals sent to the task but not handled yet
+    pub signals: SignalFlags,
+    /// Signals kept pending instead of being delivered
+    pub signal_mask: SignalFlags,
+    /// Whether the task is stopped by a signal until it gets `SIGCONT`
+    pub frozen: bool,
+    /// The signal that terminates the task, if any
+    pub killed: Option<usize>,
+    /// What to do with each signal
+    pub signal_actions: [SignalAction; MAX_SIG + 1],
+    /// The signal whose user handler is running, if any
+    pub handling_sig: Option<usize>,
+    /// The trap context interrupted by the running user handler
+    pub trap_ctx_backup: Option<TrapContext>,
+    /// Mutexes created by the process, shared with the children forked after
+    pub mutex_list: Vec<Option<Arc<dyn Mutex>>>,
+    /// Semaphores created by the process, shared the same way as mutexes
+    pub semaphore_list: Vec<Option<Arc<Semaphore>>>,
+    /// Condition variables created by the process, shared the same way as mutexes
+    pub condvar_list: Vec<Option<Arc<Condvar>>>,
+    /// Whether taking a mutex or semaphore that could deadlock is refused
+    pub deadlock_detect: bool,
 }
 
 /// Simple access to its internal fields
@@ -71,29 +196,111 @@ impl TaskControlBlockInner {
     pub fn is_zombie(&self) -> bool {
         self.get_status() == TaskStatus::Zombie
     }
-    pub fn alloc_fd(&mut self) -> usize {
-        if let Some(fd) = (0..self.fd_table.len())
-            .find(|fd| self.fd_table[*fd].is_none()) {
-            fd
+    /// Whether a signal is pending and not blocked by the mask
+    pub fn has_deliverable_signals(&self) -> bool {
+        !(self.signals - (self.signal_mask - SignalFlags::unmaskable())).is_empty()
+    }
+    /// Count an invocation of syscall `id`
+    pub fn count_syscall(&mut self, id: usize) {
+        if let Some(times) = self.syscall_times.get_mut(id) {
+            *times += 1;
+        }
+    }
+    /// Mark that the task gets the CPU
+    pub fn start_running(&mut self) {
+        self.task_status = TaskStatus::Running;
+        self.run_start_us = get_time_us();
+        self.mode_start = get_time();
+    }
+    /// Mark that the task gives up the CPU, which it always does in the kernel
+    pub fn stop_running(&mut self) {
+        self.run_time_us += get_time_us() - self.run_start_us;
+        self.leave_kernel();
+    }
+    /// Charge the time since `mode_start` to user mode,
+    /// as the task traps into the kernel
+    pub fn enter_kernel(&mut self) {
+        let now = get_time();
+        self.utime += now - self.mode_start;
+        self.mode_start = now;
+    }
+    /// Charge the time since `mode_start` to the kernel,
+    /// as the task returns to user mode or gives up the CPU
+    pub fn leave_kernel(&mut self) {
+        let now = get_time();
+        self.stime += now - self.mode_start;
+        self.mode_start = now;
+    }
+    /// Total time the task has been running, in microseconds,
+    /// which leaves out the time waiting in the ready queue
+    pub fn running_time_us(&self) -> usize {
+        if self.task_status == TaskStatus::Running {
+            self.run_time_us + get_time_us() - self.run_start_us
         } else {
+            self.run_time_us
+        }
+    }
+    /// The stride added each time the task is scheduled,
+    /// never 0 so that any task makes progress
+    pub fn pass(&self) -> u32 {
+        (BIG_STRIDE as usize / self.effective_priority()).max(1) as u32
+    }
+    /// The priority the task is scheduled with, its own one
+    /// or a higher one inherited through the mutexes it holds
+    pub fn effective_priority(&self) -> usize {
+        self.inherited
+            .iter()
+            .map(|&(_, priority)| priority)
+            .fold(self.priority, usize::max)
+    }
+    /// The lowest free fd, growing the fd table if it is full.
+    /// None if every fd below the soft `nofile` limit is open already
+    pub fn alloc_fd(&mut self) -> Option<usize> {
+        let limit = self.nofile.cur;
+        if let Some(fd) = (0..self.fd_table.len().min(limit))
+            .find(|fd| self.fd_table[*fd].is_none()) {
+            self.fd_cloexec[fd] = false;
+            Some(fd)
+        } else if self.fd_table.len() < limit {
             self.fd_table.push(None);
-            self.fd_table.len() - 1
+            self.fd_cloexec.push(false);
+            Some(self.fd_table.len() - 1)
+        } else {
+            None
+        }
+    }
+    /// Make `fd` a valid index of the fd table, filling the gap with empty slots
+    pub fn reserve_fd(&mut self, fd: usize) {
+        if fd >= self.fd_table.len() {
+            self.fd_table.resize(fd + 1, None);
+            self.fd_cloexec.resize(fd + 1, false);
         }
     }
 }
 
 impl TaskControlBlock {
+    /// The memory and block device usage of the task itself
+    pub fn usage(&self) -> Rusage {
+        let inner = self.inner_exclusive_access();
+        Rusage {
+            page_faults: inner.memory_set.page_faults() as u64,
+            resident_frames: inner.memory_set.resident_frames() as u64,
+            peak_frames: inner.memory_set.peak_frames() as u64,
+            block_read_bytes: self.block_io.read_bytes.load(Ordering::Relaxed) as u64,
+            block_written_bytes: self.block_io.written_bytes.load(Ordering::Relaxed) as u64,
+        }
+    }
     /// Get the mutex to get the RefMut TaskControlBlockInner
     pub fn inner_exclusive_access(&self) -> RefMut<'_, TaskControlBlockInner> {
         self.inner.exclusive_access()
     }
 
-    /// Create a new process
+    /// Create a new process, or None if `elf_data` is not a valid elf
     ///
-    /// At present, it is only used for the creation of initproc
-    pub fn new(elf_data: &[u8]) -> Self {
+    /// At present, it is used for the creation of initproc and spawned processes
+    pub fn new(elf_data: &[u8]) -> Option<Self> {
         // memory_set with elf program headers/trampoline/trap context/user stack
-        let (memory_set, user_sp, entry_point) = MemorySet::from_elf(elf_data);
+        let (memory_set, user_sp, entry_point) = MemorySet::from_elf(elf_data)?;
         let trap_cx_ppn = memory_set
             .translate(VirtAddr::from(TRAP_CONTEXT).into())
             .unwrap()
@@ -106,6 +313,7 @@ impl TaskControlBlock {
         let task_control_block = Self {
             pid: pid_handle,
             kernel_stack,
+            block_io: BlockIo::default(),
             inner: unsafe {
                 UPSafeCell::new(TaskControlBlockInner {
                     trap_cx_ppn,
@@ -124,6 +332,34 @@ impl TaskControlBlock {
                         // 2 -> stderr
                         Some(Arc::new(Stdout)),
                     ],
+                    fd_cloexec: alloc::vec![false; 3],
+                    nofile: RLimit { cur: MAX_FD_NUM, max: MAX_FD_NUM },
+                    cwd: ROOT_INODE.clone(),
+                    environ: Vec::new(),
+                    priority: DEFAULT_PRIORITY,
+                    inherited: Vec::new(),
+                    blocked_on: None,
+                    stride: 0,
+                    syscall_times: alloc::vec![0; MAX_SYSCALL_NUM],
+                    run_time_us: 0,
+                    run_start_us: 0,
+                    utime: 0,
+                    stime: 0,
+                    cutime: 0,
+                    cstime: 0,
+                    children_usage: Rusage::default(),
+                    mode_start: 0,
+                    signals: SignalFlags::empty(),
+                    signal_mask: SignalFlags::empty(),
+                    frozen: false,
+                    killed: None,
+                    signal_actions: [SignalAction::default(); MAX_SIG + 1],
+                    handling_sig: None,
+                    trap_ctx_backup: None,
+                    mutex_list: Vec::new(),
+                    semaphore_list: Vec::new(),
+                    condvar_list: Vec::new(),
+                    deadlock_detect: false,
                 })
             },
         };
@@ -136,22 +372,48 @@ impl TaskControlBlock {
             kernel_stack_top,
             trap_handler as usize,
         );
-        task_control_block
+        Some(task_control_block)
     }
-    /// Load a new elf to replace the original application address space and start execution
-    pub fn exec(&self, elf_data: &[u8]) {
+    /// Load a new elf to replace the original application address space and start execution,
+    /// returns false and keeps the original one if `elf_data` is not a valid elf
+    /// Replace the program with the one in `elf_data`, which is passed
+    /// `args` and `envs`. False if the program can't be loaded
+    pub fn exec(&self, elf_data: &[u8], args: &[String], envs: &[String]) -> bool {
         // memory_set with elf program headers/trampoline/trap context/user stack
-        let (memory_set, mut user_sp, entry_point) = MemorySet::from_elf(elf_data);
+        let (mut memory_set, user_sp, entry_point) = match MemorySet::from_elf(elf_data) {
+            Some(elf) => elf,
+            None => return false,
+        };
+        let (user_sp, argv, envp) = match push_args(&mut memory_set, user_sp, args, envs) {
+            Some(stack) => stack,
+            None => return false,
+        };
         let trap_cx_ppn = memory_set
             .translate(VirtAddr::from(TRAP_CONTEXT).into())
             .unwrap()
             .ppn();
+        self.release_sync_objects();
         // **** access inner exclusively
         let mut inner = self.inner_exclusive_access();
-        // substitute memory_set
+        // substitute memory_set, whose usage goes on adding up
+        memory_set.inherit_usage(&inner.memory_set);
         inner.memory_set = memory_set;
         // update trap_cx ppn
         inner.trap_cx_ppn = trap_cx_ppn;
+        // the handlers are gone with the old program
+        inner.signal_actions = [SignalAction::default(); MAX_SIG + 1];
+        inner.handling_sig = None;
+        inner.trap_ctx_backup = None;
+        for fd in 0..inner.fd_table.len() {
+            if inner.fd_cloexec[fd] {
+                inner.fd_table[fd] = None;
+                inner.fd_cloexec[fd] = false;
+            }
+        }
+        inner.deadlock_detect = false;
+        inner.environ = envs.to_vec();
+        // what it held is out of reach of the new program
+        remove_task(self.getpid());
         // initialize trap_cx
         let trap_cx = inner.get_trap_cx();
         *trap_cx = TrapContext::app_init_context(
@@ -161,6 +423,11 @@ impl TaskControlBlock {
             self.kernel_stack.get_top(),
             trap_handler as usize,
         );
+        // main(argc, argv), with the environment after
+        trap_cx.x[10] = args.len();
+        trap_cx.x[11] = argv;
+        trap_cx.x[12] = envp;
+        true
         // **** release inner automatically
     }
     /// Fork from parent to child
@@ -168,7 +435,7 @@ impl TaskControlBlock {
         // ---- access parent PCB exclusively
         let mut parent_inner = self.inner_exclusive_access();
         // copy user space(include trap context)
-        let memory_set = MemorySet::from_existed_user(&parent_inner.memory_set);
+        let memory_set = MemorySet::from_existed_user(&mut parent_inner.memory_set);
         let trap_cx_ppn = memory_set
             .translate(VirtAddr::from(TRAP_CONTEXT).into())
             .unwrap()
@@ -177,18 +444,17 @@ impl TaskControlBlock {
         let pid_handle = pid_alloc();
         let kernel_stack = KernelStack::new(&pid_handle);
         let kernel_stack_top = kernel_stack.get_top();
-        let mut new_fd_table: Vec<Option<Arc<dyn File + Send + Sync>>> = Vec::new();
-        // clone all fds from parent to child
-        for fd in parent_inner.fd_table.iter() {
-            if let Some(file) = fd {
-                new_fd_table.push(Some(file.clone()));
-            } else {
-                new_fd_table.push(None);
-            }
-        }
+        // the child refers to the same open files, sharing their offsets,
+        // but closes them on its own
+        let new_fd_table: Vec<Option<Arc<dyn File + Send + Sync>>> = parent_inner
+            .fd_table
+            .iter()
+            .map(|fd| fd.as_ref().map(Arc::clone))
+            .collect();
         let task_control_block = Arc::new(TaskControlBlock {
             pid: pid_handle,
             kernel_stack,
+            block_io: BlockIo::default(),
             inner: unsafe {
                 UPSafeCell::new(TaskControlBlockInner {
                     trap_cx_ppn,
@@ -200,9 +466,39 @@ impl TaskControlBlock {
                     children: Vec::new(),
                     exit_code: 0,
                     fd_table: new_fd_table,
+                    fd_cloexec: parent_inner.fd_cloexec.clone(),
+                    nofile: parent_inner.nofile,
+                    cwd: parent_inner.cwd.clone(),
+                    environ: parent_inner.environ.clone(),
+                    priority: parent_inner.priority,
+                    inherited: Vec::new(),
+                    blocked_on: None,
+                    stride: parent_inner.stride,
+                    syscall_times: alloc::vec![0; MAX_SYSCALL_NUM],
+                    run_time_us: 0,
+                    run_start_us: 0,
+                    utime: 0,
+                    stime: 0,
+                    cutime: 0,
+                    cstime: 0,
+                    children_usage: Rusage::default(),
+                    mode_start: 0,
+                    signals: SignalFlags::empty(),
+                    // the mask is inherited, pending signals are not
+                    signal_mask: parent_inner.signal_mask,
+                    frozen: false,
+                    killed: None,
+                    signal_actions: parent_inner.signal_actions,
+                    handling_sig: None,
+                    trap_ctx_backup: None,
+                    mutex_list: parent_inner.mutex_list.clone(),
+                    semaphore_list: parent_inner.semaphore_list.clone(),
+                    condvar_list: parent_inner.condvar_list.clone(),
+                    deadlock_detect: parent_inner.deadlock_detect,
                 })
             },
         });
+        insert_into_pid2task(task_control_block.getpid(), task_control_block.clone());
         // add child
         parent_inner.children.push(task_control_block.clone());
         // modify kernel_sp in trap_cx
@@ -217,23 +513,51 @@ impl TaskControlBlock {
     pub fn getpid(&self) -> usize {
         self.pid.0
     }
+    /// Release the blocking mutexes the task holds, handing them over to
+    /// their waiters, and drop its share of the mutexes, semaphores and
+    /// condition variables, as it exits or execs
+    pub fn release_sync_objects(&self) {
+        let mut inner = self.inner_exclusive_access();
+        let mutex_list = core::mem::take(&mut inner.mutex_list);
+        inner.semaphore_list.clear();
+        inner.condvar_list.clear();
+        inner.blocked_on = None;
+        drop(inner);
+        for mutex in mutex_list.iter().flatten() {
+            mutex.release_held_by(self);
+        }
+        // nothing is left to inherit from
+        self.inner_exclusive_access().inherited.clear();
+    }
 
-    pub fn spawn(self: &Arc<TaskControlBlock>, elf_data: &[u8]) -> Arc<TaskControlBlock> {
-        let task_control_block = Arc::new(TaskControlBlock::new(elf_data));
-        task_control_block.inner_exclusive_access().parent = Some(Arc::downgrade(self));
-
+    /// Create a child process running `elf_data` from a fresh address space,
+    /// or None if it is not a valid elf
+    pub fn spawn(self: &Arc<TaskControlBlock>, elf_data: &[u8]) -> Option<Arc<TaskControlBlock>> {
+        let task_control_block = Arc::new(TaskControlBlock::new(elf_data)?);
         let mut parent_inner = self.inner_exclusive_access();
+        let mut inner = task_control_block.inner_exclusive_access();
+        inner.parent = Some(Arc::downgrade(self));
+        inner.cwd = parent_inner.cwd.clone();
+        inner.environ = parent_inner.environ.clone();
+        inner.nofile = parent_inner.nofile;
+        // start level with the parent instead of running ahead of everyone
+        inner.stride = parent_inner.stride;
+        drop(inner);
+
         parent_inner.children.push(task_control_block.clone());
+        insert_into_pid2task(task_control_block.getpid(), task_control_block.clone());
 
-        task_control_block
+        Some(task_control_block)
     }
 }
 
 #[derive(Copy, Clone, PartialEq)]
-/// task status: UnInit, Ready, Running, Exited
+/// task status: UnInit, Ready, Running, Exited, Blocked
 pub enum TaskStatus {
     UnInit,
     Ready,
     Running,
     Zombie,
+    /// Waiting off the ready queue until it is woken up
+    Blocked,
 }
@@ -2,22 +2,214 @@
 
 use crate::config::CLOCK_FREQ;
 use crate::sbi::set_timer;
+use crate::sync::UPSafeCell;
+use crate::task::{wakeup_task, TaskControlBlock};
+use alloc::collections::BinaryHeap;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::cmp::Ordering;
+use lazy_static::*;
 use riscv::register::time;
 
 const TICKS_PER_SEC: usize = 100;
-const MICRO_PER_SEC: usize = 1_000_000;
+const MSEC_PER_SEC: u64 = 1000;
+const MICRO_PER_SEC: u64 = 1_000_000;
+const NANO_PER_SEC: u64 = 1_000_000_000;
+
+/// The magic number opening a flattened device tree, big endian
+const FDT_MAGIC: u32 = 0xd00d_feed;
+const FDT_BEGIN_NODE: u32 = 1;
+const FDT_END_NODE: u32 = 2;
+const FDT_PROP: u32 = 3;
+const FDT_NOP: u32 = 4;
+
+lazy_static! {
+    /// Ticks of the `mtime` register per second
+    static ref TIMEBASE_FREQ: UPSafeCell<u64> = unsafe { UPSafeCell::new(CLOCK_FREQ as u64) };
+    /// The latest monotonic time handed out, in nanoseconds
+    static ref LAST_MONOTONIC_NS: UPSafeCell<u64> = unsafe { UPSafeCell::new(0) };
+}
+
+/// Read the big endian u32 at `addr`
+unsafe fn be32(addr: usize) -> u32 {
+    u32::from_be(core::ptr::read_unaligned(addr as *const u32))
+}
+
+/// The `timebase-frequency` property of the `/cpus` node, or of the first
+/// cpu node having one, in the device tree at `dtb`
+unsafe fn fdt_timebase_frequency(dtb: usize) -> Option<u64> {
+    if dtb == 0 || be32(dtb) != FDT_MAGIC {
+        return None;
+    }
+    let structs = dtb + be32(dtb + 8) as usize;
+    let strings = dtb + be32(dtb + 12) as usize;
+    let mut cur = structs;
+    // depth of the current node, and whether it is `/cpus` or below it
+    let mut depth = 0usize;
+    let mut cpus_depth = None;
+    loop {
+        let token = be32(cur);
+        cur += 4;
+        match token {
+            FDT_BEGIN_NODE => {
+                let name_start = cur;
+                while *(cur as *const u8) != 0 {
+                    cur += 1;
+                }
+                let name = core::slice::from_raw_parts(name_start as *const u8, cur - name_start);
+                cur = (cur + 1 + 3) & !3;
+                depth += 1;
+                if depth == 2 && name == b"cpus" {
+                    cpus_depth = Some(depth);
+                }
+            }
+            FDT_END_NODE => {
+                if cpus_depth == Some(depth) {
+                    return None;
+                }
+                depth -= 1;
+            }
+            FDT_PROP => {
+                let len = be32(cur) as usize;
+                let name_off = be32(cur + 4) as usize;
+                let value = cur + 8;
+                cur = (value + len + 3) & !3;
+                let name_start = strings + name_off;
+                let mut name_end = name_start;
+                while *(name_end as *const u8) != 0 {
+                    name_end += 1;
+                }
+                let name = core::slice::from_raw_parts(name_start as *const u8, name_end - name_start);
+                if cpus_depth.is_some() && name == b"timebase-frequency" {
+                    return match len {
+                        4 => Some(be32(value) as u64),
+                        8 => Some((be32(value) as u64) << 32 | be32(value + 4) as u64),
+                        _ => None,
+                    };
+                }
+            }
+            FDT_NOP => {}
+            // FDT_END or garbage
+            _ => return None,
+        }
+    }
+}
+
+/// Take the timebase frequency from the device tree at `dtb` the
+/// bootloader hands over, keeping `CLOCK_FREQ` if there is none.
+/// Must be called while the device tree is still mapped as is
+pub fn init_timebase(dtb: usize) {
+    if let Some(freq) = unsafe { fdt_timebase_frequency(dtb) } {
+        if freq != 0 {
+            *TIMEBASE_FREQ.exclusive_access() = freq;
+        }
+    }
+}
+
+/// Ticks of the `mtime` register per second
+pub fn timebase_freq() -> u64 {
+    *TIMEBASE_FREQ.exclusive_access()
+}
+
+/// Convert `ticks` of the `mtime` register to units of `1 / per_sec` seconds,
+/// exact even when the frequency is not a multiple of `per_sec`
+fn ticks_to(ticks: u64, per_sec: u64) -> u64 {
+    let freq = timebase_freq();
+    ticks / freq * per_sec + ticks % freq * per_sec / freq
+}
+
+/// Convert `ticks` of the `mtime` register to clock ticks,
+/// `TICKS_PER_SEC` of them a second as for the timer interrupts
+pub fn to_clock_ticks(ticks: usize) -> usize {
+    ticks_to(ticks as u64, TICKS_PER_SEC as u64) as usize
+}
 
 /// read the `mtime` register
 pub fn get_time() -> usize {
     time::read()
 }
 
+/// get current time in milliseconds
+pub fn get_time_ms() -> usize {
+    ticks_to(time::read() as u64, MSEC_PER_SEC) as usize
+}
+
 /// get current time in microseconds
 pub fn get_time_us() -> usize {
-    time::read() / (CLOCK_FREQ / MICRO_PER_SEC)
+    ticks_to(time::read() as u64, MICRO_PER_SEC) as usize
+}
+
+/// get current time in nanoseconds
+pub fn get_time_ns() -> u64 {
+    ticks_to(time::read() as u64, NANO_PER_SEC)
+}
+
+/// Current time in nanoseconds, never less than what
+/// an earlier call returned, whichever task asked
+pub fn get_time_monotonic_ns() -> u64 {
+    let mut last = LAST_MONOTONIC_NS.exclusive_access();
+    *last = (*last).max(get_time_ns());
+    *last
 }
 
 /// set the next timer interrupt
 pub fn set_next_trigger() {
-    set_timer(get_time() + CLOCK_FREQ / TICKS_PER_SEC);
+    set_timer(get_time() + timebase_freq() as usize / TICKS_PER_SEC);
+}
+
+/// A task waiting for the time to reach `expire_ms`
+pub struct TimerCondition {
+    pub expire_ms: usize,
+    pub task: Arc<TaskControlBlock>,
+}
+
+impl PartialEq for TimerCondition {
+    fn eq(&self, other: &Self) -> bool {
+        self.expire_ms == other.expire_ms
+    }
+}
+
+impl Eq for TimerCondition {}
+
+impl PartialOrd for TimerCondition {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for TimerCondition {
+    /// Reversed, so that the heap of timers pops the earliest one first
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.expire_ms.cmp(&self.expire_ms)
+    }
+}
+
+lazy_static! {
+    /// Timers not expired yet
+    static ref TIMERS: UPSafeCell<BinaryHeap<TimerCondition>> =
+        unsafe { UPSafeCell::new(BinaryHeap::new()) };
+}
+
+/// Wake `task` up once the time reaches `expire_ms`
+pub fn add_timer(expire_ms: usize, task: Arc<TaskControlBlock>) {
+    TIMERS
+        .exclusive_access()
+        .push(TimerCondition { expire_ms, task });
+}
+
+/// Wake up the tasks whose timers have expired
+pub fn check_timer() {
+    let now = get_time_ms();
+    let mut expired = Vec::new();
+    let mut timers = TIMERS.exclusive_access();
+    while let Some(timer) = timers.peek() {
+        if timer.expire_ms > now {
+            break;
+        }
+        expired.push(timers.pop().unwrap().task);
+    }
+    drop(timers);
+    for task in expired {
+        wakeup_task(task);
+    }
 }
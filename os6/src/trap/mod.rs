@@ -15,11 +15,14 @@
 mod context;
 
 use crate::config::{TRAMPOLINE, TRAP_CONTEXT};
+use crate::fs::flush_dirty_blocks;
+use crate::mm::VirtAddr;
 use crate::syscall::syscall;
 use crate::task::{
-    current_trap_cx, current_user_token, exit_current_and_run_next, suspend_current_and_run_next,
+    current_enter_kernel, current_leave_kernel, current_task, current_trap_cx, current_user_token,
+    exit_current_and_run_next, handle_signals, suspend_current_and_run_next,
 };
-use crate::timer::set_next_trigger;
+use crate::timer::{check_timer, set_next_trigger};
 use riscv::register::{
     mtvec::TrapMode,
     scause::{self, Exception, Interrupt, Trap},
@@ -53,6 +56,7 @@ pub fn enable_timer_interrupt() {
 #[no_mangle]
 pub fn trap_handler() -> ! {
     set_kernel_trap_entry();
+    current_enter_kernel();
     let scause = scause::read();
     let stval = stval::read();
     match scause.cause() {
@@ -61,7 +65,10 @@ pub fn trap_handler() -> ! {
             let mut cx = current_trap_cx();
             cx.sepc += 4;
             // get system call return value
-            let result = syscall(cx.x[17], [cx.x[10], cx.x[11], cx.x[12], cx.x[13]]);
+            let result = syscall(
+                cx.x[17],
+                [cx.x[10], cx.x[11], cx.x[12], cx.x[13], cx.x[14], cx.x[15]],
+            );
             // cx is changed during sys_exec, so we have to call it again
             cx = current_trap_cx();
             cx.x[10] = result as usize;
@@ -72,6 +79,38 @@ pub fn trap_handler() -> ! {
         | Trap::Exception(Exception::InstructionPageFault)
         | Trap::Exception(Exception::LoadFault)
         | Trap::Exception(Exception::LoadPageFault) => {
+            let page_fault = matches!(
+                scause.cause(),
+                Trap::Exception(Exception::StorePageFault)
+                    | Trap::Exception(Exception::InstructionPageFault)
+                    | Trap::Exception(Exception::LoadPageFault)
+            );
+            let write = matches!(scause.cause(), Trap::Exception(Exception::StorePageFault));
+            // a page not accessed yet or a write to a copy-on-write page
+            // gets its frame, then the access retries
+            if page_fault
+                && current_task()
+                    .unwrap()
+                    .inner_exclusive_access()
+                    .memory_set
+                    .handle_page_fault(VirtAddr::from(stval).floor(), write)
+            {
+                trap_return();
+            }
+            if current_task()
+                .unwrap()
+                .inner_exclusive_access()
+                .memory_set
+                .is_stack_guard(VirtAddr::from(stval).floor())
+            {
+                println!(
+                    "[kernel] Stack overflow in application, bad addr = {:#x}, bad instruction = {:#x}, core dumped.",
+                    stval,
+                    current_trap_cx().sepc,
+                );
+                // stack overflow exit code
+                exit_current_and_run_next(-4);
+            }
             println!(
                 "[kernel] {:?} in application, bad addr = {:#x}, bad instruction = {:#x}, core dumped.",
                 scause.cause(),
@@ -88,6 +127,8 @@ pub fn trap_handler() -> ! {
         }
         Trap::Interrupt(Interrupt::SupervisorTimer) => {
             set_next_trigger();
+            check_timer();
+            flush_dirty_blocks();
             suspend_current_and_run_next();
         }
         _ => {
@@ -103,6 +144,9 @@ pub fn trap_handler() -> ! {
 
 #[no_mangle]
 pub fn trap_return() -> ! {
+    // the task may be stopped or killed here
+    handle_signals();
+    current_leave_kernel();
     set_user_trap_entry();
     let trap_cx_ptr = TRAP_CONTEXT;
     let user_satp = current_user_token();
@@ -0,0 +1,51 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{access, close, mkdir, open, unlink, OpenFlags, F_OK, R_OK, W_OK, X_OK};
+
+/// 测试 access，已存在的文件可以访问，不存在的文件及父目录不存在的路径返回 -1，
+/// 且不会创建文件或占用 fd，输出　Test access OK! 就算正确。
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let fname = "fname_access\0";
+    let fd = open(fname, OpenFlags::CREATE | OpenFlags::TRUNC | OpenFlags::WRONLY);
+    assert!(fd > 0);
+    let first_fd = fd;
+    close(fd as usize);
+
+    assert_eq!(access(fname, F_OK), 0);
+    assert_eq!(access(fname, R_OK | W_OK), 0);
+    assert_eq!(access(fname, X_OK), 0);
+    assert_eq!(access(fname, 0x80), -1);
+    assert_eq!(access("/\0", F_OK), 0);
+
+    // missing files are not created
+    assert_eq!(access("fname_access_missing\0", F_OK), -1);
+    assert_eq!(access("fname_access_missing\0", W_OK), -1);
+    assert_eq!(open("fname_access_missing\0", OpenFlags::RDONLY), -1);
+    assert_eq!(access("no_such_dir_access/file\0", F_OK), -1);
+
+    // through directories, which must really be directories
+    assert_eq!(mkdir("dir_access\0", 0), 0);
+    let inner = "dir_access/inner\0";
+    let fd = open(inner, OpenFlags::CREATE | OpenFlags::WRONLY);
+    assert!(fd > 0);
+    close(fd as usize);
+    assert_eq!(access(inner, R_OK), 0);
+    assert_eq!(access("dir_access/../fname_access\0", F_OK), 0);
+    assert_eq!(access("fname_access/inner\0", F_OK), -1);
+
+    // no fd is taken by any of the checks above
+    let fd = open(fname, OpenFlags::RDONLY);
+    assert_eq!(fd, first_fd);
+    close(fd as usize);
+    unlink(inner);
+    unlink("dir_access\0");
+    unlink(fname);
+    println!("Test access OK!");
+    0
+}
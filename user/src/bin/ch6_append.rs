@@ -0,0 +1,46 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{close, lseek, open, read, unlink, write, OpenFlags, SEEK_CUR, SEEK_SET};
+
+/// 测试 O_APPEND 追加写，输出　Test append OK! 就算正确。
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let fname = "fname_append\0";
+    let fd = open(fname, OpenFlags::CREATE | OpenFlags::WRONLY);
+    assert!(fd > 0);
+    write(fd as usize, b"head|");
+    close(fd as usize);
+
+    let fa = open(fname, OpenFlags::WRONLY | OpenFlags::APPEND);
+    let fb = open(fname, OpenFlags::WRONLY | OpenFlags::APPEND);
+    assert!(fa > 0 && fb > 0);
+    let (fa, fb) = (fa as usize, fb as usize);
+    assert_eq!(write(fa, b"a1|"), 3);
+    assert_eq!(write(fb, b"b1|"), 3);
+    // moving the cursor doesn't matter, each write goes to the end
+    assert_eq!(lseek(fa, 0, SEEK_SET), 0);
+    assert_eq!(write(fa, b"a2|"), 3);
+    assert_eq!(write(fb, b"b2"), 2);
+    // the cursor ends up at the end of file
+    assert_eq!(lseek(fb, 0, SEEK_CUR), 16);
+    close(fa);
+    close(fb);
+
+    let fd = open(fname, OpenFlags::RDONLY);
+    assert!(fd > 0);
+    let mut buffer = [0u8; 100];
+    let read_len = read(fd as usize, &mut buffer) as usize;
+    assert_eq!(
+        "head|a1|b1|a2|b2",
+        core::str::from_utf8(&buffer[..read_len]).unwrap()
+    );
+    close(fd as usize);
+    assert_eq!(unlink(fname), 0);
+    println!("Test append OK!");
+    0
+}
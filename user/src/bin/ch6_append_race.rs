@@ -0,0 +1,67 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{
+    close, exit, fork, fstat, open, read, unlink, waitpid, write, yield_, OpenFlags, Stat,
+};
+
+/// 测试两个进程各自打开同一文件并交替追加写入，最终大小为两者写入之和且没有数据交错，
+/// 输出　Test append race OK! 就算正确。
+
+const CHUNK: usize = 64;
+const ROUNDS: usize = 40;
+
+fn append_chunks(fname: &str, tag: u8) {
+    let fd = open(fname, OpenFlags::WRONLY | OpenFlags::APPEND);
+    assert!(fd > 0);
+    let fd = fd as usize;
+    let chunk = [tag; CHUNK];
+    for _ in 0..ROUNDS {
+        assert_eq!(write(fd, &chunk), CHUNK as isize);
+        yield_();
+    }
+    close(fd);
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let fname = "fname_append_race\0";
+    let fd = open(fname, OpenFlags::CREATE | OpenFlags::TRUNC | OpenFlags::WRONLY);
+    assert!(fd > 0);
+    close(fd as usize);
+
+    let pid = fork();
+    if pid == 0 {
+        append_chunks(fname, b'c');
+        exit(0);
+    }
+    append_chunks(fname, b'p');
+    let mut exit_code: i32 = -1;
+    assert_eq!(waitpid(pid as usize, &mut exit_code), pid);
+    assert_eq!(exit_code, 0);
+
+    let fd = open(fname, OpenFlags::RDONLY);
+    assert!(fd > 0);
+    let fd = fd as usize;
+    let stat = Stat::new();
+    assert_eq!(fstat(fd, &stat), 0);
+    assert_eq!(stat.size, (2 * CHUNK * ROUNDS) as u64);
+    let mut chunk = [0u8; CHUNK];
+    let mut parent_chunks = 0;
+    for _ in 0..2 * ROUNDS {
+        assert_eq!(read(fd, &mut chunk), CHUNK as isize);
+        assert!(chunk.iter().all(|&b| b == chunk[0]));
+        if chunk[0] == b'p' {
+            parent_chunks += 1;
+        }
+    }
+    assert_eq!(parent_chunks, ROUNDS);
+    assert_eq!(read(fd, &mut chunk), 0);
+    close(fd);
+    unlink(fname);
+    println!("Test append race OK!");
+    0
+}
@@ -0,0 +1,64 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{
+    clock_gettime, close, fstat, getrusage, gettimeofday, open, pipe, read, times, write,
+    OpenFlags, Rusage, Stat, TimeSpec, TimeVal, Tms, CLOCK_REALTIME, RUSAGE_SELF,
+};
+
+/// 测试向读写文件、fstat、pipe、取时间及资源用量的系统调用传入未映射的地址，以及未对齐或只读的 Stat 地址，
+/// 系统调用返回 -1 且内核不崩溃，输出　Test bad buffer OK! 就算正确。
+
+/// Not mapped in any user address space
+const UNMAPPED: usize = 0x5000_0000;
+/// Kernel memory, never accessible to the user
+const KERNEL: usize = 0x8020_0000;
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let fname = "fname_bad_buffer\0";
+    let fd = open(fname, OpenFlags::CREATE | OpenFlags::RDWR);
+    assert!(fd > 0);
+    let fd = fd as usize;
+    for &addr in [UNMAPPED, KERNEL].iter() {
+        let buf = unsafe { core::slice::from_raw_parts_mut(addr as *mut u8, 16) };
+        assert_eq!(write(1, buf), -1);
+        assert_eq!(write(fd, buf), -1);
+        assert_eq!(read(fd, buf), -1);
+        let st = unsafe { &*(addr as *const Stat) };
+        assert_eq!(fstat(fd, st), -1);
+        let tp = unsafe { &mut *(addr as *mut TimeSpec) };
+        assert_eq!(clock_gettime(CLOCK_REALTIME, tp), -1);
+        assert_eq!(gettimeofday(unsafe { &mut *(addr as *mut TimeVal) }), -1);
+        assert_eq!(times(unsafe { &mut *(addr as *mut Tms) }), -1);
+        assert_eq!(getrusage(RUSAGE_SELF, unsafe { &mut *(addr as *mut Rusage) }), -1);
+        assert_eq!(pipe(unsafe { core::slice::from_raw_parts_mut(addr as *mut usize, 2) }), -1);
+    }
+    // the failed pipes leave no fd open
+    let mut fds = [0usize; 2];
+    assert_eq!(pipe(&mut fds), 0);
+    assert_eq!(fds, [fd + 1, fd + 2]);
+    close(fds[0]);
+    close(fds[1]);
+    // a misaligned stat is not written at all
+    let mut words = [0u64; core::mem::size_of::<Stat>() / 8 + 1];
+    let misaligned = (words.as_mut_ptr() as usize + 1) as *const Stat;
+    assert_eq!(fstat(fd, unsafe { &*misaligned }), -1);
+    assert!(words.iter().all(|&w| w == 0));
+    // nor is one in the read-only code
+    let code = (main as usize & !7) as *const u64;
+    let before = unsafe { code.read_volatile() };
+    assert_eq!(fstat(fd, unsafe { &*(code as *const Stat) }), -1);
+    assert_eq!(unsafe { code.read_volatile() }, before);
+    // a buffer running off the end of the address space
+    let buf = unsafe { core::slice::from_raw_parts(usize::MAX as *const u8, 16) };
+    assert_eq!(write(1, buf), -1);
+    // the kernel is still fine with good buffers
+    assert_eq!(write(fd, b"ok"), 2);
+    close(fd);
+    println!("Test bad buffer OK!");
+    0
+}
@@ -0,0 +1,48 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{link, mkdir, mmap, munmap, open, spawn, unlink, OpenFlags};
+
+/// 测试传入没有结尾 \0 的路径，系统调用返回 -1 且内核不崩溃，
+/// 输出　Test bad path OK! 就算正确。
+
+const START: usize = 0x10000000;
+const PAGE_SIZE: usize = 4096;
+
+/// Fill `len` bytes at `START` with 'a' and view them as a str
+fn fill(len: usize) -> &'static str {
+    let buf = unsafe { core::slice::from_raw_parts_mut(START as *mut u8, len) };
+    for byte in buf.iter_mut() {
+        *byte = b'a';
+    }
+    core::str::from_utf8(buf).unwrap()
+}
+
+fn assert_rejected(path: &str) {
+    assert_eq!(open(path, OpenFlags::CREATE | OpenFlags::RDWR), -1);
+    assert_eq!(link(path, "fname_bad_path\0"), -1);
+    assert_eq!(link("fname_bad_path\0", path), -1);
+    assert_eq!(unlink(path), -1);
+    assert_eq!(mkdir(path, 0o755), -1);
+    assert_eq!(spawn(path), -1);
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    // runs into the unmapped page after the mapping
+    assert_eq!(mmap(START, PAGE_SIZE, 3), 0);
+    assert_rejected(fill(PAGE_SIZE));
+    munmap(START, PAGE_SIZE);
+    // terminated, but far too long for a path
+    assert_eq!(mmap(START, 2 * PAGE_SIZE, 3), 0);
+    unsafe {
+        *((START + 2 * PAGE_SIZE - 1) as *mut u8) = 0;
+    }
+    assert_rejected(fill(2 * PAGE_SIZE - 1));
+    munmap(START, 2 * PAGE_SIZE);
+    println!("Test bad path OK!");
+    0
+}
@@ -0,0 +1,48 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{brk, fork, mmap, munmap, wait};
+
+/// 测试 brk 调整堆大小，输出　Test brk OK! 就算正确。
+
+const PAGE_SIZE: usize = 4096;
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let start = brk(0);
+    assert!(start > 0);
+    let start = start as usize;
+    // page aligned, as the heap starts above the user stack
+    assert_eq!(start % PAGE_SIZE, 0);
+    assert_eq!(brk(start + PAGE_SIZE), (start + PAGE_SIZE) as isize);
+    assert_eq!(brk(0), (start + PAGE_SIZE) as isize);
+    let heap = unsafe { core::slice::from_raw_parts_mut(start as *mut u8, PAGE_SIZE) };
+    assert!(heap.iter().all(|&b| b == 0));
+    heap.fill(0x5a);
+    assert!(heap.iter().all(|&b| b == 0x5a));
+    // the break never goes below the start of the heap
+    assert_eq!(brk(start - 1), -1);
+    // nor into another mapping
+    assert_eq!(mmap(start + 2 * PAGE_SIZE, PAGE_SIZE, 3), 0);
+    assert_eq!(brk(start + 3 * PAGE_SIZE), -1);
+    assert_eq!(munmap(start + 2 * PAGE_SIZE, PAGE_SIZE), 0);
+    // shrinking frees the page, the heap is empty again
+    assert_eq!(brk(start), start as isize);
+    if fork() == 0 {
+        unsafe {
+            *(start as *mut u8) = 1;
+        }
+        return 0;
+    }
+    let mut exit_code: i32 = 0;
+    wait(&mut exit_code);
+    assert_eq!(exit_code, -2);
+    // growing again gives a zeroed page
+    assert_eq!(brk(start + PAGE_SIZE), (start + PAGE_SIZE) as isize);
+    assert_eq!(unsafe { *(start as *const u8) }, 0);
+    println!("Test brk OK!");
+    0
+}
@@ -0,0 +1,80 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{
+    chdir, close, exit, fork, getcwd, mkdir, open, read, unlink, waitpid, write, OpenFlags,
+};
+
+/// 测试 chdir/getcwd 及相对路径解析，输出　Test chdir OK! 就算正确。
+
+fn assert_cwd(expected: &str) {
+    let mut buf = [0u8; 64];
+    let len = getcwd(&mut buf);
+    assert_eq!(len, expected.len() as isize + 1);
+    assert_eq!(&buf[..expected.len()], expected.as_bytes());
+    assert_eq!(buf[expected.len()], 0);
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let test_str = "Hello, world!";
+    assert_cwd("/");
+    assert_eq!(mkdir("dir_cwd\0", 0o755), 0);
+    assert_eq!(mkdir("dir_cwd/sub\0", 0o755), 0);
+    let fd = open("dir_cwd/sub/fname\0", OpenFlags::CREATE | OpenFlags::WRONLY);
+    assert!(fd > 0);
+    write(fd as usize, test_str.as_bytes());
+    close(fd as usize);
+
+    // only directories can be entered
+    assert_eq!(chdir("dir_cwd/sub/fname\0"), -1);
+    assert_eq!(chdir("no_such_dir\0"), -1);
+    assert_cwd("/");
+
+    assert_eq!(chdir("dir_cwd/sub\0"), 0);
+    assert_cwd("/dir_cwd/sub");
+    let fd = open("fname\0", OpenFlags::RDONLY);
+    assert!(fd > 0);
+    let mut buffer = [0u8; 100];
+    let read_len = read(fd as usize, &mut buffer) as usize;
+    assert_eq!(test_str, core::str::from_utf8(&buffer[..read_len]).unwrap());
+    close(fd as usize);
+    // too small for the path and its NUL
+    assert_eq!(getcwd(&mut buffer[..12]), -1);
+
+    // the child starts where the parent is, and moves on its own
+    let pid = fork();
+    if pid == 0 {
+        assert_cwd("/dir_cwd/sub");
+        assert_eq!(chdir("..\0"), 0);
+        assert_cwd("/dir_cwd");
+        exit(0);
+    }
+    let mut exit_code: i32 = -1;
+    assert_eq!(waitpid(pid as usize, &mut exit_code), pid);
+    assert_eq!(exit_code, 0);
+    assert_cwd("/dir_cwd/sub");
+
+    // relative paths are created under the working directory
+    assert_eq!(chdir("..\0"), 0);
+    assert_cwd("/dir_cwd");
+    assert_eq!(mkdir("made\0", 0o755), 0);
+    assert_eq!(chdir("./made\0"), 0);
+    assert_cwd("/dir_cwd/made");
+    // `..` of the root is the root itself
+    assert_eq!(chdir("../../..\0"), 0);
+    assert_cwd("/");
+    assert_eq!(chdir("/dir_cwd\0"), 0);
+    assert_cwd("/dir_cwd");
+
+    assert_eq!(unlink("made\0"), 0);
+    assert_eq!(unlink("sub/fname\0"), 0);
+    assert_eq!(unlink("/dir_cwd/sub\0"), 0);
+    assert_eq!(chdir("/\0"), 0);
+    assert_eq!(unlink("dir_cwd\0"), 0);
+    println!("Test chdir OK!");
+    0
+}
@@ -0,0 +1,54 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{
+    close, dup3, exec, exit, fcntl, fork, open, unlink, waitpid, OpenFlags, FD_CLOEXEC, F_GETFD,
+    F_SETFD,
+};
+
+/// 测试 close-on-exec，exec 后带有该标志的文件描述符被关闭、其余的保留，
+/// 输出　Test cloexec OK! 就算正确。
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let fname = "fname_cloexec\0";
+    let rw = OpenFlags::CREATE | OpenFlags::RDWR;
+    // 3 is closed on exec from the start, 4 and 5 are flipped by fcntl
+    let flagged = open(fname, rw | OpenFlags::CLOEXEC);
+    let plain = open(fname, rw);
+    let unflagged = open(fname, rw | OpenFlags::CLOEXEC);
+    assert_eq!((flagged, plain, unflagged), (3, 4, 5));
+    assert_eq!(fcntl(3, F_GETFD, 0), FD_CLOEXEC as isize);
+    assert_eq!(fcntl(4, F_GETFD, 0), 0);
+    assert_eq!(fcntl(4, F_SETFD, FD_CLOEXEC), 0);
+    assert_eq!(fcntl(5, F_SETFD, 0), 0);
+    assert_eq!(fcntl(5, F_GETFD, 0), 0);
+    // a copy made by dup3 carries its own flag
+    assert_eq!(dup3(3, 6, OpenFlags::empty()), 6);
+    assert_eq!(dup3(5, 7, OpenFlags::CLOEXEC), 7);
+    assert_eq!(fcntl(6, F_GETFD, 0), 0);
+    assert_eq!(fcntl(7, F_GETFD, 0), FD_CLOEXEC as isize);
+    assert_eq!(fcntl(8, F_GETFD, 0), -1);
+    assert_eq!(fcntl(3, 100, 0), -1);
+
+    let pid = fork();
+    if pid == 0 {
+        // the flags are inherited, and the flagged fds go away only on exec
+        assert_eq!(fcntl(3, F_GETFD, 0), FD_CLOEXEC as isize);
+        exec("ch6_cloexec_probe\0", &[core::ptr::null::<u8>()]);
+        exit(-1);
+    }
+    let mut exit_code: i32 = -1;
+    assert_eq!(waitpid(pid as usize, &mut exit_code), pid);
+    // stdin, stdout, stderr, 5 and 6 survive
+    assert_eq!(exit_code, 0b0110_0111);
+    for fd in 3..8 {
+        close(fd);
+    }
+    unlink(fname);
+    println!("Test cloexec OK!");
+    0
+}
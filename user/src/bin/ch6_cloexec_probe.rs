@@ -0,0 +1,23 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{close, dup};
+
+/// ch6_cloexec 执行的程序，以退出码的各位报告 0~7 号文件描述符是否打开。
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let mut open_fds = 0;
+    for fd in 0..8 {
+        let copy = dup(fd);
+        if copy >= 0 {
+            open_fds |= 1 << fd;
+            close(copy as usize);
+        }
+    }
+    println!("open fds: {:#b}", open_fds);
+    open_fds
+}
@@ -0,0 +1,99 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{
+    close, condvar_create, condvar_signal, condvar_wait, exit, fork, lseek, mutex_blocking_create,
+    mutex_lock, mutex_unlock, open, read, unlink, waitpid, write, yield_, OpenFlags, SEEK_SET,
+};
+
+/// 测试条件变量，父进程作为生产者、子进程作为消费者，用一个互斥锁和一个条件变量
+/// 保护文件中的有界队列，输出　Test condvar OK! 就算正确。
+
+const QUEUE_SIZE: usize = 4;
+const ITEMS: usize = 200;
+/// The slot after the queue holds the number of items in it
+const COUNT_SLOT: usize = QUEUE_SIZE;
+
+fn read_slot(fd: usize, slot: usize) -> usize {
+    let mut buf = [0u8; 8];
+    lseek(fd, (slot * 8) as isize, SEEK_SET);
+    assert_eq!(read(fd, &mut buf), 8);
+    usize::from_le_bytes(buf)
+}
+
+fn write_slot(fd: usize, slot: usize, value: usize) {
+    lseek(fd, (slot * 8) as isize, SEEK_SET);
+    assert_eq!(write(fd, &value.to_le_bytes()), 8);
+}
+
+/// Take the items in order, giving the CPU away now and then
+/// so that the producer finds the queue full as well as empty
+fn consume(fname: &str, mutex_id: usize, condvar_id: usize) -> ! {
+    // an fd of its own, the file offset is shared after fork
+    let fd = open(fname, OpenFlags::RDWR);
+    assert!(fd > 0);
+    let fd = fd as usize;
+    for i in 0..ITEMS {
+        assert_eq!(mutex_lock(mutex_id), 0);
+        while read_slot(fd, COUNT_SLOT) == 0 {
+            assert_eq!(condvar_wait(condvar_id, mutex_id), 0);
+        }
+        assert_eq!(read_slot(fd, i % QUEUE_SIZE), i + 1);
+        let count = read_slot(fd, COUNT_SLOT);
+        write_slot(fd, COUNT_SLOT, count - 1);
+        assert_eq!(condvar_signal(condvar_id), 0);
+        assert_eq!(mutex_unlock(mutex_id), 0);
+        if i % 7 == 0 {
+            yield_();
+        }
+    }
+    close(fd);
+    exit(0)
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let fname = "fname_condvar\0";
+    let fd = open(fname, OpenFlags::CREATE | OpenFlags::TRUNC | OpenFlags::RDWR);
+    assert!(fd > 0);
+    let fd = fd as usize;
+    for slot in 0..=COUNT_SLOT {
+        write_slot(fd, slot, 0);
+    }
+
+    let mutex_id = mutex_blocking_create();
+    let condvar_id = condvar_create();
+    assert!(mutex_id >= 0 && condvar_id >= 0);
+    let (mutex_id, condvar_id) = (mutex_id as usize, condvar_id as usize);
+
+    let pid = fork();
+    if pid == 0 {
+        consume(fname, mutex_id, condvar_id);
+    }
+    for i in 0..ITEMS {
+        assert_eq!(mutex_lock(mutex_id), 0);
+        while read_slot(fd, COUNT_SLOT) == QUEUE_SIZE {
+            assert_eq!(condvar_wait(condvar_id, mutex_id), 0);
+        }
+        write_slot(fd, i % QUEUE_SIZE, i + 1);
+        let count = read_slot(fd, COUNT_SLOT);
+        write_slot(fd, COUNT_SLOT, count + 1);
+        assert_eq!(condvar_signal(condvar_id), 0);
+        assert_eq!(mutex_unlock(mutex_id), 0);
+    }
+    let mut exit_code: i32 = -1;
+    assert_eq!(waitpid(pid as usize, &mut exit_code), pid);
+    assert_eq!(exit_code, 0);
+    assert_eq!(read_slot(fd, COUNT_SLOT), 0);
+
+    assert_eq!(condvar_signal(100), -1);
+    assert_eq!(condvar_wait(100, mutex_id), -1);
+    assert_eq!(condvar_wait(condvar_id, 100), -1);
+    close(fd);
+    unlink(fname);
+    println!("Test condvar OK!");
+    0
+}
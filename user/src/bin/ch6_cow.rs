@@ -0,0 +1,72 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{close, fork, pipe, read, wait, write};
+
+/// 测试 fork 写时复制，输出　Test cow OK! 就算正确。
+
+/// Much more than the free frames left if every child got a copy
+const PAGES: usize = 256;
+const CHILDREN: usize = 8;
+static mut DATA: [u8; PAGES * 4096] = [0; PAGES * 4096];
+
+fn checksum() -> usize {
+    unsafe { DATA.iter().step_by(4096).map(|&b| b as usize).sum() }
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    for i in 0..PAGES {
+        unsafe {
+            DATA[i * 4096] = 1;
+        }
+    }
+    assert_eq!(checksum(), PAGES);
+    // a child writing a page leaves the one of the parent alone
+    if fork() == 0 {
+        unsafe {
+            DATA[0] = 2;
+        }
+        assert_eq!(checksum(), PAGES + 1);
+        // and the grandchild sees the page of the child
+        if fork() == 0 {
+            assert_eq!(checksum(), PAGES + 1);
+            return 0;
+        }
+        let mut exit_code: i32 = 1;
+        wait(&mut exit_code);
+        assert_eq!(exit_code, 0);
+        return 0;
+    }
+    let mut exit_code: i32 = 1;
+    wait(&mut exit_code);
+    assert_eq!(exit_code, 0);
+    assert_eq!(checksum(), PAGES);
+
+    // many children alive at once only read the pages
+    let mut pipe_fd = [0usize; 2];
+    assert_eq!(pipe(&mut pipe_fd), 0);
+    for _ in 0..CHILDREN {
+        if fork() == 0 {
+            close(pipe_fd[1]);
+            let mut byte = [0u8; 1];
+            assert_eq!(read(pipe_fd[0], &mut byte), 1);
+            close(pipe_fd[0]);
+            assert_eq!(checksum(), PAGES);
+            return 0;
+        }
+    }
+    close(pipe_fd[0]);
+    assert_eq!(write(pipe_fd[1], &[0u8; CHILDREN]), CHILDREN as isize);
+    close(pipe_fd[1]);
+    for _ in 0..CHILDREN {
+        exit_code = 1;
+        wait(&mut exit_code);
+        assert_eq!(exit_code, 0);
+    }
+    println!("Test cow OK!");
+    0
+}
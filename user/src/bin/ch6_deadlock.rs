@@ -0,0 +1,77 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{
+    enable_deadlock_detect, exit, fork, kill, mutex_blocking_create, mutex_lock, mutex_unlock,
+    semaphore_create, semaphore_down, semaphore_up, sleep, sys_enable_deadlock_detect, waitpid,
+    waitpid_nohang, SIGKILL,
+};
+
+/// 测试死锁检测，两个进程以相反顺序获取两把锁：开启检测时后一个请求被拒绝，
+/// 关闭检测时两者互相阻塞，输出　Test deadlock detection OK! 就算正确。
+
+/// Take `first` then `second`, waiting at `barrier` in between for
+/// the other child to take its first lock as well
+fn fork_locking(first: usize, second: usize, arrived: usize, barrier: usize) -> usize {
+    let pid = fork();
+    if pid == 0 {
+        assert_eq!(mutex_lock(first), 0);
+        semaphore_up(arrived);
+        assert_eq!(semaphore_down(barrier), 0);
+        mutex_lock(second);
+        exit(0);
+    }
+    pid as usize
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let mut exit_code: i32 = -1;
+    assert_eq!(enable_deadlock_detect(true), 0);
+    let a = mutex_blocking_create() as usize;
+    let b = mutex_blocking_create() as usize;
+    // taking a held lock again never succeeds
+    assert_eq!(mutex_lock(a), 0);
+    assert_eq!(mutex_lock(a), -0xdead);
+    let pid = fork();
+    if pid == 0 {
+        assert_eq!(mutex_lock(b), 0);
+        // blocks until the parent gives `a` up, it is not waiting for anything yet
+        assert_eq!(mutex_lock(a), 0);
+        assert_eq!(mutex_unlock(a), 0);
+        assert_eq!(mutex_unlock(b), 0);
+        exit(0);
+    }
+    sleep(50);
+    // the child holds `b` and waits for `a`
+    assert_eq!(mutex_lock(b), -0xdead);
+    assert_eq!(mutex_unlock(a), 0);
+    assert_eq!(waitpid(pid as usize, &mut exit_code), pid);
+    assert_eq!(exit_code, 0);
+    assert_eq!(mutex_lock(b), 0);
+    assert_eq!(mutex_unlock(b), 0);
+
+    // the same order without detection blocks both children for good
+    assert_eq!(enable_deadlock_detect(false), 0);
+    assert_eq!(sys_enable_deadlock_detect(2), -1);
+    let c = mutex_blocking_create() as usize;
+    let d = mutex_blocking_create() as usize;
+    let c_taken = semaphore_create(0) as usize;
+    let d_taken = semaphore_create(0) as usize;
+    let first = fork_locking(c, d, c_taken, d_taken);
+    let second = fork_locking(d, c, d_taken, c_taken);
+    sleep(100);
+    for &pid in [first, second].iter() {
+        assert_eq!(waitpid_nohang(pid, &mut exit_code), 0);
+    }
+    for &pid in [first, second].iter() {
+        assert_eq!(kill(pid, SIGKILL), 0);
+        assert_eq!(waitpid(pid, &mut exit_code), pid as isize);
+        assert_eq!(exit_code, -(SIGKILL as i32));
+    }
+    println!("Test deadlock detection OK!");
+    0
+}
@@ -0,0 +1,51 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{close, fstat, open, read, write, OpenFlags, Stat, StatMode};
+
+/// 测试 /dev/null 与 /dev/zero 字符设备，输出　Test dev OK! 就算正确。
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let zero = open("/dev/zero\0", OpenFlags::RDONLY);
+    assert!(zero > 0);
+    let zero = zero as usize;
+    let mut buf = [0xffu8; 100];
+    assert_eq!(read(zero, &mut buf), 100);
+    assert!(buf.iter().all(|&byte| byte == 0));
+    // it never runs dry
+    buf = [0xffu8; 100];
+    assert_eq!(read(zero, &mut buf), 100);
+    assert!(buf.iter().all(|&byte| byte == 0));
+    // opened for reading only
+    assert_eq!(write(zero, &buf), -1);
+    let stat = Stat::new();
+    assert_eq!(fstat(zero, &stat), 0);
+    assert_eq!(stat.mode, StatMode::CHR);
+    assert_eq!(stat.size, 0);
+    close(zero);
+
+    // CREATE makes no file in place of the device
+    let null = open("/dev/null\0", OpenFlags::CREATE | OpenFlags::RDWR);
+    assert!(null > 0);
+    let null = null as usize;
+    let big = [0x5au8; 4096];
+    for _ in 0..16 {
+        assert_eq!(write(null, &big), big.len() as isize);
+    }
+    assert_eq!(read(null, &mut buf), 0);
+    let stat = Stat::new();
+    assert_eq!(fstat(null, &stat), 0);
+    assert_eq!(stat.mode, StatMode::CHR);
+    assert_eq!(stat.size, 0);
+    close(null);
+
+    // the devices are not in the file system
+    assert!(open("/dev\0", OpenFlags::RDONLY) < 0);
+    assert!(open("/dev/full\0", OpenFlags::RDONLY) < 0);
+    println!("Test dev OK!");
+    0
+}
@@ -0,0 +1,39 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{close, dup, lseek, open, read, write, OpenFlags, SEEK_SET};
+
+/// 测试 dup 复制文件描述符，输出　Test dup OK! 就算正确。
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let test_str = "Hello, world!";
+    let fname = "fname_dup\0";
+    let fd = open(fname, OpenFlags::CREATE | OpenFlags::RDWR);
+    assert!(fd > 0);
+    let fd = fd as usize;
+    let new_fd = dup(fd);
+    assert!(new_fd > 0);
+    let new_fd = new_fd as usize;
+    assert_ne!(fd, new_fd);
+    assert_eq!(dup(100), -1);
+
+    write(fd, test_str.as_bytes());
+    // both descriptors share the same offset
+    assert_eq!(lseek(new_fd, 0, SEEK_SET), 0);
+    let mut buffer = [0u8; 100];
+    let read_len = read(new_fd, &mut buffer) as usize;
+    assert_eq!(test_str, core::str::from_utf8(&buffer[..read_len]).unwrap());
+
+    // closing one descriptor keeps the other alive
+    close(fd);
+    assert_eq!(lseek(new_fd, 7, SEEK_SET), 7);
+    let read_len = read(new_fd, &mut buffer) as usize;
+    assert_eq!("world!", core::str::from_utf8(&buffer[..read_len]).unwrap());
+    close(new_fd);
+    println!("Test dup OK!");
+    0
+}
@@ -0,0 +1,45 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{close, dup2, dup3, lseek, open, read, write, OpenFlags, SEEK_SET};
+
+/// 测试 dup2/dup3 复制文件描述符到指定位置，输出　Test dup2 OK! 就算正确。
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let test_str = "Hello, world!";
+    let fd = open("fname_dup2a\0", OpenFlags::CREATE | OpenFlags::RDWR);
+    assert!(fd > 0);
+    let fd = fd as usize;
+    let other_fd = open("fname_dup2b\0", OpenFlags::CREATE | OpenFlags::RDWR);
+    assert!(other_fd > 0);
+    let other_fd = other_fd as usize;
+
+    // the fd table grows to hold a far away target
+    assert_eq!(dup2(fd, 10), 10);
+    write(10, test_str.as_bytes());
+    assert_eq!(lseek(fd, 0, SEEK_SET), 0);
+    let mut buffer = [0u8; 100];
+    let read_len = read(fd, &mut buffer) as usize;
+    assert_eq!(test_str, core::str::from_utf8(&buffer[..read_len]).unwrap());
+
+    // the file held by the target is replaced
+    assert_eq!(dup2(fd, other_fd), other_fd as isize);
+    assert_eq!(lseek(other_fd, 7, SEEK_SET), 7);
+    let read_len = read(other_fd, &mut buffer) as usize;
+    assert_eq!("world!", core::str::from_utf8(&buffer[..read_len]).unwrap());
+
+    assert_eq!(dup2(fd, fd), fd as isize);
+    assert_eq!(dup2(20, 21), -1);
+    assert_eq!(dup3(fd, fd, OpenFlags::empty()), -1);
+    assert_eq!(dup3(fd, 11, OpenFlags::empty()), 11);
+    close(fd);
+    close(other_fd);
+    close(10);
+    close(11);
+    println!("Test dup2 OK!");
+    0
+}
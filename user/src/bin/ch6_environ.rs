@@ -0,0 +1,96 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+extern crate alloc;
+
+use alloc::string::String;
+use user_lib::{
+    close, dup2, environ, exec, execve, exit, fork, getenv, pipe, read, setenv, unsetenv, waitpid,
+};
+
+/// 测试环境变量，fork 出的子进程继承父进程设置的变量，exec 默认沿用当前环境，
+/// 传入新的 envp 时则被替换，输出　Test environ OK! 就算正确。
+
+const ECHO: &str = "ch6_exec_echo\0";
+
+/// Run `exec` in a child whose stdout goes to a pipe,
+/// returns what the child wrote
+fn run(exec: impl FnOnce()) -> ([u8; 256], usize) {
+    let mut fds = [0usize; 2];
+    assert_eq!(pipe(&mut fds), 0);
+    let pid = fork();
+    if pid == 0 {
+        close(fds[0]);
+        assert_eq!(dup2(fds[1], 1), 1);
+        close(fds[1]);
+        exec();
+        exit(-1);
+    }
+    close(fds[1]);
+    let mut out = [0u8; 256];
+    let mut len = 0;
+    loop {
+        let n = read(fds[0], &mut out[len..]);
+        assert!(n >= 0);
+        if n == 0 {
+            break;
+        }
+        len += n as usize;
+    }
+    close(fds[0]);
+    let mut exit_code: i32 = -1;
+    assert_eq!(waitpid(pid as usize, &mut exit_code), pid);
+    assert_eq!(exit_code, 0);
+    (out, len)
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let initial = environ().len();
+    assert_eq!(getenv("CH6_ENV"), None);
+    assert_eq!(setenv("CH6_ENV\0", "first\0"), 0);
+    assert_eq!(setenv("CH6_ENV\0", "parent\0"), 0);
+    assert_eq!(getenv("CH6_ENV"), Some(String::from("parent")));
+    assert_eq!(environ().len(), initial + 1);
+    assert_eq!(setenv("BAD=NAME\0", "x\0"), -1);
+    assert_eq!(setenv("\0", "x\0"), -1);
+
+    // the child gets a copy of its own
+    let pid = fork();
+    if pid == 0 {
+        assert_eq!(getenv("CH6_ENV"), Some(String::from("parent")));
+        assert_eq!(setenv("CH6_ENV\0", "child\0"), 0);
+        assert_eq!(getenv("CH6_ENV"), Some(String::from("child")));
+        exit(0);
+    }
+    let mut exit_code: i32 = -1;
+    assert_eq!(waitpid(pid as usize, &mut exit_code), pid);
+    assert_eq!(exit_code, 0);
+    assert_eq!(getenv("CH6_ENV"), Some(String::from("parent")));
+
+    // exec keeps the environment, unless given a new one
+    assert_eq!(unsetenv("OTHER\0"), 0);
+    let (out, len) = run(|| {
+        exec(ECHO, &[ECHO.as_ptr(), core::ptr::null::<u8>()]);
+    });
+    let out = core::str::from_utf8(&out[..len]).unwrap();
+    assert!(out.contains("env=CH6_ENV=parent\n"));
+    let (out, len) = run(|| {
+        let envs = ["OTHER=1\0".as_ptr(), core::ptr::null::<u8>()];
+        execve(ECHO, &[ECHO.as_ptr(), core::ptr::null::<u8>()], &envs);
+    });
+    assert_eq!(&out[..len], b"argc=1\narg=ch6_exec_echo\nenv=OTHER=1\n");
+    let (out, len) = run(|| {
+        execve(ECHO, &[core::ptr::null::<u8>()], &[core::ptr::null::<u8>()]);
+    });
+    assert_eq!(&out[..len], b"argc=0\n");
+
+    assert_eq!(unsetenv("CH6_ENV\0"), 0);
+    assert_eq!(getenv("CH6_ENV"), None);
+    assert_eq!(environ().len(), initial);
+    println!("Test environ OK!");
+    0
+}
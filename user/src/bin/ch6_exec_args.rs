@@ -0,0 +1,114 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{
+    close, dup2, execve, exit, fork, mmap_file, munmap, open, pipe, read, syscall, unlink, waitpid,
+    write, OpenFlags, MAP_PRIVATE, SYSCALL_EXEC,
+};
+
+/// 测试 exec 传递参数与环境变量，参数也可以在尚未访问的文件映射中，
+/// 被执行的程序逐行回显，内容与传入的一致，
+/// 输出　Test exec args OK! 就算正确。
+
+const ECHO: &str = "ch6_exec_echo\0";
+const START: usize = 0x10000000;
+
+/// Run `exec` in a child whose stdout goes to a pipe,
+/// returns what the child wrote
+fn run(exec: impl FnOnce()) -> ([u8; 256], usize) {
+    let mut fds = [0usize; 2];
+    assert_eq!(pipe(&mut fds), 0);
+    let pid = fork();
+    if pid == 0 {
+        close(fds[0]);
+        assert_eq!(dup2(fds[1], 1), 1);
+        close(fds[1]);
+        exec();
+        exit(-1);
+    }
+    close(fds[1]);
+    let mut out = [0u8; 256];
+    let mut len = 0;
+    loop {
+        let n = read(fds[0], &mut out[len..]);
+        assert!(n >= 0);
+        if n == 0 {
+            break;
+        }
+        len += n as usize;
+    }
+    close(fds[0]);
+    let mut exit_code: i32 = -1;
+    assert_eq!(waitpid(pid as usize, &mut exit_code), pid);
+    assert_eq!(exit_code, 0);
+    (out, len)
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let (out, len) = run(|| {
+        let args = [
+            ECHO.as_ptr(),
+            "hello\0".as_ptr(),
+            "with space\0".as_ptr(),
+            "\0".as_ptr(),
+            core::ptr::null::<u8>(),
+        ];
+        let envs = ["HOME=/\0".as_ptr(), "TERM=dumb\0".as_ptr(), core::ptr::null::<u8>()];
+        execve(ECHO, &args, &envs);
+    });
+    let expected = concat!(
+        "argc=4\n",
+        "arg=ch6_exec_echo\narg=hello\narg=with space\narg=\n",
+        "env=HOME=/\nenv=TERM=dumb\n",
+    );
+    assert_eq!(&out[..len], expected.as_bytes());
+
+    // null argv and envp are empty arrays
+    let (out, len) = run(|| {
+        syscall(SYSCALL_EXEC, [ECHO.as_ptr() as usize, 0, 0]);
+    });
+    assert_eq!(&out[..len], b"argc=0\n");
+
+    // the path, argv and its strings each on a page of a file mapping
+    // never accessed yet, read in as the kernel reaches them
+    let fname = "fname_exec_args\0";
+    let fd = open(fname, OpenFlags::CREATE | OpenFlags::WRONLY);
+    assert!(fd > 0);
+    let fd = fd as usize;
+    let zeros = [0u8; 512];
+    let pad = |fd: usize, len: usize| {
+        for _ in 0..(4096 - len) / 512 {
+            assert_eq!(write(fd, &zeros), 512);
+        }
+        assert_eq!(write(fd, &zeros[..(4096 - len) % 512]), ((4096 - len) % 512) as isize);
+    };
+    assert_eq!(write(fd, ECHO.as_bytes()), ECHO.len() as isize);
+    pad(fd, ECHO.len());
+    let word = core::mem::size_of::<usize>();
+    for ptr in [START, START + 2 * 4096, 0] {
+        assert_eq!(write(fd, &ptr.to_ne_bytes()), word as isize);
+    }
+    pad(fd, 3 * word);
+    assert_eq!(write(fd, b"lazy\0"), 5);
+    close(fd);
+    let fd = open(fname, OpenFlags::RDONLY);
+    assert!(fd > 0);
+    assert_eq!(mmap_file(START, 3 * 4096, 1, MAP_PRIVATE, fd as usize, 0), START as isize);
+    close(fd as usize);
+    let (out, len) = run(|| {
+        syscall(SYSCALL_EXEC, [START, START + 4096, 0]);
+    });
+    assert_eq!(&out[..len], b"argc=2\narg=ch6_exec_echo\narg=lazy\n");
+    assert_eq!(munmap(START, 3 * 4096), 0);
+    assert_eq!(unlink(fname), 0);
+
+    // a bad pointer in argv fails the exec, and the caller goes on
+    let args = [ECHO.as_ptr(), 0x10 as *const u8, core::ptr::null::<u8>()];
+    assert_eq!(execve(ECHO, &args, &[core::ptr::null::<u8>()]), -1);
+    println!("Test exec args OK!");
+    0
+}
@@ -0,0 +1,21 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::environ;
+
+/// ch6_exec_args 执行的程序，逐行输出 argc、各个参数与环境变量。
+
+#[no_mangle]
+pub fn main(argc: usize, argv: &[&str]) -> i32 {
+    println!("argc={}", argc);
+    for arg in argv {
+        println!("arg={}", arg);
+    }
+    for env in environ() {
+        println!("env={}", env);
+    }
+    0
+}
@@ -0,0 +1,75 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{
+    close, fallocate, fstat, open, pread, statfs, unlink, write, OpenFlags, Stat, Statfs,
+    FALLOC_FL_KEEP_SIZE,
+};
+
+/// 测试 fallocate，预先分配的块使空闲块减少，之后写入不再分配新块，
+/// 空闲块不足时失败且文件不变，输出　Test fallocate OK! 就算正确。
+
+const BLOCKS: usize = 16;
+
+fn free_blocks(path: &str) -> u64 {
+    let mut st = Statfs::default();
+    assert_eq!(statfs(path, &mut st), 0);
+    st.bfree
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let fname = "fname_fallocate\0";
+    let fd = open(fname, OpenFlags::CREATE | OpenFlags::TRUNC | OpenFlags::RDWR);
+    assert!(fd > 0);
+    let fd = fd as usize;
+    let before = free_blocks(fname);
+
+    // taken up front, the size stays
+    assert_eq!(fallocate(fd, FALLOC_FL_KEEP_SIZE, 0, (BLOCKS * 512) as isize), 0);
+    let stat = Stat::new();
+    assert_eq!(fstat(fd, &stat), 0);
+    assert_eq!(stat.size, 0);
+    assert_eq!(stat.blocks, BLOCKS as u64);
+    let reserved = free_blocks(fname);
+    assert_eq!(before - reserved, BLOCKS as u64);
+
+    // writing into the reserved blocks takes no more
+    let block = [0x5au8; 512];
+    for _ in 0..BLOCKS {
+        assert_eq!(write(fd, &block), 512);
+    }
+    assert_eq!(free_blocks(fname), reserved);
+
+    // growing the size, the new part reads as zeros
+    assert_eq!(fallocate(fd, 0, (BLOCKS * 512) as isize, 512), 0);
+    assert_eq!(fstat(fd, &stat), 0);
+    assert_eq!(stat.size, ((BLOCKS + 1) * 512) as u64);
+    let mut buf = [0xffu8; 512];
+    assert_eq!(pread(fd, &mut buf, BLOCKS * 512), 512);
+    assert!(buf.iter().all(|&b| b == 0));
+
+    // more than the filesystem has, the file stays as it is
+    let free = free_blocks(fname);
+    assert_eq!(fallocate(fd, 0, 0, ((free as usize + BLOCKS) * 512) as isize), -1);
+    assert_eq!(free_blocks(fname), free);
+    let after = Stat::new();
+    assert_eq!(fstat(fd, &after), 0);
+    assert_eq!(after.size, stat.size);
+    assert_eq!(after.blocks, stat.blocks);
+    assert_eq!(fallocate(fd, 0, 0, 0), -1);
+    assert_eq!(fallocate(fd, 2, 0, 512), -1);
+    close(fd);
+
+    let fd = open(fname, OpenFlags::RDONLY);
+    assert!(fd > 0);
+    assert_eq!(fallocate(fd as usize, 0, 0, 512), -1);
+    close(fd as usize);
+    assert_eq!(unlink(fname), 0);
+    assert_eq!(free_blocks("/\0"), before);
+    println!("Test fallocate OK!");
+    0
+}
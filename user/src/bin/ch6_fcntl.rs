@@ -0,0 +1,77 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{
+    close, dup, fcntl, lseek, open, pipe, read, unlink, write, OpenFlags, FD_CLOEXEC, F_DUPFD,
+    F_GETFD, F_GETFL, F_SETFD, F_SETFL, SEEK_CUR, SEEK_SET,
+};
+
+/// 测试 fcntl 的 F_GETFL/F_SETFL/F_DUPFD，管道设为非阻塞后空读立即返回 -1，
+/// 输出　Test fcntl OK! 就算正确。
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let mut pipe_fd = [0usize; 2];
+    assert_eq!(pipe(&mut pipe_fd), 0);
+    let (rfd, wfd) = (pipe_fd[0], pipe_fd[1]);
+    assert_eq!(fcntl(rfd, F_GETFL, 0), OpenFlags::RDONLY.bits() as isize);
+    assert_eq!(fcntl(wfd, F_GETFL, 0), OpenFlags::WRONLY.bits() as isize);
+
+    // an empty pipe is read without waiting
+    assert_eq!(fcntl(rfd, F_SETFL, OpenFlags::NONBLOCK.bits() as usize), 0);
+    let flags = fcntl(rfd, F_GETFL, 0);
+    assert_eq!(flags, OpenFlags::NONBLOCK.bits() as isize);
+    let mut buf = [0u8; 8];
+    assert_eq!(read(rfd, &mut buf), -1);
+    assert_eq!(write(wfd, b"abc"), 3);
+    assert_eq!(read(rfd, &mut buf), 3);
+    assert_eq!(&buf[..3], b"abc");
+    // the flags belong to the pipe, not to the fd
+    let copy = dup(rfd);
+    assert!(copy > 0);
+    assert_eq!(fcntl(copy as usize, F_GETFL, 0), flags);
+    close(copy as usize);
+    // a full pipe is written without waiting as well
+    assert_eq!(fcntl(wfd, F_SETFL, OpenFlags::NONBLOCK.bits() as usize), 0);
+    let chunk = [b'x'; 64];
+    let written = write(wfd, &chunk);
+    assert!(written > 0 && written < chunk.len() as isize);
+    assert_eq!(write(wfd, &chunk), -1);
+    assert_eq!(fcntl(rfd, F_SETFL, 0), 0);
+    assert_eq!(fcntl(rfd, F_GETFL, 0), OpenFlags::RDONLY.bits() as isize);
+
+    // lowest free fd from `arg` on, not closed on exec
+    assert_eq!(fcntl(rfd, F_SETFD, FD_CLOEXEC), 0);
+    assert_eq!(fcntl(rfd, F_DUPFD, 10), 10);
+    assert_eq!(fcntl(rfd, F_DUPFD, 10), 11);
+    assert_eq!(fcntl(10, F_GETFD, 0), 0);
+    assert!(read(10, &mut buf) > 0);
+    close(10);
+    close(11);
+
+    // append can be switched on for a file
+    let fname = "fname_fcntl\0";
+    let fd = open(fname, OpenFlags::CREATE | OpenFlags::TRUNC | OpenFlags::RDWR);
+    assert!(fd > 0);
+    let fd = fd as usize;
+    assert_eq!(fcntl(fd, F_GETFL, 0), OpenFlags::RDWR.bits() as isize);
+    assert_eq!(write(fd, b"0123"), 4);
+    assert_eq!(fcntl(fd, F_SETFL, (OpenFlags::APPEND | OpenFlags::TRUNC).bits() as usize), 0);
+    let flags = OpenFlags::RDWR | OpenFlags::APPEND;
+    assert_eq!(fcntl(fd, F_GETFL, 0), flags.bits() as isize);
+    assert_eq!(lseek(fd, 0, SEEK_SET), 0);
+    assert_eq!(write(fd, b"45"), 2);
+    assert_eq!(lseek(fd, 0, SEEK_CUR), 6);
+    close(fd);
+
+    assert_eq!(fcntl(rfd, 99, 0), -1);
+    assert_eq!(fcntl(99, F_GETFL, 0), -1);
+    close(rfd);
+    close(wfd);
+    unlink(fname);
+    println!("Test fcntl OK!");
+    0
+}
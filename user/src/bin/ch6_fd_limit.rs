@@ -0,0 +1,46 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{close, dup, open, pipe, unlink, OpenFlags};
+
+/// 测试文件描述符表按需增长直到上限，并复用最小的空闲描述符，
+/// 输出　Test fd limit OK! 就算正确。
+
+/// `MAX_FD_NUM` of the kernel
+const MAX_FD_NUM: usize = 128;
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let fname = "fname_fd_limit\0";
+    let rw = OpenFlags::CREATE | OpenFlags::RDWR;
+    // stdin, stdout and stderr take the first three
+    for fd in 3..MAX_FD_NUM {
+        assert_eq!(open(fname, rw), fd as isize);
+    }
+    assert_eq!(open(fname, rw), -1);
+    assert_eq!(dup(0), -1);
+    let mut pipe_fd = [0usize; 2];
+    assert_eq!(pipe(&mut pipe_fd), -1);
+
+    // the lowest freed fd is taken first
+    assert_eq!(close(60), 0);
+    assert_eq!(close(3), 0);
+    assert_eq!(open(fname, rw), 3);
+    assert_eq!(open(fname, rw), 60);
+    assert_eq!(open(fname, rw), -1);
+
+    // a pipe that doesn't fit leaves no fd behind
+    assert_eq!(close(100), 0);
+    assert_eq!(pipe(&mut pipe_fd), -1);
+    assert_eq!(dup(0), 100);
+
+    for fd in 3..MAX_FD_NUM {
+        assert_eq!(close(fd), 0);
+    }
+    unlink(fname);
+    println!("Test fd limit OK!");
+    0
+}
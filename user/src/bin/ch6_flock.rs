@@ -0,0 +1,77 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{
+    close, exit, flock, fork, open, pread, read, sleep, unlink, waitpid, write, OpenFlags,
+    LOCK_EX, LOCK_NB, LOCK_SH, LOCK_UN,
+};
+
+/// 测试 flock，两个进程争用排他锁时依次持有，LOCK_NB 拿不到锁时立即返回 -1，
+/// 锁在关闭文件与进程退出时释放，输出　Test flock OK! 就算正确。
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let fname = "fname_flock\0";
+    let fd = open(fname, OpenFlags::CREATE | OpenFlags::TRUNC | OpenFlags::RDWR);
+    assert!(fd > 0);
+    let fd = fd as usize;
+    assert_eq!(flock(fd, 0), -1);
+    assert_eq!(flock(fd, LOCK_SH | LOCK_EX), -1);
+    assert_eq!(flock(fd, LOCK_EX), 0);
+    // taking it again through the same opened file is fine
+    assert_eq!(flock(fd, LOCK_EX | LOCK_NB), 0);
+
+    let pid = fork();
+    if pid == 0 {
+        // an opened file of its own, which the lock held is not
+        let fd = open(fname, OpenFlags::RDWR) as usize;
+        if flock(fd, LOCK_EX | LOCK_NB) != -1 || flock(fd, LOCK_SH | LOCK_NB) != -1 {
+            exit(1);
+        }
+        // waits for the parent to be done
+        if flock(fd, LOCK_EX) != 0 {
+            exit(2);
+        }
+        let mut buffer = [0u8; 1];
+        if read(fd, &mut buffer) != 1 || buffer[0] != b'A' {
+            exit(3);
+        }
+        sleep(50);
+        write(fd, b"B");
+        // the lock goes as it exits
+        exit(0);
+    }
+    sleep(50);
+    assert_eq!(write(fd, b"A"), 1);
+    assert_eq!(flock(fd, LOCK_UN), 0);
+    // the child holds it until it exits
+    sleep(10);
+    assert_eq!(flock(fd, LOCK_EX | LOCK_NB), -1);
+    assert_eq!(flock(fd, LOCK_EX), 0);
+    let mut buffer = [0u8; 2];
+    assert_eq!(pread(fd, &mut buffer, 0), 2);
+    assert_eq!(&buffer, b"AB");
+    let mut exit_code: i32 = -1;
+    assert_eq!(waitpid(pid as usize, &mut exit_code), pid);
+    assert_eq!(exit_code, 0);
+
+    // shared locks go along, but keep an exclusive one out
+    let fd2 = open(fname, OpenFlags::RDONLY) as usize;
+    assert_eq!(flock(fd2, LOCK_SH | LOCK_NB), -1);
+    assert_eq!(flock(fd, LOCK_SH), 0);
+    assert_eq!(flock(fd2, LOCK_SH | LOCK_NB), 0);
+    assert_eq!(flock(fd, LOCK_EX | LOCK_NB), -1);
+    // closing the file releases its lock
+    close(fd2);
+    assert_eq!(flock(fd, LOCK_EX | LOCK_NB), 0);
+    close(fd);
+    let fd = open(fname, OpenFlags::RDONLY) as usize;
+    assert_eq!(flock(fd, LOCK_EX | LOCK_NB), 0);
+    close(fd);
+    unlink(fname);
+    println!("Test flock OK!");
+    0
+}
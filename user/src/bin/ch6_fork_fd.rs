@@ -0,0 +1,45 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{
+    close, exit, fork, lseek, open, read, unlink, waitpid, write, OpenFlags, SEEK_CUR, SEEK_SET,
+};
+
+/// 测试 fork 后父子进程共享打开的文件及其偏移，但各自关闭，输出　Test fork fd OK! 就算正确。
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let fname = "fname_fork_fd\0";
+    let fd = open(fname, OpenFlags::CREATE | OpenFlags::TRUNC | OpenFlags::RDWR);
+    assert!(fd > 0);
+    let fd = fd as usize;
+    assert_eq!(write(fd, b"ab"), 2);
+
+    let pid = fork();
+    if pid == 0 {
+        // starts where the parent left off
+        assert_eq!(lseek(fd, 0, SEEK_CUR), 2);
+        assert_eq!(write(fd, b"cd"), 2);
+        assert_eq!(close(fd), 0);
+        assert_eq!(write(fd, b"xx"), -1);
+        exit(0);
+    }
+    let mut exit_code: i32 = -1;
+    assert_eq!(waitpid(pid as usize, &mut exit_code), pid);
+    assert_eq!(exit_code, 0);
+
+    // the child's write moved the offset, and its close left the fd open
+    assert_eq!(lseek(fd, 0, SEEK_CUR), 4);
+    assert_eq!(write(fd, b"ef"), 2);
+    let mut buf = [0u8; 16];
+    assert_eq!(lseek(fd, 0, SEEK_SET), 0);
+    let len = read(fd, &mut buf) as usize;
+    assert_eq!(&buf[..len], b"abcdef");
+    close(fd);
+    unlink(fname);
+    println!("Test fork fd OK!");
+    0
+}
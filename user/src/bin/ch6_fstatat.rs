@@ -0,0 +1,72 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{
+    close, fstat, fstatat, mkdir, open, symlink, unlink, write, OpenFlags, Stat, StatMode,
+    AT_FDCWD, AT_SYMLINK_NOFOLLOW,
+};
+
+/// 测试 fstatat，分别以 AT_FDCWD 和目录 fd 加相对路径获取文件状态，
+/// 输出　Test fstatat OK! 就算正确。
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let fd = open("fname_fstatat\0", OpenFlags::CREATE | OpenFlags::TRUNC | OpenFlags::WRONLY);
+    assert!(fd > 0);
+    assert_eq!(write(fd as usize, b"fstatat"), 7);
+    let opened = Stat::new();
+    assert_eq!(fstat(fd as usize, &opened), 0);
+    close(fd as usize);
+
+    // relative to the working directory, as fstat sees it
+    let mut stat = Stat::new();
+    assert_eq!(fstatat(AT_FDCWD, "fname_fstatat\0", &mut stat, 0), 0);
+    assert_eq!(stat.mode, StatMode::FILE);
+    assert_eq!(stat.size, 7);
+    assert_eq!(stat.ino, opened.ino);
+    assert_eq!(stat.nlink, 1);
+    assert_eq!(fstatat(AT_FDCWD, "no_such_fstatat\0", &mut stat, 0), -1);
+
+    // relative to a directory fd
+    assert_eq!(mkdir("dir_fstatat\0", 0o755), 0);
+    let fd = open("dir_fstatat/inner\0", OpenFlags::CREATE | OpenFlags::WRONLY);
+    assert!(fd > 0);
+    assert_eq!(write(fd as usize, b"inside"), 6);
+    close(fd as usize);
+    let dirfd = open("dir_fstatat\0", OpenFlags::RDONLY);
+    assert!(dirfd > 0);
+    assert_eq!(fstatat(dirfd, "inner\0", &mut stat, 0), 0);
+    assert_eq!(stat.mode, StatMode::FILE);
+    assert_eq!(stat.size, 6);
+    assert_eq!(fstatat(dirfd, ".\0", &mut stat, 0), 0);
+    assert_eq!(stat.mode, StatMode::DIR);
+    assert_eq!(fstatat(dirfd, "../fname_fstatat\0", &mut stat, 0), 0);
+    assert_eq!(stat.ino, opened.ino);
+    assert_eq!(fstatat(dirfd, "fname_fstatat\0", &mut stat, 0), -1);
+    // a file is no directory to start from
+    let fd = open("fname_fstatat\0", OpenFlags::RDONLY);
+    assert!(fd > 0);
+    assert_eq!(fstatat(fd, "inner\0", &mut stat, 0), -1);
+    close(fd as usize);
+
+    // a symbolic link, followed or looked at itself
+    assert_eq!(symlink("inner\0", "dir_fstatat/link\0"), 0);
+    assert_eq!(fstatat(dirfd, "link\0", &mut stat, 0), 0);
+    assert_eq!(stat.mode, StatMode::FILE);
+    assert_eq!(stat.size, 6);
+    assert_eq!(fstatat(dirfd, "link\0", &mut stat, AT_SYMLINK_NOFOLLOW), 0);
+    assert_eq!(stat.mode, StatMode::LNK);
+    assert_eq!(stat.size, 5);
+    close(dirfd as usize);
+    assert_eq!(fstatat(dirfd, "inner\0", &mut stat, 0), -1);
+
+    assert_eq!(unlink("dir_fstatat/link\0"), 0);
+    assert_eq!(unlink("dir_fstatat/inner\0"), 0);
+    assert_eq!(unlink("dir_fstatat\0"), 0);
+    assert_eq!(unlink("fname_fstatat\0"), 0);
+    println!("Test fstatat OK!");
+    0
+}
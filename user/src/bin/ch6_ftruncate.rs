@@ -0,0 +1,50 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{
+    close, fstat, ftruncate, lseek, open, read, unlink, write, OpenFlags, Stat, SEEK_SET,
+};
+
+/// 测试 ftruncate 修改文件大小，输出　Test ftruncate OK! 就算正确。
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let fname = "fname_ftruncate\0";
+    let fd = open(fname, OpenFlags::CREATE | OpenFlags::RDWR);
+    assert!(fd > 0);
+    let fd = fd as usize;
+    // a few blocks so that shrinking gives some of them back
+    let data = [b'x'; 2000];
+    assert_eq!(write(fd, &data), 2000);
+    let stat = Stat::new();
+    fstat(fd, &stat);
+    assert_eq!(stat.size, 2000);
+
+    // shrink into the middle of a block, then extend again
+    assert_eq!(ftruncate(fd, 700), 0);
+    fstat(fd, &stat);
+    assert_eq!(stat.size, 700);
+    assert_eq!(ftruncate(fd, 3000), 0);
+    fstat(fd, &stat);
+    assert_eq!(stat.size, 3000);
+    assert_eq!(lseek(fd, 0, SEEK_SET), 0);
+    let mut buffer = [0u8; 3000];
+    assert_eq!(read(fd, &mut buffer), 3000);
+    assert!(buffer[..700].iter().all(|&b| b == b'x'));
+    // the old data past the shrink point doesn't come back
+    assert!(buffer[700..].iter().all(|&b| b == 0));
+
+    assert_eq!(ftruncate(fd, -1), -1);
+    assert_eq!(ftruncate(100, 0), -1);
+    close(fd);
+    // a read-only file can't be resized
+    let fd = open(fname, OpenFlags::RDONLY) as usize;
+    assert_eq!(ftruncate(fd, 0), -1);
+    close(fd);
+    assert_eq!(unlink(fname), 0);
+    println!("Test ftruncate OK!");
+    0
+}
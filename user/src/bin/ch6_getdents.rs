@@ -0,0 +1,76 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{
+    close, getdents64, mkdir, open, unlink, OpenFlags, DT_DIR, DT_REG,
+};
+
+/// 测试 getdents64 分批读取目录项，输出　Test getdents OK! 就算正确。
+
+const NAMES: [&str; 7] = [".", "..", "f0", "f1", "f2", "f3", "f4"];
+const PATHS: [&str; 5] = [
+    "dir_getdents/f0\0",
+    "dir_getdents/f1\0",
+    "dir_getdents/f2\0",
+    "dir_getdents/f3\0",
+    "dir_getdents/f4\0",
+];
+
+#[no_mangle]
+pub fn main() -> i32 {
+    assert_eq!(mkdir("dir_getdents\0", 0o755), 0);
+    for path in PATHS {
+        let fd = open(path, OpenFlags::CREATE | OpenFlags::WRONLY);
+        assert!(fd > 0);
+        // a regular file has no entries
+        let mut buf = [0u8; 64];
+        assert_eq!(getdents64(fd as usize, &mut buf), -1);
+        close(fd as usize);
+    }
+
+    let fd = open("dir_getdents\0", OpenFlags::RDONLY);
+    assert!(fd > 0);
+    let fd = fd as usize;
+    // too small for a single record
+    let mut buf = [0u8; 8];
+    assert_eq!(getdents64(fd, &mut buf), -1);
+    // room for one record at a time, so the listing takes several calls
+    let mut buf = [0u8; 40];
+    let mut seen = [0usize; NAMES.len()];
+    let mut calls = 0;
+    loop {
+        let len = getdents64(fd, &mut buf);
+        assert!(len >= 0);
+        if len == 0 {
+            break;
+        }
+        calls += 1;
+        let mut pos = 0usize;
+        while pos < len as usize {
+            let reclen = u16::from_le_bytes([buf[pos + 16], buf[pos + 17]]) as usize;
+            let d_type = buf[pos + 18];
+            let name_len = buf[pos + 19..pos + reclen]
+                .iter()
+                .position(|&b| b == 0)
+                .unwrap();
+            let name = core::str::from_utf8(&buf[pos + 19..pos + 19 + name_len]).unwrap();
+            let idx = NAMES.iter().position(|&n| n == name).unwrap();
+            assert_eq!(d_type, if idx < 2 { DT_DIR } else { DT_REG });
+            seen[idx] += 1;
+            pos += reclen;
+        }
+    }
+    assert!(calls > 1);
+    assert!(seen.iter().all(|&count| count == 1));
+    close(fd);
+
+    for path in PATHS {
+        assert_eq!(unlink(path), 0);
+    }
+    assert_eq!(unlink("dir_getdents\0"), 0);
+    println!("Test getdents OK!");
+    0
+}
@@ -0,0 +1,88 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{close, getdents64, mkdir, open, unlink, OpenFlags};
+
+/// 测试边遍历目录边删除已读过的目录项，之后的目录项既不被跳过也不重复，
+/// 输出　Test getdents unlink OK! 就算正确。
+
+const NAMES: [&str; 8] = [".", "..", "f0", "f1", "f2", "f3", "f4", "f5"];
+const PATHS: [&str; 6] = [
+    "dir_readdir/f0\0",
+    "dir_readdir/f1\0",
+    "dir_readdir/f2\0",
+    "dir_readdir/f3\0",
+    "dir_readdir/f4\0",
+    "dir_readdir/f5\0",
+];
+
+/// The names of the records filled in `buf[..len]`, by their place in `NAMES`
+fn for_each_name(buf: &[u8], len: usize, mut f: impl FnMut(usize)) {
+    let mut pos = 0usize;
+    while pos < len {
+        let reclen = u16::from_le_bytes([buf[pos + 16], buf[pos + 17]]) as usize;
+        let name_len = buf[pos + 19..pos + reclen]
+            .iter()
+            .position(|&b| b == 0)
+            .unwrap();
+        let name = core::str::from_utf8(&buf[pos + 19..pos + 19 + name_len]).unwrap();
+        f(NAMES.iter().position(|&n| n == name).unwrap());
+        pos += reclen;
+    }
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    assert_eq!(mkdir("dir_readdir\0", 0o755), 0);
+    for path in PATHS {
+        let fd = open(path, OpenFlags::CREATE | OpenFlags::WRONLY);
+        assert!(fd > 0);
+        close(fd as usize);
+    }
+
+    let fd = open("dir_readdir\0", OpenFlags::RDONLY);
+    assert!(fd > 0);
+    let fd = fd as usize;
+    // room for one record at a time, each file is removed right after
+    // it is seen, pulling the rest of the directory ahead of the offset
+    // if the entries were moved up
+    let mut buf = [0u8; 40];
+    let mut seen = [0usize; NAMES.len()];
+    loop {
+        let len = getdents64(fd, &mut buf);
+        assert!(len >= 0);
+        if len == 0 {
+            break;
+        }
+        for_each_name(&buf, len as usize, |idx| {
+            seen[idx] += 1;
+            if idx >= 2 {
+                assert_eq!(unlink(PATHS[idx - 2]), 0);
+            }
+        });
+    }
+    assert!(seen.iter().all(|&count| count == 1));
+    close(fd);
+
+    // only `.` and `..` are left once the directory is read again
+    let fd = open("dir_readdir\0", OpenFlags::RDONLY);
+    assert!(fd > 0);
+    let mut buf = [0u8; 256];
+    let mut seen = [0usize; NAMES.len()];
+    loop {
+        let len = getdents64(fd as usize, &mut buf);
+        assert!(len >= 0);
+        if len == 0 {
+            break;
+        }
+        for_each_name(&buf, len as usize, |idx| seen[idx] += 1);
+    }
+    assert_eq!(seen, [1, 1, 0, 0, 0, 0, 0, 0]);
+    close(fd as usize);
+    assert_eq!(unlink("dir_readdir\0"), 0);
+    println!("Test getdents unlink OK!");
+    0
+}
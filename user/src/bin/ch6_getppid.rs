@@ -0,0 +1,51 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{close, exit, fork, getpid, getppid, pipe, read, waitpid, write, yield_};
+
+/// 测试 getpid/getppid，子进程的父进程号即父进程的 pid，父进程退出后
+/// 孙进程被过继给初始进程，输出　Test getppid OK! 就算正确。
+
+/// The pid of the initial process, which takes in orphans
+const INITPROC_PID: isize = 0;
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let pid = getpid();
+    assert!(pid > 0);
+    assert_ne!(getppid(), pid);
+
+    let mut pipe_fd = [0usize; 2];
+    assert_eq!(pipe(&mut pipe_fd), 0);
+    let child = fork();
+    if child == 0 {
+        assert_eq!(getppid(), pid);
+        assert_ne!(getpid(), pid);
+        let child = getpid();
+        if fork() == 0 {
+            assert_eq!(getppid(), child);
+            // wait for the parent to be gone
+            while getppid() == child {
+                yield_();
+            }
+            let ppid = getppid().to_le_bytes();
+            assert_eq!(write(pipe_fd[1], &ppid), ppid.len() as isize);
+            exit(0);
+        }
+        exit(0);
+    }
+    close(pipe_fd[1]);
+    let mut exit_code: i32 = -1;
+    assert_eq!(waitpid(child as usize, &mut exit_code), child);
+    assert_eq!(exit_code, 0);
+    // the grandchild reports who took it in
+    let mut ppid = [0u8; core::mem::size_of::<isize>()];
+    assert_eq!(read(pipe_fd[0], &mut ppid), ppid.len() as isize);
+    assert_eq!(isize::from_le_bytes(ppid), INITPROC_PID);
+    close(pipe_fd[0]);
+    println!("Test getppid OK!");
+    0
+}
@@ -0,0 +1,65 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{
+    clock_gettime, gettimeofday, sys_clock_gettime, yield_, TimeSpec, TimeVal, CLOCK_MONOTONIC,
+    CLOCK_REALTIME,
+};
+
+/// 测试 gettimeofday 与 clock_gettime，相继两次读到的时间不减，
+/// 跨过整秒时微秒数回绕，输出　Test gettimeofday OK! 就算正确。
+
+fn micros(time: &TimeVal) -> usize {
+    time.sec * 1_000_000 + time.usec
+}
+
+fn nanos(time: &TimeSpec) -> usize {
+    time.sec * 1_000_000_000 + time.nsec
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let mut prev = TimeVal::new();
+    assert_eq!(gettimeofday(&mut prev), 0);
+    assert!(prev.usec < 1_000_000);
+    // until the second changes, which it does within a second
+    let mut wrapped = false;
+    while !wrapped {
+        let mut now = TimeVal::new();
+        assert_eq!(gettimeofday(&mut now), 0);
+        assert!(now.usec < 1_000_000);
+        assert!(micros(&now) >= micros(&prev));
+        if now.sec != prev.sec {
+            // unless another task ran for a whole second in between
+            if micros(&now) - micros(&prev) < 1_000_000 {
+                assert_eq!(now.sec, prev.sec + 1);
+                assert!(now.usec < prev.usec);
+            }
+            wrapped = true;
+        }
+        prev = now;
+    }
+
+    // the monotonic clock holds across switching to other tasks
+    let mut prev = TimeSpec::default();
+    assert_eq!(clock_gettime(CLOCK_MONOTONIC, &mut prev), 0);
+    for _ in 0..100 {
+        yield_();
+        let mut now = TimeSpec::default();
+        assert_eq!(clock_gettime(CLOCK_MONOTONIC, &mut now), 0);
+        assert!(now.nsec < 1_000_000_000);
+        assert!(nanos(&now) >= nanos(&prev));
+        prev = now;
+    }
+    let mut now = TimeSpec::default();
+    assert_eq!(clock_gettime(CLOCK_REALTIME, &mut now), 0);
+    let mut tv = TimeVal::new();
+    assert_eq!(gettimeofday(&mut tv), 0);
+    assert!(micros(&tv) >= nanos(&now) / 1000);
+    assert_eq!(sys_clock_gettime(7, &mut now), -1);
+    println!("Test gettimeofday OK!");
+    0
+}
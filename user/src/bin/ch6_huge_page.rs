@@ -0,0 +1,64 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{
+    getrusage, mmap_file, munmap, Rusage, MAP_ANONYMOUS, MAP_HUGETLB, MAP_PRIVATE, RUSAGE_SELF,
+};
+
+/// 测试 MAP_HUGETLB，对齐的 2MiB 一次缺页整体分配，不对齐时退回普通页，
+/// 输出　Test huge page OK! 就算正确。
+
+const START: usize = 0x20000000;
+const PAGE_SIZE: usize = 4096;
+const HUGE_PAGE_SIZE: usize = 0x200000;
+const HUGE_PAGE_PAGES: u64 = (HUGE_PAGE_SIZE / PAGE_SIZE) as u64;
+
+fn usage() -> Rusage {
+    let mut usage = Rusage::default();
+    assert_eq!(getrusage(RUSAGE_SELF, &mut usage), 0);
+    usage
+}
+
+/// Write every page of `[start, start + len)` and read it back
+fn touch(start: usize, len: usize) {
+    for offset in (0..len).step_by(PAGE_SIZE) {
+        let addr = (start + offset) as *mut usize;
+        unsafe {
+            assert_eq!(addr.read_volatile(), 0);
+            addr.write_volatile(offset);
+            assert_eq!(addr.read_volatile(), offset);
+        }
+    }
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let flags = MAP_PRIVATE | MAP_ANONYMOUS | MAP_HUGETLB;
+    // the pages of the stack used below are in before counting
+    usage();
+    let before = usage();
+    assert_eq!(mmap_file(START, HUGE_PAGE_SIZE, 3, flags, 0, 0), START as isize);
+    // a single fault gives the whole megapage its frames
+    touch(START, PAGE_SIZE);
+    let after = usage();
+    assert_eq!(after.page_faults - before.page_faults, 1);
+    assert_eq!(after.resident_frames - before.resident_frames, HUGE_PAGE_PAGES);
+    touch(START + PAGE_SIZE, HUGE_PAGE_SIZE - PAGE_SIZE);
+    assert_eq!(usage().page_faults, after.page_faults);
+    assert_eq!(munmap(START, HUGE_PAGE_SIZE), 0);
+    assert_eq!(usage().resident_frames, before.resident_frames);
+
+    // not aligned to a megapage, page by page
+    let start = START + PAGE_SIZE;
+    assert_eq!(mmap_file(start, HUGE_PAGE_SIZE, 3, flags, 0, 0), start as isize);
+    touch(start, PAGE_SIZE);
+    assert_eq!(usage().resident_frames - before.resident_frames, 1);
+    assert_eq!(munmap(start, HUGE_PAGE_SIZE), 0);
+    // only anonymous mappings
+    assert_eq!(mmap_file(START, HUGE_PAGE_SIZE, 3, MAP_PRIVATE | MAP_HUGETLB, 0, 0), -1);
+    println!("Test huge page OK!");
+    0
+}
@@ -0,0 +1,50 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{
+    close, ioctl, open, tcgetattr, tcsetattr, unlink, OpenFlags, Termios, ECHO, ICANON, TCGETS,
+};
+
+/// 测试 ioctl，读写控制台的终端设置，在规范模式与原始模式间切换、
+/// 关闭回显后再恢复，普通文件不支持，输出　Test ioctl OK! 就算正确。
+
+#[no_mangle]
+pub fn main() -> i32 {
+    // whatever the settings are, they are put back at the end
+    let mut saved = Termios::default();
+    assert_eq!(tcgetattr(0, &mut saved), 0);
+
+    // canonical without echo, as for a password
+    let mut termios = Termios { lflag: ICANON, ..saved };
+    assert_eq!(tcsetattr(0, &termios), 0);
+    let mut current = Termios::default();
+    assert_eq!(tcgetattr(0, &mut current), 0);
+    assert_eq!(current, termios);
+    // raw, then back to canonical with echo
+    termios.lflag = 0;
+    assert_eq!(tcsetattr(0, &termios), 0);
+    assert_eq!(tcgetattr(0, &mut current), 0);
+    assert_eq!(current.lflag & (ICANON | ECHO), 0);
+    termios.lflag = ICANON | ECHO;
+    assert_eq!(tcsetattr(0, &termios), 0);
+    assert_eq!(tcgetattr(0, &mut current), 0);
+    assert_eq!(current.lflag, ICANON | ECHO);
+    assert_eq!(tcsetattr(0, &saved), 0);
+    assert_eq!(ioctl(0, 0x1234, 0), -1);
+    assert_eq!(ioctl(0, TCGETS, 0), -1);
+
+    // only the console is a terminal
+    assert_eq!(tcgetattr(1, &mut current), -1);
+    let fd = open("fname_ioctl\0", OpenFlags::CREATE | OpenFlags::WRONLY);
+    assert!(fd > 0);
+    assert_eq!(tcgetattr(fd as usize, &mut current), -1);
+    assert_eq!(tcsetattr(fd as usize, &termios), -1);
+    close(fd as usize);
+    assert_eq!(tcgetattr(fd as usize, &mut current), -1);
+    unlink("fname_ioctl\0");
+    println!("Test ioctl OK!");
+    0
+}
@@ -0,0 +1,64 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{
+    close, fstat, lseek, open, read, readv, unlink, writev, IoVec, OpenFlags, Stat, SEEK_CUR,
+    SEEK_SET,
+};
+
+/// 测试 readv/writev，一次写入三段数据后整体读回，非法的段使调用失败且不写入任何数据，
+/// 输出　Test iovec OK! 就算正确。
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let fname = "fname_iovec\0";
+    let fd = open(fname, OpenFlags::CREATE | OpenFlags::TRUNC | OpenFlags::RDWR);
+    assert!(fd > 0);
+    let fd = fd as usize;
+
+    // larger than a block of the file
+    let big = [b'z'; 600];
+    let segments = [IoVec::new(b"Hello, "), IoVec::new(b"vectored "), IoVec::new(&big)];
+    assert_eq!(writev(fd, &segments), (7 + 9 + 600) as isize);
+    assert_eq!(writev(fd, &[]), 0);
+
+    let mut blob = [0u8; 616];
+    assert_eq!(lseek(fd, 0, SEEK_SET), 0);
+    assert_eq!(read(fd, &mut blob), 616);
+    assert_eq!(&blob[..16], b"Hello, vectored ");
+    assert!(blob[16..].iter().all(|&b| b == b'z'));
+
+    // scattered the other way, stopping at the end of the file
+    let (mut first, mut second, mut third) = ([0u8; 5], [0u8; 11], [0u8; 1000]);
+    assert_eq!(lseek(fd, 0, SEEK_SET), 0);
+    let segments = [
+        IoVec::new_mut(&mut first),
+        IoVec::new_mut(&mut second),
+        IoVec::new_mut(&mut third),
+    ];
+    assert_eq!(readv(fd, &segments), 616);
+    assert_eq!(&first, b"Hello");
+    assert_eq!(&second, b", vectored ");
+    assert!(third[..600].iter().all(|&b| b == b'z'));
+    assert!(third[600..].iter().all(|&b| b == 0));
+
+    // an unmapped segment fails the whole call before anything is done
+    let bad = IoVec { base: 0, len: 16 };
+    assert_eq!(writev(fd, &[IoVec::new(b"lost"), bad]), -1);
+    let stat = Stat::new();
+    assert_eq!(fstat(fd, &stat), 0);
+    assert_eq!(stat.size, 616);
+    let mut untouched = [0xaau8; 4];
+    assert_eq!(lseek(fd, 0, SEEK_SET), 0);
+    assert_eq!(readv(fd, &[IoVec::new_mut(&mut untouched), bad]), -1);
+    assert_eq!(untouched, [0xaa; 4]);
+    assert_eq!(lseek(fd, 0, SEEK_CUR), 0);
+
+    close(fd);
+    unlink(fname);
+    println!("Test iovec OK!");
+    0
+}
@@ -0,0 +1,58 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{
+    exit, fork, getpid, kill, sleep, waitpid, waitpid_nohang, SIGCHLD, SIGCONT, SIGKILL, SIGSTOP,
+    SIGTERM,
+};
+
+/// 测试 kill 发送信号及其默认处理，输出　Test kill OK! 就算正确。
+
+/// Fork a child looping forever
+fn fork_looping() -> usize {
+    let pid = fork();
+    if pid == 0 {
+        loop {}
+    }
+    pid as usize
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let mut exit_code: i32 = 0;
+    // a pid not in use, and a signal that doesn't exist
+    assert_eq!(kill(12345, SIGKILL), -1);
+    assert_eq!(kill(getpid() as usize, 32), -1);
+    assert_eq!(kill(getpid() as usize, 0), 0);
+    // ignored by default
+    assert_eq!(kill(getpid() as usize, SIGCHLD), 0);
+
+    for &signum in [SIGKILL, SIGTERM].iter() {
+        let pid = fork_looping();
+        sleep(20);
+        assert_eq!(kill(pid, signum), 0);
+        assert_eq!(waitpid(pid, &mut exit_code), pid as isize);
+        assert_eq!(exit_code, -(signum as i32));
+        // it is gone once exited
+        assert_eq!(kill(pid, signum), -1);
+    }
+
+    // a stopped child doesn't run until it gets SIGCONT
+    let pid = fork();
+    if pid == 0 {
+        sleep(50);
+        exit(3);
+    }
+    let pid = pid as usize;
+    assert_eq!(kill(pid, SIGSTOP), 0);
+    sleep(200);
+    assert_eq!(waitpid_nohang(pid, &mut exit_code), 0);
+    assert_eq!(kill(pid, SIGCONT), 0);
+    assert_eq!(waitpid(pid, &mut exit_code), pid as isize);
+    assert_eq!(exit_code, 3);
+    println!("Test kill OK!");
+    0
+}
@@ -0,0 +1,59 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{close, exit, fork, open, read, waitpid, OpenFlags};
+
+/// 测试 /proc/kmsg，子进程访存出错后内核打印的信息可以从中读到，
+/// 用户程序自己的输出不在其中，输出　Test kmsg OK! 就算正确。
+
+const BAD_ADDR: usize = 0x5000_abc0;
+const CAPACITY: usize = 0x2000;
+
+fn contains(haystack: &[u8], needle: &[u8]) -> bool {
+    haystack.windows(needle.len()).any(|window| window == needle)
+}
+
+/// Read all the kernel log held into `buf`, returns its length
+fn read_log(buf: &mut [u8]) -> usize {
+    let fd = open("/proc/kmsg\0", OpenFlags::RDONLY | OpenFlags::NONBLOCK);
+    assert!(fd > 0);
+    let fd = fd as usize;
+    let mut len = 0;
+    loop {
+        let read_size = read(fd, &mut buf[len..]);
+        assert!(read_size >= 0);
+        if read_size == 0 {
+            break;
+        }
+        len += read_size as usize;
+    }
+    close(fd);
+    len
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    assert_eq!(open("/proc/kmsg\0", OpenFlags::WRONLY), -1);
+    assert_eq!(open("/proc/nothing\0", OpenFlags::RDONLY), -1);
+    println!("kmsg user marker");
+    let pid = fork();
+    if pid == 0 {
+        unsafe { (BAD_ADDR as *mut u8).write_volatile(1) };
+        exit(0);
+    }
+    let mut exit_code: i32 = 0;
+    assert_eq!(waitpid(pid as usize, &mut exit_code), pid);
+    assert_eq!(exit_code, -2);
+
+    let mut buf = [0u8; CAPACITY + 16];
+    let len = read_log(&mut buf);
+    assert!(len > 0 && len <= CAPACITY);
+    let log = &buf[..len];
+    assert!(contains(log, b"bad addr = 0x5000abc0"));
+    assert!(!contains(log, b"kmsg user marker"));
+    println!("Test kmsg OK!");
+    0
+}
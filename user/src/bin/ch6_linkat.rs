@@ -0,0 +1,60 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{close, fstat, linkat, mkdir, open, unlink, OpenFlags, Stat, AT_FDCWD};
+
+/// 测试 linkat 的 dirfd 与 flags，AT_FDCWD 与目录 fd 可用，无效的 dirfd
+/// 与不支持的 flags 返回 -1，输出　Test linkat OK! 就算正确。
+
+/// `AT_SYMLINK_FOLLOW` of Linux, not supported
+const UNSUPPORTED_FLAG: usize = 0x400;
+
+fn nlink(path: &str) -> u32 {
+    let fd = open(path, OpenFlags::RDONLY);
+    assert!(fd > 0);
+    let stat = Stat::new();
+    assert_eq!(fstat(fd as usize, &stat), 0);
+    close(fd as usize);
+    stat.nlink
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let fname = "fname_linkat\0";
+    let fd = open(fname, OpenFlags::CREATE | OpenFlags::TRUNC | OpenFlags::WRONLY);
+    assert!(fd > 0);
+    let fd = fd as usize;
+
+    assert_eq!(linkat(AT_FDCWD, fname, AT_FDCWD, "link_linkat\0", 0), 0);
+    assert_eq!(nlink(fname), 2);
+    // a dirfd not open at all, or not a directory
+    assert_eq!(linkat(99, fname, AT_FDCWD, "bad_linkat\0", 0), -1);
+    assert_eq!(linkat(AT_FDCWD, fname, 99, "bad_linkat\0", 0), -1);
+    assert_eq!(linkat(AT_FDCWD, fname, fd as isize, "bad_linkat\0", 0), -1);
+    // flags other than 0
+    assert_eq!(linkat(AT_FDCWD, fname, AT_FDCWD, "bad_linkat\0", UNSUPPORTED_FLAG), -1);
+    assert_eq!(linkat(AT_FDCWD, fname, AT_FDCWD, "bad_linkat\0", 1), -1);
+    assert!(open("bad_linkat\0", OpenFlags::RDONLY) < 0);
+    assert_eq!(nlink(fname), 2);
+
+    // the new path relative to a directory fd
+    assert_eq!(mkdir("dir_linkat\0", 0o755), 0);
+    let dirfd = open("dir_linkat\0", OpenFlags::RDONLY);
+    assert!(dirfd > 0);
+    assert_eq!(linkat(AT_FDCWD, fname, dirfd, "inner\0", 0), 0);
+    assert_eq!(linkat(dirfd, "inner\0", AT_FDCWD, "outer_linkat\0", 0), 0);
+    assert_eq!(nlink(fname), 4);
+    close(dirfd as usize);
+    close(fd);
+
+    assert_eq!(unlink("outer_linkat\0"), 0);
+    assert_eq!(unlink("dir_linkat/inner\0"), 0);
+    assert_eq!(unlink("dir_linkat\0"), 0);
+    assert_eq!(unlink("link_linkat\0"), 0);
+    assert_eq!(unlink(fname), 0);
+    println!("Test linkat OK!");
+    0
+}
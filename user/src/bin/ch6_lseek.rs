@@ -0,0 +1,43 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{close, lseek, open, read, write, OpenFlags, SEEK_CUR, SEEK_END, SEEK_SET};
+
+/// 测试 lseek 重新定位文件偏移，输出　Test lseek OK! 就算正确。
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let test_str = "Hello, world!";
+    let fname = "fname_lseek\0";
+    let fd = open(fname, OpenFlags::CREATE | OpenFlags::RDWR);
+    assert!(fd > 0);
+    let fd = fd as usize;
+    write(fd, test_str.as_bytes());
+
+    let mut buffer = [0u8; 100];
+    assert_eq!(lseek(fd, 7, SEEK_SET), 7);
+    let read_len = read(fd, &mut buffer) as usize;
+    assert_eq!("world!", core::str::from_utf8(&buffer[..read_len]).unwrap());
+    assert_eq!(lseek(fd, -6, SEEK_CUR), 7);
+    assert_eq!(lseek(fd, 0, SEEK_END), test_str.len() as isize);
+
+    // invalid seeks leave the offset untouched
+    assert_eq!(lseek(fd, -1, SEEK_SET), -1);
+    assert_eq!(lseek(fd, 0, 3), -1);
+    assert_eq!(lseek(fd, 0, SEEK_CUR), test_str.len() as isize);
+
+    // seeking past EOF leaves a hole filled with zeros
+    assert_eq!(lseek(fd, 20, SEEK_SET), 20);
+    write(fd, "x".as_bytes());
+    assert_eq!(lseek(fd, test_str.len() as isize, SEEK_SET), 13);
+    let read_len = read(fd, &mut buffer) as usize;
+    assert_eq!(read_len, 8);
+    assert!(buffer[..7].iter().all(|b| *b == 0));
+    assert_eq!(buffer[7], b'x');
+    close(fd);
+    println!("Test lseek OK!");
+    0
+}
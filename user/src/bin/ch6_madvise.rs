@@ -0,0 +1,80 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{
+    close, getrusage, madvise, mmap_file, munmap, open, unlink, write, OpenFlags, Rusage,
+    MADV_DONTNEED, MADV_WILLNEED, MAP_ANONYMOUS, MAP_PRIVATE, RUSAGE_SELF,
+};
+
+/// 测试 madvise，DONTNEED 释放页框后匿名内存读回零、文件映射重读文件，
+/// WILLNEED 预读文件映射，输出　Test madvise OK! 就算正确。
+
+const START: usize = 0x10000000;
+const PAGE_SIZE: usize = 4096;
+const PAGES: usize = 8;
+
+fn usage() -> Rusage {
+    let mut usage = Rusage::default();
+    assert_eq!(getrusage(RUSAGE_SELF, &mut usage), 0);
+    usage
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    usage();
+    let len = PAGES * PAGE_SIZE;
+    let flags = MAP_PRIVATE | MAP_ANONYMOUS;
+    assert_eq!(mmap_file(START, len, 3, flags, 0, 0), START as isize);
+    let bytes = unsafe { core::slice::from_raw_parts_mut(START as *mut u8, len) };
+    for page in 0..PAGES {
+        bytes[page * PAGE_SIZE] = page as u8 + 1;
+    }
+    let before = usage();
+    // the frames of the pages are freed at once, and they read as zeros
+    assert_eq!(madvise(START, len, MADV_DONTNEED), 0);
+    let after = usage();
+    assert_eq!(before.resident_frames - after.resident_frames, PAGES as u64);
+    for page in 0..PAGES {
+        assert_eq!(bytes[page * PAGE_SIZE], 0);
+    }
+    // part of the range
+    bytes[0] = 1;
+    bytes[PAGE_SIZE] = 2;
+    assert_eq!(madvise(START + PAGE_SIZE, PAGE_SIZE, MADV_DONTNEED), 0);
+    assert_eq!(bytes[0], 1);
+    assert_eq!(bytes[PAGE_SIZE], 0);
+    // not aligned, not mapped, or not an advice known
+    assert_eq!(madvise(START + 1, PAGE_SIZE, MADV_DONTNEED), -1);
+    assert_eq!(madvise(START, len + PAGE_SIZE, MADV_DONTNEED), -1);
+    assert_eq!(madvise(START, len, 100), -1);
+    assert_eq!(munmap(START, len), 0);
+
+    let fname = "fname_madvise\0";
+    let fd = open(fname, OpenFlags::CREATE | OpenFlags::TRUNC | OpenFlags::RDWR);
+    assert!(fd > 0);
+    let fd = fd as usize;
+    let data = [0x5au8; PAGES * PAGE_SIZE];
+    assert_eq!(write(fd, &data), data.len() as isize);
+    assert_eq!(mmap_file(START, len, 3, MAP_PRIVATE, fd, 0), START as isize);
+    // read ahead, with no fault on the first access
+    let before = usage();
+    assert_eq!(madvise(START, len, MADV_WILLNEED), 0);
+    let after = usage();
+    assert_eq!(after.resident_frames - before.resident_frames, PAGES as u64);
+    for page in 0..PAGES {
+        assert_eq!(bytes[page * PAGE_SIZE], 0x5a);
+    }
+    assert_eq!(usage().page_faults, after.page_faults);
+    // a private copy dropped reads the file again
+    bytes[PAGE_SIZE] = 0;
+    assert_eq!(madvise(START, len, MADV_DONTNEED), 0);
+    assert_eq!(bytes[PAGE_SIZE], 0x5a);
+    assert_eq!(munmap(START, len), 0);
+    close(fd);
+    unlink(fname);
+    println!("Test madvise OK!");
+    0
+}
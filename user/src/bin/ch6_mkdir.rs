@@ -0,0 +1,43 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{close, mkdir, open, read, unlink, write, OpenFlags};
+
+/// 测试 mkdir 创建目录，输出　Test mkdir OK! 就算正确。
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let test_str = "Hello, world!";
+    assert_eq!(mkdir("dir_mkdir\0", 0o755), 0);
+    // the directory exists already, or its parent is missing
+    assert_eq!(mkdir("dir_mkdir\0", 0o755), -1);
+    assert_eq!(mkdir("no_such_dir/sub\0", 0o755), -1);
+    // bits outside the permission mask are rejected
+    assert_eq!(mkdir("dir_mkdir_bad\0", 0o1000000), -1);
+    assert_eq!(mkdir("dir_mkdir/sub\0", 0o755), 0);
+
+    let fd = open("dir_mkdir/sub/fname\0", OpenFlags::CREATE | OpenFlags::WRONLY);
+    assert!(fd > 0);
+    write(fd as usize, test_str.as_bytes());
+    close(fd as usize);
+    // `.` and `..` are ordinary entries while walking a path
+    let fd = open("dir_mkdir/./sub/../sub/fname\0", OpenFlags::RDONLY);
+    assert!(fd > 0);
+    let mut buffer = [0u8; 100];
+    let read_len = read(fd as usize, &mut buffer) as usize;
+    assert_eq!(test_str, core::str::from_utf8(&buffer[..read_len]).unwrap());
+    close(fd as usize);
+
+    // only an empty directory can be removed
+    assert_eq!(unlink("dir_mkdir/sub\0"), -1);
+    assert_eq!(unlink("dir_mkdir/sub/fname\0"), 0);
+    assert_eq!(unlink("dir_mkdir/sub/.\0"), -1);
+    assert_eq!(unlink("dir_mkdir/sub\0"), 0);
+    assert_eq!(unlink("dir_mkdir\0"), 0);
+    assert!(open("dir_mkdir/sub/fname\0", OpenFlags::RDONLY) < 0);
+    println!("Test mkdir OK!");
+    0
+}
@@ -0,0 +1,52 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{mmap, mmap_file, munmap, MAP_ANONYMOUS, MAP_PRIVATE, MAP_SHARED};
+
+/// 测试匿名 mmap 按需分配，输出　Test mmap anon OK! 就算正确。
+
+const START: usize = 0x10000000;
+const PAGE_SIZE: usize = 4096;
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let flags = MAP_PRIVATE | MAP_ANONYMOUS;
+    assert_eq!(mmap_file(START, 16 * PAGE_SIZE, 3, flags, 0, 0), START as isize);
+    for page in [0, 5, 15] {
+        let addr = (START + page * PAGE_SIZE) as *mut usize;
+        unsafe {
+            // fresh pages are zeroed
+            assert_eq!(*addr, 0);
+            *addr = page;
+        }
+    }
+    for page in [0, 5, 15] {
+        let addr = (START + page * PAGE_SIZE) as *const usize;
+        unsafe {
+            assert_eq!(*addr, page);
+        }
+    }
+    // overlapping mappings are refused, even on pages never touched
+    assert_eq!(mmap_file(START + 8 * PAGE_SIZE, PAGE_SIZE, 3, flags, 0, 0), -1);
+    assert_eq!(mmap(START + 8 * PAGE_SIZE, PAGE_SIZE, 3), -1);
+    // only private anonymous mappings
+    assert_eq!(mmap_file(START + 32 * PAGE_SIZE, PAGE_SIZE, 3, MAP_SHARED | MAP_ANONYMOUS, 0, 0), -1);
+    assert_eq!(munmap(START, 16 * PAGE_SIZE), 0);
+
+    // much more than the physical memory, only the pages touched take frames
+    let len = 64 << 20;
+    assert_eq!(mmap_file(START, len, 3, flags, 0, 0), START as isize);
+    for offset in (0..len).step_by(len / 8) {
+        let addr = (START + offset) as *mut u8;
+        unsafe {
+            *addr = 1;
+            assert_eq!(*addr, 1);
+        }
+    }
+    assert_eq!(munmap(START, len), 0);
+    println!("Test mmap anon OK!");
+    0
+}
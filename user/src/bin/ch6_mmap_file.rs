@@ -0,0 +1,83 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{
+    close, fork, mmap_file, munmap, open, read, unlink, wait, write, OpenFlags, MAP_PRIVATE,
+    MAP_SHARED,
+};
+
+/// 测试 mmap 映射文件，输出　Test mmap file OK! 就算正确。
+
+const START: usize = 0x10000000;
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let fname = "fname_mmap\0";
+    let fd = open(fname, OpenFlags::CREATE | OpenFlags::WRONLY);
+    assert!(fd > 0);
+    let fd = fd as usize;
+    // two pages and a bit, every byte known
+    let mut data = [0u8; 9000];
+    for (i, b) in data.iter_mut().enumerate() {
+        *b = (i % 251) as u8;
+    }
+    assert_eq!(write(fd, &data), 9000);
+    close(fd);
+
+    let fd = open(fname, OpenFlags::RDWR) as usize;
+    // a shared mapping reads the file and writes back to it
+    assert_eq!(mmap_file(START, 3 * 4096, 3, MAP_SHARED, fd, 0), START as isize);
+    let bytes = unsafe { core::slice::from_raw_parts_mut(START as *mut u8, 3 * 4096) };
+    assert_eq!(bytes[1234], (1234 % 251) as u8);
+    assert_eq!(bytes[8999], (8999 % 251) as u8);
+    // past the end of the file reads zero
+    assert_eq!(bytes[9000], 0);
+    bytes[100] = 0xff;
+    bytes[9100] = 0xff;
+    // overlapping mappings are refused
+    assert_eq!(mmap_file(START + 4096, 4096, 1, MAP_SHARED, fd, 0), -1);
+    assert_eq!(munmap(START, 3 * 4096), 0);
+    let mut buffer = [0u8; 9100];
+    let fd2 = open(fname, OpenFlags::RDONLY) as usize;
+    // the file doesn't grow with the writes beyond its end
+    assert_eq!(read(fd2, &mut buffer), 9000);
+    close(fd2);
+    assert_eq!(buffer[100], 0xff);
+    assert_eq!(buffer[101], (101 % 251) as u8);
+
+    // a private mapping from the second page keeps its writes
+    assert_eq!(mmap_file(START, 4096, 3, MAP_PRIVATE, fd, 4096), START as isize);
+    let bytes = unsafe { core::slice::from_raw_parts_mut(START as *mut u8, 4096) };
+    assert_eq!(bytes[0], (4096 % 251) as u8);
+    bytes[0] = 0;
+    assert_eq!(munmap(START, 4096), 0);
+    let fd2 = open(fname, OpenFlags::RDONLY) as usize;
+    assert_eq!(read(fd2, &mut buffer), 9000);
+    close(fd2);
+    assert_eq!(buffer[4096], (4096 % 251) as u8);
+
+    // writing a read-only mapping kills the process
+    assert_eq!(mmap_file(START, 4096, 1, MAP_SHARED, fd, 0), START as isize);
+    if fork() == 0 {
+        unsafe {
+            assert_eq!(*(START as *const u8).add(100), 0xff);
+            *(START as *mut u8) = 0;
+        }
+        return 0;
+    }
+    let mut exit_code: i32 = 0;
+    wait(&mut exit_code);
+    assert_eq!(exit_code, -2);
+    assert_eq!(munmap(START, 4096), 0);
+    // a file not opened for writing can't be mapped shared and writable
+    let fd2 = open(fname, OpenFlags::RDONLY) as usize;
+    assert_eq!(mmap_file(START, 4096, 3, MAP_SHARED, fd2, 0), -1);
+    close(fd2);
+    close(fd);
+    assert_eq!(unlink(fname), 0);
+    println!("Test mmap file OK!");
+    0
+}
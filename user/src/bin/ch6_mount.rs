@@ -0,0 +1,64 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{access, close, fstat, open, read, unlink, write, OpenFlags, Stat, F_OK};
+
+/// 测试挂载表，/tmp 与 /dev/shm 是两个不同的内存文件系统，同名文件互不影响，
+/// /dev 下的设备仍可打开，输出　Test mount OK! 就算正确。
+
+/// Create `path` holding `data`, returns its `dev`
+fn create(path: &str, data: &[u8]) -> u64 {
+    let fd = open(path, OpenFlags::CREATE | OpenFlags::TRUNC | OpenFlags::WRONLY);
+    assert!(fd > 0);
+    let fd = fd as usize;
+    assert_eq!(write(fd, data), data.len() as isize);
+    let stat = Stat::new();
+    assert_eq!(fstat(fd, &stat), 0);
+    close(fd);
+    stat.dev
+}
+
+fn check(path: &str, data: &[u8]) {
+    let fd = open(path, OpenFlags::RDONLY);
+    assert!(fd > 0);
+    let mut buf = [0u8; 16];
+    assert_eq!(read(fd as usize, &mut buf), data.len() as isize);
+    assert_eq!(&buf[..data.len()], data);
+    close(fd as usize);
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let tmp = "/tmp/fname_mount\0";
+    let shm = "/dev/shm/fname_mount\0";
+    // /dev/shm is mounted below /dev, the longer mount point wins
+    let tmp_dev = create(tmp, b"in tmp");
+    let shm_dev = create(shm, b"in shm");
+    assert_ne!(tmp_dev, shm_dev);
+    check(tmp, b"in tmp");
+    check(shm, b"in shm");
+    check("/dev/shm/../shm/./fname_mount\0", b"in shm");
+    assert_eq!(access("/dev/fname_mount\0", F_OK), -1);
+    assert_eq!(access("fname_mount\0", F_OK), -1);
+
+    // the devices are still there
+    let fd = open("/dev/zero\0", OpenFlags::RDONLY);
+    assert!(fd > 0);
+    let mut buf = [0xffu8; 16];
+    assert_eq!(read(fd as usize, &mut buf), 16);
+    assert!(buf.iter().all(|&b| b == 0));
+    close(fd as usize);
+    assert_eq!(access("/dev/null\0", F_OK), 0);
+    assert_eq!(open("/dev/no_such_device\0", OpenFlags::CREATE | OpenFlags::WRONLY), -1);
+
+    assert_eq!(unlink(tmp), 0);
+    assert_eq!(access(tmp, F_OK), -1);
+    assert_eq!(access(shm, F_OK), 0);
+    assert_eq!(unlink(shm), 0);
+    assert_eq!(unlink("/dev/null\0"), -1);
+    println!("Test mount OK!");
+    0
+}
@@ -0,0 +1,57 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{fork, mmap, mprotect, munmap, wait};
+
+/// 测试 mprotect 修改页权限，输出　Test mprotect OK! 就算正确。
+
+const START: usize = 0x10000000;
+const PAGE_SIZE: usize = 4096;
+
+/// Write a byte in a child, returns its exit code
+fn write_in_child(addr: usize) -> i32 {
+    if fork() == 0 {
+        unsafe {
+            *(addr as *mut u8) = 2;
+        }
+        user_lib::exit(0);
+    }
+    let mut exit_code: i32 = 0;
+    wait(&mut exit_code);
+    exit_code
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    assert_eq!(mmap(START, 3 * PAGE_SIZE, 3), 0);
+    for page in 0..3 {
+        unsafe {
+            *((START + page * PAGE_SIZE) as *mut u8) = 1;
+        }
+    }
+    // the middle page turns read-only, the others stay writable
+    let middle = START + PAGE_SIZE;
+    assert_eq!(mprotect(middle, PAGE_SIZE, 1), 0);
+    assert_eq!(write_in_child(middle), -2);
+    assert_eq!(write_in_child(START), 0);
+    assert_eq!(write_in_child(START + 2 * PAGE_SIZE), 0);
+    // still readable
+    assert_eq!(unsafe { *(middle as *const u8) }, 1);
+    // writable again
+    assert_eq!(mprotect(middle, PAGE_SIZE, 3), 0);
+    assert_eq!(write_in_child(middle), 0);
+    unsafe {
+        *(middle as *mut u8) = 3;
+        assert_eq!(*(middle as *const u8), 3);
+    }
+    // not aligned, not mapped, or no permission at all
+    assert_eq!(mprotect(middle + 1, PAGE_SIZE, 1), -1);
+    assert_eq!(mprotect(START, 4 * PAGE_SIZE, 1), -1);
+    assert_eq!(mprotect(START, PAGE_SIZE, 0), -1);
+    assert_eq!(munmap(START, 3 * PAGE_SIZE), 0);
+    println!("Test mprotect OK!");
+    0
+}
@@ -0,0 +1,59 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{mmap_file, munmap, MAP_ANONYMOUS, MAP_PRIVATE};
+
+/// 测试 munmap 只解除匿名映射中的一部分页，其余页保持不变，输出　Test munmap partial OK! 就算正确。
+
+const START: usize = 0x10000000;
+const PAGE_SIZE: usize = 4096;
+const PAGES: usize = 8;
+
+fn page(i: usize) -> *mut usize {
+    (START + i * PAGE_SIZE) as *mut usize
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let flags = MAP_PRIVATE | MAP_ANONYMOUS;
+    assert_eq!(mmap_file(START, PAGES * PAGE_SIZE, 3, flags, 0, 0), START as isize);
+    // pages 0..6 touched, the last two not yet
+    for i in 0..6 {
+        unsafe {
+            *page(i) = i + 1;
+        }
+    }
+    // a hole in the middle, covering a page never touched as well
+    assert_eq!(munmap(START + 2 * PAGE_SIZE, 2 * PAGE_SIZE), 0);
+    // the tail, half of it never touched
+    assert_eq!(munmap(START + 5 * PAGE_SIZE, 2 * PAGE_SIZE), 0);
+    for i in [0, 1, 4] {
+        unsafe {
+            assert_eq!(*page(i), i + 1);
+        }
+    }
+    unsafe {
+        // the last page is still there and fresh
+        assert_eq!(*page(7), 0);
+    }
+    // pages unmapped can't be unmapped again, nor a range across a hole
+    assert_eq!(munmap(START + 2 * PAGE_SIZE, PAGE_SIZE), -1);
+    assert_eq!(munmap(START + PAGE_SIZE, 2 * PAGE_SIZE), -1);
+    // but can be mapped again, reading as zeros
+    assert_eq!(mmap_file(START + 2 * PAGE_SIZE, 2 * PAGE_SIZE, 3, flags, 0, 0), (START + 2 * PAGE_SIZE) as isize);
+    for i in [2, 3] {
+        unsafe {
+            assert_eq!(*page(i), 0);
+        }
+    }
+    // and the whole range again, across the pieces left
+    assert_eq!(munmap(START, 5 * PAGE_SIZE), 0);
+    assert_eq!(munmap(START + 7 * PAGE_SIZE, PAGE_SIZE), 0);
+    assert_eq!(mmap_file(START, PAGES * PAGE_SIZE, 3, flags, 0, 0), START as isize);
+    assert_eq!(munmap(START, PAGES * PAGE_SIZE), 0);
+    println!("Test munmap partial OK!");
+    0
+}
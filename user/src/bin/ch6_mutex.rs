@@ -0,0 +1,71 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{
+    close, exit, fork, lseek, mutex_blocking_create, mutex_create, mutex_lock, mutex_unlock, open,
+    read, unlink, waitpid, write, yield_, OpenFlags, SEEK_SET,
+};
+
+/// 测试互斥锁，父子进程各自给文件中的计数器加一，输出　Test mutex OK! 就算正确。
+
+const ROUNDS: usize = 50;
+
+fn read_counter(fd: usize) -> usize {
+    let mut buf = [0u8; 8];
+    lseek(fd, 0, SEEK_SET);
+    assert_eq!(read(fd, &mut buf), 8);
+    usize::from_le_bytes(buf)
+}
+
+fn write_counter(fd: usize, value: usize) {
+    lseek(fd, 0, SEEK_SET);
+    assert_eq!(write(fd, &value.to_le_bytes()), 8);
+}
+
+/// Both tasks increment the counter `ROUNDS` times, giving the CPU away
+/// in the middle of each increment
+fn contend(mutex_id: usize, fd: usize) -> usize {
+    let pid = fork();
+    for _ in 0..ROUNDS {
+        assert_eq!(mutex_lock(mutex_id), 0);
+        let value = read_counter(fd);
+        yield_();
+        write_counter(fd, value + 1);
+        assert_eq!(mutex_unlock(mutex_id), 0);
+    }
+    if pid == 0 {
+        exit(0);
+    }
+    let mut exit_code: i32 = -1;
+    assert_eq!(waitpid(pid as usize, &mut exit_code), pid);
+    assert_eq!(exit_code, 0);
+    read_counter(fd)
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let fname = "fname_mutex\0";
+    let fd = open(fname, OpenFlags::CREATE | OpenFlags::TRUNC | OpenFlags::RDWR);
+    assert!(fd > 0);
+    let fd = fd as usize;
+
+    let blocking = mutex_blocking_create();
+    assert!(blocking >= 0);
+    write_counter(fd, 0);
+    assert_eq!(contend(blocking as usize, fd), 2 * ROUNDS);
+
+    let spin = mutex_create();
+    assert!(spin >= 0 && spin != blocking);
+    write_counter(fd, 0);
+    assert_eq!(contend(spin as usize, fd), 2 * ROUNDS);
+
+    assert_eq!(mutex_lock(100), -1);
+    assert_eq!(mutex_unlock(100), -1);
+    close(fd);
+    unlink(fname);
+    println!("Test mutex OK!");
+    0
+}
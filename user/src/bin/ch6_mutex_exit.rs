@@ -0,0 +1,60 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{
+    close, exit, fork, mutex_blocking_create, mutex_lock, mutex_unlock, pipe, read, sleep,
+    waitpid, write,
+};
+
+/// 测试持有阻塞互斥锁的子进程退出，锁随之释放，父进程能拿到锁并回收子进程，
+/// 输出　Test mutex exit OK! 就算正确。
+
+/// Fork a child taking `mutex_id` and exiting with it held, after `delay`
+/// milliseconds. Returns once the child holds it
+fn fork_holder(mutex_id: usize, delay: usize) -> usize {
+    let mut fds = [0usize; 2];
+    assert_eq!(pipe(&mut fds), 0);
+    let pid = fork();
+    if pid == 0 {
+        assert_eq!(mutex_lock(mutex_id), 0);
+        assert_eq!(write(fds[1], &[1]), 1);
+        if delay > 0 {
+            sleep(delay);
+        }
+        exit(7);
+    }
+    assert!(pid > 0);
+    let mut buf = [0u8; 1];
+    assert_eq!(read(fds[0], &mut buf), 1);
+    close(fds[0]);
+    close(fds[1]);
+    pid as usize
+}
+
+fn reap(pid: usize) {
+    let mut exit_code: i32 = -1;
+    assert_eq!(waitpid(pid, &mut exit_code), pid as isize);
+    assert_eq!(exit_code, 7);
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let mutex = mutex_blocking_create();
+    assert!(mutex >= 0);
+    let mutex = mutex as usize;
+    // reaped first, the lock is free afterwards
+    let pid = fork_holder(mutex, 0);
+    reap(pid);
+    assert_eq!(mutex_lock(mutex), 0);
+    assert_eq!(mutex_unlock(mutex), 0);
+    // waited for while the child is alive, it is handed over at exit
+    let pid = fork_holder(mutex, 50);
+    assert_eq!(mutex_lock(mutex), 0);
+    reap(pid);
+    assert_eq!(mutex_unlock(mutex), 0);
+    println!("Test mutex exit OK!");
+    0
+}
@@ -0,0 +1,61 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{close, fstat, link, mkdir, open, unlink, OpenFlags, Stat};
+
+/// 测试跨目录 link/unlink 后的 nlink，输出　Test nlink OK! 就算正确。
+
+#[no_mangle]
+pub fn main() -> i32 {
+    assert_eq!(mkdir("dir_nlink\0", 0o755), 0);
+    let fd = open("fname_nlink\0", OpenFlags::CREATE | OpenFlags::WRONLY);
+    assert!(fd > 0);
+    let fd = fd as usize;
+    let stat = Stat::new();
+    assert_eq!(link("fname_nlink\0", "dir_nlink/lname\0"), 0);
+    fstat(fd, &stat);
+    assert_eq!(stat.nlink, 2);
+    // a missing source or a taken name links nothing
+    assert_eq!(link("fname_nlink_missing\0", "dir_nlink/missing\0"), -1);
+    assert_eq!(open("dir_nlink/missing\0", OpenFlags::RDONLY), -1);
+    assert_eq!(link("fname_nlink\0", "dir_nlink/lname\0"), -1);
+    assert_eq!(link("fname_nlink\0", "fname_nlink\0"), -1);
+    assert_eq!(link("fname_nlink\0", "dir_nlink\0"), -1);
+    fstat(fd, &stat);
+    assert_eq!(stat.nlink, 2);
+    // the other name sees the same count
+    let lfd = open("dir_nlink/lname\0", OpenFlags::RDONLY);
+    assert!(lfd > 0);
+    let lfd = lfd as usize;
+    let lstat = Stat::new();
+    fstat(lfd, &lstat);
+    assert_eq!(lstat.ino, stat.ino);
+    assert_eq!(lstat.nlink, 2);
+    close(lfd);
+
+    assert_eq!(unlink("dir_nlink/lname\0"), 0);
+    fstat(fd, &stat);
+    assert_eq!(stat.nlink, 1);
+    close(fd);
+
+    // a directory is referred to by its parent and its own `.`
+    let dfd = open("dir_nlink\0", OpenFlags::RDONLY);
+    assert!(dfd > 0);
+    fstat(dfd as usize, &stat);
+    assert_eq!(stat.nlink, 2);
+    assert_eq!(mkdir("dir_nlink/sub\0", 0o755), 0);
+    fstat(dfd as usize, &stat);
+    assert_eq!(stat.nlink, 3);
+    assert_eq!(unlink("dir_nlink/sub\0"), 0);
+    fstat(dfd as usize, &stat);
+    assert_eq!(stat.nlink, 2);
+    close(dfd as usize);
+
+    assert_eq!(unlink("fname_nlink\0"), 0);
+    assert_eq!(unlink("dir_nlink\0"), 0);
+    println!("Test nlink OK!");
+    0
+}
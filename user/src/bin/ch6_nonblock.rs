@@ -0,0 +1,71 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{close, exit, fcntl, fork, pipe, read, waitpid, write, yield_, OpenFlags, F_SETFL};
+
+/// 测试用 F_SETFL 设为非阻塞的管道，读者在管道为空时立即得到 -1 而不是等待，
+/// 写者退出后读完全部数据得到 0，输出　Test nonblocking pipe OK! 就算正确。
+
+const CHUNK: usize = 10;
+const CHUNKS: usize = 20;
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let mut pipe_fd = [0usize; 2];
+    assert_eq!(pipe(&mut pipe_fd), 0);
+    let (rfd, wfd) = (pipe_fd[0], pipe_fd[1]);
+    // both ends turn nonblocking
+    assert_eq!(fcntl(rfd, F_SETFL, OpenFlags::NONBLOCK.bits as usize), 0);
+    assert_eq!(fcntl(wfd, F_SETFL, OpenFlags::NONBLOCK.bits as usize), 0);
+    let mut buf = [0u8; 64];
+    // nothing written yet, with the writer still there
+    assert_eq!(read(rfd, &mut buf), -1);
+
+    let pid = fork();
+    if pid == 0 {
+        close(rfd);
+        for i in 0..CHUNKS {
+            let chunk = [b'a' + i as u8; CHUNK];
+            let mut sent = 0;
+            // the pipe may be full, the rest is sent later
+            while sent < CHUNK {
+                let written = write(wfd, &chunk[sent..]);
+                if written > 0 {
+                    sent += written as usize;
+                }
+                yield_();
+            }
+        }
+        close(wfd);
+        exit(0);
+    }
+    close(wfd);
+    let mut total = 0;
+    let mut empty_reads = 0;
+    loop {
+        match read(rfd, &mut buf) {
+            -1 => {
+                empty_reads += 1;
+                yield_();
+            }
+            0 => break,
+            len => {
+                for &byte in buf[..len as usize].iter() {
+                    assert_eq!(byte, b'a' + (total / CHUNK) as u8);
+                    total += 1;
+                }
+            }
+        }
+    }
+    assert_eq!(total, CHUNK * CHUNKS);
+    assert!(empty_reads > 0);
+    let mut exit_code: i32 = -1;
+    assert_eq!(waitpid(pid as usize, &mut exit_code), pid);
+    assert_eq!(exit_code, 0);
+    close(rfd);
+    println!("Test nonblocking pipe OK!");
+    0
+}
@@ -0,0 +1,70 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{close, exit, fork, open, symlink, unlink, waitpid, yield_, OpenFlags};
+
+/// 测试 CREATE|EXCL，多个进程同时创建同一个文件只有一个成功，
+/// 文件已存在时失败，不带 EXCL 时照常打开，输出　Test open excl OK! 就算正确。
+
+const CREATORS: usize = 6;
+
+fn create_excl(path: &str) -> isize {
+    open(path, OpenFlags::CREATE | OpenFlags::EXCL | OpenFlags::WRONLY)
+}
+
+/// Race `CREATORS` processes creating `path`, returns how many made it
+fn race(path: &str) -> usize {
+    let mut pids = [0isize; CREATORS];
+    for pid in pids.iter_mut() {
+        *pid = fork();
+        if *pid == 0 {
+            yield_();
+            let fd = create_excl(path);
+            if fd < 0 {
+                exit(1);
+            }
+            close(fd as usize);
+            exit(0);
+        }
+    }
+    let mut created = 0;
+    for &pid in pids.iter() {
+        let mut exit_code: i32 = -1;
+        assert_eq!(waitpid(pid as usize, &mut exit_code), pid);
+        if exit_code == 0 {
+            created += 1;
+        }
+    }
+    created
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    for &path in ["lock_excl\0", "/tmp/lock_excl\0"].iter() {
+        unlink(path);
+        assert_eq!(race(path), 1);
+        // the lock is taken
+        assert_eq!(create_excl(path), -1);
+        // while a plain create still opens it
+        let fd = open(path, OpenFlags::CREATE | OpenFlags::WRONLY);
+        assert!(fd > 0);
+        close(fd as usize);
+        // and it can be taken again once released
+        assert_eq!(unlink(path), 0);
+        let fd = create_excl(path);
+        assert!(fd > 0);
+        close(fd as usize);
+        assert_eq!(unlink(path), 0);
+    }
+    // a symbolic link is not followed, even to nowhere
+    assert_eq!(symlink("nowhere_excl\0", "link_excl\0"), 0);
+    assert_eq!(create_excl("link_excl\0"), -1);
+    assert_eq!(open("nowhere_excl\0", OpenFlags::RDONLY), -1);
+    unlink("link_excl\0");
+    assert_eq!(create_excl("/dev/null\0"), -1);
+    println!("Test open excl OK!");
+    0
+}
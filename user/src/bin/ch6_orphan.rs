@@ -0,0 +1,71 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{close, exit, fork, getppid, pipe, read, waitpid, write, yield_};
+
+/// 测试孤儿进程的过继，父进程先于子进程退出，子进程过继给初始进程
+/// 并由其回收，输出　Test orphan OK! 就算正确。
+
+/// The pid of the initial process, which takes in orphans
+const INITPROC_PID: isize = 0;
+/// Probes forked at most before giving up on the orphan being reaped
+const MAX_PROBES: usize = 1000;
+
+#[no_mangle]
+pub fn main() -> i32 {
+    // the parent reports the pid of the orphan on one, the orphan waits on the other
+    let mut report = [0usize; 2];
+    let mut go = [0usize; 2];
+    assert_eq!(pipe(&mut report), 0);
+    assert_eq!(pipe(&mut go), 0);
+    let parent = fork();
+    if parent == 0 {
+        let orphan = fork();
+        if orphan == 0 {
+            // outlive the parent, then exit to be reaped by the initial process
+            let mut byte = [0u8; 1];
+            assert_eq!(read(go[0], &mut byte), 1);
+            assert_eq!(getppid(), INITPROC_PID);
+            exit(7);
+        }
+        let orphan = (orphan as usize).to_le_bytes();
+        assert_eq!(write(report[1], &orphan), orphan.len() as isize);
+        exit(0);
+    }
+    let mut exit_code: i32 = -1;
+    assert_eq!(waitpid(parent as usize, &mut exit_code), parent);
+    assert_eq!(exit_code, 0);
+    let mut orphan = [0u8; core::mem::size_of::<usize>()];
+    assert_eq!(read(report[0], &mut orphan), orphan.len() as isize);
+    let orphan = usize::from_le_bytes(orphan);
+    // not a child of ours, even with its parent gone
+    assert_eq!(waitpid(orphan, &mut exit_code), -1);
+    // let the orphan exit, its parent being gone by now
+    assert_eq!(write(go[1], b"!"), 1);
+    for fd in report.iter().chain(go.iter()) {
+        close(*fd);
+    }
+
+    // a pid is handed out again only once the process is reaped,
+    // the last one given back first, so a child forked after the
+    // initial process reaps the orphan gets the pid of the orphan
+    let mut reaped = false;
+    for _ in 0..MAX_PROBES {
+        let probe = fork();
+        if probe == 0 {
+            exit(0);
+        }
+        assert_eq!(waitpid(probe as usize, &mut exit_code), probe);
+        if probe as usize == orphan {
+            reaped = true;
+            break;
+        }
+        yield_();
+    }
+    assert!(reaped);
+    println!("Test orphan OK!");
+    0
+}
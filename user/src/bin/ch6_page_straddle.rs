@@ -0,0 +1,76 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{
+    close, lseek, mmap_file, munmap, open, read, unlink, write, OpenFlags, MAP_ANONYMOUS,
+    MAP_PRIVATE, SEEK_CUR, SEEK_SET,
+};
+
+/// 测试跨页缓冲区的读写，缓冲区跨越两个不相邻的物理页框，
+/// 读回的数据与写入一致，读到文件尾时返回实际字节数，输出　Test page straddle OK! 就算正确。
+
+const START: usize = 0x10000000;
+const PAGE_SIZE: usize = 4096;
+const LEN: usize = 5000;
+/// The buffers start this far before the end of their first page
+const HEAD: usize = 100;
+
+fn pattern(i: usize) -> u8 {
+    (i * 7 + i / 251) as u8
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let len = 4 * PAGE_SIZE;
+    let flags = MAP_PRIVATE | MAP_ANONYMOUS;
+    assert_eq!(mmap_file(START, len, 3, flags, 0, 0), START as isize);
+    // the later pages are touched first, so that the frames
+    // of each buffer are not in the order of its pages
+    for page in (0..4).rev() {
+        unsafe { ((START + page * PAGE_SIZE) as *mut u8).write_volatile(0) };
+    }
+    let src = unsafe {
+        core::slice::from_raw_parts_mut((START + PAGE_SIZE - HEAD) as *mut u8, LEN)
+    };
+    let dst = unsafe {
+        core::slice::from_raw_parts_mut((START + 3 * PAGE_SIZE - HEAD) as *mut u8, LEN)
+    };
+    for (i, byte) in src.iter_mut().enumerate() {
+        *byte = pattern(i);
+    }
+
+    let fname = "fname_straddle\0";
+    let fd = open(fname, OpenFlags::CREATE | OpenFlags::TRUNC | OpenFlags::RDWR);
+    assert!(fd > 0);
+    let fd = fd as usize;
+    assert_eq!(write(fd, src), LEN as isize);
+    assert_eq!(lseek(fd, 0, SEEK_CUR), LEN as isize);
+    assert_eq!(lseek(fd, 0, SEEK_SET), 0);
+    assert_eq!(read(fd, dst), LEN as isize);
+    assert!(dst.iter().enumerate().all(|(i, &b)| b == pattern(i)));
+
+    // a read past the end of file gives the bytes there are, in place
+    for byte in dst.iter_mut() {
+        *byte = 0xff;
+    }
+    let tail = 300;
+    assert_eq!(lseek(fd, (LEN - tail) as isize, SEEK_SET), (LEN - tail) as isize);
+    assert_eq!(read(fd, dst), tail as isize);
+    assert!((0..tail).all(|i| dst[i] == pattern(LEN - tail + i)));
+    assert!(dst[tail..].iter().all(|&b| b == 0xff));
+    assert_eq!(lseek(fd, 0, SEEK_CUR), LEN as isize);
+    // and ending right on the page boundary
+    assert_eq!(lseek(fd, (LEN - HEAD) as isize, SEEK_SET), (LEN - HEAD) as isize);
+    assert_eq!(read(fd, dst), HEAD as isize);
+    assert!((0..HEAD).all(|i| dst[i] == pattern(LEN - HEAD + i)));
+    assert_eq!(dst[HEAD], 0xff);
+    assert_eq!(read(fd, dst), 0);
+    close(fd);
+    unlink(fname);
+    munmap(START, len);
+    println!("Test page straddle OK!");
+    0
+}
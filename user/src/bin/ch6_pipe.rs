@@ -0,0 +1,52 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{close, fork, pipe, read, wait, write};
+
+/// 测试 pipe 进程间通信，输出　Test pipe OK! 就算正确。
+
+static STR: &str = "Hello, world!";
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let mut pipe_fd = [0usize; 2];
+    assert_eq!(pipe(&mut pipe_fd), 0);
+    if fork() == 0 {
+        // child process writes into the pipe
+        close(pipe_fd[0]);
+        assert_eq!(write(pipe_fd[1], STR.as_bytes()), STR.len() as isize);
+        close(pipe_fd[1]);
+        0
+    } else {
+        // parent process reads until EOF
+        close(pipe_fd[1]);
+        let mut buffer = [0u8; 32];
+        let mut read_len = 0usize;
+        loop {
+            let len = read(pipe_fd[0], &mut buffer[read_len..]);
+            assert!(len >= 0);
+            if len == 0 {
+                break;
+            }
+            read_len += len as usize;
+        }
+        assert_eq!(core::str::from_utf8(&buffer[..read_len]).unwrap(), STR);
+        close(pipe_fd[0]);
+        let mut child_exit_code: i32 = 0;
+        wait(&mut child_exit_code);
+        assert_eq!(child_exit_code, 0);
+
+        // writing into a pipe without readers fails
+        assert_eq!(pipe(&mut pipe_fd), 0);
+        close(pipe_fd[0]);
+        assert_eq!(write(pipe_fd[1], STR.as_bytes()), -1);
+        // the write end cannot be read
+        assert_eq!(read(pipe_fd[1], &mut buffer), -1);
+        close(pipe_fd[1]);
+        println!("Test pipe OK!");
+        0
+    }
+}
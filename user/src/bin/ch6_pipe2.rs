@@ -0,0 +1,55 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{
+    close, exec, exit, fcntl, fork, pipe, pipe2, read, waitpid, write, OpenFlags, FD_CLOEXEC,
+    F_GETFD, F_GETFL,
+};
+
+/// 测试 pipe2，创建时即给两端设置 NONBLOCK 与 CLOEXEC，不支持的标志返回 -1，
+/// exec 后两端被关闭，输出　Test pipe2 OK! 就算正确。
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let mut pipe_fd = [0usize; 2];
+    // flags a pipe can't have
+    assert_eq!(pipe2(&mut pipe_fd, OpenFlags::APPEND), -1);
+    assert_eq!(pipe2(&mut pipe_fd, OpenFlags::RDWR), -1);
+    assert_eq!(pipe2(&mut pipe_fd, OpenFlags::NONBLOCK | OpenFlags::CLOEXEC), 0);
+    assert_eq!(pipe_fd, [3, 4]);
+    let modes = [OpenFlags::RDONLY, OpenFlags::WRONLY];
+    for (&fd, &mode) in pipe_fd.iter().zip(modes.iter()) {
+        assert_eq!(fcntl(fd, F_GETFD, 0), FD_CLOEXEC as isize);
+        assert_eq!(fcntl(fd, F_GETFL, 0), (mode | OpenFlags::NONBLOCK).bits() as isize);
+    }
+    let mut buf = [0u8; 4];
+    assert_eq!(read(3, &mut buf), -1);
+    assert_eq!(write(4, b"abc"), 3);
+    assert_eq!(read(3, &mut buf), 3);
+    assert_eq!(&buf[..3], b"abc");
+    // a plain pipe is pipe2 with no flags
+    assert_eq!(pipe(&mut pipe_fd), 0);
+    assert_eq!(pipe_fd, [5, 6]);
+    for (&fd, &mode) in pipe_fd.iter().zip(modes.iter()) {
+        assert_eq!(fcntl(fd, F_GETFD, 0), 0);
+        assert_eq!(fcntl(fd, F_GETFL, 0), mode.bits() as isize);
+    }
+
+    let pid = fork();
+    if pid == 0 {
+        exec("ch6_cloexec_probe\0", &[core::ptr::null::<u8>()]);
+        exit(-1);
+    }
+    let mut exit_code: i32 = -1;
+    assert_eq!(waitpid(pid as usize, &mut exit_code), pid);
+    // stdin, stdout, stderr and the plain pipe survive
+    assert_eq!(exit_code, 0b0110_0111);
+    for fd in 3..7 {
+        close(fd);
+    }
+    println!("Test pipe2 OK!");
+    0
+}
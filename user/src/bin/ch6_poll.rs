@@ -0,0 +1,65 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{
+    close, exit, fork, get_time, pipe, poll, read, sleep, waitpid, write, PollFd, POLLIN,
+    POLLNVAL, POLLOUT,
+};
+
+/// 测试 poll，管道在子进程写入后变为可读，超时返回 0，
+/// 输出　Test poll OK! 就算正确。
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let mut pipe_fd = [0usize; 2];
+    assert_eq!(pipe(&mut pipe_fd), 0);
+    let (rfd, wfd) = (pipe_fd[0], pipe_fd[1]);
+
+    // an empty pipe can be written but not read
+    let mut fds = [PollFd::new(rfd, POLLIN), PollFd::new(wfd, POLLOUT)];
+    assert_eq!(poll(&mut fds, 0), 1);
+    assert_eq!(fds[0].revents, 0);
+    assert_eq!(fds[1].revents, POLLOUT);
+    // nothing comes within the timeout
+    let mut fds = [PollFd::new(rfd, POLLIN)];
+    let start = get_time();
+    assert_eq!(poll(&mut fds, 50), 0);
+    assert!(get_time() - start >= 50);
+    assert_eq!(fds[0].revents, 0);
+
+    let pid = fork();
+    if pid == 0 {
+        close(rfd);
+        sleep(30);
+        assert_eq!(write(wfd, b"ready"), 5);
+        close(wfd);
+        exit(0);
+    }
+    close(wfd);
+    // readable once the child writes, waiting as long as it takes
+    let mut fds = [PollFd::new(rfd, POLLIN)];
+    assert_eq!(poll(&mut fds, -1), 1);
+    assert_eq!(fds[0].revents, POLLIN);
+    // an fd not open is reported, a negative one is skipped
+    let mut fds = [PollFd::new(rfd, POLLIN), PollFd::new(99, POLLIN), PollFd::new(0, POLLIN)];
+    fds[2].fd = -1;
+    assert_eq!(poll(&mut fds, 0), 2);
+    assert_eq!(fds[1].revents, POLLNVAL);
+    assert_eq!(fds[2].revents, 0);
+    let mut buf = [0u8; 8];
+    assert_eq!(read(rfd, &mut buf), 5);
+    assert_eq!(&buf[..5], b"ready");
+    let mut exit_code: i32 = -1;
+    assert_eq!(waitpid(pid as usize, &mut exit_code), pid);
+    assert_eq!(exit_code, 0);
+    // no writer left, reading ends right away
+    let mut fds = [PollFd::new(rfd, POLLIN)];
+    assert_eq!(poll(&mut fds, -1), 1);
+    assert_eq!(read(rfd, &mut buf), 0);
+    close(rfd);
+    println!("Test poll OK!");
+    0
+}
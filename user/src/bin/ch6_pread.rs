@@ -0,0 +1,87 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{
+    close, exit, fork, lseek, open, pipe, pread, pwrite, unlink, waitpid, write, yield_,
+    OpenFlags, SEEK_CUR,
+};
+
+/// 测试 pread/pwrite，fork 后父子进程经同一个 fd 交替在不同偏移处写入，
+/// 文件偏移始终不变，输出　Test pread OK! 就算正确。
+
+const CHUNK: usize = 16;
+const CHUNKS: usize = 20;
+const PARENT_BASE: usize = 1000;
+const CHILD_BASE: usize = 3000;
+
+/// Write `CHUNKS` chunks from `base` on, the `i`-th filled with `tag + i`
+fn pwrite_chunks(fd: usize, base: usize, tag: u8) {
+    for i in 0..CHUNKS {
+        let chunk = [tag + i as u8; CHUNK];
+        assert_eq!(pwrite(fd, &chunk, base + i * CHUNK), CHUNK as isize);
+        yield_();
+    }
+}
+
+fn check_chunks(fd: usize, base: usize, tag: u8) {
+    let mut chunk = [0u8; CHUNK];
+    for i in 0..CHUNKS {
+        assert_eq!(pread(fd, &mut chunk, base + i * CHUNK), CHUNK as isize);
+        assert!(chunk.iter().all(|&b| b == tag + i as u8));
+    }
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let fname = "fname_pread\0";
+    let fd = open(fname, OpenFlags::CREATE | OpenFlags::TRUNC | OpenFlags::RDWR);
+    assert!(fd > 0);
+    let fd = fd as usize;
+    assert_eq!(write(fd, b"head"), 4);
+
+    // the child shares the fd, and its offset, with the parent
+    let pid = fork();
+    if pid == 0 {
+        pwrite_chunks(fd, CHILD_BASE, b'a');
+        assert_eq!(lseek(fd, 0, SEEK_CUR), 4);
+        exit(0);
+    }
+    pwrite_chunks(fd, PARENT_BASE, b'A');
+    let mut exit_code: i32 = -1;
+    assert_eq!(waitpid(pid as usize, &mut exit_code), pid);
+    assert_eq!(exit_code, 0);
+    assert_eq!(lseek(fd, 0, SEEK_CUR), 4);
+
+    check_chunks(fd, PARENT_BASE, b'A');
+    check_chunks(fd, CHILD_BASE, b'a');
+    // the hole between the data reads as zeros
+    let mut buf = [0xffu8; 64];
+    assert_eq!(pread(fd, &mut buf, 100), 64);
+    assert!(buf.iter().all(|&b| b == 0));
+    // up to the end and no further
+    let end = CHILD_BASE + CHUNKS * CHUNK;
+    assert_eq!(pread(fd, &mut buf, end - 10), 10);
+    assert_eq!(pread(fd, &mut buf, end), 0);
+    assert_eq!(pread(fd, &mut buf, end + 100), 0);
+    assert_eq!(lseek(fd, 0, SEEK_CUR), 4);
+    close(fd);
+
+    let fd = open(fname, OpenFlags::RDONLY);
+    assert!(fd > 0);
+    let fd = fd as usize;
+    assert_eq!(pwrite(fd, b"x", 0), -1);
+    assert_eq!(pread(fd, &mut buf[..4], 0), 4);
+    assert_eq!(&buf[..4], b"head");
+    close(fd);
+    let mut pipe_fd = [0usize; 2];
+    assert_eq!(pipe(&mut pipe_fd), 0);
+    assert_eq!(pread(pipe_fd[0], &mut buf, 0), -1);
+    close(pipe_fd[0]);
+    close(pipe_fd[1]);
+    unlink(fname);
+    println!("Test pread OK!");
+    0
+}
@@ -0,0 +1,126 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{
+    close, exit, fork, get_time, kill, mutex_blocking_create, mutex_lock, mutex_unlock, pipe,
+    read, semaphore_create, semaphore_down, semaphore_up, set_priority, waitpid, write, SIGKILL,
+};
+
+/// 测试互斥锁的优先级继承，低优先级进程持锁时，等锁的高优先级进程不会被中优先级进程长时间拖住，
+/// 比用不继承优先级的信号量时等得短得多，输出　Test priority inheritance OK! 就算正确。
+
+const LOW: isize = 2;
+const MIDDLE: isize = 8;
+const HIGH: isize = 64;
+/// How long the low priority task works holding the lock, running alone
+const WORK_MS: isize = 50;
+/// The middle priority task gives up after this long if not killed
+const SPIN_MS: isize = 5000;
+
+/// Count iterations over `ms` milliseconds
+fn calibrate(ms: isize) -> usize {
+    let start = get_time();
+    let mut iters = 0;
+    loop {
+        iters += 1;
+        if iters % 400 == 0 && get_time() - start > ms {
+            return iters;
+        }
+    }
+}
+
+fn work(iters: usize) {
+    let mut acc = 0usize;
+    for i in 0..iters {
+        acc = unsafe { core::ptr::read_volatile(&acc) }.wrapping_add(i);
+    }
+}
+
+/// A lock taken by the three tasks, a blocking mutex or a binary semaphore
+#[derive(Clone, Copy)]
+enum Lock {
+    Mutex(usize),
+    Semaphore(usize),
+}
+
+impl Lock {
+    fn lock(self) {
+        match self {
+            Lock::Mutex(id) => assert_eq!(mutex_lock(id), 0),
+            Lock::Semaphore(id) => assert_eq!(semaphore_down(id), 0),
+        }
+    }
+    fn unlock(self) {
+        match self {
+            Lock::Mutex(id) => assert_eq!(mutex_unlock(id), 0),
+            Lock::Semaphore(id) => semaphore_up(id),
+        }
+    }
+}
+
+/// The low priority task takes `lock` and works for `iters` iterations,
+/// the middle priority one keeps the CPU busy meanwhile and the high
+/// priority one waits for the lock. Returns how long the last waits in ms
+fn inversion(lock: Lock, iters: usize) -> i32 {
+    let mut fds = [0usize; 2];
+    assert_eq!(pipe(&mut fds), 0);
+    let low = fork();
+    if low == 0 {
+        assert_eq!(set_priority(LOW), LOW);
+        lock.lock();
+        assert_eq!(write(fds[1], &[1]), 1);
+        work(iters);
+        lock.unlock();
+        exit(0);
+    }
+    // the lock is held from now on
+    let mut buf = [0u8; 1];
+    assert_eq!(read(fds[0], &mut buf), 1);
+    close(fds[0]);
+    close(fds[1]);
+    let middle = fork();
+    if middle == 0 {
+        assert_eq!(set_priority(MIDDLE), MIDDLE);
+        calibrate(SPIN_MS);
+        exit(0);
+    }
+    let high = fork();
+    if high == 0 {
+        assert_eq!(set_priority(HIGH), HIGH);
+        let start = get_time();
+        lock.lock();
+        let waited = get_time() - start;
+        lock.unlock();
+        exit(waited as i32);
+    }
+    let mut waited: i32 = -1;
+    assert_eq!(waitpid(high as usize, &mut waited), high);
+    assert!(waited >= 0);
+    kill(middle as usize, SIGKILL);
+    let mut exit_code: i32 = -1;
+    assert_eq!(waitpid(middle as usize, &mut exit_code), middle);
+    assert_eq!(waitpid(low as usize, &mut exit_code), low);
+    assert_eq!(exit_code, 0);
+    waited
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let iters = calibrate(WORK_MS);
+    let mutex = mutex_blocking_create();
+    assert!(mutex >= 0);
+    let inheriting = inversion(Lock::Mutex(mutex as usize), iters);
+    let semaphore = semaphore_create(1);
+    assert!(semaphore >= 0);
+    let baseline = inversion(Lock::Semaphore(semaphore as usize), iters);
+    println!(
+        "high priority task waited {}ms with a mutex, {}ms with a semaphore",
+        inheriting, baseline
+    );
+    assert!(inheriting * 2 < baseline);
+    println!("Test priority inheritance OK!");
+    0
+}
@@ -0,0 +1,87 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+extern crate alloc;
+
+use alloc::format;
+use user_lib::{
+    close, exit, fork, getpid, getppid, open, pipe, read, waitpid, write, OpenFlags,
+};
+
+/// 测试 /proc/self/status 与 /proc/<pid>/status，读出的 Pid 与 getpid 一致，
+/// 进程退出后读取返回 -1，输出　Test proc status OK! 就算正确。
+
+/// The value of the line `name:\tvalue` in `status`
+fn field<'a>(status: &'a str, name: &str) -> &'a str {
+    status
+        .lines()
+        .find_map(|line| line.strip_prefix(name)?.strip_prefix(":\t"))
+        .unwrap()
+}
+
+/// Read the whole status at `path`, returns its length
+fn read_status(path: &str, buf: &mut [u8]) -> usize {
+    let fd = open(path, OpenFlags::RDONLY);
+    assert!(fd > 0);
+    let fd = fd as usize;
+    let mut len = 0;
+    loop {
+        // a few bytes a time, the rest comes at the right offset
+        let end = (len + 7).min(buf.len());
+        let read_size = read(fd, &mut buf[len..end]);
+        assert!(read_size >= 0);
+        if read_size == 0 {
+            break;
+        }
+        len += read_size as usize;
+    }
+    close(fd);
+    len
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let mut buf = [0u8; 256];
+    let len = read_status("/proc/self/status\0", &mut buf);
+    let status = core::str::from_utf8(&buf[..len]).unwrap();
+    let pid = getpid() as usize;
+    assert_eq!(field(status, "Pid").parse::<usize>().unwrap(), pid);
+    assert_eq!(field(status, "PPid").parse::<isize>().unwrap(), getppid());
+    assert_eq!(field(status, "State"), "R (running)");
+    assert!(field(status, "Frames").parse::<usize>().unwrap() > 0);
+
+    // a child, kept alive until it is told to go
+    let mut pipe_fd = [0usize; 2];
+    assert_eq!(pipe(&mut pipe_fd), 0);
+    let child = fork();
+    if child == 0 {
+        close(pipe_fd[1]);
+        let mut byte = [0u8; 1];
+        assert_eq!(read(pipe_fd[0], &mut byte), 1);
+        exit(0);
+    }
+    close(pipe_fd[0]);
+    let path = format!("/proc/{}/status\0", child);
+    let len = read_status(path.as_str(), &mut buf);
+    let status = core::str::from_utf8(&buf[..len]).unwrap();
+    assert_eq!(field(status, "Pid").parse::<isize>().unwrap(), child);
+    assert_eq!(field(status, "PPid").parse::<usize>().unwrap(), pid);
+    let fd = open(path.as_str(), OpenFlags::RDONLY);
+    assert!(fd > 0);
+    let fd = fd as usize;
+    assert_eq!(write(pipe_fd[1], b"x"), 1);
+    let mut exit_code: i32 = -1;
+    assert_eq!(waitpid(child as usize, &mut exit_code), child);
+    assert_eq!(exit_code, 0);
+    // the process is gone, and so is its status
+    assert_eq!(read(fd, &mut buf), -1);
+    close(fd);
+    assert_eq!(open(path.as_str(), OpenFlags::RDONLY), -1);
+    assert_eq!(open("/proc/999999/status\0", OpenFlags::RDONLY), -1);
+    assert_eq!(open("/proc/self/status\0", OpenFlags::RDWR), -1);
+    close(pipe_fd[1]);
+    println!("Test proc status OK!");
+    0
+}
@@ -0,0 +1,48 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{close, fstat, open, read, write, OpenFlags, Stat, StatMode};
+
+/// 测试 /dev/random，写入相同的种子后读出相同的字节序列，输出　Test random OK! 就算正确。
+
+const SEED: &[u8] = b"rcore";
+/// What the kernel generator yields after being seeded with `SEED`
+const EXPECTED: [u8; 16] = [98, 54, 90, 82, 86, 46, 244, 94, 88, 75, 126, 128, 2, 103, 197, 177];
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let fd = open("/dev/random\0", OpenFlags::RDWR);
+    assert!(fd > 0);
+    let fd = fd as usize;
+    let stat = Stat::new();
+    assert_eq!(fstat(fd, &stat), 0);
+    assert_eq!(stat.mode, StatMode::CHR);
+
+    // the sequence goes on from one read to the next
+    assert_eq!(write(fd, SEED), SEED.len() as isize);
+    let mut buf = [0u8; 8];
+    assert_eq!(read(fd, &mut buf), 8);
+    assert_eq!(buf, EXPECTED[..8]);
+    assert_eq!(read(fd, &mut buf), 8);
+    assert_eq!(buf, EXPECTED[8..]);
+
+    // the same seed again, from another fd
+    let other = open("/dev/random\0", OpenFlags::RDWR);
+    assert!(other > 0);
+    let other = other as usize;
+    assert_eq!(write(other, SEED), SEED.len() as isize);
+    let mut all = [0u8; 16];
+    assert_eq!(read(fd, &mut all), 16);
+    assert_eq!(all, EXPECTED);
+
+    assert_eq!(write(other, b"another seed"), 12);
+    assert_eq!(read(other, &mut all), 16);
+    assert_ne!(all, EXPECTED);
+    close(other);
+    close(fd);
+    println!("Test random OK!");
+    0
+}
@@ -0,0 +1,48 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{close, mkdir, open, readlink, symlink, unlink, OpenFlags};
+
+/// 测试 readlink，读回符号链接中保存的目标路径，不跟随链接，缓冲区不足时截断且不补 0，
+/// 不是符号链接的路径返回 -1，输出　Test readlink OK! 就算正确。
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let fd = open("fname_readlink\0", OpenFlags::CREATE | OpenFlags::WRONLY);
+    assert!(fd > 0);
+    close(fd as usize);
+    assert_eq!(mkdir("dir_readlink\0", 0o755), 0);
+    assert_eq!(symlink("../fname_readlink\0", "dir_readlink/rel\0"), 0);
+    assert_eq!(symlink("/dir_readlink/rel\0", "link_readlink\0"), 0);
+
+    let mut buffer = [0xffu8; 64];
+    // the exact target, with nothing after it
+    let target = b"../fname_readlink";
+    assert_eq!(readlink("dir_readlink/rel\0", &mut buffer), target.len() as isize);
+    assert_eq!(&buffer[..target.len()], target);
+    assert_eq!(buffer[target.len()], 0xff);
+    // a link to a link is not followed
+    let target = b"/dir_readlink/rel";
+    assert_eq!(readlink("link_readlink\0", &mut buffer), target.len() as isize);
+    assert_eq!(&buffer[..target.len()], target);
+    // cut short to the buffer, no NUL added
+    let mut small = [0xffu8; 5];
+    assert_eq!(readlink("link_readlink\0", &mut small), 5);
+    assert_eq!(&small, b"/dir_");
+    assert_eq!(readlink("link_readlink\0", &mut buffer[..0]), -1);
+    // anything but a symbolic link
+    assert_eq!(readlink("fname_readlink\0", &mut buffer), -1);
+    assert_eq!(readlink("dir_readlink\0", &mut buffer), -1);
+    assert_eq!(readlink("no_such_readlink\0", &mut buffer), -1);
+    assert_eq!(readlink("/dev/null\0", &mut buffer), -1);
+
+    assert_eq!(unlink("link_readlink\0"), 0);
+    assert_eq!(unlink("dir_readlink/rel\0"), 0);
+    assert_eq!(unlink("dir_readlink\0"), 0);
+    assert_eq!(unlink("fname_readlink\0"), 0);
+    println!("Test readlink OK!");
+    0
+}
@@ -0,0 +1,72 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{close, fstat, mkdir, open, read, rename, unlink, write, OpenFlags, Stat};
+
+/// 测试 rename，覆盖已存在的目标后旧名字消失，新名字为原内容且 nlink 正确，
+/// 输出　Test rename OK! 就算正确。
+
+fn create(fname: &str, data: &str) {
+    let fd = open(fname, OpenFlags::CREATE | OpenFlags::TRUNC | OpenFlags::WRONLY);
+    assert!(fd > 0);
+    assert_eq!(write(fd as usize, data.as_bytes()), data.len() as isize);
+    close(fd as usize);
+}
+
+/// Check the content and the number of links of `fname`
+fn check(fname: &str, data: &str, nlink: u32) {
+    let fd = open(fname, OpenFlags::RDONLY);
+    assert!(fd > 0);
+    let fd = fd as usize;
+    let mut buf = [0u8; 32];
+    assert_eq!(read(fd, &mut buf), data.len() as isize);
+    assert_eq!(&buf[..data.len()], data.as_bytes());
+    let stat = Stat::new();
+    assert_eq!(fstat(fd, &stat), 0);
+    assert_eq!(stat.nlink, nlink);
+    close(fd);
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let old = "fname_rename_old\0";
+    let new = "fname_rename_new\0";
+    create(old, "original");
+    create(new, "to be replaced");
+    assert_eq!(rename(old, new), 0);
+    assert_eq!(open(old, OpenFlags::RDONLY), -1);
+    check(new, "original", 1);
+    // onto itself nothing changes
+    assert_eq!(rename(new, new), 0);
+    check(new, "original", 1);
+    assert_eq!(rename(old, new), -1);
+
+    // across directories, and a directory along with what it holds
+    assert_eq!(mkdir("dir_rename\0", 0o755), 0);
+    assert_eq!(rename(new, "dir_rename/moved\0"), 0);
+    assert_eq!(open(new, OpenFlags::RDONLY), -1);
+    check("dir_rename/moved\0", "original", 1);
+    assert_eq!(mkdir("dir_rename2\0", 0o755), 0);
+    assert_eq!(rename("dir_rename\0", "dir_rename2/sub\0"), 0);
+    check("dir_rename2/sub/moved\0", "original", 1);
+    check("dir_rename2/sub/../sub/moved\0", "original", 1);
+    let fd = open("dir_rename2\0", OpenFlags::RDONLY);
+    assert!(fd > 0);
+    let stat = Stat::new();
+    assert_eq!(fstat(fd as usize, &stat), 0);
+    assert_eq!(stat.nlink, 3);
+    close(fd as usize);
+    // not under itself, nor a file over a directory
+    assert_eq!(rename("dir_rename2\0", "dir_rename2/sub/inner\0"), -1);
+    assert_eq!(rename("dir_rename2/sub/moved\0", "dir_rename2\0"), -1);
+    assert_eq!(rename("no_such_dir_rename/f\0", "dir_rename2/f\0"), -1);
+
+    assert_eq!(unlink("dir_rename2/sub/moved\0"), 0);
+    assert_eq!(unlink("dir_rename2/sub\0"), 0);
+    assert_eq!(unlink("dir_rename2\0"), 0);
+    println!("Test rename OK!");
+    0
+}
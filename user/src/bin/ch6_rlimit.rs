@@ -0,0 +1,72 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{
+    close, dup, dup2, exit, fork, getrlimit, open, read, setrlimit, unlink, waitpid, write,
+    OpenFlags, RLimit, RLIMIT_NOFILE,
+};
+
+/// 测试 RLIMIT_NOFILE，软限制降到已打开的 fd 数以下后新的 open 与 dup 失败，
+/// 已打开的 fd 仍然可用，限制由子进程继承，硬限制只能降低，输出　Test rlimit OK! 就算正确。
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let mut rlim = RLimit::default();
+    assert_eq!(getrlimit(RLIMIT_NOFILE, &mut rlim), 0);
+    assert!(rlim.cur >= 8 && rlim.cur <= rlim.max);
+    let hard = rlim.max;
+
+    let fname = "fname_rlimit\0";
+    let fds: [isize; 3] = [
+        open(fname, OpenFlags::CREATE | OpenFlags::TRUNC | OpenFlags::RDWR),
+        open(fname, OpenFlags::RDONLY),
+        open(fname, OpenFlags::RDONLY),
+    ];
+    assert!(fds.iter().all(|&fd| fd > 0));
+    // stdin, stdout, stderr and the files are open, more than the limit allows
+    let low = RLimit { cur: 4, max: hard };
+    assert_eq!(setrlimit(RLIMIT_NOFILE, &low), 0);
+    assert_eq!(getrlimit(RLIMIT_NOFILE, &mut rlim), 0);
+    assert_eq!((rlim.cur, rlim.max), (4, hard));
+    assert_eq!(open(fname, OpenFlags::RDONLY), -1);
+    assert_eq!(dup(fds[0] as usize), -1);
+    assert_eq!(dup2(fds[0] as usize, 6), -1);
+    // while the fds open already keep working
+    assert_eq!(write(fds[0] as usize, b"rlimit"), 6);
+    let mut buffer = [0u8; 6];
+    assert_eq!(read(fds[2] as usize, &mut buffer), 6);
+    assert_eq!(&buffer, b"rlimit");
+
+    // the child is held to the same limit
+    let pid = fork();
+    if pid == 0 {
+        let mut rlim = RLimit::default();
+        let inherited = getrlimit(RLIMIT_NOFILE, &mut rlim) == 0
+            && (rlim.cur, rlim.max) == (4, hard)
+            && open(fname, OpenFlags::RDONLY) == -1;
+        exit(if inherited { 0 } else { 1 });
+    }
+    let mut exit_code: i32 = -1;
+    assert_eq!(waitpid(pid as usize, &mut exit_code), pid);
+    assert_eq!(exit_code, 0);
+
+    // a fd below the limit freed is handed out again
+    let lowest = *fds.iter().min().unwrap();
+    assert!(lowest < 4);
+    close(lowest as usize);
+    assert_eq!(open(fname, OpenFlags::RDONLY), lowest);
+
+    // the soft limit stays under the hard one, which can't be raised again
+    assert_eq!(setrlimit(RLIMIT_NOFILE, &RLimit { cur: hard, max: hard - 1 }), -1);
+    assert_eq!(setrlimit(RLIMIT_NOFILE, &RLimit { cur: 4, max: hard - 1 }), 0);
+    assert_eq!(setrlimit(RLIMIT_NOFILE, &RLimit { cur: 4, max: hard }), -1);
+    assert_eq!(setrlimit(RLIMIT_NOFILE, &RLimit { cur: hard - 1, max: hard - 1 }), 0);
+    assert!(open(fname, OpenFlags::RDONLY) > 0);
+    assert_eq!(getrlimit(RLIMIT_NOFILE + 100, &mut rlim), -1);
+    unlink(fname);
+    println!("Test rlimit OK!");
+    0
+}
@@ -0,0 +1,70 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{
+    exit, fork, getrusage, mmap_file, munmap, waitpid, Rusage, MAP_ANONYMOUS, MAP_PRIVATE,
+    RUSAGE_CHILDREN, RUSAGE_SELF,
+};
+
+/// 测试 getrusage，按需分配的页每页恰好缺页一次，子进程回收后计入父进程，
+/// 输出　Test getrusage OK! 就算正确。
+
+const START: usize = 0x10000000;
+const PAGE_SIZE: usize = 4096;
+const PAGES: usize = 12;
+
+/// Map `PAGES` lazy pages at `START` and touch each of them twice
+fn touch_pages() {
+    let flags = MAP_PRIVATE | MAP_ANONYMOUS;
+    assert_eq!(mmap_file(START, PAGES * PAGE_SIZE, 3, flags, 0, 0), START as isize);
+    for _ in 0..2 {
+        for page in 0..PAGES {
+            let addr = (START + page * PAGE_SIZE) as *mut usize;
+            unsafe {
+                addr.write_volatile(page);
+            }
+        }
+    }
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    // both filled in once, so that their pages are in before counting
+    let mut before = Rusage::default();
+    let mut after = Rusage::default();
+    assert_eq!(getrusage(RUSAGE_SELF, &mut before), 0);
+    assert_eq!(getrusage(RUSAGE_SELF, &mut after), 0);
+
+    assert_eq!(getrusage(RUSAGE_SELF, &mut before), 0);
+    touch_pages();
+    assert_eq!(getrusage(RUSAGE_SELF, &mut after), 0);
+    assert_eq!(after.page_faults - before.page_faults, PAGES as u64);
+    assert!(after.resident_frames >= before.resident_frames + PAGES as u64);
+    assert!(after.peak_frames >= after.resident_frames);
+    assert_eq!(munmap(START, PAGES * PAGE_SIZE), 0);
+    assert_eq!(getrusage(RUSAGE_SELF, &mut before), 0);
+    assert_eq!(before.resident_frames + PAGES as u64, after.resident_frames);
+    // the peak stays
+    assert_eq!(before.peak_frames, after.peak_frames);
+
+    assert_eq!(getrusage(RUSAGE_CHILDREN, &mut before), 0);
+    assert_eq!(before.page_faults, 0);
+    let pid = fork();
+    if pid == 0 {
+        touch_pages();
+        exit(0);
+    }
+    let mut exit_code: i32 = -1;
+    assert_eq!(waitpid(pid as usize, &mut exit_code), pid);
+    assert_eq!(exit_code, 0);
+    assert_eq!(getrusage(RUSAGE_CHILDREN, &mut after), 0);
+    assert!(after.page_faults >= PAGES as u64);
+    assert!(after.peak_frames >= PAGES as u64);
+    assert_eq!(after.resident_frames, 0);
+    assert_eq!(getrusage(2, &mut after), -1);
+    println!("Test getrusage OK!");
+    0
+}
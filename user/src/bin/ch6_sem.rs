@@ -0,0 +1,84 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{
+    close, exit, fork, lseek, open, read, semaphore_create, semaphore_down, semaphore_up,
+    sys_semaphore_up, unlink, waitpid, write, yield_, OpenFlags, SEEK_SET,
+};
+
+/// 测试信号量，父进程作为生产者、子进程作为消费者，经由文件中的有界缓冲区传递数据，
+/// 输出　Test semaphore OK! 就算正确。
+
+const BUFFER_SIZE: usize = 4;
+const ITEMS: usize = 50;
+
+fn read_slot(fd: usize, slot: usize) -> usize {
+    let mut buf = [0u8; 8];
+    lseek(fd, (slot * 8) as isize, SEEK_SET);
+    assert_eq!(read(fd, &mut buf), 8);
+    usize::from_le_bytes(buf)
+}
+
+fn write_slot(fd: usize, slot: usize, value: usize) {
+    lseek(fd, (slot * 8) as isize, SEEK_SET);
+    assert_eq!(write(fd, &value.to_le_bytes()), 8);
+}
+
+/// Take the items in order, a slow consumer lets the producer fill
+/// the buffer up and wait for free slots
+fn consume(fname: &str, sem_empty: usize, sem_full: usize) -> ! {
+    // an fd of its own, the file offset is shared after fork
+    let fd = open(fname, OpenFlags::RDWR);
+    assert!(fd > 0);
+    let fd = fd as usize;
+    for i in 0..ITEMS {
+        assert_eq!(semaphore_down(sem_full), 0);
+        // an item overwritten before taken shows up here
+        assert_eq!(read_slot(fd, i % BUFFER_SIZE), i + 1);
+        semaphore_up(sem_empty);
+        yield_();
+        yield_();
+    }
+    close(fd);
+    exit(0)
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let fname = "fname_sem\0";
+    let fd = open(fname, OpenFlags::CREATE | OpenFlags::TRUNC | OpenFlags::RDWR);
+    assert!(fd > 0);
+    let fd = fd as usize;
+    for slot in 0..BUFFER_SIZE {
+        write_slot(fd, slot, 0);
+    }
+
+    let sem_empty = semaphore_create(BUFFER_SIZE);
+    let sem_full = semaphore_create(0);
+    assert!(sem_empty >= 0 && sem_full >= 0 && sem_empty != sem_full);
+    let (sem_empty, sem_full) = (sem_empty as usize, sem_full as usize);
+
+    let pid = fork();
+    if pid == 0 {
+        consume(fname, sem_empty, sem_full);
+    }
+    for i in 0..ITEMS {
+        assert_eq!(semaphore_down(sem_empty), 0);
+        write_slot(fd, i % BUFFER_SIZE, i + 1);
+        semaphore_up(sem_full);
+    }
+    let mut exit_code: i32 = -1;
+    assert_eq!(waitpid(pid as usize, &mut exit_code), pid);
+    assert_eq!(exit_code, 0);
+
+    assert_eq!(semaphore_create(-1isize as usize), -1);
+    assert_eq!(semaphore_down(100), -1);
+    assert_eq!(sys_semaphore_up(100), -1);
+    close(fd);
+    unlink(fname);
+    println!("Test semaphore OK!");
+    0
+}
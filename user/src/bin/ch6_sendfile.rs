@@ -0,0 +1,83 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{
+    close, exit, fork, lseek, open, pipe, read, sendfile, unlink, waitpid, write, OpenFlags,
+    SEEK_CUR, SEEK_SET,
+};
+
+/// 测试 sendfile，把文件经由内核直接送入管道，子进程从读端读出并核对，
+/// 输出　Test sendfile OK! 就算正确。
+
+const FILE_SIZE: usize = 2000;
+
+fn byte_at(i: usize) -> u8 {
+    (i % 251) as u8
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let fname = "fname_sendfile\0";
+    let fd = open(fname, OpenFlags::CREATE | OpenFlags::TRUNC | OpenFlags::RDWR);
+    assert!(fd > 0);
+    let fd = fd as usize;
+    let mut data = [0u8; FILE_SIZE];
+    for (i, byte) in data.iter_mut().enumerate() {
+        *byte = byte_at(i);
+    }
+    assert_eq!(write(fd, &data), FILE_SIZE as isize);
+    assert_eq!(lseek(fd, 100, SEEK_SET), 100);
+
+    // far more than the pipe holds, so a child drains it meanwhile
+    let mut pipe_fd = [0usize; 2];
+    assert_eq!(pipe(&mut pipe_fd), 0);
+    let pid = fork();
+    if pid == 0 {
+        close(pipe_fd[1]);
+        let mut buf = [0u8; 256];
+        let mut received = 0;
+        loop {
+            let len = read(pipe_fd[0], &mut buf);
+            if len <= 0 {
+                break;
+            }
+            for &byte in buf[..len as usize].iter() {
+                assert_eq!(byte, byte_at(100 + received));
+                received += 1;
+            }
+        }
+        exit(received as i32);
+    }
+    close(pipe_fd[0]);
+    // up to the end of the file, from its offset on
+    assert_eq!(sendfile(pipe_fd[1], fd, None, 5000), (FILE_SIZE - 100) as isize);
+    assert_eq!(lseek(fd, 0, SEEK_CUR), FILE_SIZE as isize);
+    close(pipe_fd[1]);
+    let mut exit_code: i32 = -1;
+    assert_eq!(waitpid(pid as usize, &mut exit_code), pid);
+    assert_eq!(exit_code, (FILE_SIZE - 100) as i32);
+
+    // from an explicit offset, leaving the offset of the file alone
+    assert_eq!(pipe(&mut pipe_fd), 0);
+    let mut offset = 10usize;
+    assert_eq!(sendfile(pipe_fd[1], fd, Some(&mut offset), 20), 20);
+    assert_eq!(offset, 30);
+    assert_eq!(lseek(fd, 0, SEEK_CUR), FILE_SIZE as isize);
+    let mut buf = [0u8; 20];
+    assert_eq!(read(pipe_fd[0], &mut buf), 20);
+    assert_eq!(&buf[..], &data[10..30]);
+
+    // bad fds, or a pipe end the wrong way round
+    assert_eq!(sendfile(pipe_fd[1], 100, None, 1), -1);
+    assert_eq!(sendfile(100, fd, None, 1), -1);
+    assert_eq!(sendfile(pipe_fd[0], fd, None, 1), -1);
+    close(pipe_fd[0]);
+    close(pipe_fd[1]);
+    close(fd);
+    unlink(fname);
+    println!("Test sendfile OK!");
+    0
+}
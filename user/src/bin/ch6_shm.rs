@@ -0,0 +1,93 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{exit, fork, shmat, shmctl, shmdt, shmget, waitpid, yield_, IPC_PRIVATE, IPC_RMID};
+
+/// 测试共享内存段，父子进程经同一段内存互相传值，按 key 查找得到同一个段，
+/// 删除后的段在分离前仍可访问，输出　Test shm OK! 就算正确。
+
+const PAGE_SIZE: usize = 4096;
+const KEY: usize = 0x5eed;
+
+fn word(addr: usize, i: usize) -> *mut usize {
+    (addr + i * core::mem::size_of::<usize>()) as *mut usize
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let id = shmget(IPC_PRIVATE, 2 * PAGE_SIZE);
+    assert!(id > 0);
+    let id = id as usize;
+    let addr = shmat(id);
+    assert!(addr > 0);
+    let addr = addr as usize;
+    assert_eq!(addr % PAGE_SIZE, 0);
+    // a fresh segment reads as zeros
+    assert_eq!(unsafe { word(addr, 0).read_volatile() }, 0);
+
+    let pid = fork();
+    if pid == 0 {
+        // the value the parent writes after forking shows up here
+        while unsafe { word(addr, 0).read_volatile() } == 0 {
+            yield_();
+        }
+        let value = unsafe { word(addr, 0).read_volatile() };
+        unsafe {
+            word(addr + PAGE_SIZE, 0).write_volatile(value + 1);
+        }
+        exit(0);
+    }
+    unsafe {
+        word(addr, 0).write_volatile(41);
+    }
+    let mut exit_code: i32 = -1;
+    assert_eq!(waitpid(pid as usize, &mut exit_code), pid);
+    assert_eq!(exit_code, 0);
+    assert_eq!(unsafe { word(addr + PAGE_SIZE, 0).read_volatile() }, 42);
+    assert_eq!(shmdt(addr), 0);
+    assert_eq!(shmdt(addr), -1);
+
+    // another process finds the segment by its key, a second attach
+    // of the same segment is mapped elsewhere but shows the same memory
+    let keyed = shmget(KEY, PAGE_SIZE);
+    assert!(keyed > 0);
+    assert_eq!(shmget(KEY, PAGE_SIZE / 2), keyed);
+    assert_eq!(shmget(KEY, 2 * PAGE_SIZE), -1);
+    let first = shmat(keyed as usize) as usize;
+    let second = shmat(keyed as usize) as usize;
+    assert_ne!(first, second);
+    let pid = fork();
+    if pid == 0 {
+        let mine = shmat(shmget(KEY, 0) as usize);
+        assert!(mine > 0);
+        unsafe {
+            word(mine as usize, 1).write_volatile(0xbeef);
+        }
+        assert_eq!(shmdt(mine as usize), 0);
+        exit(0);
+    }
+    assert_eq!(waitpid(pid as usize, &mut exit_code), pid);
+    assert_eq!(exit_code, 0);
+    assert_eq!(unsafe { word(first, 1).read_volatile() }, 0xbeef);
+    assert_eq!(unsafe { word(second, 1).read_volatile() }, 0xbeef);
+
+    // removed, but still there until detached
+    assert_eq!(shmctl(keyed as usize, IPC_RMID), 0);
+    assert_eq!(shmat(keyed as usize), -1);
+    let renewed = shmget(KEY, PAGE_SIZE);
+    assert!(renewed > 0 && renewed != keyed);
+    assert_eq!(shmctl(renewed as usize, IPC_RMID), 0);
+    unsafe {
+        word(first, 2).write_volatile(7);
+    }
+    assert_eq!(unsafe { word(second, 2).read_volatile() }, 7);
+    assert_eq!(shmdt(first), 0);
+    assert_eq!(shmdt(second), 0);
+    assert_eq!(shmctl(id, IPC_RMID), 0);
+    assert_eq!(shmctl(id, IPC_RMID), -1);
+    println!("Test shm OK!");
+    0
+}
@@ -0,0 +1,25 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{exit, fork, shutdown, waitpid};
+
+/// 测试 shutdown，只有初始进程可以关机，其余进程调用返回 -1 且继续运行，
+/// 输出　Test shutdown OK! 就算正确。
+
+#[no_mangle]
+pub fn main() -> i32 {
+    assert_eq!(shutdown(false), -1);
+    assert_eq!(shutdown(true), -1);
+    let pid = fork();
+    if pid == 0 {
+        exit(if shutdown(false) == -1 { 0 } else { 1 });
+    }
+    let mut exit_code: i32 = -1;
+    assert_eq!(waitpid(pid as usize, &mut exit_code), pid);
+    assert_eq!(exit_code, 0);
+    println!("Test shutdown OK!");
+    0
+}
@@ -0,0 +1,77 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use core::sync::atomic::{AtomicUsize, Ordering};
+use user_lib::{
+    getpid, kill, sigaction, sigprocmask, sigreturn, SignalAction, SIGKILL, SIGTERM, SIGUSR1,
+    SIGUSR2, SIG_DFL, SIG_IGN,
+};
+
+/// 测试用户信号处理函数，输出　Test sigaction OK! 就算正确。
+
+static HANDLED: AtomicUsize = AtomicUsize::new(0);
+static DEPTH: AtomicUsize = AtomicUsize::new(0);
+static COUNT: AtomicUsize = AtomicUsize::new(0);
+
+extern "C" fn record(signum: usize) {
+    HANDLED.store(signum, Ordering::SeqCst);
+    sigreturn();
+    unreachable!();
+}
+
+/// Sends itself the signal once more, which waits until this one returns
+extern "C" fn reenter(signum: usize) {
+    assert_eq!(DEPTH.fetch_add(1, Ordering::SeqCst), 0);
+    if COUNT.fetch_add(1, Ordering::SeqCst) == 0 {
+        assert_eq!(kill(getpid() as usize, signum), 0);
+    }
+    DEPTH.fetch_sub(1, Ordering::SeqCst);
+    sigreturn();
+    unreachable!();
+}
+
+fn action(handler: usize) -> SignalAction {
+    SignalAction { handler, mask: 0 }
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let pid = getpid() as usize;
+    let mut old = action(12345);
+    assert_eq!(sigaction(SIGUSR1, Some(&action(record as usize)), Some(&mut old)), 0);
+    assert_eq!(old.handler, SIG_DFL);
+    assert_eq!(sigaction(SIGKILL, Some(&action(record as usize)), None), -1);
+    assert_eq!(sigaction(64, Some(&action(record as usize)), None), -1);
+
+    // the handler runs, then kill returns as it would without it
+    let local = 0x5a5a_a5a5usize;
+    assert_eq!(kill(pid, SIGUSR1), 0);
+    assert_eq!(HANDLED.load(Ordering::SeqCst), SIGUSR1);
+    assert_eq!(local, 0x5a5a_a5a5);
+
+    // the same signal is not delivered again until the handler returns
+    assert_eq!(sigaction(SIGUSR2, Some(&action(reenter as usize)), None), 0);
+    assert_eq!(kill(pid, SIGUSR2), 0);
+    assert_eq!(COUNT.load(Ordering::SeqCst), 2);
+    assert_eq!(DEPTH.load(Ordering::SeqCst), 0);
+
+    // a masked signal waits until it is unmasked
+    HANDLED.store(0, Ordering::SeqCst);
+    assert_eq!(sigprocmask(1 << SIGUSR1), 0);
+    assert_eq!(kill(pid, SIGUSR1), 0);
+    assert_eq!(HANDLED.load(Ordering::SeqCst), 0);
+    assert_eq!(sigprocmask(0), 1 << SIGUSR1);
+    assert_eq!(HANDLED.load(Ordering::SeqCst), SIGUSR1);
+
+    // an ignored signal doesn't terminate
+    assert_eq!(sigaction(SIGTERM, Some(&action(SIG_IGN)), None), 0);
+    assert_eq!(kill(pid, SIGTERM), 0);
+    let mut old = action(0);
+    assert_eq!(sigaction(SIGUSR1, None, Some(&mut old)), 0);
+    assert_eq!(old.handler, record as usize);
+    println!("Test sigaction OK!");
+    0
+}
@@ -0,0 +1,54 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{exit, fork, get_time, kill, sleep_blocking, task_info, waitpid, TaskInfo, SIGKILL};
+
+/// 测试阻塞式 sleep，输出　Test sleep OK! 就算正确。
+
+fn running_time() -> usize {
+    let info = TaskInfo::new();
+    assert_eq!(task_info(&info), 0);
+    info.time
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let start = get_time();
+    assert_eq!(sleep_blocking(0), 0);
+    assert_eq!(sleep_blocking(50), 0);
+    assert!(get_time() - start >= 50);
+
+    // a busy child keeps running while we sleep
+    let start = get_time();
+    let pid = fork();
+    if pid == 0 {
+        while get_time() - start < 150 {}
+        exit(running_time() as i32);
+    }
+    let before = running_time();
+    assert_eq!(sleep_blocking(100), 0);
+    assert!(get_time() - start >= 100);
+    // sleeping is not running
+    assert!(running_time() - before < 50);
+    let mut exit_code: i32 = 0;
+    assert_eq!(waitpid(pid as usize, &mut exit_code), pid);
+    assert!(exit_code >= 50);
+
+    // a sleeping child can still be killed
+    let start = get_time();
+    let pid = fork();
+    if pid == 0 {
+        sleep_blocking(10_000);
+        exit(0);
+    }
+    sleep_blocking(20);
+    assert_eq!(kill(pid as usize, SIGKILL), 0);
+    assert_eq!(waitpid(pid as usize, &mut exit_code), pid);
+    assert_eq!(exit_code, -(SIGKILL as i32));
+    assert!(get_time() - start < 5_000);
+    println!("Test sleep OK!");
+    0
+}
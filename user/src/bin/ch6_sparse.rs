@@ -0,0 +1,62 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{
+    close, fstat, ftruncate, lseek, open, pread, statfs, unlink, write, OpenFlags, Stat, Statfs,
+    SEEK_SET,
+};
+
+/// 测试稀疏文件，越过文件末尾写入或用 ftruncate 扩大文件只占用实际写入的块，
+/// 空洞读出全为 0，输出　Test sparse file OK! 就算正确。
+
+const SPARSE_SIZE: usize = 1 << 20;
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let mut before = Statfs::default();
+    assert_eq!(statfs("/\0", &mut before), 0);
+    let fname = "fname_sparse\0";
+    let fd = open(fname, OpenFlags::CREATE | OpenFlags::TRUNC | OpenFlags::RDWR);
+    assert!(fd > 0);
+    let fd = fd as usize;
+    assert_eq!(write(fd, b"head"), 4);
+    assert_eq!(lseek(fd, (SPARSE_SIZE - 4) as isize, SEEK_SET), (SPARSE_SIZE - 4) as isize);
+    assert_eq!(write(fd, b"tail"), 4);
+
+    let stat = Stat::new();
+    assert_eq!(fstat(fd, &stat), 0);
+    assert_eq!(stat.size, SPARSE_SIZE as u64);
+    // a block at each end plus the index blocks leading to the last one
+    assert!(stat.blocks <= 4);
+    let mut used = Statfs::default();
+    assert_eq!(statfs(fname, &mut used), 0);
+    assert!(before.bfree - used.bfree <= 4);
+
+    // the hole reads as zeros
+    let mut buf = [0xffu8; 512];
+    assert_eq!(pread(fd, &mut buf, SPARSE_SIZE / 2), 512);
+    assert!(buf.iter().all(|&b| b == 0));
+    assert_eq!(pread(fd, &mut buf[..4], SPARSE_SIZE - 4), 4);
+    assert_eq!(&buf[..4], b"tail");
+
+    // growing takes no block at all
+    assert_eq!(ftruncate(fd, (2 * SPARSE_SIZE) as isize), 0);
+    let mut grown = Statfs::default();
+    assert_eq!(statfs(fname, &mut grown), 0);
+    assert_eq!(grown.bfree, used.bfree);
+    assert_eq!(fstat(fd, &stat), 0);
+    assert_eq!(stat.size, (2 * SPARSE_SIZE) as u64);
+    assert_eq!(pread(fd, &mut buf, 2 * SPARSE_SIZE - 512), 512);
+    assert!(buf.iter().all(|&b| b == 0));
+
+    close(fd);
+    assert_eq!(unlink(fname), 0);
+    let mut after = Statfs::default();
+    assert_eq!(statfs("/\0", &mut after), 0);
+    assert_eq!(after.bfree, before.bfree);
+    println!("Test sparse file OK!");
+    0
+}
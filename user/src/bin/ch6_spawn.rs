@@ -0,0 +1,33 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{close, mkdir, open, spawn, unlink, waitpid, write, OpenFlags};
+
+/// 测试 spawn 只接受存在的 elf 文件，输出　Test spawn OK! 就算正确。
+
+#[no_mangle]
+pub fn main() -> i32 {
+    assert_eq!(spawn("no_such_file\0"), -1);
+    // neither a plain file nor a directory is an elf
+    let fd = open("fname_spawn\0", OpenFlags::CREATE | OpenFlags::WRONLY);
+    assert!(fd > 0);
+    write(fd as usize, b"\x7fELF, but not really");
+    close(fd as usize);
+    assert_eq!(spawn("fname_spawn\0"), -1);
+    assert_eq!(mkdir("dir_spawn\0", 0o755), 0);
+    assert_eq!(spawn("dir_spawn\0"), -1);
+
+    let cpid = spawn("ch5_exit0\0");
+    assert!(cpid > 0);
+    let mut exit_code: i32 = 0;
+    assert_eq!(waitpid(cpid as usize, &mut exit_code), cpid);
+    assert_eq!(exit_code, 66778);
+
+    assert_eq!(unlink("fname_spawn\0"), 0);
+    assert_eq!(unlink("dir_spawn\0"), 0);
+    println!("Test spawn OK!");
+    0
+}
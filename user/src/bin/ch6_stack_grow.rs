@@ -0,0 +1,47 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{exit, fork, waitpid};
+
+/// 测试按需增长的用户栈，有限深度的递归使栈向下增长若干页后正常返回，
+/// 每层栈帧的内容保持不变，输出　Test stack grow OK! 就算正确。
+
+/// Deep enough to take about 25KB of stack, several pages below the first
+const DEPTH: usize = 48;
+
+/// Recurse `depth` more times with a frame of about half a kilobyte
+/// filled with `depth`, and check that it is intact on the way back
+fn recurse(depth: usize) -> usize {
+    let mut frame = [0u8; 500];
+    let frame_ptr = frame.as_mut_ptr();
+    for i in 0..frame.len() {
+        unsafe {
+            frame_ptr.add(i).write_volatile(depth as u8);
+        }
+    }
+    let below = if depth == 0 { 0 } else { recurse(depth - 1) };
+    for i in 0..frame.len() {
+        assert_eq!(unsafe { frame_ptr.add(i).read_volatile() }, depth as u8);
+    }
+    below + depth
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    assert_eq!(recurse(DEPTH), DEPTH * (DEPTH + 1) / 2);
+    // once grown, the stack stays, and a child gets a copy of it
+    assert_eq!(recurse(DEPTH), DEPTH * (DEPTH + 1) / 2);
+    let pid = fork();
+    if pid == 0 {
+        assert_eq!(recurse(DEPTH * 3 / 2), (DEPTH * 3 / 2) * (DEPTH * 3 / 2 + 1) / 2);
+        exit(0);
+    }
+    let mut exit_code: i32 = -1;
+    assert_eq!(waitpid(pid as usize, &mut exit_code), pid);
+    assert_eq!(exit_code, 0);
+    println!("Test stack grow OK!");
+    0
+}
@@ -0,0 +1,47 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{brk, exit, fork, mmap, waitpid};
+
+/// 测试用户栈下方的保护页，无限递归的子进程撞上保护页后以栈溢出的退出码 -4 结束，
+/// 保护页也不能被 mmap 占用，输出　Test stack overflow OK! 就算正确。
+
+const PAGE_SIZE: usize = 4096;
+const USER_STACK_SIZE: usize = 4096 * 16;
+
+/// Recurse with a frame of a few hundred bytes until the stack runs out
+fn recurse(depth: usize) -> usize {
+    let mut frame = [0u8; 256];
+    let frame_ptr = frame.as_mut_ptr();
+    unsafe {
+        frame_ptr.write_volatile(depth as u8);
+    }
+    if depth == usize::MAX {
+        return 0;
+    }
+    // used after the call, so that the frame can't be reused
+    recurse(depth + 1) + unsafe { frame_ptr.read_volatile() } as usize
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    // the heap starts right at the top of the user stack
+    let stack_top = brk(0) as usize;
+    let guard = stack_top - USER_STACK_SIZE - PAGE_SIZE;
+    assert_eq!(mmap(guard, PAGE_SIZE, 3), -1);
+
+    let pid = fork();
+    if pid == 0 {
+        let depth = recurse(0);
+        println!("recursed {} times without overflowing", depth);
+        exit(0);
+    }
+    let mut exit_code: i32 = 0;
+    assert_eq!(waitpid(pid as usize, &mut exit_code), pid);
+    assert_eq!(exit_code, -4);
+    println!("Test stack overflow OK!");
+    0
+}
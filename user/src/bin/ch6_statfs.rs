@@ -0,0 +1,49 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{close, open, statfs, unlink, write, OpenFlags, Statfs};
+
+/// 测试 statfs，写入文件后空闲块和空闲 inode 减少，删除后恢复原值，
+/// 输出　Test statfs OK! 就算正确。
+
+const BLOCKS: usize = 64;
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let mut before = Statfs::default();
+    assert_eq!(statfs("/\0", &mut before), 0);
+    assert_eq!(before.bsize, 512);
+    assert!(before.bfree <= before.blocks);
+    assert!(before.ffree <= before.files);
+
+    let fname = "fname_statfs\0";
+    let fd = open(fname, OpenFlags::CREATE | OpenFlags::TRUNC | OpenFlags::WRONLY);
+    assert!(fd > 0);
+    let fd = fd as usize;
+    let block = [0x5au8; 512];
+    for _ in 0..BLOCKS {
+        assert_eq!(write(fd, &block), 512);
+    }
+    let mut used = Statfs::default();
+    assert_eq!(statfs(fname, &mut used), 0);
+    // the data blocks plus an indirect block or two
+    let taken = before.bfree - used.bfree;
+    assert!(taken >= BLOCKS as u64 && taken <= BLOCKS as u64 + 2);
+    assert_eq!(before.ffree - used.ffree, 1);
+    assert_eq!(used.blocks, before.blocks);
+    assert_eq!(used.files, before.files);
+
+    close(fd);
+    assert_eq!(unlink(fname), 0);
+    let mut after = Statfs::default();
+    assert_eq!(statfs("/\0", &mut after), 0);
+    assert_eq!(after.bfree, before.bfree);
+    assert_eq!(after.ffree, before.ffree);
+
+    assert_eq!(statfs("/no_such_file\0", &mut after), -1);
+    println!("Test statfs OK!");
+    0
+}
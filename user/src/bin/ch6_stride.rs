@@ -0,0 +1,48 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{exit, fork, get_time, set_priority, waitpid};
+
+/// 测试 stride 调度，优先级为 2 倍的进程得到约 2 倍的运行时间，
+/// 输出　Test stride OK! 就算正确。
+
+const MAX_TIME: isize = 2000;
+
+/// Count iterations over `MAX_TIME` milliseconds at priority `prio`
+fn count_during(prio: isize) -> i32 {
+    assert_eq!(set_priority(prio), prio);
+    let start_time = get_time();
+    let mut acc = 0;
+    loop {
+        acc += 1;
+        if acc % 400 == 0 && get_time() - start_time > MAX_TIME {
+            return acc;
+        }
+    }
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let prios = [5, 10];
+    let mut pids = [0usize; 2];
+    for (pid, &prio) in pids.iter_mut().zip(prios.iter()) {
+        let ret = fork();
+        if ret == 0 {
+            exit(count_during(prio));
+        }
+        *pid = ret as usize;
+    }
+    let mut counts = [0i32; 2];
+    for (count, &pid) in counts.iter_mut().zip(pids.iter()) {
+        assert_eq!(waitpid(pid, count), pid as isize);
+    }
+    let (low, high) = (counts[0] as i64, counts[1] as i64);
+    println!("priority 5: {}, priority 10: {}", low, high);
+    // twice the priority, about twice the time
+    assert!(high * 10 >= low * 15 && high * 10 <= low * 25);
+    println!("Test stride OK!");
+    0
+}
@@ -0,0 +1,73 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{close, fstat, mkdir, open, read, symlink, unlink, write, OpenFlags, Stat, StatMode};
+
+/// 测试符号链接，输出　Test symlink OK! 就算正确。
+
+fn read_str(fd: usize, buffer: &mut [u8]) -> &str {
+    let len = read(fd, buffer) as usize;
+    core::str::from_utf8(&buffer[..len]).unwrap()
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let test_str = "Hello, symlink!";
+    let mut buffer = [0u8; 100];
+    assert_eq!(mkdir("dir_symlink\0", 0o755), 0);
+    let fd = open("dir_symlink/target\0", OpenFlags::CREATE | OpenFlags::WRONLY);
+    assert!(fd > 0);
+    write(fd as usize, test_str.as_bytes());
+    close(fd as usize);
+
+    // a chain of a relative, an absolute and a directory link
+    assert_eq!(symlink("target\0", "dir_symlink/link0\0"), 0);
+    assert_eq!(symlink("/dir_symlink/link0\0", "link1\0"), 0);
+    assert_eq!(symlink("dir_symlink\0", "link_dir\0"), 0);
+    assert_eq!(symlink("target\0", "link1\0"), -1);
+    for path in ["link1\0", "link_dir/link0\0", "link_dir/target\0"].iter() {
+        let fd = open(path, OpenFlags::RDONLY);
+        assert!(fd > 0);
+        assert_eq!(read_str(fd as usize, &mut buffer), test_str);
+        let stat = Stat::new();
+        assert_eq!(fstat(fd as usize, &stat), 0);
+        assert_eq!(stat.mode, StatMode::FILE);
+        close(fd as usize);
+    }
+
+    // the link itself holds the path it points to
+    let fd = open("link1\0", OpenFlags::RDONLY | OpenFlags::NOFOLLOW);
+    assert!(fd > 0);
+    let stat = Stat::new();
+    assert_eq!(fstat(fd as usize, &stat), 0);
+    assert_eq!(stat.mode, StatMode::LNK);
+    assert_eq!(read_str(fd as usize, &mut buffer), "/dir_symlink/link0");
+    close(fd as usize);
+    assert_eq!(open("link1\0", OpenFlags::WRONLY | OpenFlags::NOFOLLOW), -1);
+
+    // a dangling link, then a cycle
+    assert_eq!(symlink("no_such_file\0", "link_dangling\0"), 0);
+    assert_eq!(open("link_dangling\0", OpenFlags::RDONLY), -1);
+    assert_eq!(symlink("link_loop1\0", "link_loop0\0"), 0);
+    assert_eq!(symlink("link_loop0\0", "link_loop1\0"), 0);
+    assert_eq!(open("link_loop0\0", OpenFlags::RDONLY), -1);
+    assert_eq!(open("link_loop0/target\0", OpenFlags::RDONLY), -1);
+    assert!(open("link_loop0\0", OpenFlags::RDONLY | OpenFlags::NOFOLLOW) > 0);
+
+    // removing a link leaves its target alone
+    for path in ["link_loop0\0", "link_loop1\0", "link_dangling\0", "link_dir\0", "link1\0"].iter() {
+        assert_eq!(unlink(path), 0);
+    }
+    assert_eq!(unlink("dir_symlink/link0\0"), 0);
+    let fd = open("dir_symlink/target\0", OpenFlags::RDONLY);
+    assert!(fd > 0);
+    assert_eq!(read_str(fd as usize, &mut buffer), test_str);
+    close(fd as usize);
+    assert_eq!(unlink("dir_symlink/target\0"), 0);
+    assert_eq!(unlink("dir_symlink\0"), 0);
+    println!("Test symlink OK!");
+    0
+}
@@ -0,0 +1,32 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{close, open, read, sync, unlink, write, OpenFlags};
+
+/// 测试 sync 写回脏块，输出　Test sync OK! 就算正确。
+
+#[no_mangle]
+pub fn main() -> i32 {
+    // nothing opened yet
+    assert_eq!(sync(), 0);
+    let fname = "fname_sync\0";
+    let fd = open(fname, OpenFlags::CREATE | OpenFlags::WRONLY);
+    assert!(fd > 0);
+    let fd = fd as usize;
+    let data = [b's'; 1000];
+    assert_eq!(write(fd, &data), 1000);
+    assert_eq!(sync(), 0);
+    close(fd);
+    let fd = open(fname, OpenFlags::RDONLY) as usize;
+    let mut buffer = [0u8; 1000];
+    assert_eq!(read(fd, &mut buffer), 1000);
+    assert!(buffer.iter().all(|&b| b == b's'));
+    close(fd);
+    assert_eq!(unlink(fname), 0);
+    assert_eq!(sync(), 0);
+    println!("Test sync OK!");
+    0
+}
@@ -0,0 +1,59 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{
+    exit, fork, get_time, getpid, task_info, waitpid, write, yield_, TaskInfo, TaskStatus,
+    SYSCALL_FORK, SYSCALL_GETPID, SYSCALL_TASK_INFO, SYSCALL_WAITPID, SYSCALL_WRITE,
+    SYSCALL_YIELD,
+};
+
+/// 测试 task_info 统计的系统调用次数与运行时间，输出　Test task info syscalls OK! 就算正确。
+
+const BUSY_TIME: isize = 1000;
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let info = TaskInfo::new();
+    for _ in 0..3 {
+        getpid();
+    }
+    for _ in 0..2 {
+        yield_();
+    }
+    write(1, b"");
+    assert_eq!(task_info(&info), 0);
+    assert_eq!(info.status, TaskStatus::Running);
+    assert_eq!(info.syscall_times[SYSCALL_GETPID], 3);
+    assert_eq!(info.syscall_times[SYSCALL_YIELD], 2);
+    assert_eq!(info.syscall_times[SYSCALL_WRITE], 1);
+    assert_eq!(info.syscall_times[SYSCALL_TASK_INFO], 1);
+    assert_eq!(info.syscall_times[SYSCALL_FORK], 0);
+
+    // the child keeps the CPU busy while we mostly wait for it
+    let before = info.time;
+    let start = get_time();
+    let pid = fork();
+    if pid == 0 {
+        let info = TaskInfo::new();
+        assert_eq!(task_info(&info), 0);
+        // a child counts from scratch
+        assert_eq!(info.syscall_times[SYSCALL_GETPID], 0);
+        assert_eq!(info.syscall_times[SYSCALL_TASK_INFO], 1);
+        while get_time() - start < BUSY_TIME {}
+        exit(0);
+    }
+    let mut exit_code: i32 = 0;
+    assert_eq!(waitpid(pid as usize, &mut exit_code), pid);
+    let elapsed = (get_time() - start) as usize;
+    assert_eq!(task_info(&info), 0);
+    assert_eq!(info.syscall_times[SYSCALL_FORK], 1);
+    assert!(info.syscall_times[SYSCALL_WAITPID] >= 1);
+    assert_eq!(info.syscall_times[SYSCALL_TASK_INFO], 2);
+    // waiting in the ready queue is not running
+    assert!(info.time - before < elapsed / 2);
+    println!("Test task info syscalls OK!");
+    0
+}
@@ -0,0 +1,55 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{exit, fork, times, waitpid, Tms, CLK_TCK};
+
+/// 测试 times，子进程忙循环消耗 CPU，被回收后父进程的 cutime 随之增加，
+/// 输出　Test times OK! 就算正确。
+
+/// CPU time the child burns in user mode, in clock ticks
+const BUSY_TICKS: usize = CLK_TCK / 5;
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let mut before = Tms::default();
+    let start = times(&mut before);
+    assert!(start >= 0);
+    let pid = fork();
+    if pid == 0 {
+        let mut tms = Tms::default();
+        let mut spins = 0usize;
+        let spins_ptr = &mut spins as *mut usize;
+        while tms.utime < BUSY_TICKS {
+            for _ in 0..10000 {
+                unsafe {
+                    spins_ptr.write_volatile(spins_ptr.read_volatile() + 1);
+                }
+            }
+            assert!(times(&mut tms) >= 0);
+        }
+        // nothing reaped yet
+        assert_eq!(tms.cutime, 0);
+        assert_eq!(tms.cstime, 0);
+        exit(0);
+    }
+    // the child is not counted before it is reaped
+    let mut running = Tms::default();
+    times(&mut running);
+    assert_eq!(running.cutime, before.cutime);
+    let mut exit_code: i32 = -1;
+    assert_eq!(waitpid(pid as usize, &mut exit_code), pid);
+    assert_eq!(exit_code, 0);
+
+    let mut after = Tms::default();
+    let end = times(&mut after);
+    assert!(end >= start + BUSY_TICKS as isize);
+    assert!(after.cutime >= before.cutime + BUSY_TICKS);
+    assert!(after.cstime >= before.cstime);
+    assert!(after.utime >= before.utime);
+    assert!(after.stime >= before.stime);
+    println!("Test times OK!");
+    0
+}
@@ -0,0 +1,48 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{close, fstat, link, open, read, sleep, unlink, write, OpenFlags, Stat};
+
+/// 测试文件时间戳，输出　Test timestamps OK! 就算正确。
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let fname = "fname_timestamps\0";
+    let lname = "lname_timestamps\0";
+    let fd = open(fname, OpenFlags::CREATE | OpenFlags::RDWR);
+    assert!(fd > 0);
+    let fd = fd as usize;
+    write(fd, b"Hello, world!");
+    let written = Stat::new();
+    fstat(fd, &written);
+    assert!(written.mtime > 0);
+    assert_eq!(written.mtime, written.ctime);
+    close(fd);
+
+    sleep(20);
+    let fd = open(fname, OpenFlags::RDONLY) as usize;
+    let mut buffer = [0u8; 100];
+    read(fd, &mut buffer);
+    let accessed = Stat::new();
+    fstat(fd, &accessed);
+    // reading only bumps atime
+    assert!(written.mtime < accessed.atime);
+    assert_eq!(accessed.mtime, written.mtime);
+    assert_eq!(accessed.ctime, written.ctime);
+
+    sleep(20);
+    // a new link changes the inode but not its data
+    assert_eq!(link(fname, lname), 0);
+    let linked = Stat::new();
+    fstat(fd, &linked);
+    assert!(accessed.atime < linked.ctime);
+    assert_eq!(linked.mtime, written.mtime);
+    close(fd);
+    assert_eq!(unlink(lname), 0);
+    assert_eq!(unlink(fname), 0);
+    println!("Test timestamps OK!");
+    0
+}
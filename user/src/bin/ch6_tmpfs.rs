@@ -0,0 +1,63 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{
+    access, close, fstat, lseek, open, read, statfs, unlink, write, OpenFlags, Stat, Statfs, F_OK,
+    SEEK_SET,
+};
+
+/// 测试 /tmp 下的内存文件系统，写入读出的内容一致，且 easy-fs 的空闲块数不变，
+/// 输出　Test tmpfs OK! 就算正确。
+
+const BLOCKS: usize = 16;
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let mut before = Statfs::default();
+    assert_eq!(statfs("/\0", &mut before), 0);
+
+    let fname = "/tmp/fname_tmpfs\0";
+    let fd = open(fname, OpenFlags::CREATE | OpenFlags::TRUNC | OpenFlags::RDWR);
+    assert!(fd > 0);
+    let fd = fd as usize;
+    for i in 0..BLOCKS {
+        let block = [i as u8; 512];
+        assert_eq!(write(fd, &block), 512);
+    }
+    let stat = Stat::new();
+    assert_eq!(fstat(fd, &stat), 0);
+    assert_eq!(stat.size, (BLOCKS * 512) as u64);
+    assert_eq!(stat.nlink, 1);
+    assert_eq!(lseek(fd, 0, SEEK_SET), 0);
+    let mut block = [0u8; 512];
+    for i in 0..BLOCKS {
+        assert_eq!(read(fd, &mut block), 512);
+        assert!(block.iter().all(|&b| b == i as u8));
+    }
+    assert_eq!(read(fd, &mut block), 0);
+    close(fd);
+
+    // nothing reaches the disk
+    let mut after = Statfs::default();
+    assert_eq!(statfs("/\0", &mut after), 0);
+    assert_eq!(after.bfree, before.bfree);
+    assert_eq!(after.ffree, before.ffree);
+    assert_eq!(access("fname_tmpfs\0", F_OK), -1);
+    // the same file through a relative path from the root
+    assert_eq!(access("tmp/fname_tmpfs\0", F_OK), 0);
+    let fd = open("tmp/fname_tmpfs\0", OpenFlags::RDONLY);
+    assert!(fd > 0);
+    assert_eq!(read(fd as usize, &mut block), 512);
+    assert!(block.iter().all(|&b| b == 0));
+    close(fd as usize);
+
+    assert_eq!(unlink(fname), 0);
+    assert_eq!(access(fname, F_OK), -1);
+    assert_eq!(open(fname, OpenFlags::RDONLY), -1);
+    assert_eq!(open("/tmp/no_such_dir/file\0", OpenFlags::CREATE | OpenFlags::WRONLY), -1);
+    println!("Test tmpfs OK!");
+    0
+}
@@ -0,0 +1,42 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{close, lseek, open, read, unlink, write, OpenFlags, SEEK_END};
+
+/// 测试 O_TRUNC 截断文件，输出　Test trunc OK! 就算正确。
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let test_str = "Hello, world!";
+    let fname = "fname_trunc\0";
+    // a missing file is simply created empty
+    let fd = open(fname, OpenFlags::CREATE | OpenFlags::WRONLY | OpenFlags::TRUNC);
+    assert!(fd > 0);
+    assert_eq!(lseek(fd as usize, 0, SEEK_END), 0);
+    write(fd as usize, test_str.as_bytes());
+    close(fd as usize);
+
+    // creating an existing file keeps its content
+    let fd = open(fname, OpenFlags::CREATE | OpenFlags::RDWR);
+    assert!(fd > 0);
+    let mut buffer = [0u8; 100];
+    let read_len = read(fd as usize, &mut buffer) as usize;
+    assert_eq!(test_str, core::str::from_utf8(&buffer[..read_len]).unwrap());
+    close(fd as usize);
+
+    // truncating needs the write permission
+    assert_eq!(open(fname, OpenFlags::RDONLY | OpenFlags::TRUNC), -1);
+    let fd = open(fname, OpenFlags::RDWR | OpenFlags::TRUNC);
+    assert!(fd > 0);
+    assert_eq!(lseek(fd as usize, 0, SEEK_END), 0);
+    close(fd as usize);
+    let fd = open(fname, OpenFlags::RDONLY);
+    assert_eq!(read(fd as usize, &mut buffer), 0);
+    close(fd as usize);
+    assert_eq!(unlink(fname), 0);
+    println!("Test trunc OK!");
+    0
+}
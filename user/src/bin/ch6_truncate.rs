@@ -0,0 +1,51 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{close, fstat, mkdir, open, read, truncate, unlink, write, OpenFlags, Stat};
+
+/// 测试按路径的 truncate，缩短后重新打开文件截断处之后的数据不再存在，
+/// 不存在的路径与目录返回 -1，输出　Test truncate OK! 就算正确。
+
+fn size_of(path: &str) -> u64 {
+    let fd = open(path, OpenFlags::RDONLY);
+    assert!(fd > 0);
+    let stat = Stat::new();
+    fstat(fd as usize, &stat);
+    close(fd as usize);
+    stat.size
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    for &fname in ["fname_truncate\0", "/tmp/fname_truncate\0"].iter() {
+        let fd = open(fname, OpenFlags::CREATE | OpenFlags::TRUNC | OpenFlags::WRONLY);
+        assert!(fd > 0);
+        assert_eq!(write(fd as usize, &[b'x'; 2000]), 2000);
+        close(fd as usize);
+
+        // no fd is held while cutting it short
+        assert_eq!(truncate(fname, 600), 0);
+        assert_eq!(size_of(fname), 600);
+        assert_eq!(truncate(fname, 1500), 0);
+        let fd = open(fname, OpenFlags::RDONLY);
+        assert!(fd > 0);
+        let mut buffer = [0xffu8; 2000];
+        assert_eq!(read(fd as usize, &mut buffer), 1500);
+        close(fd as usize);
+        assert!(buffer[..600].iter().all(|&b| b == b'x'));
+        // the bytes past the cut are gone for good
+        assert!(buffer[600..1500].iter().all(|&b| b == 0));
+        assert_eq!(truncate(fname, -1), -1);
+        assert_eq!(unlink(fname), 0);
+        assert_eq!(truncate(fname, 0), -1);
+    }
+    assert_eq!(mkdir("dir_truncate\0", 0), 0);
+    assert_eq!(truncate("dir_truncate\0", 0), -1);
+    assert_eq!(unlink("dir_truncate\0"), 0);
+    assert_eq!(truncate("nowhere_truncate\0", 0), -1);
+    println!("Test truncate OK!");
+    0
+}
@@ -0,0 +1,53 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{mmap_file, munmap, uname, Utsname, MAP_ANONYMOUS, MAP_PRIVATE};
+
+/// 测试 uname，machine 字段为 riscv64，各字段以 NUL 结尾，
+/// 结构体跨越页边界时也能正确填写，输出　Test uname OK! 就算正确。
+
+const START: usize = 0x10000000;
+const PAGE_SIZE: usize = 4096;
+
+fn check(utsname: &Utsname) {
+    assert_eq!(Utsname::field(&utsname.machine), "riscv64");
+    let fields = [
+        &utsname.sysname,
+        &utsname.nodename,
+        &utsname.release,
+        &utsname.version,
+        &utsname.machine,
+        &utsname.domainname,
+    ];
+    for field in fields.iter() {
+        assert!(!Utsname::field(field).is_empty());
+        assert_eq!(field[64], 0);
+    }
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let mut utsname = Utsname::new();
+    assert_eq!(uname(&mut utsname), 0);
+    check(&utsname);
+    println!("{} {}", Utsname::field(&utsname.sysname), Utsname::field(&utsname.release));
+
+    // the machine field lies across the page boundary
+    let flags = MAP_PRIVATE | MAP_ANONYMOUS;
+    assert_eq!(mmap_file(START, 2 * PAGE_SIZE, 3, flags, 0, 0), START as isize);
+    let straddling = unsafe { &mut *((START + PAGE_SIZE - 4 * 65 - 3) as *mut Utsname) };
+    assert_eq!(uname(straddling), 0);
+    check(straddling);
+    munmap(START + PAGE_SIZE, PAGE_SIZE);
+    // and cut short by an unmapped page
+    assert_eq!(uname(straddling), -1);
+    munmap(START, PAGE_SIZE);
+
+    let unmapped = unsafe { &mut *(0x5000_0000 as *mut Utsname) };
+    assert_eq!(uname(unmapped), -1);
+    println!("Test uname OK!");
+    0
+}
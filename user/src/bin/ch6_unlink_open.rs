@@ -0,0 +1,40 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{close, dup, fstat, lseek, open, read, unlink, write, OpenFlags, Stat, SEEK_SET};
+
+/// 测试 unlink 仍被打开的文件，输出　Test unlink opened file OK! 就算正确。
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let test_str = "Hello, world!";
+    let fname = "fname_unlink_open\0";
+    let fd = open(fname, OpenFlags::CREATE | OpenFlags::RDWR);
+    assert!(fd > 0);
+    let fd = fd as usize;
+    let fd2 = dup(fd) as usize;
+    assert_eq!(unlink(fname), 0);
+    assert!(open(fname, OpenFlags::RDONLY) < 0);
+    let stat = Stat::new();
+    fstat(fd, &stat);
+    assert_eq!(stat.nlink, 0);
+    // reads and writes keep working until the last close
+    assert_eq!(write(fd, test_str.as_bytes()), test_str.len() as isize);
+    close(fd);
+    assert_eq!(lseek(fd2, 0, SEEK_SET), 0);
+    let mut buffer = [0u8; 100];
+    let read_len = read(fd2, &mut buffer) as usize;
+    assert_eq!(test_str, core::str::from_utf8(&buffer[..read_len]).unwrap());
+    close(fd2);
+    // the name can be used again for a new file
+    let fd = open(fname, OpenFlags::CREATE | OpenFlags::RDWR);
+    assert!(fd > 0);
+    assert_eq!(read(fd as usize, &mut buffer), 0);
+    close(fd as usize);
+    assert_eq!(unlink(fname), 0);
+    println!("Test unlink opened file OK!");
+    0
+}
@@ -27,6 +27,88 @@ static TESTS: &[&str] = &[
     "ch6_file1\0",
     "ch6_file2\0",
     "ch6_file3\0",
+    "ch6_lseek\0",
+    "ch6_dup\0",
+    "ch6_dup2\0",
+    "ch6_pipe\0",
+    "ch6_mkdir\0",
+    "ch6_getdents\0",
+    "ch6_nlink\0",
+    "ch6_unlink_open\0",
+    "ch6_append\0",
+    "ch6_trunc\0",
+    "ch6_ftruncate\0",
+    "ch6_timestamps\0",
+    "ch6_sync\0",
+    "ch6_cow\0",
+    "ch6_mmap_file\0",
+    "ch6_mmap_anon\0",
+    "ch6_brk\0",
+    "ch6_mprotect\0",
+    "ch6_bad_buffer\0",
+    "ch6_bad_path\0",
+    "ch6_symlink\0",
+    "ch6_stride\0",
+    "ch6_spawn\0",
+    "ch6_task_info\0",
+    "ch6_wnohang\0",
+    "ch6_kill\0",
+    "ch6_sigaction\0",
+    "ch6_sleep\0",
+    "ch6_mutex\0",
+    "ch6_sem\0",
+    "ch6_condvar\0",
+    "ch6_deadlock\0",
+    "ch6_fork_fd\0",
+    "ch6_cloexec\0",
+    "ch6_fd_limit\0",
+    "ch6_chdir\0",
+    "ch6_dev\0",
+    "ch6_random\0",
+    "ch6_statfs\0",
+    "ch6_pread\0",
+    "ch6_iovec\0",
+    "ch6_sendfile\0",
+    "ch6_append_race\0",
+    "ch6_access\0",
+    "ch6_rename\0",
+    "ch6_fcntl\0",
+    "ch6_nonblock\0",
+    "ch6_poll\0",
+    "ch6_tmpfs\0",
+    "ch6_mount\0",
+    "ch6_gettimeofday\0",
+    "ch6_times\0",
+    "ch6_exec_args\0",
+    "ch6_environ\0",
+    "ch6_stack_overflow\0",
+    "ch6_stack_grow\0",
+    "ch6_shm\0",
+    "ch6_sparse\0",
+    "ch6_fallocate\0",
+    "ch6_fstatat\0",
+    "ch6_ioctl\0",
+    "ch6_getppid\0",
+    "ch6_orphan\0",
+    "ch6_rusage\0",
+    "ch6_huge_page\0",
+    "ch6_madvise\0",
+    "ch6_page_straddle\0",
+    "ch6_pipe2\0",
+    "ch6_shutdown\0",
+    "ch6_uname\0",
+    "ch6_kmsg\0",
+    "ch6_proc_status\0",
+    "ch6_open_excl\0",
+    "ch6_truncate\0",
+    "ch6_rlimit\0",
+    "ch6_getdents_unlink\0",
+    "ch6_linkat\0",
+    "ch6_flock\0",
+    "ch6_readlink\0",
+    "ch6_prio_inherit\0",
+    "ch6_mutex_exit\0",
+    "ch6_munmap_partial\0",
 ];
 
 use user_lib::{spawn, waitpid};
@@ -0,0 +1,65 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{exit, fork, getpid, sleep, sys_waitpid, wait, waitpid, waitpid_nohang};
+
+/// 测试 waitpid 的 WNOHANG 选项，退出码地址不可写时子进程不被回收，
+/// 为空时回收但不写退出码，输出　Test wnohang OK! 就算正确。
+
+/// Not mapped in any user address space
+const UNMAPPED: usize = 0x5000_0000;
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let mut exit_code: i32 = 0;
+    // no such child at all
+    assert_eq!(waitpid_nohang(getpid() as usize, &mut exit_code), -1);
+    assert_eq!(wait(&mut exit_code), -1);
+
+    let pid = fork();
+    if pid == 0 {
+        sleep(200);
+        exit(42);
+    }
+    let mut polls = 0;
+    let found = loop {
+        match waitpid_nohang(pid as usize, &mut exit_code) {
+            0 => {
+                polls += 1;
+                sleep(10);
+            }
+            found => break found,
+        }
+    };
+    assert_eq!(found, pid);
+    assert_eq!(exit_code, 42);
+    assert!(polls > 0);
+    // the child is reaped once collected
+    assert_eq!(waitpid_nohang(pid as usize, &mut exit_code), -1);
+
+    // without WNOHANG the parent waits for the child
+    let pid = fork();
+    if pid == 0 {
+        sleep(50);
+        exit(7);
+    }
+    assert_eq!(waitpid(pid as usize, &mut exit_code), pid);
+    assert_eq!(exit_code, 7);
+
+    // a bad exit code pointer leaves the child to be reaped later,
+    // a null one reaps it with the exit code thrown away
+    let pid = fork();
+    if pid == 0 {
+        exit(9);
+    }
+    assert_eq!(sys_waitpid(pid, UNMAPPED as *mut i32, 0), -1);
+    let code = (main as usize & !3) as *mut i32;
+    assert_eq!(sys_waitpid(pid, code, 0), -1);
+    assert_eq!(sys_waitpid(pid, core::ptr::null_mut(), 0), pid);
+    assert_eq!(waitpid_nohang(pid as usize, &mut exit_code), -1);
+    println!("Test wnohang OK!");
+    0
+}
@@ -13,11 +13,17 @@ const BS: u8 = 0x08u8;
 
 use alloc::string::String;
 use user_lib::console::getchar;
-use user_lib::{exec, flush, fork, waitpid};
+use user_lib::{exec, flush, fork, tcgetattr, tcsetattr, waitpid, Termios, ECHO, ICANON};
 
 #[no_mangle]
 pub fn main() -> i32 {
     println!("Rust user shell");
+    // the shell edits and echoes the line itself, key by key
+    let mut termios = Termios::default();
+    if tcgetattr(0, &mut termios) == 0 {
+        termios.lflag &= !(ICANON | ECHO);
+        tcsetattr(0, &termios);
+    }
     let mut line: String = String::new();
     print!(">> ");
     flush();
@@ -83,8 +83,10 @@ pub fn main() -> i32 {
                         }
                         // output redirection
                         if !output.is_empty() {
-                            let output_fd =
-                                open(output.as_str(), OpenFlags::CREATE | OpenFlags::WRONLY);
+                            let output_fd = open(
+                                output.as_str(),
+                                OpenFlags::CREATE | OpenFlags::WRONLY | OpenFlags::TRUNC,
+                            );
                             if output_fd == -1 {
                                 println!("Error when opening file {}", output);
                                 return -4;
@@ -13,6 +13,7 @@ extern crate core;
 #[macro_use]
 extern crate bitflags;
 
+use alloc::string::String;
 use alloc::vec::Vec;
 use buddy_system_allocator::LockedHeap;
 pub use console::{flush, STDIN, STDOUT};
@@ -80,8 +81,13 @@ bitflags! {
         const RDONLY = 0;
         const WRONLY = 1 << 0;
         const RDWR = 1 << 1;
+        const EXCL = 1 << 7;
         const CREATE = 1 << 9;
         const TRUNC = 1 << 10;
+        const APPEND = 1 << 11;
+        const NONBLOCK = 1 << 12;
+        const NOFOLLOW = 1 << 17;
+        const CLOEXEC = 1 << 19;
     }
 }
 
@@ -98,6 +104,137 @@ impl TimeVal {
     }
 }
 
+#[repr(C)]
+#[derive(Debug, Default)]
+pub struct TimeSpec {
+    pub sec: usize,
+    pub nsec: usize,
+}
+
+/// CPU times of a process and of its reaped children, in clock ticks
+#[repr(C)]
+#[derive(Debug, Default)]
+pub struct Tms {
+    pub utime: usize,
+    pub stime: usize,
+    pub cutime: usize,
+    pub cstime: usize,
+}
+
+/// Memory and block device usage of a process or of its reaped children
+#[repr(C)]
+#[derive(Debug, Default)]
+pub struct Rusage {
+    pub page_faults: u64,
+    pub resident_frames: u64,
+    pub peak_frames: u64,
+    pub block_read_bytes: u64,
+    pub block_written_bytes: u64,
+}
+
+/// The soft and the hard limit on a resource
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy)]
+pub struct RLimit {
+    pub cur: usize,
+    pub max: usize,
+}
+
+/// Limit on the fds of a process, every fd opened is below it
+pub const RLIMIT_NOFILE: usize = 7;
+
+/// Identity of the kernel and of the machine it runs on,
+/// every field a NUL terminated string
+#[repr(C)]
+pub struct Utsname {
+    pub sysname: [u8; 65],
+    pub nodename: [u8; 65],
+    pub release: [u8; 65],
+    pub version: [u8; 65],
+    pub machine: [u8; 65],
+    pub domainname: [u8; 65],
+}
+
+impl Utsname {
+    pub fn new() -> Self {
+        Self {
+            sysname: [0; 65],
+            nodename: [0; 65],
+            release: [0; 65],
+            version: [0; 65],
+            machine: [0; 65],
+            domainname: [0; 65],
+        }
+    }
+    /// The string in `field`, up to the NUL
+    pub fn field(field: &[u8; 65]) -> &str {
+        let len = field.iter().position(|&b| b == 0).unwrap_or(field.len());
+        core::str::from_utf8(&field[..len]).unwrap()
+    }
+}
+
+/// Usage of the calling process itself
+pub const RUSAGE_SELF: isize = 0;
+/// Usage of the reaped children of the calling process
+pub const RUSAGE_CHILDREN: isize = -1;
+
+/// The key of a shared memory segment nobody else can look up
+pub const IPC_PRIVATE: usize = 0;
+/// Remove a shared memory segment once every process detaches it
+pub const IPC_RMID: usize = 0;
+
+/// Clock ticks a second
+pub const CLK_TCK: usize = 100;
+
+/// The time since boot
+pub const CLOCK_REALTIME: usize = 0;
+/// The time since boot, never going backward
+pub const CLOCK_MONOTONIC: usize = 1;
+
+/// A buffer of vectored I/O
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+pub struct IoVec {
+    pub base: usize,
+    pub len: usize,
+}
+
+impl IoVec {
+    /// A buffer to write from
+    pub fn new(buf: &[u8]) -> Self {
+        Self {
+            base: buf.as_ptr() as usize,
+            len: buf.len(),
+        }
+    }
+    /// A buffer to read into
+    pub fn new_mut(buf: &mut [u8]) -> Self {
+        Self {
+            base: buf.as_mut_ptr() as usize,
+            len: buf.len(),
+        }
+    }
+}
+
+/// An fd watched by `poll`
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+pub struct PollFd {
+    pub fd: i32,
+    pub events: i16,
+    pub revents: i16,
+}
+
+impl PollFd {
+    pub fn new(fd: usize, events: i16) -> Self {
+        Self {
+            fd: fd as i32,
+            events,
+            revents: 0,
+        }
+    }
+}
+
 #[derive(Copy, Clone, PartialEq, Debug)]
 pub enum TaskStatus {
     UnInit,
@@ -114,6 +251,7 @@ pub struct SyscallInfo {
 
 const MAX_SYSCALL_NUM: usize = 500;
 
+#[repr(C)]
 #[derive(Debug)]
 pub struct TaskInfo {
     pub status: TaskStatus,
@@ -142,8 +280,18 @@ pub struct Stat {
     pub mode: StatMode,
     /// number of hard links
     pub nlink: u32,
+    /// size of file in bytes
+    pub size: u64,
+    /// time of last access in nanoseconds
+    pub atime: u64,
+    /// time of last modification in nanoseconds
+    pub mtime: u64,
+    /// time of last status change in nanoseconds
+    pub ctime: u64,
+    /// number of 512-byte blocks allocated, holes left out
+    pub blocks: u64,
     /// unused pad
-    pad: [u64; 7],
+    pad: [u64; 2],
 }
 
 impl Stat {
@@ -153,7 +301,12 @@ impl Stat {
             ino: 0,
             mode: StatMode::NULL,
             nlink: 0,
-            pad: [0; 7],
+            size: 0,
+            atime: 0,
+            mtime: 0,
+            ctime: 0,
+            blocks: 0,
+            pad: [0; 2],
         }
     }
 }
@@ -164,6 +317,21 @@ impl Default for Stat {
     }
 }
 
+#[repr(C)]
+#[derive(Debug, Default)]
+pub struct Statfs {
+    /// size of a block in bytes
+    pub bsize: u64,
+    /// data blocks in total
+    pub blocks: u64,
+    /// data blocks not allocated
+    pub bfree: u64,
+    /// inodes in total
+    pub files: u64,
+    /// inodes not allocated
+    pub ffree: u64,
+}
+
 bitflags! {
     pub struct StatMode: u32 {
         const NULL  = 0;
@@ -171,10 +339,101 @@ bitflags! {
         const DIR   = 0o040000;
         /// ordinary regular file
         const FILE  = 0o100000;
+        /// symbolic link
+        const LNK   = 0o120000;
+        /// character device
+        const CHR   = 0o020000;
     }
 }
 
-const AT_FDCWD: isize = -100;
+/// `dirfd` standing for the working directory
+pub const AT_FDCWD: isize = -100;
+/// Look at a symbolic link itself in `fstatat`
+pub const AT_SYMLINK_NOFOLLOW: usize = 0x100;
+
+pub const SEEK_SET: usize = 0;
+pub const SEEK_CUR: usize = 1;
+pub const SEEK_END: usize = 2;
+
+/// Keep the size of the file as it is in `fallocate`
+pub const FALLOC_FL_KEEP_SIZE: usize = 1;
+
+pub const F_DUPFD: usize = 0;
+pub const F_GETFD: usize = 1;
+pub const F_SETFD: usize = 2;
+pub const F_GETFL: usize = 3;
+pub const F_SETFL: usize = 4;
+pub const FD_CLOEXEC: usize = 1;
+
+/// `flock` operations, `LOCK_NB` is added to `LOCK_SH` or `LOCK_EX`
+/// to fail instead of waiting
+pub const LOCK_SH: usize = 1;
+pub const LOCK_EX: usize = 2;
+pub const LOCK_NB: usize = 4;
+pub const LOCK_UN: usize = 8;
+
+/// `ioctl` request reading the terminal settings
+pub const TCGETS: usize = 0x5401;
+/// `ioctl` request changing the terminal settings
+pub const TCSETS: usize = 0x5402;
+/// Local mode: input is edited and released a line at a time
+pub const ICANON: u32 = 0o2;
+/// Local mode: keys typed are echoed
+pub const ECHO: u32 = 0o10;
+
+/// Terminal settings
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Termios {
+    pub iflag: u32,
+    pub oflag: u32,
+    pub cflag: u32,
+    /// local modes, `ICANON` and `ECHO`
+    pub lflag: u32,
+}
+
+pub const POLLIN: i16 = 0x1;
+pub const POLLOUT: i16 = 0x4;
+pub const POLLNVAL: i16 = 0x20;
+
+pub const F_OK: usize = 0;
+pub const R_OK: usize = 4;
+pub const W_OK: usize = 2;
+pub const X_OK: usize = 1;
+
+pub const DT_DIR: u8 = 4;
+pub const DT_REG: u8 = 8;
+pub const DT_LNK: u8 = 10;
+
+pub const WNOHANG: usize = 1;
+
+pub const SIGINT: usize = 2;
+pub const SIGKILL: usize = 9;
+pub const SIGUSR1: usize = 10;
+pub const SIGSEGV: usize = 11;
+pub const SIGUSR2: usize = 12;
+pub const SIGTERM: usize = 15;
+pub const SIGCHLD: usize = 17;
+pub const SIGCONT: usize = 18;
+pub const SIGSTOP: usize = 19;
+
+pub const SIG_DFL: usize = 0;
+pub const SIG_IGN: usize = 1;
+
+/// What to do when a signal is delivered, `mask` holds bit `n` for signal `n`
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SignalAction {
+    pub handler: usize,
+    pub mask: u32,
+}
+
+pub const MAP_SHARED: usize = 0x01;
+pub const MAP_PRIVATE: usize = 0x02;
+pub const MAP_ANONYMOUS: usize = 0x20;
+pub const MAP_HUGETLB: usize = 0x40000;
+pub const MADV_WILLNEED: usize = 3;
+pub const MADV_DONTNEED: usize = 4;
 
 pub fn open(path: &str, flags: OpenFlags) -> isize {
     sys_openat(AT_FDCWD as usize, path, flags.bits, OpenFlags::RDWR.bits)
@@ -187,26 +446,132 @@ pub fn close(fd: usize) -> isize {
     sys_close(fd)
 }
 
+pub fn lseek(fd: usize, offset: isize, whence: usize) -> isize {
+    sys_lseek(fd, offset, whence)
+}
+
 pub fn read(fd: usize, buf: &mut [u8]) -> isize {
     sys_read(fd, buf)
 }
 
+pub fn ftruncate(fd: usize, length: isize) -> isize {
+    sys_ftruncate(fd, length)
+}
+pub fn truncate(path: &str, length: isize) -> isize {
+    sys_truncate(path, length)
+}
+
+pub fn fallocate(fd: usize, mode: usize, offset: isize, len: isize) -> isize {
+    sys_fallocate(fd, mode, offset, len)
+}
+
+pub fn sync() -> isize {
+    sys_sync()
+}
+
+pub fn getdents64(fd: usize, buf: &mut [u8]) -> isize {
+    sys_getdents64(fd, buf)
+}
+
 pub fn write(fd: usize, buf: &[u8]) -> isize {
     sys_write(fd, buf)
 }
 
+/// Read into the buffers of `iov` one after another
+pub fn readv(fd: usize, iov: &[IoVec]) -> isize {
+    sys_readv(fd, iov)
+}
+
+/// Write the buffers of `iov` one after another
+pub fn writev(fd: usize, iov: &[IoVec]) -> isize {
+    sys_writev(fd, iov)
+}
+
+/// Copy up to `count` bytes from `in_fd` to `out_fd` in the kernel, reading
+/// at `*offset` and moving it if given, or at the offset of `in_fd` if not
+pub fn sendfile(out_fd: usize, in_fd: usize, offset: Option<&mut usize>, count: usize) -> isize {
+    let offset = match offset {
+        Some(offset) => offset as *mut usize,
+        None => core::ptr::null_mut(),
+    };
+    sys_sendfile(out_fd, in_fd, offset, count)
+}
+
+/// Wait up to `timeout` ms, forever if negative, for any of `fds` to be
+/// ready. Returns the number of ready fds, with `revents` filled
+pub fn poll(fds: &mut [PollFd], timeout: isize) -> isize {
+    sys_poll(fds, timeout)
+}
+
+pub fn pread(fd: usize, buf: &mut [u8], offset: usize) -> isize {
+    sys_pread(fd, buf, offset)
+}
+
+pub fn pwrite(fd: usize, buf: &[u8], offset: usize) -> isize {
+    sys_pwrite(fd, buf, offset)
+}
+
 pub fn link(old_path: &str, new_path: &str) -> isize {
     sys_linkat(AT_FDCWD as usize, old_path, AT_FDCWD as usize, new_path, 0)
 }
+pub fn linkat(
+    old_dirfd: isize,
+    old_path: &str,
+    new_dirfd: isize,
+    new_path: &str,
+    flags: usize,
+) -> isize {
+    sys_linkat(old_dirfd as usize, old_path, new_dirfd as usize, new_path, flags)
+}
+
+pub fn symlink(target: &str, link_path: &str) -> isize {
+    sys_symlinkat(target, AT_FDCWD as usize, link_path)
+}
+
+/// Read the target of the symbolic link at `path` into `buf`, cut short
+/// to fit with no NUL added. Returns the number of bytes read or -1
+pub fn readlink(path: &str, buf: &mut [u8]) -> isize {
+    sys_readlinkat(AT_FDCWD as usize, path, buf)
+}
+
+pub fn rename(old_path: &str, new_path: &str) -> isize {
+    sys_renameat2(AT_FDCWD as usize, old_path, AT_FDCWD as usize, new_path, 0)
+}
 
 pub fn unlink(path: &str) -> isize {
     sys_unlinkat(AT_FDCWD as usize, path, 0)
 }
 
+pub fn mkdir(path: &str, mode: u32) -> isize {
+    sys_mkdirat(AT_FDCWD as usize, path, mode)
+}
+
+pub fn statfs(path: &str, st: &mut Statfs) -> isize {
+    sys_statfs(path, st)
+}
+
+/// Returns 0 if `path` exists and allows what `mode` asks for
+pub fn access(path: &str, mode: usize) -> isize {
+    sys_faccessat(AT_FDCWD as usize, path, mode)
+}
+
+pub fn chdir(path: &str) -> isize {
+    sys_chdir(path)
+}
+
+/// Returns the length of the path written to `buf` including the NUL
+pub fn getcwd(buf: &mut [u8]) -> isize {
+    sys_getcwd(buf)
+}
+
 pub fn fstat(fd: usize, st: &Stat) -> isize {
     sys_fstat(fd, st)
 }
 
+pub fn fstatat(dirfd: isize, path: &str, st: &mut Stat, flags: usize) -> isize {
+    sys_fstatat(dirfd as usize, path, st, flags)
+}
+
 pub fn mail_read(buf: &mut [u8]) -> isize {
     sys_mail_read(buf)
 }
@@ -219,6 +584,12 @@ pub fn exit(exit_code: i32) -> ! {
     console::flush();
     sys_exit(exit_code);
 }
+/// Power off after writing back the dirty blocks, only the initial
+/// process may, and it does not return then
+pub fn shutdown(failure: bool) -> isize {
+    console::flush();
+    sys_shutdown(failure)
+}
 
 pub fn yield_() -> isize {
     sys_yield()
@@ -232,50 +603,168 @@ pub fn get_time() -> isize {
     }
 }
 
+pub fn gettimeofday(time: &mut TimeVal) -> isize {
+    sys_get_time(time, 0)
+}
+
+pub fn times(buf: &mut Tms) -> isize {
+    sys_times(buf)
+}
+
+pub fn getrusage(who: isize, usage: &mut Rusage) -> isize {
+    sys_getrusage(who, usage)
+}
+pub fn uname(buf: &mut Utsname) -> isize {
+    sys_uname(buf)
+}
+pub fn getrlimit(resource: usize, rlim: &mut RLimit) -> isize {
+    sys_getrlimit(resource, rlim)
+}
+pub fn setrlimit(resource: usize, rlim: &RLimit) -> isize {
+    sys_setrlimit(resource, rlim)
+}
+
+pub fn clock_gettime(clock: usize, tp: &mut TimeSpec) -> isize {
+    sys_clock_gettime(clock, tp)
+}
+
 pub fn getpid() -> isize {
     sys_getpid()
 }
+pub fn getppid() -> isize {
+    sys_getppid()
+}
 
 pub fn fork() -> isize {
     sys_fork()
 }
 
+/// Run the program at `path` in place of this one, with the same environment
 pub fn exec(path: &str, args: &[*const u8]) -> isize {
-    sys_exec(path, args)
+    let env = environ_bytes();
+    let mut envs: Vec<*const u8> = Vec::new();
+    let mut start = 0;
+    for (i, &byte) in env.iter().enumerate() {
+        if byte == 0 {
+            envs.push(env[start..].as_ptr());
+            start = i + 1;
+        }
+    }
+    envs.push(core::ptr::null::<u8>());
+    sys_exec(path, args, envs.as_slice())
 }
 
-pub fn set_priority(prio: isize) -> isize {
-    sys_set_priority(prio)
+/// `exec` with the environment `envs`, a null terminated array like `args`
+pub fn execve(path: &str, args: &[*const u8], envs: &[*const u8]) -> isize {
+    sys_exec(path, args, envs)
 }
 
-pub fn wait(exit_code: &mut i32) -> isize {
+/// The environment, each `NAME=value` string ended by a NUL
+fn environ_bytes() -> Vec<u8> {
+    let mut buf = Vec::new();
     loop {
-        match sys_waitpid(-1, exit_code as *mut _) {
-            -2 => {
-                sys_yield();
-            }
-            n => {
-                return n;
-            }
+        let len = sys_environ(buf.as_mut_slice());
+        assert!(len >= 0);
+        if len as usize <= buf.len() {
+            buf.truncate(len as usize);
+            return buf;
         }
+        buf.resize(len as usize, 0);
     }
 }
 
-pub fn waitpid(pid: usize, exit_code: &mut i32) -> isize {
-    loop {
-        match sys_waitpid(pid as isize, exit_code as *mut _) {
-            -2 => {
-                sys_yield();
-            }
-            n => {
-                return n;
-            }
+/// The environment as `NAME=value` strings
+pub fn environ() -> Vec<String> {
+    environ_bytes()
+        .split(|&byte| byte == 0)
+        .filter(|env| !env.is_empty())
+        .map(|env| String::from_utf8_lossy(env).into_owned())
+        .collect()
+}
+
+/// The value of the environment variable `name`
+pub fn getenv(name: &str) -> Option<String> {
+    environ().into_iter().find_map(|env| {
+        let (env_name, value) = env.split_once('=')?;
+        if env_name == name {
+            Some(String::from(value))
+        } else {
+            None
         }
-    }
+    })
+}
+
+/// Set the environment variable `name` to `value`, both NUL terminated
+pub fn setenv(name: &str, value: &str) -> isize {
+    sys_setenv(name, Some(value))
+}
+
+/// Remove the environment variable `name`, NUL terminated
+pub fn unsetenv(name: &str) -> isize {
+    sys_setenv(name, None)
+}
+
+pub fn shmget(key: usize, size: usize) -> isize {
+    sys_shmget(key, size)
 }
 
-pub fn sleep_blocking(sleep_ms: usize) {
-    sys_sleep(sleep_ms);
+pub fn shmat(id: usize) -> isize {
+    sys_shmat(id)
+}
+
+pub fn shmdt(addr: usize) -> isize {
+    sys_shmdt(addr)
+}
+
+pub fn shmctl(id: usize, cmd: usize) -> isize {
+    sys_shmctl(id, cmd)
+}
+
+pub fn set_priority(prio: isize) -> isize {
+    sys_set_priority(prio)
+}
+
+pub fn wait(exit_code: &mut i32) -> isize {
+    sys_waitpid(-1, exit_code as *mut _, 0)
+}
+
+pub fn waitpid(pid: usize, exit_code: &mut i32) -> isize {
+    sys_waitpid(pid as isize, exit_code as *mut _, 0)
+}
+
+pub fn kill(pid: usize, signum: usize) -> isize {
+    sys_kill(pid, signum)
+}
+
+pub fn sigaction(
+    signum: usize,
+    action: Option<&SignalAction>,
+    old_action: Option<&mut SignalAction>,
+) -> isize {
+    sys_sigaction(
+        signum,
+        action.map_or(core::ptr::null(), |action| action as *const _),
+        old_action.map_or(core::ptr::null_mut(), |action| action as *mut _),
+    )
+}
+
+pub fn sigprocmask(mask: u32) -> isize {
+    sys_sigprocmask(mask)
+}
+
+/// Must end every signal handler, it returns to where the signal came
+pub fn sigreturn() -> isize {
+    sys_sigreturn()
+}
+
+/// Returns 0 at once if the child is still running
+pub fn waitpid_nohang(pid: usize, exit_code: &mut i32) -> isize {
+    sys_waitpid(pid as isize, exit_code as *mut _, WNOHANG)
+}
+
+/// Block for `sleep_ms` milliseconds, returns -1 if a signal comes first
+pub fn sleep_blocking(sleep_ms: usize) -> isize {
+    sys_sleep(sleep_ms)
 }
 
 pub fn sleep(period_ms: usize) {
@@ -285,13 +774,36 @@ pub fn sleep(period_ms: usize) {
     }
 }
 pub fn mmap(start: usize, len: usize, prot: usize) -> isize {
-    sys_mmap(start, len, prot)
+    sys_mmap(start, len, prot, 0, 0, 0)
+}
+
+pub fn mmap_file(
+    start: usize,
+    len: usize,
+    prot: usize,
+    flags: usize,
+    fd: usize,
+    offset: usize,
+) -> isize {
+    sys_mmap(start, len, prot, flags, fd, offset)
 }
 
 pub fn munmap(start: usize, len: usize) -> isize {
     sys_munmap(start, len)
 }
 
+pub fn mprotect(addr: usize, len: usize, prot: usize) -> isize {
+    sys_mprotect(addr, len, prot)
+}
+
+pub fn madvise(addr: usize, len: usize, advice: usize) -> isize {
+    sys_madvise(addr, len, advice)
+}
+
+pub fn brk(addr: usize) -> isize {
+    sys_brk(addr)
+}
+
 pub fn spawn(path: &str) -> isize {
     sys_spawn(path)
 }
@@ -299,8 +811,38 @@ pub fn spawn(path: &str) -> isize {
 pub fn dup(fd: usize) -> isize {
     sys_dup(fd)
 }
+pub fn dup2(old_fd: usize, new_fd: usize) -> isize {
+    // `dup3` refuses an fd onto itself, which `dup2` takes if it is open
+    if old_fd == new_fd {
+        return if fcntl(old_fd, F_GETFD, 0) < 0 { -1 } else { new_fd as isize };
+    }
+    sys_dup3(old_fd, new_fd, 0)
+}
+pub fn dup3(old_fd: usize, new_fd: usize, flags: OpenFlags) -> isize {
+    sys_dup3(old_fd, new_fd, flags.bits)
+}
+pub fn fcntl(fd: usize, cmd: usize, arg: usize) -> isize {
+    sys_fcntl(fd, cmd, arg)
+}
+pub fn ioctl(fd: usize, request: usize, argp: usize) -> isize {
+    sys_ioctl(fd, request, argp)
+}
+pub fn flock(fd: usize, operation: usize) -> isize {
+    sys_flock(fd, operation)
+}
+pub fn tcgetattr(fd: usize, termios: &mut Termios) -> isize {
+    sys_ioctl(fd, TCGETS, termios as *mut _ as usize)
+}
+pub fn tcsetattr(fd: usize, termios: &Termios) -> isize {
+    sys_ioctl(fd, TCSETS, termios as *const _ as usize)
+}
 pub fn pipe(pipe_fd: &mut [usize]) -> isize {
-    sys_pipe(pipe_fd)
+    sys_pipe2(pipe_fd, 0)
+}
+
+/// A pipe whose ends are opened with `flags`, `NONBLOCK` or `CLOEXEC`
+pub fn pipe2(pipe_fd: &mut [usize], flags: OpenFlags) -> isize {
+    sys_pipe2(pipe_fd, flags.bits)
 }
 
 pub fn task_info(info: &TaskInfo) -> isize {
@@ -333,8 +875,8 @@ pub fn mutex_blocking_create() -> isize {
 pub fn mutex_lock(mutex_id: usize) -> isize {
     sys_mutex_lock(mutex_id)
 }
-pub fn mutex_unlock(mutex_id: usize) {
-    sys_mutex_unlock(mutex_id);
+pub fn mutex_unlock(mutex_id: usize) -> isize {
+    sys_mutex_unlock(mutex_id)
 }
 pub fn semaphore_create(res_count: usize) -> isize {
     sys_semaphore_create(res_count)
@@ -351,9 +893,9 @@ pub fn semaphore_down(sem_id: usize) -> isize {
 pub fn condvar_create() -> isize {
     sys_condvar_create(0)
 }
-pub fn condvar_signal(condvar_id: usize) {
-    sys_condvar_signal(condvar_id);
+pub fn condvar_signal(condvar_id: usize) -> isize {
+    sys_condvar_signal(condvar_id)
 }
-pub fn condvar_wait(condvar_id: usize, mutex_id: usize) {
-    sys_condvar_wait(condvar_id, mutex_id);
+pub fn condvar_wait(condvar_id: usize, mutex_id: usize) -> isize {
+    sys_condvar_wait(condvar_id, mutex_id)
 }
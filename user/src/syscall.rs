@@ -1,32 +1,78 @@
-use crate::TaskInfo;
+use crate::{SignalAction, TaskInfo};
 
-use super::{Stat, TimeVal};
+use super::{IoVec, PollFd, RLimit, Rusage, Stat, Statfs, TimeSpec, TimeVal, Tms, Utsname};
 
+pub const SYSCALL_GETCWD: usize = 17;
+pub const SYSCALL_TRUNCATE: usize = 45;
+pub const SYSCALL_FTRUNCATE: usize = 46;
+pub const SYSCALL_FALLOCATE: usize = 47;
+pub const SYSCALL_FACCESSAT: usize = 48;
+pub const SYSCALL_CHDIR: usize = 49;
 pub const SYSCALL_OPENAT: usize = 56;
 pub const SYSCALL_CLOSE: usize = 57;
+pub const SYSCALL_GETDENTS64: usize = 61;
+pub const SYSCALL_LSEEK: usize = 62;
 pub const SYSCALL_READ: usize = 63;
 pub const SYSCALL_WRITE: usize = 64;
+pub const SYSCALL_READV: usize = 65;
+pub const SYSCALL_WRITEV: usize = 66;
+pub const SYSCALL_PREAD: usize = 67;
+pub const SYSCALL_PWRITE: usize = 68;
+pub const SYSCALL_SENDFILE: usize = 71;
+pub const SYSCALL_PPOLL: usize = 73;
+pub const SYSCALL_MKDIRAT: usize = 34;
 pub const SYSCALL_UNLINKAT: usize = 35;
+pub const SYSCALL_SYMLINKAT: usize = 36;
 pub const SYSCALL_LINKAT: usize = 37;
+pub const SYSCALL_STATFS: usize = 43;
+pub const SYSCALL_READLINKAT: usize = 78;
+pub const SYSCALL_FSTATAT: usize = 79;
 pub const SYSCALL_FSTAT: usize = 80;
+pub const SYSCALL_SYNC: usize = 81;
 pub const SYSCALL_EXIT: usize = 93;
 pub const SYSCALL_SLEEP: usize = 101;
+pub const SYSCALL_CLOCK_GETTIME: usize = 113;
+pub const SYSCALL_TIMES: usize = 153;
+pub const SYSCALL_UNAME: usize = 160;
+pub const SYSCALL_GETRLIMIT: usize = 163;
+pub const SYSCALL_SETRLIMIT: usize = 164;
+pub const SYSCALL_GETRUSAGE: usize = 165;
 pub const SYSCALL_YIELD: usize = 124;
+pub const SYSCALL_KILL: usize = 129;
+pub const SYSCALL_SIGACTION: usize = 134;
+pub const SYSCALL_SIGPROCMASK: usize = 135;
+pub const SYSCALL_SIGRETURN: usize = 139;
 pub const SYSCALL_GETTIMEOFDAY: usize = 169;
 pub const SYSCALL_GETPID: usize = 172;
+pub const SYSCALL_GETPPID: usize = 173;
+pub const SYSCALL_SHMGET: usize = 194;
+pub const SYSCALL_SHMCTL: usize = 195;
+pub const SYSCALL_SHMAT: usize = 196;
+pub const SYSCALL_SHMDT: usize = 197;
 pub const SYSCALL_GETTID: usize = 178;
 pub const SYSCALL_FORK: usize = 220;
 pub const SYSCALL_EXEC: usize = 221;
 pub const SYSCALL_WAITPID: usize = 260;
+pub const SYSCALL_RENAMEAT2: usize = 276;
 pub const SYSCALL_SET_PRIORITY: usize = 140;
+pub const SYSCALL_SHUTDOWN: usize = 142;
+pub const SYSCALL_BRK: usize = 214;
 pub const SYSCALL_MUNMAP: usize = 215;
 pub const SYSCALL_MMAP: usize = 222;
+pub const SYSCALL_MPROTECT: usize = 226;
+pub const SYSCALL_MADVISE: usize = 233;
 pub const SYSCALL_SPAWN: usize = 400;
+pub const SYSCALL_DUP3: usize = 404;
 pub const SYSCALL_MAIL_READ: usize = 401;
 pub const SYSCALL_MAIL_WRITE: usize = 402;
 pub const SYSCALL_DUP: usize = 24;
+pub const SYSCALL_FCNTL: usize = 25;
+pub const SYSCALL_IOCTL: usize = 29;
+pub const SYSCALL_FLOCK: usize = 32;
 pub const SYSCALL_PIPE: usize = 59;
 pub const SYSCALL_TASK_INFO: usize = 410;
+pub const SYSCALL_ENVIRON: usize = 411;
+pub const SYSCALL_SETENV: usize = 412;
 pub const SYSCALL_THREAD_CREATE: usize = 460;
 pub const SYSCALL_WAITTID: usize = 462;
 pub const SYSCALL_MUTEX_CREATE: usize = 463;
@@ -88,6 +134,36 @@ pub fn sys_close(fd: usize) -> isize {
     syscall(SYSCALL_CLOSE, [fd, 0, 0])
 }
 
+pub fn sys_truncate(path: &str, length: isize) -> isize {
+    syscall(SYSCALL_TRUNCATE, [path.as_ptr() as usize, length as usize, 0])
+}
+
+pub fn sys_ftruncate(fd: usize, length: isize) -> isize {
+    syscall(SYSCALL_FTRUNCATE, [fd, length as usize, 0])
+}
+
+pub fn sys_fallocate(fd: usize, mode: usize, offset: isize, len: isize) -> isize {
+    syscall6(
+        SYSCALL_FALLOCATE,
+        [fd, mode, offset as usize, len as usize, 0, 0],
+    )
+}
+
+pub fn sys_sync() -> isize {
+    syscall(SYSCALL_SYNC, [0, 0, 0])
+}
+
+pub fn sys_getdents64(fd: usize, buffer: &mut [u8]) -> isize {
+    syscall(
+        SYSCALL_GETDENTS64,
+        [fd, buffer.as_mut_ptr() as usize, buffer.len()],
+    )
+}
+
+pub fn sys_lseek(fd: usize, offset: isize, whence: usize) -> isize {
+    syscall(SYSCALL_LSEEK, [fd, offset as usize, whence])
+}
+
 pub fn sys_read(fd: usize, buffer: &mut [u8]) -> isize {
     syscall(
         SYSCALL_READ,
@@ -99,6 +175,36 @@ pub fn sys_write(fd: usize, buffer: &[u8]) -> isize {
     syscall(SYSCALL_WRITE, [fd, buffer.as_ptr() as usize, buffer.len()])
 }
 
+pub fn sys_readv(fd: usize, iov: &[IoVec]) -> isize {
+    syscall(SYSCALL_READV, [fd, iov.as_ptr() as usize, iov.len()])
+}
+
+pub fn sys_writev(fd: usize, iov: &[IoVec]) -> isize {
+    syscall(SYSCALL_WRITEV, [fd, iov.as_ptr() as usize, iov.len()])
+}
+
+pub fn sys_sendfile(out_fd: usize, in_fd: usize, offset: *mut usize, count: usize) -> isize {
+    syscall6(SYSCALL_SENDFILE, [out_fd, in_fd, offset as usize, count, 0, 0])
+}
+
+pub fn sys_poll(fds: &mut [PollFd], timeout: isize) -> isize {
+    syscall(SYSCALL_PPOLL, [fds.as_mut_ptr() as usize, fds.len(), timeout as usize])
+}
+
+pub fn sys_pread(fd: usize, buffer: &mut [u8], offset: usize) -> isize {
+    syscall6(
+        SYSCALL_PREAD,
+        [fd, buffer.as_mut_ptr() as usize, buffer.len(), offset, 0, 0],
+    )
+}
+
+pub fn sys_pwrite(fd: usize, buffer: &[u8], offset: usize) -> isize {
+    syscall6(
+        SYSCALL_PWRITE,
+        [fd, buffer.as_ptr() as usize, buffer.len(), offset, 0, 0],
+    )
+}
+
 pub fn sys_linkat(
     old_dirfd: usize,
     old_path: &str,
@@ -119,14 +225,75 @@ pub fn sys_linkat(
     )
 }
 
+pub fn sys_getcwd(buf: &mut [u8]) -> isize {
+    syscall(SYSCALL_GETCWD, [buf.as_mut_ptr() as usize, buf.len(), 0])
+}
+
+pub fn sys_statfs(path: &str, st: &mut Statfs) -> isize {
+    syscall(SYSCALL_STATFS, [path.as_ptr() as usize, st as *mut _ as usize, 0])
+}
+
+pub fn sys_faccessat(dirfd: usize, path: &str, mode: usize) -> isize {
+    syscall(SYSCALL_FACCESSAT, [dirfd, path.as_ptr() as usize, mode])
+}
+
+pub fn sys_chdir(path: &str) -> isize {
+    syscall(SYSCALL_CHDIR, [path.as_ptr() as usize, 0, 0])
+}
+
+pub fn sys_mkdirat(dirfd: usize, path: &str, mode: u32) -> isize {
+    syscall(SYSCALL_MKDIRAT, [dirfd, path.as_ptr() as usize, mode as usize])
+}
+
+pub fn sys_renameat2(
+    old_dirfd: usize,
+    old_path: &str,
+    new_dirfd: usize,
+    new_path: &str,
+    flags: usize,
+) -> isize {
+    syscall6(
+        SYSCALL_RENAMEAT2,
+        [
+            old_dirfd,
+            old_path.as_ptr() as usize,
+            new_dirfd,
+            new_path.as_ptr() as usize,
+            flags,
+            0,
+        ],
+    )
+}
+
 pub fn sys_unlinkat(dirfd: usize, path: &str, flags: usize) -> isize {
     syscall(SYSCALL_UNLINKAT, [dirfd, path.as_ptr() as usize, flags])
 }
 
+pub fn sys_symlinkat(target: &str, new_dirfd: usize, link_path: &str) -> isize {
+    syscall(
+        SYSCALL_SYMLINKAT,
+        [target.as_ptr() as usize, new_dirfd, link_path.as_ptr() as usize],
+    )
+}
+
 pub fn sys_fstat(fd: usize, st: &Stat) -> isize {
     syscall(SYSCALL_FSTAT, [fd, st as *const _ as usize, 0])
 }
 
+pub fn sys_fstatat(dirfd: usize, path: &str, st: &mut Stat, flags: usize) -> isize {
+    syscall6(
+        SYSCALL_FSTATAT,
+        [dirfd, path.as_ptr() as usize, st as *mut _ as usize, flags, 0, 0],
+    )
+}
+
+pub fn sys_readlinkat(dirfd: usize, path: &str, buf: &mut [u8]) -> isize {
+    syscall6(
+        SYSCALL_READLINKAT,
+        [dirfd, path.as_ptr() as usize, buf.as_mut_ptr() as usize, buf.len(), 0, 0],
+    )
+}
+
 pub fn sys_mail_read(buffer: &mut [u8]) -> isize {
     syscall(
         SYSCALL_MAIL_READ,
@@ -146,6 +313,10 @@ pub fn sys_exit(exit_code: i32) -> ! {
     panic!("sys_exit never returns!");
 }
 
+pub fn sys_shutdown(failure: bool) -> isize {
+    syscall(SYSCALL_SHUTDOWN, [failure as usize, 0, 0])
+}
+
 pub fn sys_sleep(sleep_ms: usize) -> isize {
     syscall(SYSCALL_SLEEP, [sleep_ms, 0, 0])
 }
@@ -158,37 +329,132 @@ pub fn sys_get_time(time: &TimeVal, tz: usize) -> isize {
     syscall(SYSCALL_GETTIMEOFDAY, [time as *const _ as usize, tz, 0])
 }
 
+pub fn sys_times(buf: &mut Tms) -> isize {
+    syscall(SYSCALL_TIMES, [buf as *mut _ as usize, 0, 0])
+}
+
+pub fn sys_getrusage(who: isize, usage: &mut Rusage) -> isize {
+    syscall(SYSCALL_GETRUSAGE, [who as usize, usage as *mut _ as usize, 0])
+}
+
+pub fn sys_uname(buf: &mut Utsname) -> isize {
+    syscall(SYSCALL_UNAME, [buf as *mut _ as usize, 0, 0])
+}
+
+pub fn sys_getrlimit(resource: usize, rlim: &mut RLimit) -> isize {
+    syscall(SYSCALL_GETRLIMIT, [resource, rlim as *mut _ as usize, 0])
+}
+
+pub fn sys_setrlimit(resource: usize, rlim: &RLimit) -> isize {
+    syscall(SYSCALL_SETRLIMIT, [resource, rlim as *const _ as usize, 0])
+}
+
+pub fn sys_clock_gettime(clock: usize, tp: &mut TimeSpec) -> isize {
+    syscall(SYSCALL_CLOCK_GETTIME, [clock, tp as *mut _ as usize, 0])
+}
+
 pub fn sys_getpid() -> isize {
     syscall(SYSCALL_GETPID, [0, 0, 0])
 }
 
+pub fn sys_getppid() -> isize {
+    syscall(SYSCALL_GETPPID, [0, 0, 0])
+}
+
 pub fn sys_fork() -> isize {
     syscall(SYSCALL_FORK, [0, 0, 0])
 }
 
-pub fn sys_exec(path: &str, args: &[*const u8]) -> isize {
+pub fn sys_exec(path: &str, args: &[*const u8], envs: &[*const u8]) -> isize {
     syscall(
         SYSCALL_EXEC,
-        [path.as_ptr() as usize, args.as_ptr() as usize, 0],
+        [path.as_ptr() as usize, args.as_ptr() as usize, envs.as_ptr() as usize],
+    )
+}
+
+pub fn sys_kill(pid: usize, signum: usize) -> isize {
+    syscall(SYSCALL_KILL, [pid, signum, 0])
+}
+
+pub fn sys_sigaction(
+    signum: usize,
+    action: *const SignalAction,
+    old_action: *mut SignalAction,
+) -> isize {
+    syscall(
+        SYSCALL_SIGACTION,
+        [signum, action as usize, old_action as usize],
     )
 }
 
-pub fn sys_waitpid(pid: isize, xstatus: *mut i32) -> isize {
-    syscall(SYSCALL_WAITPID, [pid as usize, xstatus as usize, 0])
+pub fn sys_sigprocmask(mask: u32) -> isize {
+    syscall(SYSCALL_SIGPROCMASK, [mask as usize, 0, 0])
+}
+
+pub fn sys_sigreturn() -> isize {
+    syscall(SYSCALL_SIGRETURN, [0, 0, 0])
+}
+
+pub fn sys_waitpid(pid: isize, xstatus: *mut i32, options: usize) -> isize {
+    syscall(SYSCALL_WAITPID, [pid as usize, xstatus as usize, options])
 }
 
 pub fn sys_set_priority(prio: isize) -> isize {
     syscall(SYSCALL_SET_PRIORITY, [prio as usize, 0, 0])
 }
 
-pub fn sys_mmap(start: usize, len: usize, prot: usize) -> isize {
-    syscall(SYSCALL_MMAP, [start, len, prot])
+pub fn sys_mmap(
+    start: usize,
+    len: usize,
+    prot: usize,
+    flags: usize,
+    fd: usize,
+    offset: usize,
+) -> isize {
+    syscall6(SYSCALL_MMAP, [start, len, prot, flags, fd, offset])
 }
 
 pub fn sys_munmap(start: usize, len: usize) -> isize {
     syscall(SYSCALL_MUNMAP, [start, len, 0])
 }
 
+pub fn sys_mprotect(addr: usize, len: usize, prot: usize) -> isize {
+    syscall(SYSCALL_MPROTECT, [addr, len, prot])
+}
+
+pub fn sys_madvise(addr: usize, len: usize, advice: usize) -> isize {
+    syscall(SYSCALL_MADVISE, [addr, len, advice])
+}
+
+pub fn sys_brk(addr: usize) -> isize {
+    syscall(SYSCALL_BRK, [addr, 0, 0])
+}
+
+pub fn sys_environ(buf: &mut [u8]) -> isize {
+    syscall(SYSCALL_ENVIRON, [buf.as_mut_ptr() as usize, buf.len(), 0])
+}
+
+pub fn sys_setenv(name: &str, value: Option<&str>) -> isize {
+    let value = value.map_or(0, |value| value.as_ptr() as usize);
+    syscall(SYSCALL_SETENV, [name.as_ptr() as usize, value, 0])
+}
+
+pub fn sys_shmget(key: usize, size: usize) -> isize {
+    syscall(SYSCALL_SHMGET, [key, size, 0])
+}
+
+pub fn sys_shmctl(id: usize, cmd: usize) -> isize {
+    syscall(SYSCALL_SHMCTL, [id, cmd, 0])
+}
+
+pub fn sys_shmat(id: usize) -> isize {
+    syscall(SYSCALL_SHMAT, [id, 0, 0])
+}
+
+pub fn sys_shmdt(addr: usize) -> isize {
+    syscall(SYSCALL_SHMDT, [addr, 0, 0])
+}
+
 pub fn sys_spawn(path: &str) -> isize {
     syscall(SYSCALL_SPAWN, [path.as_ptr() as usize, 0, 0])
 }
@@ -197,8 +463,24 @@ pub fn sys_dup(fd: usize) -> isize {
     syscall(SYSCALL_DUP, [fd, 0, 0])
 }
 
-pub fn sys_pipe(pipe: &mut [usize]) -> isize {
-    syscall(SYSCALL_PIPE, [pipe.as_mut_ptr() as usize, 0, 0])
+pub fn sys_dup3(old_fd: usize, new_fd: usize, flags: u32) -> isize {
+    syscall(SYSCALL_DUP3, [old_fd, new_fd, flags as usize])
+}
+
+pub fn sys_fcntl(fd: usize, cmd: usize, arg: usize) -> isize {
+    syscall(SYSCALL_FCNTL, [fd, cmd, arg])
+}
+
+pub fn sys_ioctl(fd: usize, request: usize, argp: usize) -> isize {
+    syscall(SYSCALL_IOCTL, [fd, request, argp])
+}
+
+pub fn sys_flock(fd: usize, operation: usize) -> isize {
+    syscall(SYSCALL_FLOCK, [fd, operation, 0])
+}
+
+pub fn sys_pipe2(pipe: &mut [usize], flags: u32) -> isize {
+    syscall(SYSCALL_PIPE, [pipe.as_mut_ptr() as usize, flags as usize, 0])
 }
 
 pub fn sys_task_info(info: &TaskInfo) -> isize {